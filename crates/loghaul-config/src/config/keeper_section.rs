@@ -0,0 +1,90 @@
+use std::default::Default;
+use loghaul::KeeperConfig;
+use loghaul::KeeperEofStrategy;
+use internal::config_duration::parse_duration;
+
+/// What to do when a source reaches EOF
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum EofStrategyConfig {
+    Drop,
+    Resume,
+}
+
+/// The `[keeper]` section of a pipeline config
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct KeeperSection {
+    /// How long the keeper sleeps between steps, eg. `100ms`
+    #[serde(default = "default_interval")]
+    pub interval: String,
+
+    #[serde(default = "default_eof_strategy")]
+    pub eof_strategy: EofStrategyConfig,
+
+    /// How long to wait before resuming a source; required for the `resume` strategy
+    pub resume_after: Option<String>,
+}
+
+impl KeeperSection {
+    /// Return a description of every problem with this section
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        match parse_duration(&self.interval) {
+            Ok(d) if d.as_secs() == 0 && d.subsec_nanos() == 0 => {
+                errors.push("keeper.interval: must be greater than zero".to_string());
+            }
+            Ok(_) => {}
+            Err(e) => errors.push(format!("keeper.interval: {}", e))
+        }
+        match (self.eof_strategy, self.resume_after.as_ref()) {
+            (EofStrategyConfig::Resume, None) => {
+                errors.push("keeper.resume_after: required when keeper.eof_strategy is \"resume\"".to_string());
+            }
+            (EofStrategyConfig::Drop, Some(_)) => {
+                errors.push("keeper.resume_after: only valid when keeper.eof_strategy is \"resume\"".to_string());
+            }
+            (_, Some(value)) => {
+                match parse_duration(value) {
+                    Ok(_) => {}
+                    Err(e) => errors.push(format!("keeper.resume_after: {}", e))
+                }
+            }
+            _ => {}
+        }
+        return errors;
+    }
+
+    /// Convert into a keeper config; the section must already be valid.
+    pub fn to_keeper_config(&self) -> KeeperConfig {
+        let eof_strategy = match self.eof_strategy {
+            EofStrategyConfig::Drop => KeeperEofStrategy::DropSource,
+            EofStrategyConfig::Resume => {
+                let cooldown = self.resume_after.as_ref().and_then(|v| parse_duration(v).ok()).unwrap_or_default();
+                KeeperEofStrategy::ResumeSourceAfterCooldown(cooldown)
+            }
+        };
+        let mut config = KeeperConfig::default();
+        config.interval = parse_duration(&self.interval).unwrap_or(config.interval);
+        config.eof_strategy = eof_strategy;
+        return config;
+    }
+}
+
+impl Default for KeeperSection {
+    fn default() -> Self {
+        return KeeperSection {
+            interval: default_interval(),
+            eof_strategy: default_eof_strategy(),
+            resume_after: None,
+        };
+    }
+}
+
+fn default_interval() -> String {
+    "100ms".to_string()
+}
+
+fn default_eof_strategy() -> EofStrategyConfig {
+    EofStrategyConfig::Drop
+}
@@ -0,0 +1,9 @@
+pub mod pipeline_config;
+pub mod keeper_section;
+pub mod source_config;
+pub mod target_config;
+pub mod pipeline_diff;
+pub mod pipeline_reloader;
+pub mod transform_config;
+pub mod route_config;
+pub mod transforms;
@@ -0,0 +1,413 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use toml;
+use loghaul::Stream;
+use loghaul::Keeper;
+use loghaul::KeeperLog;
+use loghaul::ProcessorChain;
+use config::keeper_section::KeeperSection;
+use config::source_config::SourceConfig;
+use config::target_config::TargetConfig;
+use config::transform_config::TransformConfig;
+use config::transform_config::build_processor_chain;
+use config::route_config::RouteConfig;
+use config::route_config::build_router;
+use config::route_config::validate_routes;
+use loghaul::Router;
+use LoghaulConfigError;
+use LoghaulConfigErrorCode;
+
+/// A declarative description of a pipeline: the sources to read, the targets to
+/// write to, and how the keeper should run them.
+///
+/// ```
+///     use loghaul_config::PipelineConfig;
+///     let config = PipelineConfig::from_str(r#"
+///         [keeper]
+///         interval = "250ms"
+///         eof_strategy = "resume"
+///         resume_after = "5s"
+///
+///         [sources.api]
+///         type = "file"
+///         path = "/var/log/api.log"
+///
+///         [[transforms]]
+///         type = "split_lines"
+///
+///         [targets.console]
+///         type = "stdout"
+///     "#).unwrap();
+///     assert_eq!(config.sources.len(), 1);
+/// ```
+#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PipelineConfig {
+    #[serde(default)]
+    pub keeper: KeeperSection,
+
+    #[serde(default)]
+    pub sources: BTreeMap<String, SourceConfig>,
+
+    /// Processors applied to every record, in order, before it reaches the targets
+    #[serde(default)]
+    pub transforms: Vec<TransformConfig>,
+
+    #[serde(default)]
+    pub targets: BTreeMap<String, TargetConfig>,
+
+    /// Which targets receive which records; without routes every target receives everything
+    #[serde(default)]
+    pub routes: Vec<RouteConfig>,
+}
+
+impl PipelineConfig {
+    /// Read and validate a config file
+    pub fn from_file(path: impl AsRef<Path>) -> Result<PipelineConfig, LoghaulConfigError> {
+        let mut raw = String::new();
+        match File::open(path.as_ref()).and_then(|mut fp| fp.read_to_string(&mut raw)) {
+            Ok(_) => {}
+            Err(err) => {
+                return Err(LoghaulConfigError::new(LoghaulConfigErrorCode::UnableToReadFile,
+                                                   &format!("{}: {}", path.as_ref().display(), err)));
+            }
+        }
+        return PipelineConfig::from_str(&raw);
+    }
+
+    /// Parse and validate a config from a toml string
+    pub fn from_str(raw: &str) -> Result<PipelineConfig, LoghaulConfigError> {
+        let config: PipelineConfig = match toml::from_str(raw) {
+            Ok(c) => c,
+            Err(err) => {
+                return Err(LoghaulConfigError::new(LoghaulConfigErrorCode::InvalidSyntax, &err.to_string()));
+            }
+        };
+        config.validate()?;
+        return Ok(config);
+    }
+
+    /// Check every section, reporting all problems at once, one per line.
+    pub fn validate(&self) -> Result<(), LoghaulConfigError> {
+        let mut errors = self.keeper.validate();
+        if self.sources.is_empty() {
+            errors.push("sources: at least one source is required".to_string());
+        }
+        if self.targets.is_empty() {
+            errors.push("targets: at least one target is required".to_string());
+        }
+        for (name, source) in self.sources.iter() {
+            errors.extend(source.validate(name));
+        }
+        for (index, transform) in self.transforms.iter().enumerate() {
+            errors.extend(transform.validate(index, &self.targets));
+        }
+        for (name, target) in self.targets.iter() {
+            errors.extend(target.validate(name));
+        }
+        for (index, route) in self.routes.iter().enumerate() {
+            errors.extend(route.validate(index, &self.sources, &self.targets));
+        }
+        errors.extend(validate_routes(&self.routes));
+        if errors.len() > 0 {
+            return Err(LoghaulConfigError::new(LoghaulConfigErrorCode::InvalidValue, &errors.join("\n")));
+        }
+        return Ok(());
+    }
+
+    /// Build a stream containing every source and target in this config
+    pub fn build_stream(&self) -> Result<Stream, LoghaulConfigError> {
+        return self.build().map(|(stream, _)| stream);
+    }
+
+    /// Build the stream, and the ids of the stream sources created for each source entry
+    pub(crate) fn build(&self) -> Result<(Stream, BTreeMap<String, Vec<String>>), LoghaulConfigError> {
+        let mut stream = Stream::new();
+        let mut source_ids = BTreeMap::new();
+        for (name, source) in self.sources.iter() {
+            match source.build() {
+                Ok(sources) => {
+                    source_ids.insert(name.clone(), sources.iter().map(|s| s.id().unwrap_or(String::new())).collect());
+                    sources.into_iter().for_each(|s| stream.add_boxed_source(s));
+                }
+                Err(err) => {
+                    return Err(LoghaulConfigError::new(LoghaulConfigErrorCode::BuildFailed, &format!("sources.{}: {}", name, err)));
+                }
+            }
+        }
+        for (name, target) in self.targets.iter() {
+            match target.build() {
+                Ok(target) => stream.add_boxed_target(Some(name.clone()), target),
+                Err(err) => {
+                    return Err(LoghaulConfigError::new(LoghaulConfigErrorCode::BuildFailed, &format!("targets.{}: {}", name, err)));
+                }
+            }
+        }
+        stream.set_processors(self.build_processors()?);
+        stream.set_router(self.build_router(&source_ids)?);
+        return Ok((stream, source_ids));
+    }
+
+    /// Build the router declared by the routes, given the stream source ids each source expanded to
+    pub fn build_router(&self, source_ids: &BTreeMap<String, Vec<String>>) -> Result<Router, LoghaulConfigError> {
+        return build_router(&self.routes, source_ids).map_err(|e| LoghaulConfigError::new(LoghaulConfigErrorCode::BuildFailed, &e));
+    }
+
+    /// Build the processor chain declared by the transforms
+    pub fn build_processors(&self) -> Result<ProcessorChain, LoghaulConfigError> {
+        return build_processor_chain(&self.transforms).map_err(|e| LoghaulConfigError::new(LoghaulConfigErrorCode::BuildFailed, &e));
+    }
+
+    /// Build the stream and start a keeper running it
+    pub fn start(&self, logger: Option<Box<KeeperLog + Send>>) -> Result<Keeper, LoghaulConfigError> {
+        let stream = self.build_stream()?;
+        let mut config = self.keeper.to_keeper_config();
+        config.logger = logger;
+        return Ok(Keeper::new(stream, Some(config)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PipelineConfig;
+    use LoghaulConfigErrorCode;
+    use loghaul::KeeperEofStrategy;
+    use std::env;
+    use std::fs;
+    use std::io::Write;
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_full_config() {
+        let config = PipelineConfig::from_str(r#"
+            [keeper]
+            interval = "10ms"
+            eof_strategy = "resume"
+            resume_after = "2s"
+
+            [sources.api]
+            type = "file"
+            path = "/var/log/api.log"
+
+            [sources.pods]
+            type = "glob"
+            pattern = "/var/log/pods/*.log"
+
+            [targets.archive]
+            type = "file"
+            path = "/archive/all.log"
+
+            [targets.console]
+            type = "stdout"
+        "#).unwrap();
+
+        assert_eq!(config.sources.len(), 2);
+        assert_eq!(config.targets.len(), 2);
+        let keeper = config.keeper.to_keeper_config();
+        assert_eq!(keeper.interval, Duration::from_millis(10));
+        match keeper.eof_strategy {
+            KeeperEofStrategy::ResumeSourceAfterCooldown(d) => assert_eq!(d, Duration::from_secs(2)),
+            _ => panic!("expected resume strategy")
+        }
+    }
+
+    #[test]
+    fn test_reports_every_invalid_value() {
+        let err = PipelineConfig::from_str(r#"
+            [keeper]
+            interval = "10 parsecs"
+            eof_strategy = "resume"
+
+            [sources.api]
+            type = "file"
+            path = ""
+
+            [sources.broken]
+            type = "glob"
+            pattern = "/var/log/[.log"
+        "#).unwrap_err();
+
+        assert_eq!(err.code(), LoghaulConfigErrorCode::InvalidValue);
+        let lines: Vec<&str> = err.message().lines().collect();
+        assert_eq!(lines, vec!(
+            "keeper.interval: invalid duration '10 parsecs', unknown unit ' parsecs' (expected ms, s, m or h)",
+            "keeper.resume_after: required when keeper.eof_strategy is \"resume\"",
+            "targets: at least one target is required",
+            "sources.api.path: must not be empty",
+            "sources.broken.pattern: invalid glob '/var/log/[.log' at position 9: invalid range pattern",
+        ));
+    }
+
+    #[test]
+    fn test_reports_invalid_routes() {
+        let err = PipelineConfig::from_str(r#"
+            [sources.api]
+            type = "file"
+            path = "/var/log/api.log"
+
+            [[routes]]
+            sources = ["web"]
+            payload = "(unclosed"
+            targets = ["pager"]
+
+            [[routes]]
+            default = true
+            fields = { level = "info" }
+            targets = []
+
+            [[routes]]
+            default = true
+            targets = ["console"]
+
+            [targets.console]
+            type = "stdout"
+        "#).unwrap_err();
+
+        let lines: Vec<&str> = err.message().lines().collect();
+        assert_eq!(lines, vec!(
+            "routes[0].targets: unknown target 'pager'",
+            "routes[0].sources: unknown source 'web'",
+            "routes[0].payload: invalid regex '(unclosed': unclosed group",
+            "routes[1].targets: at least one target is required",
+            "routes[1]: a default route can't have sources, fields or payload",
+            "routes: only one default route is allowed, found routes[1], routes[2]",
+        ));
+    }
+
+    #[test]
+    fn test_rejects_unknown_types_and_fields() {
+        let err = PipelineConfig::from_str(r#"
+            [sources.api]
+            type = "kafka"
+        "#).unwrap_err();
+        assert_eq!(err.code(), LoghaulConfigErrorCode::InvalidSyntax);
+        assert!(err.message().contains("unknown variant `kafka`"), "{}", err.message());
+
+        let err = PipelineConfig::from_str(r#"
+            [targets.out]
+            type = "file"
+            path = "/tmp/out.log"
+            mode = "fast"
+        "#).unwrap_err();
+        assert_eq!(err.code(), LoghaulConfigErrorCode::InvalidSyntax);
+        assert!(err.message().contains("unknown field `mode`"), "{}", err.message());
+
+        let err = PipelineConfig::from_str(r#"
+            [[routes]]
+            targets = ["out"]
+            level = "error"
+        "#).unwrap_err();
+        assert!(err.message().contains("unknown field `level`"), "{}", err.message());
+
+        let err = PipelineConfig::from_str(r#"
+            [[transforms]]
+            type = "teleport"
+        "#).unwrap_err();
+        assert!(err.message().contains("unknown transform type 'teleport'"), "{}", err.message());
+
+        let err = PipelineConfig::from_str(r#"
+            [[transforms]]
+            type = "split_lines"
+            on_error = "explode"
+        "#).unwrap_err();
+        assert!(err.message().contains("transform 'split_lines': on_error: unknown variant `explode`"), "{}", err.message());
+
+        let err = PipelineConfig::from_str(r#"
+            [[transforms]]
+            type = "split_lines"
+            separator = ";"
+        "#).unwrap_err();
+        assert!(err.message().contains("transform 'split_lines': unknown field `separator`"), "{}", err.message());
+    }
+
+    #[test]
+    fn test_start_keeper_from_config() {
+        let dir = env::temp_dir().join(format!("loghaul_config_test_{}", ::std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("input.log");
+        let output = dir.join("output.log");
+        fs::File::create(&input).unwrap().write_all(b"hello\nworld\n").unwrap();
+
+        let config = PipelineConfig::from_str(&format!(r#"
+            [keeper]
+            interval = "1ms"
+
+            [sources.input]
+            type = "glob"
+            pattern = "{}"
+
+            [[transforms]]
+            id = "lines"
+            type = "split_lines"
+            on_error = "pass"
+
+            [[transforms]]
+            type = "syslog"
+
+            [targets.output]
+            type = "file"
+            path = "{}"
+        "#, dir.join("*.log").display(), output.display()));
+
+        // The output file doesn't exist yet, so the glob only matches the input
+        let mut keeper = config.unwrap().start(None).unwrap();
+        sleep(Duration::from_millis(100));
+        keeper.halt();
+
+        let contents = fs::read_to_string(&output).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(contents, "hello\nworld\n");
+        assert_eq!(keeper.metrics().processor("lines").unwrap().records_out, 2);
+        assert_eq!(keeper.metrics().processor("syslog").unwrap().records_out, 2);
+        assert_eq!(keeper.metrics().target("output").unwrap().records_out, 2);
+    }
+
+    #[test]
+    fn test_file_target_writes_fields() {
+        let dir = env::temp_dir().join(format!("loghaul_config_fields_test_{}", ::std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("input.log");
+        let output = dir.join("output.jsonl");
+        fs::File::create(&input).unwrap().write_all(b"{\"level\":\"warn\",\"took\":12}\n").unwrap();
+
+        let config = PipelineConfig::from_str(&format!(r#"
+            [keeper]
+            interval = "1ms"
+
+            [sources.input]
+            type = "file"
+            path = "{}"
+
+            [[transforms]]
+            type = "split_lines"
+
+            [[transforms]]
+            type = "json"
+
+            [[transforms]]
+            type = "enrich"
+            tags = {{ env = "prod" }}
+
+            [targets.output]
+            type = "file"
+            path = "{}"
+            format = "json"
+        "#, input.display(), output.display()));
+
+        let mut keeper = config.unwrap().start(None).unwrap();
+        sleep(Duration::from_millis(100));
+        keeper.halt();
+
+        let contents = fs::read_to_string(&output).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(contents, format!(
+            "{{\"source\":\"{}\",\"payload\":{},\"fields\":{}}}\n",
+            input.display(),
+            r#""{\"level\":\"warn\",\"took\":12}""#,
+            r#"{"env":"prod","level":"warn","took":12}"#,
+        ));
+    }
+}
@@ -0,0 +1,105 @@
+use std::collections::BTreeMap;
+use PipelineConfig;
+
+/// The changes needed to turn a running pipeline into a new one.
+/// Entries whose config is identical in both pipelines are left alone, so
+/// they keep their open files, offsets and buffers.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct PipelineDiff {
+    pub sources_added: Vec<String>,
+    pub sources_removed: Vec<String>,
+    pub sources_changed: Vec<String>,
+    pub sources_unchanged: Vec<String>,
+    pub targets_added: Vec<String>,
+    pub targets_removed: Vec<String>,
+    pub targets_changed: Vec<String>,
+    pub transforms_changed: bool,
+    pub routes_changed: bool,
+    pub keeper_changed: bool,
+}
+
+impl PipelineDiff {
+    pub fn between(old: &PipelineConfig, new: &PipelineConfig) -> PipelineDiff {
+        let mut diff = PipelineDiff::default();
+        {
+            let (added, removed, changed, unchanged) = diff_maps(&old.sources, &new.sources);
+            diff.sources_added = added;
+            diff.sources_removed = removed;
+            diff.sources_changed = changed;
+            diff.sources_unchanged = unchanged;
+        }
+        {
+            let (added, removed, changed, _) = diff_maps(&old.targets, &new.targets);
+            diff.targets_added = added;
+            diff.targets_removed = removed;
+            diff.targets_changed = changed;
+        }
+        diff.transforms_changed = old.transforms != new.transforms;
+        diff.routes_changed = old.routes != new.routes;
+        diff.keeper_changed = old.keeper != new.keeper;
+        return diff;
+    }
+
+    /// True if applying this diff would change nothing
+    pub fn is_empty(&self) -> bool {
+        return self.sources_added.is_empty() && self.sources_removed.is_empty() && self.sources_changed.is_empty()
+            && self.targets_added.is_empty() && self.targets_removed.is_empty() && self.targets_changed.is_empty()
+            && !self.transforms_changed && !self.routes_changed && !self.keeper_changed;
+    }
+}
+
+/// Split the keys of two maps into (added, removed, changed, unchanged)
+fn diff_maps<T: PartialEq>(old: &BTreeMap<String, T>, new: &BTreeMap<String, T>) -> (Vec<String>, Vec<String>, Vec<String>, Vec<String>) {
+    let added = new.keys().filter(|k| !old.contains_key(*k)).cloned().collect();
+    let removed = old.keys().filter(|k| !new.contains_key(*k)).cloned().collect();
+    let changed = new.iter().filter(|&(k, v)| old.get(k).map(|o| o != v).unwrap_or(false)).map(|(k, _)| k.clone()).collect();
+    let unchanged = new.iter().filter(|&(k, v)| old.get(k).map(|o| o == v).unwrap_or(false)).map(|(k, _)| k.clone()).collect();
+    return (added, removed, changed, unchanged);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PipelineDiff;
+    use PipelineConfig;
+
+    #[test]
+    fn test_diff_pipelines() {
+        let old = PipelineConfig::from_str(r#"
+            [sources.same]
+            type = "file"
+            path = "/a.log"
+            [sources.moved]
+            type = "file"
+            path = "/b.log"
+            [sources.gone]
+            type = "file"
+            path = "/c.log"
+            [targets.out]
+            type = "stdout"
+        "#).unwrap();
+        let new = PipelineConfig::from_str(r#"
+            [keeper]
+            interval = "1s"
+            [sources.same]
+            type = "file"
+            path = "/a.log"
+            [sources.moved]
+            type = "file"
+            path = "/b2.log"
+            [sources.fresh]
+            type = "glob"
+            pattern = "/d/*.log"
+            [targets.out]
+            type = "stdout"
+        "#).unwrap();
+
+        let diff = PipelineDiff::between(&old, &new);
+        assert_eq!(diff.sources_added, vec!("fresh"));
+        assert_eq!(diff.sources_removed, vec!("gone"));
+        assert_eq!(diff.sources_changed, vec!("moved"));
+        assert_eq!(diff.sources_unchanged, vec!("same"));
+        assert!(diff.targets_added.is_empty() && diff.targets_removed.is_empty() && diff.targets_changed.is_empty());
+        assert!(diff.keeper_changed);
+        assert!(PipelineDiff::between(&new, &new).is_empty());
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::BTreeMap;
+use loghaul::Keeper;
+use loghaul::KeeperChanges;
+use loghaul::KeeperLog;
+use loghaul::KeeperLogEntry;
+use loghaul::Source;
+use loghaul::Target;
+use PipelineConfig;
+use PipelineDiff;
+use LoghaulConfigError;
+use LoghaulConfigErrorCode;
+
+/// Keeps track of what a running keeper was built from, so a new config can be
+/// applied to it in place rather than restarting the whole pipeline.
+pub struct PipelineReloader {
+    config: PipelineConfig,
+    source_ids: BTreeMap<String, Vec<String>>,
+}
+
+impl PipelineReloader {
+    /// Build the pipeline and start a keeper running it
+    pub fn start(config: PipelineConfig, logger: Option<Box<KeeperLog + Send>>) -> Result<(Keeper, PipelineReloader), LoghaulConfigError> {
+        let (stream, source_ids) = config.build()?;
+        let mut keeper_config = config.keeper.to_keeper_config();
+        keeper_config.logger = logger;
+        let keeper = Keeper::new(stream, Some(keeper_config));
+        return Ok((keeper, PipelineReloader {
+            config,
+            source_ids,
+        }));
+    }
+
+    /// The config currently running
+    pub fn config(&self) -> &PipelineConfig {
+        return &self.config;
+    }
+
+    /// Move the running keeper to a new config.
+    /// If the new config is invalid, or any part of it can't be built, nothing is
+    /// changed: a `KeeperReloadRejected` entry is logged and the old pipeline keeps running.
+    pub fn reload(&mut self, keeper: &mut Keeper, next: Result<PipelineConfig, LoghaulConfigError>) -> Result<PipelineDiff, LoghaulConfigError> {
+        let next = match next {
+            Ok(n) => n,
+            Err(err) => {
+                keeper.notify(KeeperLogEntry::KeeperReloadRejected(err.message().to_string()));
+                return Err(err);
+            }
+        };
+
+        // Build everything up front, so a failure leaves the running pipeline untouched
+        let diff = PipelineDiff::between(&self.config, &next);
+        let mut new_sources: BTreeMap<String, Vec<Box<Source + Send + 'static>>> = BTreeMap::new();
+        for name in diff.sources_added.iter().chain(diff.sources_changed.iter()).chain(diff.sources_unchanged.iter()) {
+            match next.sources[name].build() {
+                Ok(sources) => {
+                    new_sources.insert(name.clone(), sources);
+                }
+                Err(err) => {
+                    let err = LoghaulConfigError::new(LoghaulConfigErrorCode::BuildFailed, &format!("sources.{}: {}", name, err));
+                    keeper.notify(KeeperLogEntry::KeeperReloadRejected(err.message().to_string()));
+                    return Err(err);
+                }
+            }
+        }
+        let new_processors = if diff.transforms_changed {
+            match next.build_processors() {
+                Ok(p) => Some(p),
+                Err(err) => {
+                    keeper.notify(KeeperLogEntry::KeeperReloadRejected(err.message().to_string()));
+                    return Err(err);
+                }
+            }
+        } else {
+            None
+        };
+        let mut new_targets: Vec<(String, Box<Target + Send + 'static>)> = Vec::new();
+        for name in diff.targets_added.iter().chain(diff.targets_changed.iter()) {
+            match next.targets[name].build() {
+                Ok(target) => new_targets.push((name.clone(), target)),
+                Err(err) => {
+                    let err = LoghaulConfigError::new(LoghaulConfigErrorCode::BuildFailed, &format!("targets.{}: {}", name, err));
+                    keeper.notify(KeeperLogEntry::KeeperReloadRejected(err.message().to_string()));
+                    return Err(err);
+                }
+            }
+        }
+
+        // Unchanged sources keep running; only files a glob didn't match before are added
+        let mut added_sources: Vec<Box<Source + Send + 'static>> = Vec::new();
+        let mut next_ids: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (name, sources) in new_sources.into_iter() {
+            let known = match diff.sources_unchanged.contains(&name) {
+                true => self.source_ids.get(&name).cloned().unwrap_or(Vec::new()),
+                false => Vec::new()
+            };
+            let mut ids = known.clone();
+            for source in sources.into_iter() {
+                let id = source.id().unwrap_or(String::new());
+                if !known.contains(&id) {
+                    ids.push(id);
+                    added_sources.push(source);
+                }
+            }
+            next_ids.insert(name, ids);
+        }
+
+        // While sources are swapped, route the old ids too so data drained from them is routed as before
+        let routes_apply = diff.routes_changed || (!next.routes.is_empty() && next_ids != self.source_ids);
+        let mut transition_ids = next_ids.clone();
+        for (name, ids) in self.source_ids.iter() {
+            transition_ids.entry(name.clone()).or_insert(Vec::new()).extend(ids.iter().cloned());
+        }
+        let (transition_router, final_router) = match (next.build_router(&transition_ids), next.build_router(&next_ids)) {
+            (Ok(transition), Ok(last)) => (transition, last),
+            (Err(err), _) | (_, Err(err)) => {
+                keeper.notify(KeeperLogEntry::KeeperReloadRejected(err.message().to_string()));
+                return Err(err);
+            }
+        };
+
+        // Everything is applied in a single step of the worker, so no record sees the pipeline
+        // half changed. Processors, routes and targets change first, so data drained from
+        // removed sources reaches the new targets.
+        let mut changes = KeeperChanges::default();
+        changes.processors = new_processors;
+        if routes_apply {
+            changes.transition_router = Some(transition_router);
+        }
+        changes.targets_removed = diff.targets_removed.iter().chain(diff.targets_changed.iter()).cloned().collect();
+        changes.targets_added = new_targets;
+        for name in diff.sources_removed.iter().chain(diff.sources_changed.iter()) {
+            changes.sources_removed.extend(self.source_ids.get(name).cloned().unwrap_or(Vec::new()));
+        }
+        changes.sources_added = added_sources;
+        if routes_apply && transition_ids != next_ids {
+            changes.router = Some(final_router);
+        }
+        if diff.keeper_changed {
+            let schedule = next.keeper.to_keeper_config();
+            changes.schedule = Some((schedule.interval, schedule.eof_strategy));
+        }
+        match keeper.reconfigure(changes) {
+            Ok(_) => {}
+            Err(e) => {
+                let err = LoghaulConfigError::new(LoghaulConfigErrorCode::BuildFailed, &format!("keeper: {}", e));
+                keeper.notify(KeeperLogEntry::KeeperReloadRejected(err.message().to_string()));
+                return Err(err);
+            }
+        }
+        self.source_ids = next_ids;
+
+        keeper.notify(KeeperLogEntry::KeeperReloaded);
+        self.config = next;
+        return Ok(diff);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PipelineReloader;
+    use PipelineConfig;
+    use loghaul::KeeperLogEntry;
+    use loghaul::mock::MockKeeperLog;
+    use std::env;
+    use std::fs;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::path::Path;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    fn append(path: &Path, line: &str) {
+        OpenOptions::new().create(true).append(true).open(path).unwrap().write_all(line.as_bytes()).unwrap();
+    }
+
+    fn config(input: &Path, output: &str) -> String {
+        format!(r#"
+            [keeper]
+            interval = "1ms"
+            [sources.input]
+            type = "file"
+            path = "{}"
+            [targets.output]
+            type = "file"
+            path = "{}"
+        "#, input.display(), output)
+    }
+
+    #[test]
+    fn test_reload_keeps_offsets_and_rejects_invalid_config() {
+        let dir = env::temp_dir().join(format!("loghaul_reload_test_{}", ::std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("input.log");
+        let first = dir.join("first.log");
+        let second = dir.join("second.log");
+        append(&input, "one\n");
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let initial = PipelineConfig::from_str(&config(&input, &first.to_string_lossy())).unwrap();
+        let (mut keeper, mut reloader) = PipelineReloader::start(initial, Some(Box::new(MockKeeperLog::new(log.clone())))).unwrap();
+        sleep(Duration::from_millis(50));
+
+        // An invalid config is rejected and the pipeline keeps running
+        let rejected = reloader.reload(&mut keeper, PipelineConfig::from_str("[keeper]\ninterval = \"soon\""));
+        assert!(rejected.is_err());
+
+        // Swap the target; the unchanged source must not re-read "one"
+        let diff = reloader.reload(&mut keeper, PipelineConfig::from_str(&config(&input, &second.to_string_lossy()))).unwrap();
+        assert_eq!(diff.targets_changed, vec!("output"));
+        assert_eq!(diff.sources_unchanged, vec!("input"));
+        sleep(Duration::from_millis(50));
+        append(&input, "two\n");
+        sleep(Duration::from_millis(50));
+        keeper.halt();
+
+        let first_contents = fs::read_to_string(&first).unwrap();
+        let second_contents = fs::read_to_string(&second).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(first_contents, "one\n");
+        assert_eq!(second_contents, "two\n");
+
+        let logs = MockKeeperLog::convert_to_vec(log);
+        assert!(logs.iter().any(|e| match e {
+            KeeperLogEntry::KeeperReloadRejected(message) => message.contains("keeper.interval"),
+            _ => false
+        }));
+        assert!(logs.contains(&KeeperLogEntry::KeeperReloaded));
+        assert!(logs.contains(&KeeperLogEntry::KeeperTargetAdded("output".to_string())));
+    }
+}
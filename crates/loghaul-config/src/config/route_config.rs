@@ -0,0 +1,155 @@
+use std::collections::BTreeMap;
+use loghaul::Route;
+use loghaul::RouteSelector;
+use loghaul::Router;
+use SourceConfig;
+use TargetConfig;
+use internal::config_regex::check_regex;
+
+/// A single `[[routes]]` entry.
+/// A route sends records matching every one of its selectors to its targets; a
+/// route without selectors matches everything. Records matching no route go to
+/// the route marked `default = true`, or are dropped if there isn't one.
+/// Without any routes, every record goes to every target.
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RouteConfig {
+    /// Names of the targets matching records are sent to
+    pub targets: Vec<String>,
+
+    /// Only match records read from one of these sources
+    #[serde(default)]
+    pub sources: Vec<String>,
+
+    /// Only match records whose fields have exactly these values
+    #[serde(default)]
+    pub fields: BTreeMap<String, String>,
+
+    /// Only match records whose payload matches this regex
+    pub payload: Option<String>,
+
+    /// Catch every record no other route matches
+    #[serde(default)]
+    pub default: bool,
+}
+
+impl RouteConfig {
+    /// Return a description of every problem with this route
+    pub fn validate(&self, index: usize, sources: &BTreeMap<String, SourceConfig>, targets: &BTreeMap<String, TargetConfig>) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.targets.is_empty() {
+            errors.push(format!("routes[{}].targets: at least one target is required", index));
+        }
+        for target in self.targets.iter().filter(|t| !targets.contains_key(*t)) {
+            errors.push(format!("routes[{}].targets: unknown target '{}'", index, target));
+        }
+        for source in self.sources.iter().filter(|s| !sources.contains_key(*s)) {
+            errors.push(format!("routes[{}].sources: unknown source '{}'", index, source));
+        }
+        match self.payload {
+            Some(ref pattern) => match check_regex(pattern) {
+                Ok(_) => {}
+                Err(e) => errors.push(format!("routes[{}].payload: {}", index, e))
+            },
+            None => {}
+        }
+        if self.default && (!self.sources.is_empty() || !self.fields.is_empty() || self.payload.is_some()) {
+            errors.push(format!("routes[{}]: a default route can't have sources, fields or payload", index));
+        }
+        return errors;
+    }
+
+    fn build(&self, source_ids: &BTreeMap<String, Vec<String>>) -> Result<Route, String> {
+        let targets: Vec<&str> = self.targets.iter().map(|t| t.as_str()).collect();
+        let mut route = Route::new(&targets);
+        if !self.sources.is_empty() {
+            let ids = self.sources.iter().flat_map(|s| source_ids.get(s).cloned().unwrap_or(Vec::new())).collect();
+            route = route.with_selector(RouteSelector::Source(ids));
+        }
+        for (key, value) in self.fields.iter() {
+            route = route.with_selector(RouteSelector::field_equals(key, value));
+        }
+        match self.payload {
+            Some(ref pattern) => {
+                route = route.with_selector(RouteSelector::payload(pattern).map_err(|e| e.to_string())?);
+            }
+            None => {}
+        }
+        return Ok(route);
+    }
+}
+
+/// Check that at most one route is marked as the default
+pub fn validate_routes(routes: &[RouteConfig]) -> Vec<String> {
+    let defaults: Vec<String> = routes.iter().enumerate().filter(|&(_, r)| r.default).map(|(i, _)| format!("routes[{}]", i)).collect();
+    if defaults.len() > 1 {
+        return vec!(format!("routes: only one default route is allowed, found {}", defaults.join(", ")));
+    }
+    return Vec::new();
+}
+
+/// Build a router from a list of routes, given the stream source ids each config source expanded to
+pub fn build_router(routes: &[RouteConfig], source_ids: &BTreeMap<String, Vec<String>>) -> Result<Router, String> {
+    let mut router = Router::new();
+    for (index, route) in routes.iter().enumerate() {
+        if route.default {
+            let targets: Vec<&str> = route.targets.iter().map(|t| t.as_str()).collect();
+            router = router.with_default(&targets);
+        } else {
+            router.add_route(route.build(source_ids).map_err(|e| format!("routes[{}]: {}", index, e))?);
+        }
+    }
+    return Ok(router);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use super::build_router;
+    use loghaul::Record;
+    use PipelineConfig;
+
+    #[test]
+    fn test_build_router() {
+        let config = PipelineConfig::from_str(r#"
+            [sources.api]
+            type = "file"
+            path = "/var/log/api.log"
+
+            [[routes]]
+            sources = ["api"]
+            payload = "ERROR"
+            targets = ["pager", "archive"]
+
+            [[routes]]
+            fields = { level = "debug" }
+            targets = ["console"]
+
+            [[routes]]
+            default = true
+            targets = ["archive"]
+
+            [targets.pager]
+            type = "file"
+            path = "/var/log/pager.log"
+
+            [targets.archive]
+            type = "file"
+            path = "/var/log/all.log"
+
+            [targets.console]
+            type = "stdout"
+        "#).unwrap();
+
+        let mut source_ids = BTreeMap::new();
+        source_ids.insert("api".to_string(), vec!("/var/log/api.log".to_string()));
+        let router = build_router(&config.routes, &source_ids).unwrap();
+
+        let mut record = Record::new("/var/log/api.log", b"ERROR failed".to_vec());
+        assert_eq!(router.select(&record), Some(vec!("pager", "archive")));
+        record.source = "/var/log/web.log".to_string();
+        assert_eq!(router.select(&record), Some(vec!("archive")));
+        record.set_field("level", "debug");
+        assert_eq!(router.select(&record), Some(vec!("console")));
+    }
+}
@@ -0,0 +1,59 @@
+use loghaul::Source;
+use loghaul_file::FileSource;
+use glob;
+
+/// A single `[sources.<name>]` entry
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum SourceConfig {
+    /// Tail a single file
+    File { path: String },
+
+    /// Tail every file matching a glob pattern when the pipeline is built
+    Glob { pattern: String },
+}
+
+impl SourceConfig {
+    /// Return a description of every problem with this source
+    pub fn validate(&self, name: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+        match self {
+            SourceConfig::File { path } => {
+                if path.trim().is_empty() {
+                    errors.push(format!("sources.{}.path: must not be empty", name));
+                }
+            }
+            SourceConfig::Glob { pattern } => {
+                match glob::Pattern::new(pattern) {
+                    Ok(_) => {}
+                    Err(e) => errors.push(format!("sources.{}.pattern: invalid glob '{}' at position {}: {}", name, pattern, e.pos, e.msg))
+                }
+            }
+        }
+        return errors;
+    }
+
+    /// Create the sources this entry describes; a glob may expand to any number of files.
+    pub fn build(&self) -> Result<Vec<Box<Source + Send + 'static>>, String> {
+        return match self {
+            SourceConfig::File { path } => Ok(vec!(Box::new(FileSource::new(path)))),
+            SourceConfig::Glob { pattern } => {
+                let paths = glob::glob(pattern).map_err(|e| e.to_string())?;
+                let mut sources: Vec<Box<Source + Send + 'static>> = Vec::new();
+                for path in paths {
+                    match path {
+                        Ok(p) => {
+                            if p.is_file() {
+                                sources.push(Box::new(FileSource::new(p)));
+                            }
+                        }
+                        Err(e) => {
+                            return Err(e.to_string());
+                        }
+                    }
+                }
+                Ok(sources)
+            }
+        };
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::BTreeMap;
+use loghaul::Target;
+use loghaul::RecordFormat;
+use loghaul_file::FileTarget;
+use loghaul_stdio::StdoutTarget;
+use loghaul_stdio::ExecTarget;
+use loghaul_http::WebhookTarget;
+use internal::config_duration::parse_duration;
+
+/// A single `[targets.<name>]` entry
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum TargetConfig {
+    /// Append every record to a file
+    File {
+        path: String,
+        #[serde(default)]
+        format: TargetFormatConfig,
+    },
+
+    /// Write every record to standard output
+    Stdout {
+        #[serde(default)]
+        format: TargetFormatConfig,
+    },
+
+    /// Post every record to an `http://` URL
+    Webhook {
+        url: String,
+        #[serde(default)]
+        headers: BTreeMap<String, String>,
+        /// How long to wait for the server, `5s` by default
+        timeout: Option<String>,
+        /// Records are sent as JSON unless set
+        #[serde(default = "TargetFormatConfig::json")]
+        format: TargetFormatConfig,
+    },
+
+    /// Run a program for every record, with the record on its standard input
+    Exec {
+        /// The program and its arguments
+        command: Vec<String>,
+        /// How long a command may run before it is killed, `30s` by default
+        timeout: Option<String>,
+        /// Records are written as JSON unless set
+        #[serde(default = "TargetFormatConfig::json")]
+        format: TargetFormatConfig,
+    },
+}
+
+/// How a target writes records
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum TargetFormatConfig {
+    /// One JSON object per line holding the source, payload and fields
+    Json,
+    /// The payload only, as it was read or last changed
+    Raw,
+}
+
+impl Default for TargetFormatConfig {
+    fn default() -> TargetFormatConfig {
+        TargetFormatConfig::Raw
+    }
+}
+
+impl TargetFormatConfig {
+    fn json() -> TargetFormatConfig {
+        TargetFormatConfig::Json
+    }
+
+    fn to_record_format(&self) -> RecordFormat {
+        return match self {
+            TargetFormatConfig::Json => RecordFormat::Json,
+            TargetFormatConfig::Raw => RecordFormat::Raw,
+        };
+    }
+}
+
+impl TargetConfig {
+    /// Return a description of every problem with this target
+    pub fn validate(&self, name: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+        match self {
+            TargetConfig::File { path, .. } => {
+                if path.trim().is_empty() {
+                    errors.push(format!("targets.{}.path: must not be empty", name));
+                }
+            }
+            TargetConfig::Stdout { .. } => {}
+            TargetConfig::Webhook { url, headers, timeout, .. } => {
+                match WebhookTarget::check_url(url) {
+                    Ok(_) => {}
+                    Err(e) => errors.push(format!("targets.{}.url: {}", name, e)),
+                }
+                for (header, value) in headers.iter() {
+                    if header.is_empty() || header.contains(|c: char| c == ':' || c.is_whitespace()) || value.contains(|c| c == '\r' || c == '\n') {
+                        errors.push(format!("targets.{}.headers.{}: invalid header", name, header));
+                    }
+                }
+                match timeout.as_ref().map(|timeout| parse_duration(timeout)) {
+                    Some(Ok(timeout)) if timeout.as_millis() == 0 => errors.push(format!("targets.{}.timeout: must be at least 1ms", name)),
+                    Some(Err(e)) => errors.push(format!("targets.{}.timeout: {}", name, e)),
+                    _ => {}
+                }
+            }
+            TargetConfig::Exec { command, timeout, .. } => {
+                if command.first().map(|program| program.trim().is_empty()).unwrap_or(true) {
+                    errors.push(format!("targets.{}.command: must name a program", name));
+                }
+                match timeout.as_ref().map(|timeout| parse_duration(timeout)) {
+                    Some(Ok(timeout)) if timeout.as_millis() == 0 => errors.push(format!("targets.{}.timeout: must be at least 1ms", name)),
+                    Some(Err(e)) => errors.push(format!("targets.{}.timeout: {}", name, e)),
+                    _ => {}
+                }
+            }
+        }
+        return errors;
+    }
+
+    /// Create the target this entry describes
+    pub fn build(&self) -> Result<Box<Target + Send + 'static>, String> {
+        return match self {
+            TargetConfig::File { path, format } => Ok(Box::new(FileTarget::new(path).with_format(format.to_record_format()))),
+            TargetConfig::Stdout { format } => Ok(Box::new(StdoutTarget::new().with_format(format.to_record_format()))),
+            TargetConfig::Webhook { url, headers, timeout, format } => {
+                let mut webhook = WebhookTarget::new(url).with_format(format.to_record_format());
+                for (header, value) in headers.iter() {
+                    webhook = webhook.with_header(header, value);
+                }
+                match timeout {
+                    Some(timeout) => webhook = webhook.with_timeout(parse_duration(timeout)?),
+                    None => {}
+                }
+                Ok(Box::new(webhook))
+            }
+            TargetConfig::Exec { command, timeout, format } => {
+                let command: Vec<&str> = command.iter().map(|c| c.as_str()).collect();
+                let mut exec = ExecTarget::new(&command).with_format(format.to_record_format());
+                match timeout {
+                    Some(timeout) => exec = exec.with_timeout(parse_duration(timeout)?),
+                    None => {}
+                }
+                Ok(Box::new(exec))
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use PipelineConfig;
+    use super::TargetConfig;
+    use super::TargetFormatConfig;
+
+    fn target_errors(targets: &str) -> Vec<String> {
+        let config = PipelineConfig::from_str(&format!(r#"
+            [sources.api]
+            type = "file"
+            path = "/var/log/api.log"
+            {}
+        "#, targets));
+        return config.unwrap_err().message().lines().map(|line| line.to_string()).collect();
+    }
+
+    #[test]
+    fn test_default_formats() {
+        let config = PipelineConfig::from_str(r#"
+            [sources.api]
+            type = "file"
+            path = "/var/log/api.log"
+
+            [targets.archive]
+            type = "file"
+            path = "/archive/all.log"
+
+            [targets.console]
+            type = "stdout"
+
+            [targets.pager]
+            type = "webhook"
+            url = "http://alerts.internal:8080/hooks/loghaul"
+
+            [targets.script]
+            type = "exec"
+            command = ["/usr/local/bin/page"]
+        "#).unwrap();
+        let format = |name: &str| match config.targets[name] {
+            TargetConfig::File { format, .. } | TargetConfig::Stdout { format } => format,
+            TargetConfig::Webhook { format, .. } | TargetConfig::Exec { format, .. } => format,
+        };
+        assert_eq!(format("archive"), TargetFormatConfig::Raw);
+        assert_eq!(format("console"), TargetFormatConfig::Raw);
+        assert_eq!(format("pager"), TargetFormatConfig::Json);
+        assert_eq!(format("script"), TargetFormatConfig::Json);
+    }
+
+    #[test]
+    fn test_webhook_and_exec_targets() {
+        let config = PipelineConfig::from_str(r#"
+            [sources.api]
+            type = "file"
+            path = "/var/log/api.log"
+
+            [targets.pager]
+            type = "webhook"
+            url = "http://alerts.internal:8080/hooks/loghaul"
+            headers = { Authorization = "Bearer secret" }
+            timeout = "2s"
+
+            [targets.script]
+            type = "exec"
+            command = ["/usr/local/bin/page", "--team", "ops"]
+            timeout = "10s"
+            format = "raw"
+        "#).unwrap();
+        assert_eq!(config.targets["pager"].build().unwrap().id(), Some("http://alerts.internal:8080/hooks/loghaul".to_string()));
+        assert_eq!(config.targets["script"].build().unwrap().id(), Some("/usr/local/bin/page --team ops".to_string()));
+
+        assert_eq!(target_errors(r#"
+            [targets.pager]
+            type = "webhook"
+            url = "https://hooks.example.com/alerts"
+            headers = { "X Team" = "ops" }
+            timeout = "soon"
+
+            [targets.script]
+            type = "exec"
+            command = []
+            timeout = "0s"
+        "#), vec!(
+            "targets.pager.url: invalid url 'https://hooks.example.com/alerts', https is not supported",
+            "targets.pager.headers.X Team: invalid header",
+            "targets.pager.timeout: invalid duration 'soon', expected a number followed by ms, s, m or h",
+            "targets.script.command: must name a program",
+            "targets.script.timeout: must be at least 1ms",
+        ));
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::BTreeMap;
+use serde::de;
+use serde::Deserialize;
+use serde::Deserializer;
+use toml;
+use loghaul::Processor;
+use loghaul::ProcessorChain;
+use loghaul::ProcessorErrorPolicy;
+use loghaul::SplitLinesProcessor;
+use loghaul_parse::SyslogProcessor;
+use TargetConfig;
+use config::transforms::multiline_settings::MultilineSettings;
+use config::transforms::json_settings::JsonSettings;
+use config::transforms::logfmt_settings::LogfmtSettings;
+use config::transforms::grok_settings::GrokSettings;
+use config::transforms::access_log_settings::AccessLogSettings;
+use config::transforms::timestamp_settings::TimestampSettings;
+use config::transforms::enrich_settings::EnrichSettings;
+use config::transforms::redact_settings::RedactSettings;
+use config::transforms::sample_settings::SampleSettings;
+use config::transforms::rate_limit_settings::RateLimitSettings;
+use config::transforms::dedup_settings::DedupSettings;
+use config::transforms::aggregate_settings::AggregateSettings;
+use config::transforms::alert_settings::AlertSettings;
+use config::transforms::template_settings::TemplateSettings;
+use config::transforms::order_settings::OrderSettings;
+use config::transforms::session_settings::SessionSettings;
+use config::transforms::script_settings::ScriptSettings;
+use config::transforms::wasm_settings::WasmSettings;
+
+/// What to do with a record when a transform fails on it
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum OnErrorConfig {
+    /// Discard the record
+    Drop,
+    /// Pass the record on unchanged
+    Pass,
+    /// Send the record, as it was before the transform, to the `dead_letter` target
+    #[serde(rename = "dead_letter")]
+    DeadLetter,
+}
+
+/// A single `[[transforms]]` entry; transforms run in the order they are declared.
+/// Every transform takes a `type`, an optional `id` used to label metrics, and
+/// an optional `on_error` policy, plus the settings for that type.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TransformConfig {
+    pub id: Option<String>,
+    pub on_error: OnErrorConfig,
+    /// The target failed records are sent to when `on_error` is `dead_letter`
+    pub dead_letter: Option<String>,
+    pub kind: TransformKind,
+}
+
+/// The type specific settings of a transform
+#[derive(Debug, PartialEq, Clone)]
+pub enum TransformKind {
+    /// Split each record into one record per line
+    SplitLines,
+    /// Merge lines belonging to one event, such as a stack trace, into one record
+    Multiline(MultilineSettings),
+    /// Parse each payload as a json object into fields
+    Json(JsonSettings),
+    /// Parse logfmt or other `key=value` pairs into fields
+    Logfmt(LogfmtSettings),
+    /// Extract fields with the first of several grok patterns to match
+    Grok(GrokSettings),
+    /// Parse RFC 3164 or RFC 5424 syslog lines into fields
+    Syslog,
+    /// Parse nginx or Apache access log lines into fields
+    AccessLog(AccessLogSettings),
+    /// Find the event time and write it to a field, normalized to UTC
+    Timestamp(TimestampSettings),
+    /// Add the host name, static tags and source path details to every record
+    Enrich(EnrichSettings),
+    /// Replace sensitive values such as emails, card numbers and keys
+    Redact(RedactSettings),
+    /// Keep one record in every N, at random or by the value of a key field
+    Sample(SampleSettings),
+    /// Limit the records per second from each source or key, summarizing what was dropped
+    RateLimit(RateLimitSettings),
+    /// Collapse repeated records into one and a count of the repeats
+    Dedup(DedupSettings),
+    /// Derive counters and histograms over windows of event time, emitted as summary records
+    Aggregate(AggregateSettings),
+    /// Emit an alert record when records match a rule more often than its threshold
+    Alert(AlertSettings),
+    /// Tag every record with the template of its message, learned as records arrive
+    Template(TemplateSettings),
+    /// Hold records back for a delay and release them sorted by event time across sources
+    Order(OrderSettings),
+    /// Combine records sharing a key field into one session record once the key goes quiet
+    Session(SessionSettings),
+    /// Run a Rhai script on every record
+    Script(ScriptSettings),
+    /// Run every record through a WebAssembly plugin
+    Wasm(WasmSettings),
+}
+
+/// Settings for transforms that don't take any
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(deny_unknown_fields)]
+struct NoSettings {}
+
+impl TransformConfig {
+    /// Return a description of every problem with this transform
+    pub fn validate(&self, index: usize, targets: &BTreeMap<String, TargetConfig>) -> Vec<String> {
+        let mut errors = Vec::new();
+        match self.id {
+            Some(ref id) if id.trim().is_empty() => errors.push(format!("transforms[{}].id: must not be empty", index)),
+            _ => {}
+        }
+        match (self.on_error, self.dead_letter.as_ref()) {
+            (OnErrorConfig::DeadLetter, None) => {
+                errors.push(format!("transforms[{}].dead_letter: required when on_error is \"dead_letter\"", index));
+            }
+            (OnErrorConfig::DeadLetter, Some(target)) if !targets.contains_key(target) => {
+                errors.push(format!("transforms[{}].dead_letter: unknown target '{}'", index, target));
+            }
+            (OnErrorConfig::DeadLetter, Some(_)) => {}
+            (_, Some(_)) => {
+                errors.push(format!("transforms[{}].dead_letter: only valid when on_error is \"dead_letter\"", index));
+            }
+            (_, None) => {}
+        }
+        let prefix = format!("transforms[{}]", index);
+        match self.kind {
+            TransformKind::SplitLines | TransformKind::Syslog => {}
+            TransformKind::Multiline(ref settings) => errors.extend(settings.validate(&prefix)),
+            TransformKind::Json(ref settings) => errors.extend(settings.validate(&prefix)),
+            TransformKind::Logfmt(ref settings) => errors.extend(settings.validate(&prefix)),
+            TransformKind::Grok(ref settings) => errors.extend(settings.validate(&prefix)),
+            TransformKind::AccessLog(ref settings) => errors.extend(settings.validate(&prefix)),
+            TransformKind::Timestamp(ref settings) => errors.extend(settings.validate(&prefix)),
+            TransformKind::Enrich(ref settings) => errors.extend(settings.validate(&prefix)),
+            TransformKind::Redact(ref settings) => errors.extend(settings.validate(&prefix)),
+            TransformKind::Sample(ref settings) => errors.extend(settings.validate(&prefix)),
+            TransformKind::RateLimit(ref settings) => errors.extend(settings.validate(&prefix)),
+            TransformKind::Dedup(ref settings) => errors.extend(settings.validate(&prefix)),
+            TransformKind::Aggregate(ref settings) => errors.extend(settings.validate(&prefix, targets)),
+            TransformKind::Alert(ref settings) => errors.extend(settings.validate(&prefix, targets)),
+            TransformKind::Template(ref settings) => errors.extend(settings.validate(&prefix, targets)),
+            TransformKind::Order(ref settings) => errors.extend(settings.validate(&prefix, targets)),
+            TransformKind::Session(ref settings) => errors.extend(settings.validate(&prefix, targets)),
+            TransformKind::Script(ref settings) => errors.extend(settings.validate(&prefix)),
+            TransformKind::Wasm(ref settings) => errors.extend(settings.validate(&prefix)),
+        }
+        return errors;
+    }
+
+    /// Create the processor this entry describes
+    pub fn build(&self) -> Result<Box<Processor + Send + 'static>, String> {
+        return match self.kind {
+            TransformKind::SplitLines => Ok(Box::new(SplitLinesProcessor::new())),
+            TransformKind::Syslog => Ok(Box::new(SyslogProcessor::new())),
+            TransformKind::Multiline(ref settings) => Ok(Box::new(settings.build()?)),
+            TransformKind::Json(ref settings) => Ok(Box::new(settings.build()?)),
+            TransformKind::Logfmt(ref settings) => Ok(Box::new(settings.build()?)),
+            TransformKind::Grok(ref settings) => Ok(Box::new(settings.build()?)),
+            TransformKind::AccessLog(ref settings) => Ok(Box::new(settings.build()?)),
+            TransformKind::Timestamp(ref settings) => Ok(Box::new(settings.build()?)),
+            TransformKind::Enrich(ref settings) => Ok(Box::new(settings.build()?)),
+            TransformKind::Redact(ref settings) => Ok(Box::new(settings.build()?)),
+            TransformKind::Sample(ref settings) => Ok(Box::new(settings.build()?)),
+            TransformKind::RateLimit(ref settings) => Ok(Box::new(settings.build()?)),
+            TransformKind::Dedup(ref settings) => Ok(Box::new(settings.build()?)),
+            TransformKind::Aggregate(ref settings) => Ok(Box::new(settings.build()?)),
+            TransformKind::Alert(ref settings) => Ok(Box::new(settings.build()?)),
+            TransformKind::Template(ref settings) => Ok(Box::new(settings.build()?)),
+            TransformKind::Order(ref settings) => Ok(Box::new(settings.build()?)),
+            TransformKind::Session(ref settings) => Ok(Box::new(settings.build()?)),
+            TransformKind::Script(ref settings) => Ok(Box::new(settings.build()?)),
+            TransformKind::Wasm(ref settings) => Ok(Box::new(settings.build()?)),
+        };
+    }
+
+    fn policy(&self) -> ProcessorErrorPolicy {
+        return match self.on_error {
+            OnErrorConfig::Drop => ProcessorErrorPolicy::Drop,
+            OnErrorConfig::Pass => ProcessorErrorPolicy::PassThrough,
+            OnErrorConfig::DeadLetter => ProcessorErrorPolicy::DeadLetter(self.dead_letter.clone().unwrap_or(String::new())),
+        };
+    }
+}
+
+/// Build a processor chain from a list of transforms
+pub fn build_processor_chain(transforms: &[TransformConfig]) -> Result<ProcessorChain, String> {
+    let mut chain = ProcessorChain::new();
+    for (index, transform) in transforms.iter().enumerate() {
+        let processor = transform.build().map_err(|e| format!("transforms[{}]: {}", index, e))?;
+        chain.push(transform.id.clone(), transform.policy(), processor);
+    }
+    return Ok(chain);
+}
+
+impl<'de> Deserialize<'de> for TransformConfig {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut table = toml::value::Table::deserialize(deserializer)?;
+        let kind = match table.remove("type") {
+            Some(toml::Value::String(kind)) => kind,
+            Some(other) => {
+                return Err(de::Error::custom(format!("transform type must be a string, found {}", other.type_str())));
+            }
+            None => {
+                return Err(de::Error::missing_field("type"));
+            }
+        };
+        let id = match table.remove("id") {
+            Some(toml::Value::String(id)) => Some(id),
+            Some(other) => {
+                return Err(de::Error::custom(format!("transform '{}': id must be a string, found {}", kind, other.type_str())));
+            }
+            None => None
+        };
+        let on_error = match table.remove("on_error") {
+            Some(value) => value.try_into::<OnErrorConfig>()
+                .map_err(|e| de::Error::custom(format!("transform '{}': on_error: {}", kind, e)))?,
+            None => OnErrorConfig::Drop
+        };
+        let dead_letter = match table.remove("dead_letter") {
+            Some(toml::Value::String(target)) => Some(target),
+            Some(other) => {
+                return Err(de::Error::custom(format!("transform '{}': dead_letter must be a string, found {}", kind, other.type_str())));
+            }
+            None => None
+        };
+        let settings = toml::Value::Table(table);
+        let kind = match kind.as_str() {
+            "split_lines" => {
+                settings_of::<NoSettings, D::Error>(&kind, settings)?;
+                TransformKind::SplitLines
+            }
+            "multiline" => TransformKind::Multiline(settings_of(&kind, settings)?),
+            "json" => TransformKind::Json(settings_of(&kind, settings)?),
+            "logfmt" => TransformKind::Logfmt(settings_of(&kind, settings)?),
+            "grok" => TransformKind::Grok(settings_of(&kind, settings)?),
+            "syslog" => {
+                settings_of::<NoSettings, D::Error>(&kind, settings)?;
+                TransformKind::Syslog
+            }
+            "access_log" => TransformKind::AccessLog(settings_of(&kind, settings)?),
+            "timestamp" => TransformKind::Timestamp(settings_of(&kind, settings)?),
+            "enrich" => TransformKind::Enrich(settings_of(&kind, settings)?),
+            "redact" => TransformKind::Redact(settings_of(&kind, settings)?),
+            "sample" => TransformKind::Sample(settings_of(&kind, settings)?),
+            "rate_limit" => TransformKind::RateLimit(settings_of(&kind, settings)?),
+            "dedup" => TransformKind::Dedup(settings_of(&kind, settings)?),
+            "aggregate" => TransformKind::Aggregate(settings_of(&kind, settings)?),
+            "alert" => TransformKind::Alert(settings_of(&kind, settings)?),
+            "template" => TransformKind::Template(settings_of(&kind, settings)?),
+            "order" => TransformKind::Order(settings_of(&kind, settings)?),
+            "session" => TransformKind::Session(settings_of(&kind, settings)?),
+            "script" => TransformKind::Script(settings_of(&kind, settings)?),
+            "wasm" => TransformKind::Wasm(settings_of(&kind, settings)?),
+            other => {
+                return Err(de::Error::custom(format!("unknown transform type '{}'", other)));
+            }
+        };
+        return Ok(TransformConfig {
+            id,
+            on_error,
+            dead_letter,
+            kind,
+        });
+    }
+}
+
+/// Deserialize the type specific settings of a transform, naming the transform in any error
+fn settings_of<T: de::DeserializeOwned, E: de::Error>(kind: &str, settings: toml::Value) -> Result<T, E> {
+    return settings.try_into::<T>().map_err(|e| E::custom(format!("transform '{}': {}", kind, e)));
+}
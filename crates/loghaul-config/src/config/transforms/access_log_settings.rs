@@ -0,0 +1,81 @@
+use loghaul_parse::AccessLogFormat;
+use loghaul_parse::AccessLogProcessor;
+
+/// The syntax of a custom access log format
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessLogStyle {
+    /// An nginx `log_format` string, eg. `$remote_addr [$time_local] "$request" $status`
+    Nginx,
+    /// An Apache `LogFormat` string, eg. `%h %t "%r" %>s`
+    Apache,
+}
+
+/// Settings for the `access_log` transform
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AccessLogSettings {
+    /// The format lines are written in; the combined format if unset
+    pub format: Option<String>,
+
+    #[serde(default = "default_access_log_style")]
+    pub style: AccessLogStyle,
+}
+
+impl AccessLogSettings {
+    /// Return a description of every problem with these settings, each starting with `prefix`
+    pub fn validate(&self, prefix: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+        match self.compile() {
+            Ok(_) => {}
+            Err(e) => errors.push(format!("{}.format: {}", prefix, e))
+        }
+        return errors;
+    }
+
+    /// Create the processor these settings describe
+    pub fn build(&self) -> Result<AccessLogProcessor, String> {
+        return Ok(AccessLogProcessor::with_format(self.compile()?));
+    }
+
+    fn compile(&self) -> Result<AccessLogFormat, String> {
+        return match (self.format.as_ref(), self.style) {
+            (None, _) => Ok(AccessLogFormat::combined()),
+            (Some(format), AccessLogStyle::Nginx) => AccessLogFormat::nginx(format),
+            (Some(format), AccessLogStyle::Apache) => AccessLogFormat::apache(format),
+        };
+    }
+}
+
+fn default_access_log_style() -> AccessLogStyle {
+    AccessLogStyle::Nginx
+}
+
+#[cfg(test)]
+mod tests {
+    use loghaul::Record;
+    use loghaul::RecordValue;
+    use internal::config_test_helpers::TestChain;
+    use internal::config_test_helpers::config_errors;
+
+    #[test]
+    fn test_access_log_transform() {
+        let mut chain = TestChain::new(r#"
+            [[transforms]]
+            type = "access_log"
+            format = '$remote_addr "$request" $status $request_time'
+        "#);
+        chain.run(Record::new("web", b"10.0.0.1 \"GET / HTTP/1.1\" 200 0.004\n".to_vec()));
+        assert_eq!(chain.output[0].field("method"), Some(&RecordValue::from("GET")));
+        assert_eq!(chain.output[0].field("request_time"), Some(&RecordValue::Float(0.004)));
+
+        assert_eq!(config_errors(r#"
+            [[transforms]]
+            type = "access_log"
+            style = "apache"
+            format = "%h %Q"
+        "#), vec!(
+            "transforms[0].format: unsupported directive '%Q' in '%h %Q'",
+        ));
+    }
+}
@@ -0,0 +1,248 @@
+use std::collections::BTreeMap;
+use loghaul::AggregateMetric;
+use loghaul::AggregateProcessor;
+use loghaul::RouteSelector;
+use TargetConfig;
+use internal::config_regex::check_regex;
+use internal::config_duration::parse_duration;
+use internal::config_defaults::default_max_keys;
+use internal::config_defaults::default_timestamp_target;
+
+/// Settings for the `aggregate` transform
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AggregateSettings {
+    /// The length of each window
+    pub window: String,
+
+    /// How long after a window ends records for it are still accepted
+    pub lateness: Option<String>,
+
+    /// The field holding the event time, as RFC 3339 or milliseconds since the epoch
+    #[serde(default = "default_timestamp_target")]
+    pub time_field: String,
+
+    /// Place records by the time they are processed, ignoring event times
+    #[serde(default)]
+    pub processing_time: bool,
+
+    /// Send summaries only to this target, instead of routing them
+    pub target: Option<String>,
+
+    /// The source summaries come from, `aggregate` by default
+    pub source: Option<String>,
+
+    /// The most groups to keep across the open windows
+    #[serde(default = "default_max_keys")]
+    pub max_groups: usize,
+
+    /// The metrics to derive
+    pub metrics: Vec<AggregateMetricConfig>,
+}
+
+/// What an aggregated metric measures
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum AggregateMetricType {
+    /// The number of matching records
+    Count,
+    /// The distribution of the `field` of matching records
+    Histogram,
+}
+
+/// A single metric of the `aggregate` transform
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AggregateMetricConfig {
+    /// The name summaries carry in their `metric` field
+    pub name: String,
+
+    #[serde(rename = "type")]
+    pub kind: AggregateMetricType,
+
+    /// The numeric field a histogram measures
+    pub field: Option<String>,
+
+    /// Keep the metric separately for each combination of values of these fields
+    #[serde(default)]
+    pub group_by: Vec<String>,
+
+    /// Only measure records whose fields have exactly these values
+    #[serde(default)]
+    pub fields: BTreeMap<String, String>,
+
+    /// Only measure records whose fields match these regexes
+    #[serde(default)]
+    pub matches: BTreeMap<String, String>,
+}
+
+impl AggregateSettings {
+    /// Return a description of every problem with these settings, each starting with `prefix`
+    pub fn validate(&self, prefix: &str, targets: &BTreeMap<String, TargetConfig>) -> Vec<String> {
+        let mut errors = Vec::new();
+        match parse_duration(&self.window) {
+            Ok(window) if window.as_millis() == 0 => errors.push(format!("{}.window: must be at least 1ms", prefix)),
+            Ok(_) => {}
+            Err(e) => errors.push(format!("{}.window: {}", prefix, e))
+        }
+        match self.lateness.as_ref().map(|lateness| parse_duration(lateness)) {
+            Some(Err(e)) => errors.push(format!("{}.lateness: {}", prefix, e)),
+            _ => {}
+        }
+        if self.time_field.trim().is_empty() {
+            errors.push(format!("{}.time_field: must not be empty", prefix));
+        }
+        match self.target {
+            Some(ref target) if !targets.contains_key(target) => {
+                errors.push(format!("{}.target: unknown target '{}'", prefix, target));
+            }
+            _ => {}
+        }
+        if self.max_groups == 0 {
+            errors.push(format!("{}.max_groups: must be greater than zero", prefix));
+        }
+        if self.metrics.is_empty() {
+            errors.push(format!("{}.metrics: at least one metric is required", prefix));
+        }
+        for (position, metric) in self.metrics.iter().enumerate() {
+            errors.extend(metric.validate(&format!("{}.metrics[{}]", prefix, position)));
+        }
+        return errors;
+    }
+
+    /// Create the processor these settings describe
+    pub fn build(&self) -> Result<AggregateProcessor, String> {
+        let mut aggregate = AggregateProcessor::new(parse_duration(&self.window)?)
+            .with_max_groups(self.max_groups);
+        aggregate = match self.processing_time {
+            true => aggregate.with_processing_time(),
+            false => aggregate.with_time_field(&self.time_field),
+        };
+        match self.lateness {
+            Some(ref lateness) => aggregate = aggregate.with_lateness(parse_duration(lateness)?),
+            None => {}
+        }
+        match self.target {
+            Some(ref target) => aggregate = aggregate.with_target(target),
+            None => {}
+        }
+        match self.source {
+            Some(ref source) => aggregate = aggregate.with_source(source),
+            None => {}
+        }
+        for (position, metric) in self.metrics.iter().enumerate() {
+            aggregate = aggregate.with_metric(metric.build().map_err(|e| format!("metrics[{}]: {}", position, e))?);
+        }
+        return Ok(aggregate);
+    }
+}
+
+impl AggregateMetricConfig {
+    fn validate(&self, prefix: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.name.trim().is_empty() {
+            errors.push(format!("{}.name: must not be empty", prefix));
+        }
+        match (self.kind, self.field.as_ref()) {
+            (AggregateMetricType::Histogram, None) => errors.push(format!("{}.field: required for a histogram", prefix)),
+            (AggregateMetricType::Histogram, Some(field)) if field.trim().is_empty() => errors.push(format!("{}.field: must not be empty", prefix)),
+            (AggregateMetricType::Count, Some(_)) => errors.push(format!("{}.field: only valid for a histogram", prefix)),
+            _ => {}
+        }
+        if self.group_by.iter().any(|f| f.trim().is_empty()) {
+            errors.push(format!("{}.group_by: field names must not be empty", prefix));
+        }
+        for (field, pattern) in self.matches.iter() {
+            match check_regex(pattern) {
+                Ok(_) => {}
+                Err(e) => errors.push(format!("{}.matches.{}: {}", prefix, field, e))
+            }
+        }
+        return errors;
+    }
+
+    fn build(&self) -> Result<AggregateMetric, String> {
+        let mut metric = match self.kind {
+            AggregateMetricType::Count => AggregateMetric::count(&self.name),
+            AggregateMetricType::Histogram => AggregateMetric::histogram(&self.name, self.field.as_ref().map(|f| f.as_str()).unwrap_or("")),
+        };
+        metric = metric.with_group_by(&self.group_by.iter().map(|f| f.as_str()).collect::<Vec<_>>());
+        for (field, value) in self.fields.iter() {
+            metric = metric.with_selector(RouteSelector::field_equals(field, value));
+        }
+        for (field, pattern) in self.matches.iter() {
+            metric = metric.with_selector(RouteSelector::field_matches(field, pattern).map_err(|e| e.to_string())?);
+        }
+        return Ok(metric);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use loghaul::Record;
+    use internal::config_test_helpers::TestChain;
+    use internal::config_test_helpers::config_errors;
+
+    #[test]
+    fn test_aggregate_transform() {
+        let mut chain = TestChain::new(r#"
+            [[transforms]]
+            type = "aggregate"
+            window = "1m"
+            lateness = "10s"
+            target = "metrics"
+
+            [[transforms.metrics]]
+            name = "http_5xx"
+            type = "count"
+            group_by = ["path"]
+            matches = { status = "^5" }
+
+            [[transforms.metrics]]
+            name = "latency"
+            type = "histogram"
+            field = "dur"
+            fields = { path = "/a" }
+
+            [targets.metrics]
+            type = "stdout"
+        "#);
+        for (time, path, status) in vec!(("10:00:01", "/a", 500), ("10:00:02", "/b", 200), ("10:00:30", "/a", 503), ("10:01:10", "/b", 502)).into_iter() {
+            let mut record = Record::new("api", b"request\n".to_vec());
+            record.set_field("timestamp", format!("2024-05-01T{}Z", time));
+            record.set_field("path", path);
+            record.set_field("status", status);
+            record.set_field("dur", 12);
+            chain.run(record);
+        }
+        let summaries: Vec<&Record> = chain.output.iter().filter(|r| r.source == "aggregate").collect();
+        assert_eq!(summaries.iter().map(|r| r.payload_str().unwrap()).collect::<Vec<_>>(), vec!(
+            "metric=http_5xx window_start=2024-05-01T10:00:00.000Z window_end=2024-05-01T10:01:00.000Z path=/a count=2\n",
+            "metric=latency window_start=2024-05-01T10:00:00.000Z window_end=2024-05-01T10:01:00.000Z count=2 sum=24 min=12 max=12 p50=12 p95=12 p99=12\n",
+        ));
+        assert!(summaries.iter().all(|r| r.route == Some("metrics".to_string())));
+
+        assert_eq!(config_errors(r#"
+            [[transforms]]
+            type = "aggregate"
+            window = "0s"
+            target = "nowhere"
+
+            [[transforms.metrics]]
+            name = "latency"
+            type = "histogram"
+            matches = { status = "(" }
+
+            [[transforms.metrics]]
+            name = "lines"
+            type = "count"
+            field = "dur"
+        "#), vec!(
+            "transforms[0].window: must be at least 1ms",
+            "transforms[0].target: unknown target 'nowhere'",
+            "transforms[0].metrics[0].field: required for a histogram",
+            "transforms[0].metrics[0].matches.status: invalid regex '(': unclosed group",
+            "transforms[0].metrics[1].field: only valid for a histogram",
+        ));
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::BTreeMap;
+use loghaul::AlertRule;
+use loghaul::AlertProcessor;
+use loghaul::RouteSelector;
+use TargetConfig;
+use internal::config_regex::check_regex;
+use internal::config_duration::parse_duration;
+use internal::config_defaults::default_max_keys;
+
+/// Settings for the `alert` transform
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AlertSettings {
+    /// The target alerts are sent to
+    pub target: String,
+
+    /// The source alerts come from, `alert` by default
+    pub source: Option<String>,
+
+    /// The most rule and group pairs to track at once
+    #[serde(default = "default_max_keys")]
+    pub max_groups: usize,
+
+    /// The rules to alert on
+    pub rules: Vec<AlertRuleConfig>,
+}
+
+/// A single rule of the `alert` transform
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AlertRuleConfig {
+    /// The name alerts carry in their `alert` field
+    pub name: String,
+
+    /// Fire when more than this many records match within the window
+    #[serde(default)]
+    pub threshold: u64,
+
+    /// How far back matches are counted
+    pub window: String,
+
+    /// How long to stay quiet after firing, the length of the window by default
+    pub cooldown: Option<String>,
+
+    /// Count and alert separately for each combination of values of these fields
+    #[serde(default)]
+    pub group_by: Vec<String>,
+
+    /// Only count records whose payload matches this regex
+    pub payload: Option<String>,
+
+    /// Only count records whose fields have exactly these values
+    #[serde(default)]
+    pub fields: BTreeMap<String, String>,
+
+    /// Only count records whose fields match these regexes
+    #[serde(default)]
+    pub matches: BTreeMap<String, String>,
+}
+
+impl AlertSettings {
+    /// Return a description of every problem with these settings, each starting with `prefix`
+    pub fn validate(&self, prefix: &str, targets: &BTreeMap<String, TargetConfig>) -> Vec<String> {
+        let mut errors = Vec::new();
+        if !targets.contains_key(&self.target) {
+            errors.push(format!("{}.target: unknown target '{}'", prefix, self.target));
+        }
+        if self.max_groups == 0 {
+            errors.push(format!("{}.max_groups: must be greater than zero", prefix));
+        }
+        if self.rules.is_empty() {
+            errors.push(format!("{}.rules: at least one rule is required", prefix));
+        }
+        for (position, rule) in self.rules.iter().enumerate() {
+            errors.extend(rule.validate(&format!("{}.rules[{}]", prefix, position)));
+        }
+        return errors;
+    }
+
+    /// Create the processor these settings describe
+    pub fn build(&self) -> Result<AlertProcessor, String> {
+        let mut alerts = AlertProcessor::new()
+            .with_target(&self.target)
+            .with_max_groups(self.max_groups);
+        match self.source {
+            Some(ref source) => alerts = alerts.with_source(source),
+            None => {}
+        }
+        for (position, rule) in self.rules.iter().enumerate() {
+            alerts = alerts.with_rule(rule.build().map_err(|e| format!("rules[{}]: {}", position, e))?);
+        }
+        return Ok(alerts);
+    }
+}
+
+impl AlertRuleConfig {
+    fn validate(&self, prefix: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.name.trim().is_empty() {
+            errors.push(format!("{}.name: must not be empty", prefix));
+        }
+        match parse_duration(&self.window) {
+            Ok(window) if window.as_secs() == 0 => errors.push(format!("{}.window: must be at least 1s", prefix)),
+            Ok(_) => {}
+            Err(e) => errors.push(format!("{}.window: {}", prefix, e))
+        }
+        match self.cooldown.as_ref().map(|cooldown| parse_duration(cooldown)) {
+            Some(Err(e)) => errors.push(format!("{}.cooldown: {}", prefix, e)),
+            _ => {}
+        }
+        if self.group_by.iter().any(|f| f.trim().is_empty()) {
+            errors.push(format!("{}.group_by: field names must not be empty", prefix));
+        }
+        match self.payload.as_ref().map(|pattern| check_regex(pattern)) {
+            Some(Err(e)) => errors.push(format!("{}.payload: {}", prefix, e)),
+            _ => {}
+        }
+        for (field, pattern) in self.matches.iter() {
+            match check_regex(pattern) {
+                Ok(_) => {}
+                Err(e) => errors.push(format!("{}.matches.{}: {}", prefix, field, e))
+            }
+        }
+        if self.payload.is_none() && self.fields.is_empty() && self.matches.is_empty() {
+            errors.push(format!("{}: at least one of payload, fields or matches is required", prefix));
+        }
+        return errors;
+    }
+
+    fn build(&self) -> Result<AlertRule, String> {
+        let mut rule = AlertRule::new(&self.name, self.threshold, parse_duration(&self.window)?)
+            .with_group_by(&self.group_by.iter().map(|f| f.as_str()).collect::<Vec<_>>());
+        match self.cooldown {
+            Some(ref cooldown) => rule = rule.with_cooldown(parse_duration(cooldown)?),
+            None => {}
+        }
+        match self.payload {
+            Some(ref pattern) => rule = rule.with_selector(RouteSelector::payload(pattern).map_err(|e| e.to_string())?),
+            None => {}
+        }
+        for (field, value) in self.fields.iter() {
+            rule = rule.with_selector(RouteSelector::field_equals(field, value));
+        }
+        for (field, pattern) in self.matches.iter() {
+            rule = rule.with_selector(RouteSelector::field_matches(field, pattern).map_err(|e| e.to_string())?);
+        }
+        return Ok(rule);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use loghaul::Record;
+    use internal::config_test_helpers::TestChain;
+    use internal::config_test_helpers::config_errors;
+
+    #[test]
+    fn test_alert_transform() {
+        let mut chain = TestChain::new(r#"
+            [[transforms]]
+            type = "alert"
+            target = "pager"
+
+            [[transforms.rules]]
+            name = "out_of_memory"
+            threshold = 1
+            window = "5m"
+            payload = "OutOfMemoryError"
+            group_by = ["hostname"]
+
+            [targets.pager]
+            type = "stdout"
+        "#);
+        for host in vec!("web-1", "web-2", "web-1", "web-1").into_iter() {
+            let mut record = Record::new("api", b"java.lang.OutOfMemoryError\n".to_vec());
+            record.set_field("hostname", host);
+            chain.run(record);
+        }
+        let alerts: Vec<&Record> = chain.output.iter().filter(|r| r.source == "alert").collect();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].payload_str(), Some("alert out_of_memory: 2 matching records within 300s (hostname=web-1)\n"));
+        assert_eq!(alerts[0].route, Some("pager".to_string()));
+
+        assert_eq!(config_errors(r#"
+            [[transforms]]
+            type = "alert"
+            target = "nowhere"
+
+            [[transforms.rules]]
+            name = "errors"
+            window = "soon"
+
+            [[transforms.rules]]
+            name = "panics"
+            window = "1m"
+            cooldown = "1 hour"
+            payload = "("
+        "#), vec!(
+            "transforms[0].target: unknown target 'nowhere'",
+            "transforms[0].rules[0].window: invalid duration 'soon', expected a number followed by ms, s, m or h",
+            "transforms[0].rules[0]: at least one of payload, fields or matches is required",
+            "transforms[0].rules[1].cooldown: invalid duration '1 hour', unknown unit ' hour' (expected ms, s, m or h)",
+            "transforms[0].rules[1].payload: invalid regex '(': unclosed group",
+        ));
+    }
+}
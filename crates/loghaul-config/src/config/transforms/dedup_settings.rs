@@ -0,0 +1,104 @@
+use loghaul::DedupProcessor;
+use internal::config_duration::parse_duration;
+use internal::config_defaults::default_max_keys;
+
+/// Settings for the `dedup` transform
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct DedupSettings {
+    /// Compare these fields instead of the payload
+    pub fields: Option<Vec<String>>,
+
+    /// Collapse repeats within this long of the first record, rather than only consecutive ones
+    pub window: Option<String>,
+
+    /// End a run of consecutive repeats once none has arrived for this long
+    #[serde(default = "default_dedup_timeout")]
+    pub timeout: String,
+
+    /// The most windows to track at once
+    #[serde(default = "default_max_keys")]
+    pub max_keys: usize,
+}
+
+impl DedupSettings {
+    /// Return a description of every problem with these settings, each starting with `prefix`
+    pub fn validate(&self, prefix: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+        match self.fields {
+            Some(ref fields) if fields.is_empty() => errors.push(format!("{}.fields: at least one field is required", prefix)),
+            Some(ref fields) if fields.iter().any(|f| f.trim().is_empty()) => {
+                errors.push(format!("{}.fields: field names must not be empty", prefix));
+            }
+            _ => {}
+        }
+        match self.window.as_ref().map(|window| parse_duration(window)) {
+            Some(Err(e)) => errors.push(format!("{}.window: {}", prefix, e)),
+            _ => {}
+        }
+        match parse_duration(&self.timeout) {
+            Ok(_) => {}
+            Err(e) => errors.push(format!("{}.timeout: {}", prefix, e))
+        }
+        if self.max_keys == 0 {
+            errors.push(format!("{}.max_keys: must be greater than zero", prefix));
+        }
+        return errors;
+    }
+
+    /// Create the processor these settings describe
+    pub fn build(&self) -> Result<DedupProcessor, String> {
+        let mut dedup = DedupProcessor::new()
+            .with_timeout(parse_duration(&self.timeout)?)
+            .with_max_keys(self.max_keys);
+        match self.fields {
+            Some(ref fields) => dedup = dedup.with_fields(&fields.iter().map(|f| f.as_str()).collect::<Vec<_>>()),
+            None => {}
+        }
+        match self.window {
+            Some(ref window) => dedup = dedup.with_window(parse_duration(window)?),
+            None => {}
+        }
+        return Ok(dedup);
+    }
+}
+
+fn default_dedup_timeout() -> String {
+    "5s".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use loghaul::Record;
+    use loghaul::RecordValue;
+    use internal::config_test_helpers::TestChain;
+    use internal::config_test_helpers::config_errors;
+
+    #[test]
+    fn test_dedup_transform() {
+        let mut chain = TestChain::new(r#"
+            [[transforms]]
+            type = "dedup"
+            fields = ["level"]
+        "#);
+        for (level, line) in vec!(("error", "disk full\n"), ("error", "disk still full\n"), ("info", "cleaned up\n")).into_iter() {
+            let mut record = Record::new("api", line.as_bytes().to_vec());
+            record.set_field("level", level);
+            chain.run(record);
+        }
+        assert_eq!(chain.payloads(), vec!("disk full\n", "last message repeated 1 times\n", "cleaned up\n"));
+        assert_eq!(chain.output[1].field("repeated"), Some(&RecordValue::Int(1)));
+
+        assert_eq!(config_errors(r#"
+            [[transforms]]
+            type = "dedup"
+            fields = []
+            window = "1 minute"
+            max_keys = 0
+        "#), vec!(
+            "transforms[0].fields: at least one field is required",
+            "transforms[0].window: invalid duration '1 minute', unknown unit ' minute' (expected ms, s, m or h)",
+            "transforms[0].max_keys: must be greater than zero",
+        ));
+    }
+}
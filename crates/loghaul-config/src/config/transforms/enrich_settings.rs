@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+use regex::Regex;
+use loghaul::EnrichProcessor;
+use internal::config_regex::check_regex;
+
+/// Settings for the `enrich` transform
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct EnrichSettings {
+    /// The field the host name is written to
+    pub hostname: Option<String>,
+
+    /// Fields set to the same value on every record
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+
+    /// Fields set from environment variables, as `field = "VARIABLE"`
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+
+    /// The field the source id, the path of a file source, is written to
+    pub path_field: Option<String>,
+
+    /// A pattern matched against the source id; each named group becomes a field
+    pub path_pattern: Option<String>,
+}
+
+impl EnrichSettings {
+    /// Return a description of every problem with these settings, each starting with `prefix`
+    pub fn validate(&self, prefix: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.hostname.is_none() && self.tags.is_empty() && self.env.is_empty() && self.path_field.is_none() && self.path_pattern.is_none() {
+            errors.push(format!("{}: one of hostname, tags, env, path_field or path_pattern is required", prefix));
+        }
+        let fields = vec!(
+            ("hostname", self.hostname.iter().collect::<Vec<_>>()),
+            ("tags", self.tags.keys().collect()),
+            ("env", self.env.keys().collect()),
+            ("path_field", self.path_field.iter().collect()),
+        );
+        for (name, fields) in fields.into_iter() {
+            if fields.iter().any(|field| field.trim().is_empty()) {
+                errors.push(format!("{}.{}: field names must not be empty", prefix, name));
+            }
+        }
+        match self.path_pattern {
+            Some(ref pattern) => match check_regex(pattern) {
+                Ok(_) if Regex::new(pattern).map(|r| r.capture_names().any(|n| n.is_some())).unwrap_or(false) => {}
+                Ok(_) => errors.push(format!("{}.path_pattern: must have a named group such as (?P<pod>...)", prefix)),
+                Err(e) => errors.push(format!("{}.path_pattern: {}", prefix, e))
+            },
+            None => {}
+        }
+        return errors;
+    }
+
+    /// Create the processor these settings describe
+    pub fn build(&self) -> Result<EnrichProcessor, String> {
+        let mut enrich = EnrichProcessor::new();
+        match self.hostname {
+            Some(ref field) => enrich = enrich.with_hostname(field),
+            None => {}
+        }
+        for (field, value) in self.tags.iter() {
+            enrich = enrich.with_value(field, value.clone());
+        }
+        for (field, variable) in self.env.iter() {
+            enrich = enrich.with_env(field, variable);
+        }
+        match self.path_field {
+            Some(ref field) => enrich = enrich.with_source_field(field),
+            None => {}
+        }
+        match self.path_pattern {
+            Some(ref pattern) => enrich = enrich.with_source_pattern(pattern).map_err(|e| e.to_string())?,
+            None => {}
+        }
+        return Ok(enrich);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use loghaul::Record;
+    use loghaul::RecordValue;
+    use internal::config_test_helpers::TestChain;
+    use internal::config_test_helpers::config_errors;
+
+    #[test]
+    fn test_enrich_transform() {
+        let mut chain = TestChain::new(r#"
+            [[transforms]]
+            type = "enrich"
+            hostname = "host"
+            tags = { env = "production", region = "eu-west-1" }
+            path_field = "file"
+            path_pattern = '^/var/log/pods/(?P<namespace>[^_/]+)_(?P<pod>[^_/]+)_'
+        "#);
+        chain.run(Record::new("/var/log/pods/shop_cart-5c8_42/api/0.log", b"hi\n".to_vec()));
+        assert_eq!(chain.output[0].field("region"), Some(&RecordValue::from("eu-west-1")));
+        assert_eq!(chain.output[0].field("pod"), Some(&RecordValue::from("cart-5c8")));
+        assert_eq!(chain.output[0].field("file"), Some(&RecordValue::from("/var/log/pods/shop_cart-5c8_42/api/0.log")));
+        assert!(chain.output[0].field("host").is_some());
+
+        assert_eq!(config_errors(r#"
+            [[transforms]]
+            type = "enrich"
+
+            [[transforms]]
+            type = "enrich"
+            tags = { "" = "x" }
+            path_pattern = '^/var/log/pods/([^_]+)_'
+        "#), vec!(
+            "transforms[0]: one of hostname, tags, env, path_field or path_pattern is required",
+            "transforms[1].tags: field names must not be empty",
+            "transforms[1].path_pattern: must have a named group such as (?P<pod>...)",
+        ));
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::BTreeMap;
+use loghaul_parse::GrokLibrary;
+use loghaul_parse::GrokProcessor;
+
+/// Settings for the `grok` transform
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct GrokSettings {
+    /// Patterns to try in order; the first to match wins
+    pub patterns: Vec<GrokPatternConfig>,
+
+    /// Extra named patterns, usable as `%{NAME}` alongside the builtin ones
+    #[serde(default)]
+    pub definitions: BTreeMap<String, String>,
+
+    /// Match against this field instead of the payload
+    pub field: Option<String>,
+}
+
+/// A grok pattern, either on its own or with a name used to label its match count
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(untagged)]
+pub enum GrokPatternConfig {
+    Plain(String),
+    Named { name: String, pattern: String },
+}
+
+impl GrokSettings {
+    /// Return a description of every problem with these settings, each starting with `prefix`
+    pub fn validate(&self, prefix: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.patterns.is_empty() {
+            errors.push(format!("{}.patterns: at least one pattern is required", prefix));
+        }
+        let library = self.library();
+        for (position, (_, pattern)) in self.labelled().into_iter().enumerate() {
+            match library.compile(pattern) {
+                Ok(_) => {}
+                Err(e) => errors.push(format!("{}.patterns[{}]: {}", prefix, position, e))
+            }
+        }
+        return errors;
+    }
+
+    /// Create the processor these settings describe
+    pub fn build(&self) -> Result<GrokProcessor, String> {
+        let mut grok = GrokProcessor::with_library(self.library());
+        for (label, pattern) in self.labelled().into_iter() {
+            grok.add_pattern(&label, pattern).map_err(|e| e.to_string())?;
+        }
+        match self.field {
+            Some(ref field) => grok = grok.with_field(field),
+            None => {}
+        }
+        return Ok(grok);
+    }
+
+    fn library(&self) -> GrokLibrary {
+        let mut library = GrokLibrary::new();
+        for (name, pattern) in self.definitions.iter() {
+            library.add_pattern(name, pattern);
+        }
+        return library;
+    }
+
+    /// Every pattern with its label, which is its name or its position
+    fn labelled(&self) -> Vec<(String, &str)> {
+        return self.patterns.iter().enumerate().map(|(index, p)| match p {
+            GrokPatternConfig::Plain(pattern) => (index.to_string(), pattern.as_str()),
+            GrokPatternConfig::Named { name, pattern } => (name.clone(), pattern.as_str()),
+        }).collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use loghaul::Record;
+    use loghaul::RecordValue;
+    use internal::config_test_helpers::TestChain;
+    use internal::config_test_helpers::config_errors;
+
+    #[test]
+    fn test_grok_transform() {
+        let mut chain = TestChain::new(r#"
+            [[transforms]]
+            type = "grok"
+            definitions = { REQUEST = "req-%{INT}" }
+            patterns = [
+                { name = "tagged", pattern = "^%{LOGLEVEL:level} \\[%{REQUEST:request}\\]" },
+                "^%{LOGLEVEL:level}",
+            ]
+        "#);
+        chain.run(Record::new("api", b"WARN [req-7] slow".to_vec()));
+        chain.run(Record::new("api", b"INFO ok".to_vec()));
+        assert_eq!(chain.output[0].field("request"), Some(&RecordValue::from("req-7")));
+        assert_eq!(chain.output[1].field("level"), Some(&RecordValue::from("INFO")));
+        assert_eq!(chain.counter("grok", "match:tagged"), Some(1));
+        assert_eq!(chain.counter("grok", "match:1"), Some(1));
+
+        assert_eq!(config_errors(r#"
+            [[transforms]]
+            type = "grok"
+            patterns = ["%{LOGLEVEL:level}", "%{NOPE}"]
+
+            [[transforms]]
+            type = "grok"
+            patterns = []
+        "#), vec!(
+            "transforms[0].patterns[1]: unknown pattern 'NOPE'",
+            "transforms[1].patterns: at least one pattern is required",
+        ));
+    }
+}
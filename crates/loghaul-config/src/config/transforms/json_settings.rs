@@ -0,0 +1,141 @@
+use loghaul_parse::JsonProcessor;
+use loghaul_parse::JsonInvalidPolicy;
+use internal::config_defaults::default_true;
+
+/// What the json transform does with a payload that isn't a json object
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum JsonInvalidConfig {
+    /// Fail the record, leaving it to `on_error`
+    Fail,
+    /// Pass the record on untouched
+    Pass,
+    /// Pass the record on with the parse error in `error_field`
+    Tag,
+}
+
+/// Settings for the `json` transform
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct JsonSettings {
+    #[serde(default = "default_json_invalid")]
+    pub invalid: JsonInvalidConfig,
+
+    /// The field the parse error is written to when `invalid` is `tag`
+    pub error_field: Option<String>,
+
+    /// Flatten nested objects and arrays into `parent.child` keys, rather than keeping them as json text
+    #[serde(default = "default_true")]
+    pub flatten: bool,
+
+    #[serde(default = "default_separator")]
+    pub separator: String,
+
+    /// Reject payloads nested deeper than this
+    #[serde(default = "default_max_depth")]
+    pub max_depth: usize,
+}
+
+impl JsonSettings {
+    /// Return a description of every problem with these settings, each starting with `prefix`
+    pub fn validate(&self, prefix: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.max_depth == 0 {
+            errors.push(format!("{}.max_depth: must be greater than zero", prefix));
+        }
+        if self.flatten && self.separator.is_empty() {
+            errors.push(format!("{}.separator: must not be empty", prefix));
+        }
+        match (self.invalid, self.error_field.as_ref()) {
+            (JsonInvalidConfig::Tag, Some(field)) if field.trim().is_empty() => {
+                errors.push(format!("{}.error_field: must not be empty", prefix));
+            }
+            (JsonInvalidConfig::Tag, _) => {}
+            (_, Some(_)) => {
+                errors.push(format!("{}.error_field: only valid when invalid is \"tag\"", prefix));
+            }
+            (_, None) => {}
+        }
+        return errors;
+    }
+
+    /// Create the processor these settings describe
+    pub fn build(&self) -> Result<JsonProcessor, String> {
+        let invalid = match self.invalid {
+            JsonInvalidConfig::Fail => JsonInvalidPolicy::Fail,
+            JsonInvalidConfig::Pass => JsonInvalidPolicy::Pass,
+            JsonInvalidConfig::Tag => JsonInvalidPolicy::Tag(self.error_field.clone().unwrap_or("json_error".to_string())),
+        };
+        let json = JsonProcessor::new().with_invalid(invalid).with_max_depth(self.max_depth);
+        return Ok(match self.flatten {
+            true => json.with_separator(&self.separator),
+            false => json.without_flatten(),
+        });
+    }
+}
+
+fn default_json_invalid() -> JsonInvalidConfig {
+    JsonInvalidConfig::Fail
+}
+
+fn default_separator() -> String {
+    ".".to_string()
+}
+
+fn default_max_depth() -> usize {
+    32
+}
+
+#[cfg(test)]
+mod tests {
+    use loghaul::Record;
+    use loghaul::RecordValue;
+    use internal::config_test_helpers::TestChain;
+    use internal::config_test_helpers::config_errors;
+
+    #[test]
+    fn test_json_transform() {
+        let mut chain = TestChain::new(r#"
+            [[transforms]]
+            type = "json"
+            separator = "_"
+            max_depth = 4
+            on_error = "dead_letter"
+            dead_letter = "rejects"
+
+            [[transforms]]
+            type = "json"
+            invalid = "tag"
+            error_field = "oops"
+
+            [targets.rejects]
+            type = "stdout"
+        "#);
+        assert_eq!(chain.chain.ids(), vec!("json", "json"));
+        chain.run(Record::new("api", b"{\"http\": {\"status\": 404}}\n".to_vec()));
+        chain.run(Record::new("api", b"{oops\n".to_vec()));
+        assert_eq!(chain.output[0].field("http_status"), Some(&RecordValue::Int(404)));
+        assert_eq!(chain.output[1].route, Some("rejects".to_string()));
+        assert!(chain.output[1].fields.is_empty());
+    }
+
+    #[test]
+    fn test_json_transform_validation() {
+        assert_eq!(config_errors(r#"
+            [[transforms]]
+            type = "json"
+            max_depth = 0
+            error_field = "oops"
+            on_error = "dead_letter"
+
+            [[transforms]]
+            type = "json"
+            dead_letter = "nowhere"
+        "#), vec!(
+            "transforms[0].dead_letter: required when on_error is \"dead_letter\"",
+            "transforms[0].max_depth: must be greater than zero",
+            "transforms[0].error_field: only valid when invalid is \"tag\"",
+            "transforms[1].dead_letter: only valid when on_error is \"dead_letter\"",
+        ));
+    }
+}
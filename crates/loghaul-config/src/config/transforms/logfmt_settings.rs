@@ -0,0 +1,91 @@
+use loghaul_parse::LogfmtProcessor;
+use internal::config_defaults::default_true;
+
+/// Settings for the `logfmt` transform
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct LogfmtSettings {
+    /// The character between a key and its value
+    #[serde(default = "default_delimiter")]
+    pub delimiter: String,
+
+    /// A character separating pairs, as well as whitespace, eg. `,` or `&`
+    pub separator: Option<String>,
+
+    /// Convert unquoted numbers and booleans, rather than keeping every value as a string
+    #[serde(default = "default_true")]
+    pub infer_types: bool,
+}
+
+impl LogfmtSettings {
+    /// Return a description of every problem with these settings, each starting with `prefix`
+    pub fn validate(&self, prefix: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+        match single_char(&self.delimiter) {
+            Some(c) if !c.is_whitespace() && c != '"' => {}
+            _ => errors.push(format!("{}.delimiter: must be a single character other than whitespace or '\"'", prefix))
+        }
+        match self.separator.as_ref().map(|s| single_char(s)) {
+            Some(Some(c)) if c != '"' && Some(c) != single_char(&self.delimiter) => {}
+            Some(_) => errors.push(format!("{}.separator: must be a single character other than '\"' or the delimiter", prefix)),
+            None => {}
+        }
+        return errors;
+    }
+
+    /// Create the processor these settings describe
+    pub fn build(&self) -> Result<LogfmtProcessor, String> {
+        let mut logfmt = LogfmtProcessor::new().with_delimiter(single_char(&self.delimiter).unwrap_or('='));
+        match self.separator.as_ref().and_then(|s| single_char(s)) {
+            Some(separator) => logfmt = logfmt.with_pair_separator(separator),
+            None => {}
+        }
+        if !self.infer_types {
+            logfmt = logfmt.without_type_inference();
+        }
+        return Ok(logfmt);
+    }
+}
+
+/// The only character in a string, if it has exactly one
+fn single_char(value: &str) -> Option<char> {
+    let mut chars = value.chars();
+    return match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None
+    };
+}
+
+fn default_delimiter() -> String {
+    "=".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use loghaul::Record;
+    use loghaul::RecordValue;
+    use internal::config_test_helpers::TestChain;
+    use internal::config_test_helpers::config_errors;
+
+    #[test]
+    fn test_logfmt_transform() {
+        let mut chain = TestChain::new(r#"
+            [[transforms]]
+            type = "logfmt"
+            delimiter = ":"
+            separator = ","
+        "#);
+        chain.run(Record::new("api", b"user:bob,retries:3\n".to_vec()));
+        assert_eq!(chain.output[0].field("user"), Some(&RecordValue::from("bob")));
+        assert_eq!(chain.output[0].field("retries"), Some(&RecordValue::Int(3)));
+
+        assert_eq!(config_errors(r#"
+            [[transforms]]
+            type = "logfmt"
+            delimiter = "=>"
+            separator = " "
+        "#), vec!(
+            "transforms[0].delimiter: must be a single character other than whitespace or '\"'",
+        ));
+    }
+}
@@ -0,0 +1,18 @@
+pub mod multiline_settings;
+pub mod json_settings;
+pub mod logfmt_settings;
+pub mod grok_settings;
+pub mod access_log_settings;
+pub mod timestamp_settings;
+pub mod enrich_settings;
+pub mod redact_settings;
+pub mod sample_settings;
+pub mod rate_limit_settings;
+pub mod dedup_settings;
+pub mod aggregate_settings;
+pub mod alert_settings;
+pub mod template_settings;
+pub mod order_settings;
+pub mod session_settings;
+pub mod script_settings;
+pub mod wasm_settings;
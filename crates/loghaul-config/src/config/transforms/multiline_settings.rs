@@ -0,0 +1,122 @@
+use loghaul::MultilineProcessor;
+use internal::config_regex::check_regex;
+use internal::config_duration::parse_duration;
+
+/// Settings for the `multiline` transform; at least one pattern is required
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct MultilineSettings {
+    /// Lines matching this begin a new event
+    pub start: Option<String>,
+
+    /// Lines matching this join the current event
+    pub continuation: Option<String>,
+
+    /// Lines matching this end the current event
+    pub end: Option<String>,
+
+    /// Release an event once it has this many lines
+    #[serde(default = "default_max_lines")]
+    pub max_lines: usize,
+
+    /// Release an event once no line has joined it for this long
+    #[serde(default = "default_multiline_timeout")]
+    pub timeout: String,
+}
+
+impl MultilineSettings {
+    /// Return a description of every problem with these settings, each starting with `prefix`
+    pub fn validate(&self, prefix: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.patterns().is_empty() {
+            errors.push(format!("{}: one of start, continuation or end is required", prefix));
+        }
+        for (name, pattern) in self.patterns().into_iter() {
+            match check_regex(pattern) {
+                Ok(_) => {}
+                Err(e) => errors.push(format!("{}.{}: {}", prefix, name, e))
+            }
+        }
+        if self.max_lines == 0 {
+            errors.push(format!("{}.max_lines: must be greater than zero", prefix));
+        }
+        match parse_duration(&self.timeout) {
+            Ok(_) => {}
+            Err(e) => errors.push(format!("{}.timeout: {}", prefix, e))
+        }
+        return errors;
+    }
+
+    /// Create the processor these settings describe
+    pub fn build(&self) -> Result<MultilineProcessor, String> {
+        let mut multiline = MultilineProcessor::new()
+            .with_max_lines(self.max_lines)
+            .with_timeout(parse_duration(&self.timeout)?);
+        for (name, pattern) in self.patterns().into_iter() {
+            multiline = match name {
+                "start" => multiline.with_start(pattern),
+                "continuation" => multiline.with_continuation(pattern),
+                _ => multiline.with_end(pattern),
+            }.map_err(|e| e.to_string())?;
+        }
+        return Ok(multiline);
+    }
+
+    fn patterns(&self) -> Vec<(&'static str, &String)> {
+        let mut patterns = Vec::new();
+        for (name, pattern) in vec!(("start", &self.start), ("continuation", &self.continuation), ("end", &self.end)).into_iter() {
+            match pattern {
+                Some(pattern) => patterns.push((name, pattern)),
+                None => {}
+            }
+        }
+        return patterns;
+    }
+}
+
+fn default_max_lines() -> usize {
+    500
+}
+
+fn default_multiline_timeout() -> String {
+    "1s".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use loghaul::Record;
+    use internal::config_test_helpers::TestChain;
+    use internal::config_test_helpers::config_errors;
+
+    #[test]
+    fn test_multiline_transform() {
+        let mut chain = TestChain::new(r#"
+            [[transforms]]
+            type = "split_lines"
+
+            [[transforms]]
+            type = "multiline"
+            continuation = '^(\s|Caused by:)'
+            max_lines = 50
+            timeout = "250ms"
+        "#);
+        chain.run(Record::new("api", b"ERROR boom\n\tat Main.run\nCaused by: oops\nINFO ok\n".to_vec()));
+        assert_eq!(chain.payloads(), vec!("ERROR boom\n\tat Main.run\nCaused by: oops\n"));
+
+        assert_eq!(config_errors(r#"
+            [[transforms]]
+            type = "multiline"
+            max_lines = 0
+            timeout = "soon"
+
+            [[transforms]]
+            type = "multiline"
+            start = "(unclosed"
+        "#), vec!(
+            "transforms[0]: one of start, continuation or end is required",
+            "transforms[0].max_lines: must be greater than zero",
+            "transforms[0].timeout: invalid duration 'soon', expected a number followed by ms, s, m or h",
+            "transforms[1].start: invalid regex '(unclosed': unclosed group",
+        ));
+    }
+}
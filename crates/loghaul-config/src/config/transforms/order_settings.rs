@@ -0,0 +1,138 @@
+use std::collections::BTreeMap;
+use loghaul::OrderProcessor;
+use loghaul::OrderLatePolicy;
+use TargetConfig;
+use internal::config_duration::parse_duration;
+use internal::config_defaults::default_timestamp_target;
+
+/// What the order transform does with records older than records it already released
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderLateConfig {
+    /// Release them at once, out of order
+    Pass,
+    /// Discard them
+    Drop,
+    /// Release them at once, only to the `late_target` target
+    Route,
+}
+
+/// Settings for the `order` transform
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct OrderSettings {
+    /// How long records are held back to wait for earlier ones
+    pub delay: String,
+
+    /// The field holding the event time, as RFC 3339 or milliseconds since the epoch
+    #[serde(default = "default_timestamp_target")]
+    pub time_field: String,
+
+    #[serde(default = "default_order_late")]
+    pub late: OrderLateConfig,
+
+    /// The target late records are sent to when `late` is `route`
+    pub late_target: Option<String>,
+
+    /// The most records to hold at once
+    #[serde(default = "default_order_max_buffered")]
+    pub max_buffered: usize,
+}
+
+impl OrderSettings {
+    /// Return a description of every problem with these settings, each starting with `prefix`
+    pub fn validate(&self, prefix: &str, targets: &BTreeMap<String, TargetConfig>) -> Vec<String> {
+        let mut errors = Vec::new();
+        match parse_duration(&self.delay) {
+            Ok(_) => {}
+            Err(e) => errors.push(format!("{}.delay: {}", prefix, e))
+        }
+        if self.time_field.trim().is_empty() {
+            errors.push(format!("{}.time_field: must not be empty", prefix));
+        }
+        match (self.late, self.late_target.as_ref()) {
+            (OrderLateConfig::Route, None) => {
+                errors.push(format!("{}.late_target: required when late is \"route\"", prefix));
+            }
+            (OrderLateConfig::Route, Some(target)) if !targets.contains_key(target) => {
+                errors.push(format!("{}.late_target: unknown target '{}'", prefix, target));
+            }
+            (OrderLateConfig::Route, Some(_)) => {}
+            (_, Some(_)) => {
+                errors.push(format!("{}.late_target: only valid when late is \"route\"", prefix));
+            }
+            (_, None) => {}
+        }
+        if self.max_buffered == 0 {
+            errors.push(format!("{}.max_buffered: must be greater than zero", prefix));
+        }
+        return errors;
+    }
+
+    /// Create the processor these settings describe
+    pub fn build(&self) -> Result<OrderProcessor, String> {
+        let late = match self.late {
+            OrderLateConfig::Pass => OrderLatePolicy::Pass,
+            OrderLateConfig::Drop => OrderLatePolicy::Drop,
+            OrderLateConfig::Route => OrderLatePolicy::Route(self.late_target.clone().unwrap_or(String::new())),
+        };
+        let order = OrderProcessor::new(parse_duration(&self.delay)?)
+            .with_time_field(&self.time_field)
+            .with_late_policy(late)
+            .with_max_buffered(self.max_buffered);
+        return Ok(order);
+    }
+}
+
+fn default_order_late() -> OrderLateConfig {
+    OrderLateConfig::Pass
+}
+
+fn default_order_max_buffered() -> usize {
+    100000
+}
+
+#[cfg(test)]
+mod tests {
+    use loghaul::Record;
+    use internal::config_test_helpers::TestChain;
+    use internal::config_test_helpers::config_errors;
+
+    #[test]
+    fn test_order_transform() {
+        let mut chain = TestChain::new(r#"
+            [[transforms]]
+            type = "order"
+            delay = "5s"
+            late = "route"
+            late_target = "late"
+
+            [targets.late]
+            type = "stdout"
+        "#);
+        for &(source, second) in [("api", 3), ("web", 1), ("api", 9), ("web", 2)].iter() {
+            let mut record = Record::new(source, format!("{}\n", second).into_bytes());
+            record.set_field("timestamp", format!("2024-05-01T10:00:{:02}Z", second));
+            chain.run(record);
+        }
+        let released: Vec<(&str, &str)> = chain.output.iter().map(|r| (r.source.as_str(), r.payload_str().unwrap())).collect();
+        assert_eq!(released, vec!(("web", "1\n"), ("api", "3\n"), ("web", "2\n")));
+        assert_eq!(chain.output[2].route, Some("late".to_string()));
+
+        assert_eq!(config_errors(r#"
+            [[transforms]]
+            type = "order"
+            delay = "later"
+            late = "route"
+
+            [[transforms]]
+            type = "order"
+            delay = "1s"
+            late_target = "out"
+        "#), vec!(
+            "transforms[0].delay: invalid duration 'later', expected a number followed by ms, s, m or h",
+            "transforms[0].late_target: required when late is \"route\"",
+            "transforms[1].late_target: only valid when late is \"route\"",
+        ));
+    }
+}
@@ -0,0 +1,109 @@
+use loghaul::RateLimitProcessor;
+use internal::config_duration::parse_duration;
+use internal::config_defaults::default_max_keys;
+
+/// Settings for the `rate_limit` transform
+#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitSettings {
+    /// Records allowed per second
+    pub rate: f64,
+
+    /// Records allowed at once; the rate, rounded up, if unset
+    pub burst: Option<u64>,
+
+    /// Limit each value of this field separately, instead of each source
+    pub key: Option<String>,
+
+    /// How often to emit a record counting the records that were dropped
+    #[serde(default = "default_summary_interval")]
+    pub summary_interval: String,
+
+    /// The most sources or keys to track at once
+    #[serde(default = "default_max_keys")]
+    pub max_keys: usize,
+}
+
+impl RateLimitSettings {
+    /// Return a description of every problem with these settings, each starting with `prefix`
+    pub fn validate(&self, prefix: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+        if !(self.rate > 0.0) || self.rate.is_infinite() {
+            errors.push(format!("{}.rate: must be greater than zero", prefix));
+        }
+        if self.burst == Some(0) {
+            errors.push(format!("{}.burst: must be greater than zero", prefix));
+        }
+        match self.key {
+            Some(ref key) if key.trim().is_empty() => errors.push(format!("{}.key: must not be empty", prefix)),
+            _ => {}
+        }
+        match parse_duration(&self.summary_interval) {
+            Ok(_) => {}
+            Err(e) => errors.push(format!("{}.summary_interval: {}", prefix, e))
+        }
+        if self.max_keys == 0 {
+            errors.push(format!("{}.max_keys: must be greater than zero", prefix));
+        }
+        return errors;
+    }
+
+    /// Create the processor these settings describe
+    pub fn build(&self) -> Result<RateLimitProcessor, String> {
+        let mut limit = RateLimitProcessor::new(self.rate, self.burst())
+            .with_summary_interval(parse_duration(&self.summary_interval)?)
+            .with_max_keys(self.max_keys);
+        match self.key {
+            Some(ref key) => limit = limit.with_key_field(key),
+            None => {}
+        }
+        return Ok(limit);
+    }
+
+    fn burst(&self) -> u64 {
+        return self.burst.unwrap_or(self.rate.ceil().max(1.0) as u64);
+    }
+}
+
+fn default_summary_interval() -> String {
+    "10s".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use loghaul::Record;
+    use internal::config_test_helpers::TestChain;
+    use internal::config_test_helpers::config_errors;
+
+    #[test]
+    fn test_rate_limit_transform() {
+        let mut chain = TestChain::new(r#"
+            [[transforms]]
+            type = "rate_limit"
+            rate = 0.5
+            key = "service"
+            summary_interval = "1m"
+        "#);
+        for service in ["a", "a", "b"].iter() {
+            let mut record = Record::new("api", service.as_bytes().to_vec());
+            record.set_field("service", *service);
+            chain.run(record);
+        }
+        assert_eq!(chain.payloads(), vec!("a", "b"));
+        assert_eq!(chain.counter("rate_limit", "suppressed"), Some(1));
+
+        assert_eq!(config_errors(r#"
+            [[transforms]]
+            type = "rate_limit"
+            rate = 0.0
+            burst = 0
+            summary_interval = "soon"
+            max_keys = 0
+        "#), vec!(
+            "transforms[0].rate: must be greater than zero",
+            "transforms[0].burst: must be greater than zero",
+            "transforms[0].summary_interval: invalid duration 'soon', expected a number followed by ms, s, m or h",
+            "transforms[0].max_keys: must be greater than zero",
+        ));
+    }
+}
@@ -0,0 +1,179 @@
+use std::env;
+use loghaul_parse::RedactDetector;
+use loghaul_parse::RedactMode;
+use loghaul_parse::RedactProcessor;
+use internal::config_defaults::default_true;
+
+/// What the redact transform replaces sensitive values with
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum RedactModeConfig {
+    /// As many `*` as the value has characters
+    Mask,
+    /// The `token` setting
+    Token,
+    /// A keyed hash of the value, so equal values stay equal
+    Hmac,
+}
+
+/// Settings for the `redact` transform
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RedactSettings {
+    /// Builtin detectors to run; every builtin detector if unset
+    pub detectors: Option<Vec<String>>,
+
+    /// Extra patterns to redact; only the first capture group is redacted, if there is one
+    #[serde(default)]
+    pub patterns: Vec<RedactPatternConfig>,
+
+    #[serde(default = "default_redact_mode")]
+    pub mode: RedactModeConfig,
+
+    /// The replacement when `mode` is `token`, `[REDACTED]` by default
+    pub token: Option<String>,
+
+    /// The key when `mode` is `hmac`
+    pub key: Option<String>,
+
+    /// An environment variable holding the key when `mode` is `hmac`, instead of `key`
+    pub key_env: Option<String>,
+
+    /// Only redact these fields, rather than every field
+    pub fields: Option<Vec<String>>,
+
+    /// Redact the payload as well as fields
+    #[serde(default = "default_true")]
+    pub payload: bool,
+}
+
+/// A redaction pattern, either on its own or with a name used to label its counter
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(untagged)]
+pub enum RedactPatternConfig {
+    Plain(String),
+    Named { name: String, pattern: String },
+}
+
+impl RedactSettings {
+    /// Return a description of every problem with these settings, each starting with `prefix`
+    pub fn validate(&self, prefix: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+        match self.detectors() {
+            Ok(ref detectors) if detectors.is_empty() => {
+                errors.push(format!("{}: at least one detector or pattern is required", prefix));
+            }
+            Ok(_) => {}
+            Err(e) => errors.push(format!("{}.{}", prefix, e))
+        }
+        match self.mode() {
+            Ok(_) => {}
+            Err(e) => errors.push(format!("{}.key: {}", prefix, e))
+        }
+        if self.token.is_some() && self.mode != RedactModeConfig::Token {
+            errors.push(format!("{}.token: only valid when mode is \"token\"", prefix));
+        }
+        if (self.key.is_some() || self.key_env.is_some()) && self.mode != RedactModeConfig::Hmac {
+            errors.push(format!("{}.key: only valid when mode is \"hmac\"", prefix));
+        }
+        return errors;
+    }
+
+    /// Create the processor these settings describe
+    pub fn build(&self) -> Result<RedactProcessor, String> {
+        let mut redact = RedactProcessor::new(self.mode()?).with_detectors(self.detectors()?);
+        if !self.payload {
+            redact = redact.without_payload();
+        }
+        match self.fields {
+            Some(ref fields) => redact = redact.with_fields(&fields.iter().map(|f| f.as_str()).collect::<Vec<_>>()),
+            None => {}
+        }
+        return Ok(redact);
+    }
+
+    fn detectors(&self) -> Result<Vec<RedactDetector>, String> {
+        let mut detectors = match self.detectors {
+            Some(ref names) => {
+                let mut detectors = Vec::new();
+                for name in names.iter() {
+                    match RedactDetector::builtin(name) {
+                        Some(detector) => detectors.push(detector),
+                        None => {
+                            return Err(format!("detectors: unknown detector '{}', expected email, credit_card, ipv4, ipv6, bearer, aws_key or jwt", name));
+                        }
+                    }
+                }
+                detectors
+            }
+            None => RedactDetector::builtins(),
+        };
+        for (index, pattern) in self.patterns.iter().enumerate() {
+            let (name, pattern) = match pattern {
+                RedactPatternConfig::Plain(pattern) => (index.to_string(), pattern),
+                RedactPatternConfig::Named { name, pattern } => (name.clone(), pattern),
+            };
+            detectors.push(RedactDetector::custom(&name, pattern).map_err(|e| format!("patterns[{}]: {}", index, e))?);
+        }
+        return Ok(detectors);
+    }
+
+    fn mode(&self) -> Result<RedactMode, String> {
+        return match self.mode {
+            RedactModeConfig::Mask => Ok(RedactMode::Mask),
+            RedactModeConfig::Token => Ok(RedactMode::Token(self.token.clone().unwrap_or("[REDACTED]".to_string()))),
+            RedactModeConfig::Hmac => match (self.key.as_ref(), self.key_env.as_ref()) {
+                (Some(key), None) => Ok(RedactMode::Hmac(key.as_bytes().to_vec())),
+                (None, Some(variable)) => match env::var(variable) {
+                    Ok(ref key) if !key.is_empty() => Ok(RedactMode::Hmac(key.as_bytes().to_vec())),
+                    _ => Err(format!("environment variable '{}' is not set", variable)),
+                },
+                _ => Err("exactly one of key or key_env is required when mode is \"hmac\"".to_string()),
+            },
+        };
+    }
+}
+
+fn default_redact_mode() -> RedactModeConfig {
+    RedactModeConfig::Token
+}
+
+#[cfg(test)]
+mod tests {
+    use loghaul::Record;
+    use internal::config_test_helpers::TestChain;
+    use internal::config_test_helpers::config_errors;
+
+    #[test]
+    fn test_redact_transform() {
+        let mut chain = TestChain::new(r#"
+            [[transforms]]
+            type = "redact"
+            detectors = ["email"]
+            patterns = [{ name = "session", pattern = 'session=(\w+)' }]
+            mode = "mask"
+        "#);
+        chain.run(Record::new("api", b"login bob@ex.io session=abc from 10.0.0.1\n".to_vec()));
+        assert_eq!(chain.payloads(), vec!("login ********* session=*** from 10.0.0.1\n"));
+        assert_eq!(chain.counter("redact", "redacted:session"), Some(1));
+
+        assert_eq!(config_errors(r#"
+            [[transforms]]
+            type = "redact"
+            detectors = ["email", "phone"]
+            mode = "hmac"
+            token = "[X]"
+
+            [[transforms]]
+            type = "redact"
+            detectors = []
+            key = "secret"
+        "#), vec!(
+            "transforms[0].detectors: unknown detector 'phone', expected email, credit_card, ipv4, ipv6, bearer, aws_key or jwt",
+            "transforms[0].key: exactly one of key or key_env is required when mode is \"hmac\"",
+            "transforms[0].token: only valid when mode is \"token\"",
+            "transforms[1]: at least one detector or pattern is required",
+            "transforms[1].key: only valid when mode is \"hmac\"",
+        ));
+    }
+}
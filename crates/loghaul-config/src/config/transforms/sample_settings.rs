@@ -0,0 +1,70 @@
+use loghaul::SampleProcessor;
+
+/// Settings for the `sample` transform
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SampleSettings {
+    /// Keep one record in every `rate`
+    pub rate: u64,
+
+    /// Keep or drop records by the value of this field, so records sharing it stay together
+    pub key: Option<String>,
+}
+
+impl SampleSettings {
+    /// Return a description of every problem with these settings, each starting with `prefix`
+    pub fn validate(&self, prefix: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.rate == 0 {
+            errors.push(format!("{}.rate: must be greater than zero", prefix));
+        }
+        match self.key {
+            Some(ref key) if key.trim().is_empty() => errors.push(format!("{}.key: must not be empty", prefix)),
+            _ => {}
+        }
+        return errors;
+    }
+
+    /// Create the processor these settings describe
+    pub fn build(&self) -> Result<SampleProcessor, String> {
+        let mut sample = SampleProcessor::new(self.rate);
+        match self.key {
+            Some(ref key) => sample = sample.with_key_field(key),
+            None => {}
+        }
+        return Ok(sample);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use loghaul::Record;
+    use internal::config_test_helpers::TestChain;
+    use internal::config_test_helpers::config_errors;
+
+    #[test]
+    fn test_sample_transform() {
+        let mut chain = TestChain::new(r#"
+            [[transforms]]
+            type = "sample"
+            rate = 1
+            key = "service"
+        "#);
+        for service in ["a", "a", "b"].iter() {
+            let mut record = Record::new("api", service.as_bytes().to_vec());
+            record.set_field("service", *service);
+            chain.run(record);
+        }
+        assert_eq!(chain.payloads(), vec!("a", "a", "b"));
+
+        assert_eq!(config_errors(r#"
+            [[transforms]]
+            type = "sample"
+            rate = 0
+            key = ""
+        "#), vec!(
+            "transforms[0].rate: must be greater than zero",
+            "transforms[0].key: must not be empty",
+        ));
+    }
+}
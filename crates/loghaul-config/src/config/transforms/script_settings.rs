@@ -0,0 +1,125 @@
+use loghaul_script::ScriptProcessor;
+
+/// Settings for the `script` transform
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ScriptSettings {
+    /// The script file, which is read and compiled when the config is loaded
+    pub path: String,
+
+    /// Fail a record once its script has performed this many operations
+    #[serde(default = "default_max_operations")]
+    pub max_operations: u64,
+
+    /// Fail a record once its script builds a string longer than this many bytes
+    #[serde(default = "default_max_string_size")]
+    pub max_string_size: usize,
+
+    /// Fail a record once its script builds an array or map with more than this many entries
+    #[serde(default = "default_max_collection_size")]
+    pub max_collection_size: usize,
+
+    /// Fail a record once its script emits more than this many records
+    #[serde(default = "default_max_emitted")]
+    pub max_emitted: usize,
+
+    /// Fail a record once the records its script emits total more than this many bytes
+    #[serde(default = "default_max_emitted_bytes")]
+    pub max_emitted_bytes: usize,
+}
+
+impl ScriptSettings {
+    /// Return a description of every problem with these settings, each starting with `prefix`
+    pub fn validate(&self, prefix: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+        match ScriptProcessor::from_file(&self.path) {
+            Ok(_) => {}
+            Err(e) => errors.push(format!("{}.path: {}", prefix, e))
+        }
+        let limits = vec!(
+            ("max_operations", self.max_operations as usize),
+            ("max_string_size", self.max_string_size),
+            ("max_collection_size", self.max_collection_size),
+            ("max_emitted_bytes", self.max_emitted_bytes),
+        );
+        for (name, limit) in limits.into_iter() {
+            if limit == 0 {
+                errors.push(format!("{}.{}: must be greater than zero", prefix, name));
+            }
+        }
+        return errors;
+    }
+
+    /// Create the processor these settings describe
+    pub fn build(&self) -> Result<ScriptProcessor, String> {
+        let script = ScriptProcessor::from_file(&self.path)?
+            .with_max_operations(self.max_operations)
+            .with_max_string_size(self.max_string_size)
+            .with_max_collection_size(self.max_collection_size)
+            .with_max_emitted(self.max_emitted, self.max_emitted_bytes);
+        return Ok(script);
+    }
+}
+
+fn default_max_operations() -> u64 {
+    100000
+}
+
+fn default_max_string_size() -> usize {
+    1024 * 1024
+}
+
+fn default_max_collection_size() -> usize {
+    10000
+}
+
+fn default_max_emitted() -> usize {
+    1000
+}
+
+fn default_max_emitted_bytes() -> usize {
+    16 * 1024 * 1024
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use loghaul::Record;
+    use loghaul::RecordValue;
+    use internal::config_test_helpers::TestChain;
+    use internal::config_test_helpers::config_errors;
+
+    #[test]
+    fn test_script_transform() {
+        let dir = env::temp_dir().join(format!("loghaul_script_test_{}", ::std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("tag.rhai"), "fields.tagged = true; if payload.contains(\"debug\") { drop(); }").unwrap();
+        fs::write(dir.join("broken.rhai"), "fields.x = ;").unwrap();
+
+        let mut chain = TestChain::new(&format!(r#"
+            [[transforms]]
+            type = "script"
+            path = '{}'
+            max_operations = 1000
+        "#, dir.join("tag.rhai").display()));
+        chain.run(Record::new("api", b"debug noise".to_vec()));
+        chain.run(Record::new("api", b"started".to_vec()));
+        assert_eq!(chain.output.len(), 1);
+        assert_eq!(chain.output[0].field("tagged"), Some(&RecordValue::Bool(true)));
+
+        let lines = config_errors(&format!(r#"
+            [[transforms]]
+            type = "script"
+            path = '{}'
+            max_collection_size = 0
+            max_emitted_bytes = 0
+        "#, dir.join("broken.rhai").display()));
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with(&format!("transforms[0].path: {}: ", dir.join("broken.rhai").display())), "{}", lines[0]);
+        assert!(lines[0].contains("line 1"), "{}", lines[0]);
+        assert_eq!(lines[1], "transforms[0].max_collection_size: must be greater than zero");
+        assert_eq!(lines[2], "transforms[0].max_emitted_bytes: must be greater than zero");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::BTreeMap;
+use loghaul::SessionProcessor;
+use loghaul::RouteSelector;
+use TargetConfig;
+use internal::config_regex::check_regex;
+use internal::config_duration::parse_duration;
+use internal::config_defaults::default_max_keys;
+use internal::config_defaults::default_timestamp_target;
+
+/// Settings for the `session` transform
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SessionSettings {
+    /// The field records are grouped by, such as `request_id`
+    pub key: String,
+
+    /// How long a session stays open without a new record
+    #[serde(default = "default_session_timeout")]
+    pub timeout: String,
+
+    /// The field holding the event time, as RFC 3339 or milliseconds since the epoch
+    #[serde(default = "default_timestamp_target")]
+    pub time_field: String,
+
+    /// A regex marking records whose payload matches it as errors
+    pub error_payload: Option<String>,
+
+    /// Field regexes marking records with a matching field as errors; without these
+    /// or `error_payload`, records with an error `level` are errors
+    #[serde(default)]
+    pub error_matches: BTreeMap<String, String>,
+
+    /// The most sessions to keep open at once
+    #[serde(default = "default_max_keys")]
+    pub max_sessions: usize,
+
+    /// The most records to keep in one session
+    #[serde(default = "default_session_max_events")]
+    pub max_events: usize,
+
+    /// Send session records only to this target, instead of routing them
+    pub target: Option<String>,
+
+    /// The source session records come from, `session` by default
+    pub source: Option<String>,
+}
+
+impl SessionSettings {
+    /// Return a description of every problem with these settings, each starting with `prefix`
+    pub fn validate(&self, prefix: &str, targets: &BTreeMap<String, TargetConfig>) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.key.trim().is_empty() {
+            errors.push(format!("{}.key: must not be empty", prefix));
+        }
+        match parse_duration(&self.timeout) {
+            Ok(timeout) if timeout.as_millis() == 0 => errors.push(format!("{}.timeout: must be at least 1ms", prefix)),
+            Ok(_) => {}
+            Err(e) => errors.push(format!("{}.timeout: {}", prefix, e))
+        }
+        if self.time_field.trim().is_empty() {
+            errors.push(format!("{}.time_field: must not be empty", prefix));
+        }
+        match self.error_payload.as_ref().map(|pattern| check_regex(pattern)) {
+            Some(Err(e)) => errors.push(format!("{}.error_payload: {}", prefix, e)),
+            _ => {}
+        }
+        for (field, pattern) in self.error_matches.iter() {
+            match check_regex(pattern) {
+                Ok(_) => {}
+                Err(e) => errors.push(format!("{}.error_matches.{}: {}", prefix, field, e))
+            }
+        }
+        if self.max_sessions == 0 {
+            errors.push(format!("{}.max_sessions: must be greater than zero", prefix));
+        }
+        if self.max_events == 0 {
+            errors.push(format!("{}.max_events: must be greater than zero", prefix));
+        }
+        match self.target {
+            Some(ref target) if !targets.contains_key(target) => {
+                errors.push(format!("{}.target: unknown target '{}'", prefix, target));
+            }
+            _ => {}
+        }
+        return errors;
+    }
+
+    /// Create the processor these settings describe
+    pub fn build(&self) -> Result<SessionProcessor, String> {
+        let mut sessions = SessionProcessor::new(&self.key)
+            .with_timeout(parse_duration(&self.timeout)?)
+            .with_time_field(&self.time_field)
+            .with_max_sessions(self.max_sessions)
+            .with_max_events(self.max_events);
+        match self.error_payload {
+            Some(ref pattern) => sessions = sessions.with_error_selector(RouteSelector::payload(pattern).map_err(|e| e.to_string())?),
+            None => {}
+        }
+        for (field, pattern) in self.error_matches.iter() {
+            sessions = sessions.with_error_selector(RouteSelector::field_matches(field, pattern).map_err(|e| e.to_string())?);
+        }
+        match self.target {
+            Some(ref target) => sessions = sessions.with_target(target),
+            None => {}
+        }
+        match self.source {
+            Some(ref source) => sessions = sessions.with_source(source),
+            None => {}
+        }
+        return Ok(sessions);
+    }
+}
+
+fn default_session_timeout() -> String {
+    "30s".to_string()
+}
+
+fn default_session_max_events() -> usize {
+    1000
+}
+
+#[cfg(test)]
+mod tests {
+    use loghaul::Record;
+    use loghaul::RecordValue;
+    use internal::config_test_helpers::TestChain;
+    use internal::config_test_helpers::config_errors;
+
+    #[test]
+    fn test_session_transform() {
+        let mut chain = TestChain::new(r#"
+            [[transforms]]
+            type = "session"
+            key = "request_id"
+            timeout = "10s"
+            error_matches = { status = "^5" }
+            target = "traces"
+
+            [targets.traces]
+            type = "stdout"
+        "#);
+        for &(source, millis, status) in [("gateway", 100, 200), ("orders", 400, 503), ("gateway", 250, 200)].iter() {
+            let mut record = Record::new(source, format!("{} {}\n", source, status).into_bytes());
+            record.set_field("request_id", "r1");
+            record.set_field("status", RecordValue::Int(status));
+            record.set_field("timestamp", format!("2024-05-01T10:00:00.{:03}Z", millis));
+            chain.run(record);
+        }
+        assert!(chain.output.is_empty());
+        chain.end_source("gateway");
+        chain.end_source("orders");
+        assert_eq!(chain.payloads(), vec!("gateway 200\ngateway 200\norders 503\n"));
+        assert_eq!(chain.output[0].field("duration_ms"), Some(&RecordValue::Int(300)));
+        assert_eq!(chain.output[0].field("error"), Some(&RecordValue::Bool(true)));
+        assert_eq!(chain.output[0].route, Some("traces".to_string()));
+
+        assert_eq!(config_errors(r#"
+            [[transforms]]
+            type = "session"
+            key = ""
+            timeout = "soon"
+            error_payload = "("
+            max_sessions = 0
+            target = "nowhere"
+        "#), vec!(
+            "transforms[0].key: must not be empty",
+            "transforms[0].timeout: invalid duration 'soon', expected a number followed by ms, s, m or h",
+            "transforms[0].error_payload: invalid regex '(': unclosed group",
+            "transforms[0].max_sessions: must be greater than zero",
+            "transforms[0].target: unknown target 'nowhere'",
+        ));
+    }
+}
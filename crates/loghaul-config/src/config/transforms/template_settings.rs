@@ -0,0 +1,176 @@
+use std::collections::BTreeMap;
+use loghaul_parse::TemplateProcessor;
+use TargetConfig;
+use internal::config_duration::parse_duration;
+use internal::config_defaults::default_true;
+use internal::config_defaults::default_max_keys;
+
+/// Settings for the `template` transform
+#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TemplateSettings {
+    /// Read the message from this field instead of the payload
+    pub field: Option<String>,
+
+    /// The depth of the parse tree; messages are routed by their first `depth - 3` tokens
+    #[serde(default = "default_template_depth")]
+    pub depth: usize,
+
+    /// The share of tokens a message must have in common with a template to join it
+    #[serde(default = "default_template_similarity")]
+    pub similarity: f64,
+
+    /// The most distinct tokens routed separately at each level of the tree
+    #[serde(default = "default_template_max_children")]
+    pub max_children: usize,
+
+    /// Stop learning new templates once this many are known
+    #[serde(default = "default_max_keys")]
+    pub max_templates: usize,
+
+    /// The file templates are loaded from and saved to
+    pub state_file: Option<String>,
+
+    /// How often to save changed templates
+    #[serde(default = "default_template_save_interval")]
+    pub save_interval: String,
+
+    /// Emit a record for every new template
+    #[serde(default = "default_true")]
+    pub events: bool,
+
+    /// Send new template records only to this target, instead of routing them
+    pub target: Option<String>,
+
+    /// The source new template records come from, `template` by default
+    pub source: Option<String>,
+}
+
+impl TemplateSettings {
+    /// Return a description of every problem with these settings, each starting with `prefix`
+    pub fn validate(&self, prefix: &str, targets: &BTreeMap<String, TargetConfig>) -> Vec<String> {
+        let mut errors = Vec::new();
+        match self.field {
+            Some(ref field) if field.trim().is_empty() => errors.push(format!("{}.field: must not be empty", prefix)),
+            _ => {}
+        }
+        if self.depth < 3 {
+            errors.push(format!("{}.depth: must be at least 3", prefix));
+        }
+        if !(self.similarity >= 0.0 && self.similarity <= 1.0) {
+            errors.push(format!("{}.similarity: must be between 0 and 1", prefix));
+        }
+        if self.max_children < 2 {
+            errors.push(format!("{}.max_children: must be at least 2", prefix));
+        }
+        if self.max_templates == 0 {
+            errors.push(format!("{}.max_templates: must be greater than zero", prefix));
+        }
+        match self.state_file.as_ref().map(|path| TemplateProcessor::new().with_state_file(path)) {
+            Some(Err(e)) => errors.push(format!("{}.state_file: {}", prefix, e)),
+            _ => {}
+        }
+        match parse_duration(&self.save_interval) {
+            Ok(_) => {}
+            Err(e) => errors.push(format!("{}.save_interval: {}", prefix, e))
+        }
+        match self.target {
+            Some(ref target) if !targets.contains_key(target) => {
+                errors.push(format!("{}.target: unknown target '{}'", prefix, target));
+            }
+            _ => {}
+        }
+        return errors;
+    }
+
+    /// Create the processor these settings describe
+    pub fn build(&self) -> Result<TemplateProcessor, String> {
+        let mut templates = TemplateProcessor::new()
+            .with_depth(self.depth)
+            .with_similarity(self.similarity)
+            .with_max_children(self.max_children)
+            .with_max_templates(self.max_templates)
+            .with_save_interval(parse_duration(&self.save_interval)?);
+        match self.field {
+            Some(ref field) => templates = templates.with_field(field),
+            None => {}
+        }
+        match self.state_file {
+            Some(ref path) => templates = templates.with_state_file(path)?,
+            None => {}
+        }
+        match self.target {
+            Some(ref target) => templates = templates.with_target(target),
+            None => {}
+        }
+        match self.source {
+            Some(ref source) => templates = templates.with_source(source),
+            None => {}
+        }
+        return Ok(match self.events {
+            true => templates,
+            false => templates.without_events(),
+        });
+    }
+}
+
+fn default_template_depth() -> usize {
+    4
+}
+
+fn default_template_similarity() -> f64 {
+    0.4
+}
+
+fn default_template_max_children() -> usize {
+    100
+}
+
+fn default_template_save_interval() -> String {
+    "10s".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use loghaul::Record;
+    use loghaul::RecordValue;
+    use internal::config_test_helpers::TestChain;
+    use internal::config_test_helpers::config_errors;
+
+    #[test]
+    fn test_template_transform() {
+        let dir = env::temp_dir().join(format!("loghaul_template_config_test_{}", ::std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("known.json"), r#"{"version":1,"templates":[{"id":7,"template":"User <*> logged in","count":3}]}"#).unwrap();
+        fs::write(dir.join("broken.json"), "{").unwrap();
+
+        let mut chain = TestChain::new(&format!(r#"
+            [[transforms]]
+            type = "template"
+            state_file = "{}"
+            events = false
+        "#, dir.join("known.json").display()));
+        for line in vec!("User alice logged in\n", "Disk full\n").into_iter() {
+            chain.run(Record::new("api", line.as_bytes().to_vec()));
+        }
+        let ids: Vec<Option<&RecordValue>> = chain.output.iter().map(|r| r.field("template_id")).collect();
+        assert_eq!(ids, vec!(Some(&RecordValue::Int(7)), Some(&RecordValue::Int(8))));
+
+        let lines = config_errors(&format!(r#"
+            [[transforms]]
+            type = "template"
+            depth = 2
+            similarity = 1.5
+            state_file = "{}"
+            target = "nowhere"
+        "#, dir.join("broken.json").display()));
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "transforms[0].depth: must be at least 3");
+        assert_eq!(lines[1], "transforms[0].similarity: must be between 0 and 1");
+        assert!(lines[2].starts_with("transforms[0].state_file: invalid state file "), "{}", lines[2]);
+        assert_eq!(lines[3], "transforms[0].target: unknown target 'nowhere'");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,99 @@
+use loghaul_parse::TimestampFormat;
+use loghaul_parse::TimestampProcessor;
+use loghaul_parse::TimestampZone;
+use internal::config_defaults::default_timestamp_target;
+
+/// Settings for the `timestamp` transform
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TimestampSettings {
+    /// Formats to try in order: `rfc3339`, `epoch_seconds`, `epoch_millis`, `syslog`
+    /// or a strftime pattern; all four named formats if empty
+    #[serde(default)]
+    pub formats: Vec<String>,
+
+    /// Read the timestamp from this field instead of the payload
+    pub field: Option<String>,
+
+    /// The field the normalized timestamp is written to
+    #[serde(default = "default_timestamp_target")]
+    pub target: String,
+
+    /// The zone of timestamps without an offset, an IANA name or an offset such as `+02:00`
+    pub timezone: Option<String>,
+}
+
+impl TimestampSettings {
+    /// Return a description of every problem with these settings, each starting with `prefix`
+    pub fn validate(&self, prefix: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+        for (position, name) in self.formats.iter().enumerate() {
+            match TimestampFormat::from_name(name) {
+                Ok(_) => {}
+                Err(e) => errors.push(format!("{}.formats[{}]: {}", prefix, position, e))
+            }
+        }
+        if self.target.trim().is_empty() {
+            errors.push(format!("{}.target: must not be empty", prefix));
+        }
+        match self.timezone.as_ref().map(|zone| TimestampZone::from_name(zone)) {
+            Some(Err(e)) => errors.push(format!("{}.timezone: {}", prefix, e)),
+            _ => {}
+        }
+        return errors;
+    }
+
+    /// Create the processor these settings describe
+    pub fn build(&self) -> Result<TimestampProcessor, String> {
+        let mut timestamps = TimestampProcessor::new().with_target(&self.target);
+        if !self.formats.is_empty() {
+            timestamps = timestamps.with_formats(self.formats()?);
+        }
+        match self.field {
+            Some(ref field) => timestamps = timestamps.with_field(field),
+            None => {}
+        }
+        match self.timezone {
+            Some(ref zone) => timestamps = timestamps.with_zone(TimestampZone::from_name(zone)?),
+            None => {}
+        }
+        return Ok(timestamps);
+    }
+
+    fn formats(&self) -> Result<Vec<TimestampFormat>, String> {
+        return self.formats.iter().map(|name| TimestampFormat::from_name(name)).collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use loghaul::Record;
+    use loghaul::RecordValue;
+    use internal::config_test_helpers::TestChain;
+    use internal::config_test_helpers::config_errors;
+
+    #[test]
+    fn test_timestamp_transform() {
+        let mut chain = TestChain::new(r#"
+            [[transforms]]
+            type = "access_log"
+
+            [[transforms]]
+            type = "timestamp"
+            field = "time"
+            formats = ["%d/%b/%Y:%H:%M:%S %z"]
+        "#);
+        chain.run(Record::new("web", b"10.0.0.1 - - [01/May/2024:12:00:00 +0200] \"GET / HTTP/1.1\" 200 5 \"-\" \"curl\"\n".to_vec()));
+        assert_eq!(chain.output[0].field("timestamp"), Some(&RecordValue::from("2024-05-01T10:00:00.000Z")));
+
+        assert_eq!(config_errors(r#"
+            [[transforms]]
+            type = "timestamp"
+            formats = ["rfc3339", "iso"]
+            timezone = "Europe/Springfield"
+        "#), vec!(
+            "transforms[0].formats[1]: unknown timestamp format 'iso', expected rfc3339, epoch_seconds, epoch_millis, syslog or a strftime pattern",
+            "transforms[0].timezone: unknown timezone 'Europe/Springfield'",
+        ));
+    }
+}
@@ -0,0 +1,160 @@
+use loghaul_wasm::WasmProcessor;
+use internal::config_duration::parse_duration;
+use internal::config_defaults::default_true;
+
+/// Settings for the `wasm` transform
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct WasmSettings {
+    /// The compiled plugin module, which is loaded when the config is
+    pub path: String,
+
+    /// The fuel each record may use, roughly one unit per instruction
+    #[serde(default = "default_wasm_fuel")]
+    pub fuel: u64,
+
+    /// The most bytes the plugin's memory may grow to
+    #[serde(default = "default_wasm_max_memory")]
+    pub max_memory: usize,
+
+    /// Fail a record once the plugin emits more than this many records for it
+    #[serde(default = "default_wasm_max_emitted")]
+    pub max_emitted: usize,
+
+    /// Fail a record once the records the plugin emits for it total more than this many bytes
+    #[serde(default = "default_wasm_max_emitted_bytes")]
+    pub max_emitted_bytes: usize,
+
+    /// Fail records the plugin takes longer than this to handle
+    #[serde(default = "default_wasm_time_limit")]
+    pub time_limit: String,
+
+    /// Swap the plugin for the new module when the file changes
+    #[serde(default = "default_true")]
+    pub reload: bool,
+
+    /// How often to check the file for changes
+    #[serde(default = "default_wasm_reload_interval")]
+    pub reload_interval: String,
+}
+
+impl WasmSettings {
+    /// Return a description of every problem with these settings, each starting with `prefix`
+    pub fn validate(&self, prefix: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+        match WasmProcessor::from_file(&self.path) {
+            Ok(_) => {}
+            Err(e) => errors.push(format!("{}.path: {}", prefix, e))
+        }
+        if self.fuel == 0 {
+            errors.push(format!("{}.fuel: must be greater than zero", prefix));
+        }
+        if self.max_memory == 0 {
+            errors.push(format!("{}.max_memory: must be greater than zero", prefix));
+        }
+        if self.max_emitted_bytes == 0 {
+            errors.push(format!("{}.max_emitted_bytes: must be greater than zero", prefix));
+        }
+        for (name, duration) in vec!(("time_limit", &self.time_limit), ("reload_interval", &self.reload_interval)).into_iter() {
+            match parse_duration(duration) {
+                Ok(_) => {}
+                Err(e) => errors.push(format!("{}.{}: {}", prefix, name, e))
+            }
+        }
+        return errors;
+    }
+
+    /// Create the processor these settings describe
+    pub fn build(&self) -> Result<WasmProcessor, String> {
+        let wasm = WasmProcessor::from_file(&self.path)?
+            .with_fuel(self.fuel)
+            .with_max_memory(self.max_memory)
+            .with_max_emitted(self.max_emitted, self.max_emitted_bytes)
+            .with_time_limit(parse_duration(&self.time_limit)?)
+            .with_reload_interval(parse_duration(&self.reload_interval)?);
+        return Ok(match self.reload {
+            true => wasm,
+            false => wasm.without_reload(),
+        });
+    }
+}
+
+fn default_wasm_fuel() -> u64 {
+    10000000
+}
+
+fn default_wasm_max_memory() -> usize {
+    64 * 1024 * 1024
+}
+
+fn default_wasm_max_emitted() -> usize {
+    1000
+}
+
+fn default_wasm_max_emitted_bytes() -> usize {
+    16 * 1024 * 1024
+}
+
+fn default_wasm_time_limit() -> String {
+    "1s".to_string()
+}
+
+fn default_wasm_reload_interval() -> String {
+    "1s".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use wat;
+    use loghaul::Record;
+    use loghaul::RecordValue;
+    use internal::config_test_helpers::TestChain;
+    use internal::config_test_helpers::config_errors;
+
+    #[test]
+    fn test_wasm_transform() {
+        let dir = env::temp_dir().join(format!("loghaul_wasm_config_test_{}", ::std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("pass.wasm"), wat::parse_str(r#"
+            (module
+                (import "loghaul" "emit" (func $emit (param i32 i32)))
+                (memory (export "memory") 1)
+                (func (export "loghaul_abi_version") (result i32) i32.const 1)
+                (func (export "loghaul_alloc") (param i32) (result i32) i32.const 1024)
+                (func (export "loghaul_process") (param i32 i32) (result i32)
+                    (call $emit (local.get 0) (local.get 1))
+                    i32.const 0))
+        "#).unwrap()).unwrap();
+        fs::write(dir.join("broken.wasm"), b"not a module").unwrap();
+
+        let mut chain = TestChain::new(&format!(r#"
+            [[transforms]]
+            type = "wasm"
+            path = '{}'
+            fuel = 100000
+            time_limit = "50ms"
+            reload = false
+        "#, dir.join("pass.wasm").display()));
+        let mut record = Record::new("api", b"hello".to_vec());
+        record.set_field("status", RecordValue::Int(200));
+        chain.run(record.clone());
+        assert_eq!(chain.output, vec!(record));
+
+        let lines = config_errors(&format!(r#"
+            [[transforms]]
+            type = "wasm"
+            path = '{}'
+            fuel = 0
+            max_emitted_bytes = 0
+            reload_interval = "often"
+        "#, dir.join("broken.wasm").display()));
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with(&format!("transforms[0].path: {}: ", dir.join("broken.wasm").display())), "{}", lines[0]);
+        assert_eq!(lines[1], "transforms[0].fuel: must be greater than zero");
+        assert_eq!(lines[2], "transforms[0].max_emitted_bytes: must be greater than zero");
+        assert!(lines[3].starts_with("transforms[0].reload_interval: invalid duration 'often'"), "{}", lines[3]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,49 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LoghaulConfigErrorCode {
+    UnableToReadFile,
+    InvalidSyntax,
+    InvalidValue,
+    BuildFailed,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct LoghaulConfigError {
+    code: LoghaulConfigErrorCode,
+    message: String,
+}
+
+impl LoghaulConfigError {
+    pub fn new(code: LoghaulConfigErrorCode, message: &str) -> LoghaulConfigError {
+        return LoghaulConfigError {
+            code,
+            message: message.to_string(),
+        };
+    }
+
+    pub fn code(&self) -> LoghaulConfigErrorCode {
+        return self.code;
+    }
+
+    /// A human readable description of exactly what was wrong with the config
+    pub fn message(&self) -> &str {
+        return &self.message;
+    }
+}
+
+impl Error for LoghaulConfigError {}
+
+impl fmt::Display for LoghaulConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}: {}", self.code, self.message)
+    }
+}
+
+impl From<io::Error> for LoghaulConfigError {
+    fn from(err: io::Error) -> Self {
+        return LoghaulConfigError::new(LoghaulConfigErrorCode::UnableToReadFile, &err.to_string());
+    }
+}
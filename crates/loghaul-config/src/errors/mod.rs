@@ -0,0 +1 @@
+pub mod loghaul_config_error;
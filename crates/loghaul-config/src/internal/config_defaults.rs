@@ -0,0 +1,11 @@
+pub fn default_true() -> bool {
+    true
+}
+
+pub fn default_max_keys() -> usize {
+    10000
+}
+
+pub fn default_timestamp_target() -> String {
+    "timestamp".to_string()
+}
@@ -0,0 +1,52 @@
+use std::time::Duration;
+
+/// About a hundred years
+const MAX_DURATION_MILLIS: u64 = 100 * 366 * 24 * 60 * 60 * 1000;
+
+/// Parse a human duration such as `250ms`, `5s`, `2m` or `1h`.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    if amount.is_empty() {
+        return Err(format!("invalid duration '{}', expected a number followed by ms, s, m or h", value));
+    }
+    let amount: u64 = match amount.parse() {
+        Ok(v) => v,
+        Err(_) => {
+            return Err(format!("invalid duration '{}', the number is out of range", value));
+        }
+    };
+    let millis = match unit {
+        "ms" => Some(amount),
+        "s" => amount.checked_mul(1000),
+        "m" => amount.checked_mul(60 * 1000),
+        "h" => amount.checked_mul(60 * 60 * 1000),
+        "" => return Err(format!("invalid duration '{}', missing a unit (ms, s, m or h)", value)),
+        other => return Err(format!("invalid duration '{}', unknown unit '{}' (expected ms, s, m or h)", value, other))
+    };
+    // Durations are added to instants, which panics past the clock's range, so keep them well inside it
+    return match millis {
+        Some(millis) if millis <= MAX_DURATION_MILLIS => Ok(Duration::from_millis(millis)),
+        _ => Err(format!("invalid duration '{}', the number is out of range", value)),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_duration;
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("5s"), Ok(Duration::from_secs(5)));
+        assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+        assert_eq!(parse_duration("1h"), Ok(Duration::from_secs(3600)));
+        assert!(parse_duration("10").unwrap_err().contains("missing a unit"));
+        assert!(parse_duration("10 days").unwrap_err().contains("unknown unit ' days'"));
+        assert!(parse_duration("ms").is_err());
+        assert!(parse_duration("999999999999999999m").unwrap_err().contains("out of range"));
+        assert!(parse_duration("99999999999h").unwrap_err().contains("out of range"));
+    }
+}
@@ -0,0 +1,25 @@
+use regex::Regex;
+
+/// Check a regex compiles, describing the problem on a single line if it doesn't.
+pub fn check_regex(pattern: &str) -> Result<(), String> {
+    return match Regex::new(pattern) {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            // Syntax errors are rendered over several lines with the reason last
+            let error = e.to_string();
+            let reason = error.lines().last().unwrap_or("").trim_start_matches("error: ").to_string();
+            Err(format!("invalid regex '{}': {}", pattern, reason))
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check_regex;
+
+    #[test]
+    fn test_check_regex() {
+        assert!(check_regex("^ERROR\\s+(?P<code>\\d+)").is_ok());
+        assert_eq!(check_regex("[a-").unwrap_err(), "invalid regex '[a-': unclosed character class");
+    }
+}
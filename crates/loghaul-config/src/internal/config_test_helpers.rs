@@ -0,0 +1,67 @@
+use loghaul::Record;
+use loghaul::ProcessorChain;
+use loghaul::MetricsRegistry;
+use loghaul::LoghaulErrorAggregate;
+use PipelineConfig;
+use LoghaulConfigError;
+
+/// Parse a pipeline reading `/var/log/api.log` as `api` into the stdout target `out`,
+/// followed by the given transforms and any other targets they need
+pub fn pipeline_with(transforms: &str) -> Result<PipelineConfig, LoghaulConfigError> {
+    return PipelineConfig::from_str(&format!(r#"
+        [sources.api]
+        type = "file"
+        path = "/var/log/api.log"
+
+        [targets.out]
+        type = "stdout"
+        {}
+    "#, transforms));
+}
+
+/// The problems found with a pipeline using the given transforms, one per line
+pub fn config_errors(transforms: &str) -> Vec<String> {
+    return pipeline_with(transforms).unwrap_err().message().lines().map(|line| line.to_string()).collect();
+}
+
+/// The processor chain of a pipeline using the given transforms, with the records it has emitted
+pub struct TestChain {
+    pub chain: ProcessorChain,
+    pub metrics: MetricsRegistry,
+    pub output: Vec<Record>,
+    pub errors: LoghaulErrorAggregate,
+}
+
+impl TestChain {
+    pub fn new(transforms: &str) -> TestChain {
+        let metrics = MetricsRegistry::new();
+        let mut chain = pipeline_with(transforms).unwrap().build_processors().unwrap();
+        chain.attach(&metrics);
+        return TestChain {
+            chain,
+            metrics,
+            output: Vec::new(),
+            errors: LoghaulErrorAggregate::new(),
+        };
+    }
+
+    /// Run a record through the chain
+    pub fn run(&mut self, record: Record) {
+        self.chain.run(record, &self.metrics, &mut self.output, &mut self.errors);
+    }
+
+    /// Tell the chain a source has no more records
+    pub fn end_source(&mut self, source: &str) {
+        self.chain.end_source(source, &self.metrics, &mut self.output, &mut self.errors);
+    }
+
+    /// The payloads of the records emitted so far
+    pub fn payloads(&self) -> Vec<&str> {
+        return self.output.iter().map(|record| record.payload_str().unwrap()).collect();
+    }
+
+    /// A metrics counter of a processor
+    pub fn counter(&self, processor: &str, name: &str) -> Option<u64> {
+        return self.metrics.snapshot().processor(processor).and_then(|p| p.counters.get(name).cloned());
+    }
+}
@@ -0,0 +1,5 @@
+pub mod config_duration;
+pub mod config_regex;
+pub mod config_defaults;
+#[cfg(test)]
+pub mod config_test_helpers;
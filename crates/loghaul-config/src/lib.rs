@@ -0,0 +1,61 @@
+#[cfg(test)]
+extern crate wat;
+extern crate loghaul;
+extern crate loghaul_file;
+extern crate loghaul_stdio;
+extern crate loghaul_http;
+extern crate loghaul_parse;
+extern crate loghaul_script;
+extern crate loghaul_wasm;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate toml;
+extern crate glob;
+extern crate regex;
+
+mod config;
+mod errors;
+mod internal;
+
+pub use config::pipeline_config::PipelineConfig;
+pub use config::keeper_section::KeeperSection;
+pub use config::keeper_section::EofStrategyConfig;
+pub use config::source_config::SourceConfig;
+pub use config::target_config::TargetConfig;
+pub use config::transform_config::TransformConfig;
+pub use config::transform_config::TransformKind;
+pub use config::transform_config::OnErrorConfig;
+pub use config::transforms::json_settings::JsonSettings;
+pub use config::transforms::json_settings::JsonInvalidConfig;
+pub use config::transforms::logfmt_settings::LogfmtSettings;
+pub use config::transforms::grok_settings::GrokSettings;
+pub use config::transforms::grok_settings::GrokPatternConfig;
+pub use config::transforms::access_log_settings::AccessLogSettings;
+pub use config::transforms::access_log_settings::AccessLogStyle;
+pub use config::transforms::multiline_settings::MultilineSettings;
+pub use config::transforms::timestamp_settings::TimestampSettings;
+pub use config::transforms::enrich_settings::EnrichSettings;
+pub use config::transforms::redact_settings::RedactSettings;
+pub use config::transforms::redact_settings::RedactModeConfig;
+pub use config::transforms::redact_settings::RedactPatternConfig;
+pub use config::transforms::sample_settings::SampleSettings;
+pub use config::transforms::rate_limit_settings::RateLimitSettings;
+pub use config::transforms::dedup_settings::DedupSettings;
+pub use config::transforms::aggregate_settings::AggregateSettings;
+pub use config::transforms::aggregate_settings::AggregateMetricConfig;
+pub use config::transforms::aggregate_settings::AggregateMetricType;
+pub use config::transforms::alert_settings::AlertSettings;
+pub use config::transforms::alert_settings::AlertRuleConfig;
+pub use config::transforms::template_settings::TemplateSettings;
+pub use config::transforms::order_settings::OrderSettings;
+pub use config::transforms::order_settings::OrderLateConfig;
+pub use config::transforms::session_settings::SessionSettings;
+pub use config::transforms::script_settings::ScriptSettings;
+pub use config::transforms::wasm_settings::WasmSettings;
+pub use config::route_config::RouteConfig;
+pub use config::pipeline_diff::PipelineDiff;
+pub use config::pipeline_reloader::PipelineReloader;
+
+pub use errors::loghaul_config_error::LoghaulConfigError;
+pub use errors::loghaul_config_error::LoghaulConfigErrorCode;
@@ -18,9 +18,13 @@ impl LoghaulFileError {
     pub fn new(code: LoghaulFileErrorCode, detail: Option<&Error>) -> LoghaulFileError {
         return LoghaulFileError {
             code,
-            message: format!("{:?}", detail),
+            message: detail.map(|e| e.to_string()).unwrap_or(String::new()),
         };
     }
+
+    pub fn message(&self) -> &str {
+        return &self.message;
+    }
 }
 
 impl Error for LoghaulFileError {}
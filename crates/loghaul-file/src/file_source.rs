@@ -8,12 +8,13 @@ use LoghaulFileError;
 use LoghaulFileErrorCode;
 use loghaul::Source;
 use loghaul::LoghaulErrorCode;
-use std::error::Error;
 use std::io::Read;
 
 pub struct FileSource {
     path: PathBuf,
     fp: Option<File>,
+    finite: bool,
+    failed: bool,
 }
 
 impl FileSource {
@@ -21,9 +22,19 @@ impl FileSource {
         return FileSource {
             path: PathBuf::from(path.as_ref()),
             fp: None,
+            finite: false,
+            failed: false,
         };
     }
 
+    /// EOF once the current end of the file is reached, instead of waiting for more data.
+    /// A file that can't be opened is reported as an error once, then EOFs.
+    pub fn closed(path: impl AsRef<Path>) -> FileSource {
+        let mut rtn = FileSource::new(path);
+        rtn.finite = true;
+        return rtn;
+    }
+
     fn close(&mut self) {
         self.fp = None;
     }
@@ -44,8 +55,13 @@ impl FileSource {
     }
 
     fn read_pending_lines(&mut self, buffer: &mut Vec<u8>) -> Result<StreamEntry, LoghaulFileError> {
-        if self.open_fp().is_err() {
-            return Ok(StreamEntry::EOF);
+        match self.open_fp() {
+            Ok(_) => {}
+            Err(err) if self.finite && !self.failed => {
+                self.failed = true;
+                return Err(err);
+            }
+            Err(_) => return Ok(StreamEntry::EOF),
         }
 
         let fp = self.fp.as_mut().unwrap();
@@ -53,6 +69,7 @@ impl FileSource {
             Ok(size) => {
                 match size {
                     v if { v > 0 } => Ok(StreamEntry::Data),
+                    _ if self.finite => Ok(StreamEntry::EOF),
                     _ => Ok(StreamEntry::NoData)
                 }
             }
@@ -67,7 +84,7 @@ impl Source for FileSource {
     fn poll(&mut self, buffer: &mut Vec<u8>) -> Result<StreamEntry, LoghaulError> {
         match self.read_pending_lines(buffer) {
             Ok(v) => Ok(v),
-            Err(e) => Err(LoghaulError::from(LoghaulErrorCode::SourceErr(e.description().to_string())))
+            Err(e) => Err(LoghaulError::from(LoghaulErrorCode::SourceErr(format!("{}: {}", self.path.display(), e.message()))))
         }
     }
 
@@ -75,6 +92,10 @@ impl Source for FileSource {
         self.close();
         return Ok(());
     }
+
+    fn id(&self) -> Option<String> {
+        Some(self.path.to_string_lossy().to_string())
+    }
 }
 
 #[cfg(test)]
@@ -139,5 +160,41 @@ mod tests {
 
         let output: Vec<String> = results.lock().unwrap().iter().map(|i| (&i).to_string()).collect();
         assert_eq!(3, output.len());
+
+        let metrics = keeper.metrics();
+        assert_eq!(3, metrics.source(&input_path.path).unwrap().records_in);
+    }
+
+    #[test]
+    fn test_closed_file_source_eof_at_end() {
+        let input_path = random_test_file();
+        input_path.write("One\nTwo\n");
+
+        let mut stream = Stream::new()
+            .with_source(FileSource::closed(&input_path.path))
+            .with_target(MockTarget::new(|_, _| -> Result<(), LoghaulError> { Ok(()) }));
+
+        let mut dropped = Vec::new();
+        assert!(stream.step(&mut dropped).is_ok());
+        assert_eq!(dropped.len(), 0);
+        assert!(stream.step(&mut dropped).is_ok());
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(stream.metrics().snapshot().source(&input_path.path).unwrap().bytes_in, 8);
+    }
+
+    #[test]
+    fn test_closed_file_source_reports_open_error() {
+        let input_path = random_test_file();
+        input_path.write("");
+        let missing = format!("{}.missing", input_path.path);
+        let mut stream = Stream::new().with_source(FileSource::closed(&missing));
+
+        let mut dropped = Vec::new();
+        assert!(stream.step(&mut dropped).is_err());
+        assert!(stream.step(&mut dropped).is_ok());
+        assert_eq!(dropped.len(), 1);
+        let snapshot = stream.metrics().snapshot();
+        assert_eq!(snapshot.source(&missing).unwrap().errors, 1);
+        assert_eq!(snapshot.source(&missing).unwrap().eofs, 1);
     }
 }
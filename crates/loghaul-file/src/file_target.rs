@@ -1,6 +1,8 @@
 use loghaul::Target;
 use loghaul::LoghaulError;
 use loghaul::StreamEntry;
+use loghaul::Record;
+use loghaul::RecordFormat;
 use std::path::Path;
 use std::path::PathBuf;
 use std::fs::File;
@@ -12,6 +14,7 @@ use LoghaulFileErrorCode;
 pub struct FileTarget {
     path: PathBuf,
     fp: Option<File>,
+    format: RecordFormat,
 }
 
 impl FileTarget {
@@ -19,9 +22,16 @@ impl FileTarget {
         return FileTarget {
             path: PathBuf::from(path.as_ref()),
             fp: None,
+            format: RecordFormat::Raw,
         };
     }
 
+    /// Write records in this format instead of their raw payload
+    pub fn with_format(mut self, format: RecordFormat) -> FileTarget {
+        self.format = format;
+        return self;
+    }
+
     fn write(&mut self, data: &Vec<u8>) -> Result<(), LoghaulFileError> {
         self.open_fp()?;
         if self.fp.is_some() {
@@ -68,11 +78,24 @@ impl Target for FileTarget {
         };
         Ok(())
     }
+
+    fn consume_record(&mut self, record: &Record) -> Result<(), LoghaulError> {
+        return match self.format {
+            RecordFormat::Raw => self.consume(StreamEntry::Data, &record.payload),
+            format => self.consume(StreamEntry::Data, &format.encode(record)),
+        };
+    }
+
+    fn id(&self) -> Option<String> {
+        Some(self.path.to_string_lossy().to_string())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::FileTarget;
+    use loghaul::FnProcessor;
+    use loghaul::RecordFormat;
     use loghaul::Stream;
     use loghaul::mock::MockSource;
     use std::sync::{Arc, Mutex};
@@ -107,4 +130,31 @@ mod tests {
         let contents = read_entire_file(&output_path.path).unwrap();
         assert_eq!(10, contents.len());
     }
+
+    #[test]
+    fn test_file_target_writes_fields_as_json() {
+        let output_path = random_test_file();
+
+        let stream = Stream::new()
+            .with_named_source("api", MockSource::closed(vec!("{\"level\":\"warn\"}\n")))
+            .with_processor(FnProcessor::map(|mut record| {
+                record.set_field("level", "warn");
+                record.set_field("env", "prod");
+                record
+            }))
+            .with_target(FileTarget::new(&output_path.path).with_format(RecordFormat::Json));
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut keeper = Keeper::new(stream, Some(KeeperConfig {
+            interval: Duration::from_millis(1),
+            eof_strategy: KeeperEofStrategy::DropSource,
+            logger: Some(Box::new(MockKeeperLog::new(log))),
+        }));
+
+        sleep(Duration::from_millis(100));
+        keeper.halt();
+
+        let contents = read_entire_file(&output_path.path).unwrap();
+        assert_eq!(contents, vec!("{\"source\":\"api\",\"payload\":\"{\\\"level\\\":\\\"warn\\\"}\",\"fields\":{\"env\":\"prod\",\"level\":\"warn\"}}"));
+    }
 }
@@ -0,0 +1,5 @@
+extern crate loghaul;
+
+mod webhook_target;
+
+pub use webhook_target::WebhookTarget;
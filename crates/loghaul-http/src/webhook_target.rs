@@ -0,0 +1,373 @@
+use loghaul::Target;
+use loghaul::LoghaulError;
+use loghaul::LoghaulErrorCode;
+use loghaul::StreamEntry;
+use loghaul::Record;
+use loghaul::RecordFormat;
+use std::io::Read;
+use std::io::Write;
+use std::mem;
+use std::net::SocketAddr;
+use std::net::TcpStream;
+use std::net::ToSocketAddrs;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::mpsc;
+use std::sync::mpsc::SyncSender;
+use std::sync::mpsc::TrySendError;
+use std::thread;
+use std::time::Duration;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 5;
+
+/// How many records may wait to be posted before new ones are dropped
+const MAX_QUEUED: usize = 1000;
+
+/// Only this much of a response is read, enough for its status line
+const MAX_RESPONSE_BYTES: usize = 8192;
+
+/// Posts every record to an `http://` URL, one request each, as the body of the request.
+///
+/// Records are sent as JSON by default. They are posted one at a time from a background
+/// thread, so a slow or unreachable server never holds up the stream; up to 1000 records
+/// wait to be sent, and records arriving while the queue is full are dropped. Every address
+/// the host resolves to is tried in turn. A response other than 2xx, or no response within
+/// the timeout, is reported as an error by the next record; the record isn't retried.
+/// HTTPS isn't supported, so reach HTTPS endpoints through a local proxy.
+///
+/// ```
+///     extern crate loghaul;
+///     extern crate loghaul_http;
+///     use loghaul::Stream;
+///     use loghaul_http::WebhookTarget;
+///     let pager = WebhookTarget::new("http://alerts.internal:8080/hooks/loghaul")
+///         .with_header("Authorization", "Bearer secret");
+///     let stream = Stream::new().with_target(pager);
+/// ```
+pub struct WebhookTarget {
+    url: String,
+    endpoint: Result<Endpoint, String>,
+    headers: Vec<(String, String)>,
+    timeout: Duration,
+    format: RecordFormat,
+    queue: Option<SyncSender<Vec<u8>>>,
+    failures: Arc<Mutex<Vec<String>>>,
+}
+
+/// Posts queued records, off the stream's thread
+struct WebhookSender {
+    endpoint: Endpoint,
+    headers: Vec<(String, String)>,
+    timeout: Duration,
+    content_type: &'static str,
+}
+
+/// Where requests are sent
+#[derive(Clone)]
+struct Endpoint {
+    /// The host and port, as given in the URL
+    authority: String,
+    path: String,
+}
+
+impl WebhookTarget {
+    /// Post records to this URL; an invalid URL fails every record
+    pub fn new(url: &str) -> WebhookTarget {
+        return WebhookTarget {
+            url: url.to_string(),
+            endpoint: parse_url(url),
+            headers: Vec::new(),
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECS),
+            format: RecordFormat::Json,
+            queue: None,
+            failures: Arc::new(Mutex::new(Vec::new())),
+        };
+    }
+
+    /// Check that a URL can be posted to
+    pub fn check_url(url: &str) -> Result<(), String> {
+        return parse_url(url).map(|_| ());
+    }
+
+    /// Send this header with every request
+    pub fn with_header(mut self, name: &str, value: &str) -> WebhookTarget {
+        self.headers.push((name.to_string(), value.to_string()));
+        return self;
+    }
+
+    /// Give up on connecting, sending or reading the response after this long, five seconds by default
+    pub fn with_timeout(mut self, timeout: Duration) -> WebhookTarget {
+        self.timeout = timeout;
+        return self;
+    }
+
+    /// Send records in this format instead of JSON
+    pub fn with_format(mut self, format: RecordFormat) -> WebhookTarget {
+        self.format = format;
+        return self;
+    }
+
+    /// Queue a record for the sender, starting it on first use
+    fn send(&mut self, body: &[u8]) -> Result<(), String> {
+        if self.queue.is_none() {
+            let endpoint = self.endpoint.as_ref().map_err(|e| e.clone())?;
+            let sender = WebhookSender {
+                endpoint: endpoint.clone(),
+                headers: self.headers.clone(),
+                timeout: self.timeout,
+                content_type: match self.format {
+                    RecordFormat::Json => "application/json",
+                    RecordFormat::Raw => "application/octet-stream",
+                },
+            };
+            let (queue, receiver) = mpsc::sync_channel::<Vec<u8>>(MAX_QUEUED);
+            let failures = self.failures.clone();
+            thread::spawn(move || {
+                // Ends once the target is dropped and what it queued has been sent
+                for body in receiver.iter() {
+                    match sender.post(&body) {
+                        Ok(_) => {}
+                        Err(e) => failures.lock().expect("webhook failures lock").push(e),
+                    }
+                }
+            });
+            self.queue = Some(queue);
+        }
+        return match self.queue.as_ref().expect("queue is started").try_send(body.to_vec()) {
+            Ok(_) => Ok(()),
+            Err(TrySendError::Full(_)) => Err("too many records waiting to be sent, record dropped".to_string()),
+            Err(TrySendError::Disconnected(_)) => Err("the sender stopped, record dropped".to_string()),
+        };
+    }
+
+    /// Report what went wrong in the background since the last call
+    fn failures(&self) -> Result<(), LoghaulError> {
+        let failures = mem::replace(&mut *self.failures.lock().expect("webhook failures lock"), Vec::new());
+        return match failures.is_empty() {
+            true => Ok(()),
+            false => Err(self.error(&failures.join(", "))),
+        };
+    }
+
+    fn error(&self, message: &str) -> LoghaulError {
+        return LoghaulError::from(LoghaulErrorCode::TargetErr(format!("{}: {}", self.url, message)));
+    }
+}
+
+impl WebhookSender {
+    fn post(&self, body: &[u8]) -> Result<(), String> {
+        let endpoint = &self.endpoint;
+        let addresses = endpoint.authority.to_socket_addrs().map_err(|e| e.to_string())?;
+        let mut stream = connect(addresses, self.timeout)?;
+        stream.set_read_timeout(Some(self.timeout)).map_err(|e| e.to_string())?;
+        stream.set_write_timeout(Some(self.timeout)).map_err(|e| e.to_string())?;
+
+        let mut request = format!("POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n", endpoint.path, endpoint.authority, self.content_type, body.len());
+        for &(ref name, ref value) in self.headers.iter() {
+            request.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        request.push_str("\r\n");
+        let mut request = request.into_bytes();
+        request.extend_from_slice(body);
+        stream.write_all(&request).map_err(|e| e.to_string())?;
+
+        let mut response = Vec::new();
+        let mut buffer = [0; 1024];
+        while response.len() < MAX_RESPONSE_BYTES && !response.windows(2).any(|w| w == b"\r\n") {
+            match stream.read(&mut buffer).map_err(|e| e.to_string())? {
+                0 => break,
+                read => response.extend_from_slice(&buffer[..read]),
+            }
+        }
+        let status_line = String::from_utf8_lossy(&response);
+        let status_line = status_line.lines().next().unwrap_or("");
+        return match status_line.split(' ').nth(1) {
+            Some(status) if status.len() == 3 && status.starts_with('2') => Ok(()),
+            Some(_) => Err(format!("the server replied {}", status_line.trim())),
+            None => Err("the server sent no response".to_string()),
+        };
+    }
+}
+
+/// Connect to the first of these addresses that accepts a connection
+fn connect<A: Iterator<Item = SocketAddr>>(addresses: A, timeout: Duration) -> Result<TcpStream, String> {
+    let mut last_error = "no address found".to_string();
+    for address in addresses {
+        match TcpStream::connect_timeout(&address, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = format!("{}: {}", address, e),
+        }
+    }
+    return Err(last_error);
+}
+
+/// Split an `http://host[:port][/path]` URL into where to connect and what to request
+fn parse_url(url: &str) -> Result<Endpoint, String> {
+    let rest = match url.trim() {
+        url if url.starts_with("http://") => &url["http://".len()..],
+        url if url.starts_with("https://") => return Err(format!("invalid url '{}', https is not supported", url)),
+        url => return Err(format!("invalid url '{}', expected http://host[:port]/path", url)),
+    };
+    let (authority, path) = match rest.find(|c| c == '/' || c == '?') {
+        Some(split) if rest[split..].starts_with('/') => (&rest[..split], rest[split..].to_string()),
+        Some(split) => (&rest[..split], format!("/{}", &rest[split..])),
+        None => (rest, "/".to_string()),
+    };
+    if authority.is_empty() || authority.contains('@') || path.contains(char::is_whitespace) {
+        return Err(format!("invalid url '{}', expected http://host[:port]/path", url.trim()));
+    }
+    let authority = match authority.rfind(':') {
+        Some(colon) if !authority.ends_with(']') => match authority[colon + 1..].parse::<u16>() {
+            Ok(_) => authority.to_string(),
+            Err(_) => return Err(format!("invalid url '{}', bad port '{}'", url.trim(), &authority[colon + 1..])),
+        },
+        _ => format!("{}:80", authority),
+    };
+    return Ok(Endpoint {
+        authority: authority,
+        path: path,
+    });
+}
+
+impl Target for WebhookTarget {
+    fn consume(&mut self, entry: StreamEntry, data: &Vec<u8>) -> Result<(), LoghaulError> {
+        match entry {
+            StreamEntry::NoData => {}
+            StreamEntry::EOF => {}
+            StreamEntry::Data => {
+                match self.send(data) {
+                    Ok(_) => {}
+                    Err(e) => return Err(self.error(&e)),
+                }
+            }
+        };
+        return self.failures();
+    }
+
+    fn consume_record(&mut self, record: &Record) -> Result<(), LoghaulError> {
+        return self.consume(StreamEntry::Data, &self.format.encode(record));
+    }
+
+    fn id(&self) -> Option<String> {
+        Some(self.url.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WebhookTarget;
+    use super::connect;
+    use loghaul::Target;
+    use loghaul::Record;
+    use loghaul::RecordFormat;
+    use loghaul::StreamEntry;
+    use std::io::Read;
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::thread;
+    use std::thread::sleep;
+    use std::time::Duration;
+    use std::time::Instant;
+
+    /// Poll a webhook target until it reports a failure, or give up after a few seconds
+    fn next_failure(target: &mut WebhookTarget) -> Option<String> {
+        let started = Instant::now();
+        while started.elapsed() < Duration::from_secs(5) {
+            match target.consume(StreamEntry::NoData, &Vec::new()) {
+                Ok(_) => sleep(Duration::from_millis(10)),
+                Err(e) => return Some(e.to_string()),
+            }
+        }
+        return None;
+    }
+
+    /// Answer each request with the next status, returning the requests received
+    fn serve(listener: TcpListener, statuses: Vec<&'static str>) -> thread::JoinHandle<Vec<String>> {
+        return thread::spawn(move || {
+            let mut requests = Vec::new();
+            for status in statuses.into_iter() {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = Vec::new();
+                let mut buffer = [0; 1024];
+                loop {
+                    let read = stream.read(&mut buffer).unwrap();
+                    request.extend_from_slice(&buffer[..read]);
+                    let text = String::from_utf8_lossy(&request).to_string();
+                    let complete = match text.find("\r\n\r\n") {
+                        Some(end) => {
+                            let length = text.lines().filter_map(|l| l.strip_prefix("Content-Length: ")).next().unwrap().parse::<usize>().unwrap();
+                            request.len() >= end + 4 + length
+                        }
+                        None => false,
+                    };
+                    if read == 0 || complete {
+                        break;
+                    }
+                }
+                stream.write_all(format!("HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status).as_bytes()).unwrap();
+                requests.push(String::from_utf8_lossy(&request).to_string());
+            }
+            requests
+        });
+    }
+
+    #[test]
+    fn test_posts_records() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hooks/loghaul?team=ops", listener.local_addr().unwrap());
+        let server = serve(listener, vec!("200 OK", "503 Service Unavailable", "204 No Content"));
+
+        let mut target = WebhookTarget::new(&url).with_header("Authorization", "Bearer secret");
+        let mut record = Record::new("alert", b"alert out_of_memory\n".to_vec());
+        record.set_field("alert", "out_of_memory");
+        target.consume_record(&record).unwrap();
+        target.consume_record(&record).unwrap();
+        assert!(next_failure(&mut target).unwrap().contains("the server replied HTTP/1.1 503 Service Unavailable"));
+
+        let mut raw = WebhookTarget::new(&url).with_format(RecordFormat::Raw);
+        raw.consume_record(&record).unwrap();
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("POST /hooks/loghaul?team=ops HTTP/1.1\r\n"));
+        assert!(requests[0].contains("\r\nContent-Type: application/json\r\n"));
+        assert!(requests[0].contains("\r\nAuthorization: Bearer secret\r\n"));
+        assert!(requests[0].ends_with("\r\n\r\n{\"source\":\"alert\",\"payload\":\"alert out_of_memory\",\"fields\":{\"alert\":\"out_of_memory\"}}\n"));
+        assert!(requests[2].ends_with("\r\n\r\nalert out_of_memory\n"));
+    }
+
+    #[test]
+    fn test_tries_every_address() {
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let open = listener.local_addr().unwrap();
+        let stream = connect(vec!(closed, open).into_iter(), Duration::from_secs(1)).unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), open);
+        let error = connect(vec!(closed).into_iter(), Duration::from_secs(1)).unwrap_err();
+        assert!(error.starts_with(&format!("{}: ", closed)));
+        assert_eq!(connect(Vec::new().into_iter(), Duration::from_secs(1)).unwrap_err(), "no address found");
+    }
+
+    #[test]
+    fn test_unreachable_server_does_not_block() {
+        // Nothing answers at this address, so connecting waits for the timeout
+        let mut target = WebhookTarget::new("http://10.255.255.1:9/hook").with_timeout(Duration::from_secs(2));
+        let started = Instant::now();
+        for _ in 0..10 {
+            target.consume_record(&Record::new("alert", b"alert\n".to_vec())).unwrap();
+        }
+        assert!(started.elapsed() < Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_invalid_urls() {
+        assert!(WebhookTarget::check_url("http://localhost:8080/hook").is_ok());
+        assert!(WebhookTarget::check_url("http://[::1]/hook").is_ok());
+        assert_eq!(WebhookTarget::check_url("https://example.com/hook"), Err("invalid url 'https://example.com/hook', https is not supported".to_string()));
+        assert_eq!(WebhookTarget::check_url("example.com/hook"), Err("invalid url 'example.com/hook', expected http://host[:port]/path".to_string()));
+        assert_eq!(WebhookTarget::check_url("http://example.com:http/"), Err("invalid url 'http://example.com:http/', bad port 'http'".to_string()));
+
+        let mut target = WebhookTarget::new("ftp://example.com");
+        let error = target.consume_record(&Record::new("alert", b"alert\n".to_vec())).unwrap_err();
+        assert!(error.to_string().contains("ftp://example.com: invalid url"));
+    }
+}
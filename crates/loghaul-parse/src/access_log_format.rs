@@ -0,0 +1,334 @@
+use regex::Regex;
+use loghaul::RecordValue;
+
+/// The nginx `combined` format; Apache's combined format writes identical lines
+pub const COMBINED_FORMAT: &str = "$remote_addr - $remote_user [$time_local] \"$request\" $status $body_bytes_sent \"$http_referer\" \"$http_user_agent\"";
+
+/// How a captured value is written to the record
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum FieldKind {
+    String,
+    Int,
+    Float,
+    /// `METHOD PATH PROTOCOL`, split into `method`, `path` and `protocol`
+    Request,
+}
+
+#[derive(Debug, Clone)]
+enum Token {
+    Literal(String),
+    /// A variable, captured into a field, or skipped if it has no field
+    Variable(Option<String>, FieldKind),
+}
+
+/// An access log line format, compiled into a parser.
+///
+/// Formats can be written with nginx `log_format` variables (`$remote_addr`,
+/// `$status`, `${request_time}`...) or Apache `LogFormat` directives (`%h`, `%>s`,
+/// `%{User-Agent}i`...). Well known variables become typed fields named `client_ip`,
+/// `user`, `time`, `method`, `path`, `protocol`, `status`, `bytes`, `referer`,
+/// `user_agent` and `request_time`; any other nginx variable keeps its own name.
+///
+/// ```
+///     extern crate loghaul_parse;
+///     use loghaul_parse::AccessLogFormat;
+///     let format = AccessLogFormat::nginx("$remote_addr [$time_local] \"$request\" $status $request_time").unwrap();
+///     let fields = format.parse("10.0.0.1 [10/Oct/2023:13:55:36 +0000] \"GET / HTTP/1.1\" 200 0.012").unwrap();
+///     assert_eq!(fields.len(), 7);
+/// ```
+#[derive(Debug, Clone)]
+pub struct AccessLogFormat {
+    regex: Regex,
+    fields: Vec<(Option<String>, FieldKind)>,
+}
+
+impl AccessLogFormat {
+    /// The combined format used by default by both nginx and Apache
+    pub fn combined() -> AccessLogFormat {
+        return AccessLogFormat::nginx(COMBINED_FORMAT).expect("the combined format compiles");
+    }
+
+    /// Compile an nginx `log_format` string
+    pub fn nginx(format: &str) -> Result<AccessLogFormat, String> {
+        return AccessLogFormat::compile(tokenize_nginx(format)?);
+    }
+
+    /// Compile an Apache `LogFormat` string
+    pub fn apache(format: &str) -> Result<AccessLogFormat, String> {
+        return AccessLogFormat::compile(tokenize_apache(format)?);
+    }
+
+    fn compile(tokens: Vec<Token>) -> Result<AccessLogFormat, String> {
+        let mut pattern = String::from("^");
+        let mut fields = Vec::new();
+        for (index, token) in tokens.iter().enumerate() {
+            match token {
+                Token::Literal(text) => pattern.push_str(&::regex::escape(text)),
+                Token::Variable(field, kind) => {
+                    // A value runs up to the first character of the literal after it
+                    let next = match tokens.get(index + 1) {
+                        Some(Token::Literal(text)) => text.chars().next(),
+                        Some(Token::Variable(_, _)) => {
+                            return Err("two variables must be separated by some text".to_string());
+                        }
+                        None => None,
+                    };
+                    let value = match next {
+                        Some('"') => r#"(?:[^"\\]|\\.)*"#.to_string(),
+                        Some(c) => format!("[^{}]*", ::regex::escape(&c.to_string())),
+                        None => ".*".to_string(),
+                    };
+                    pattern.push_str(&format!("({})", value));
+                    fields.push((field.clone(), *kind));
+                }
+            }
+        }
+        pattern.push('$');
+        let regex = Regex::new(&pattern).map_err(|e| e.to_string())?;
+        return Ok(AccessLogFormat {
+            regex,
+            fields,
+        });
+    }
+
+    /// Parse a line, or return None if it doesn't match the format.
+    /// Values logged as `-` or left empty are left out.
+    pub fn parse(&self, line: &str) -> Option<Vec<(String, RecordValue)>> {
+        let found = self.regex.captures(line)?;
+        let mut output = Vec::with_capacity(self.fields.len());
+        for (index, &(ref field, kind)) in self.fields.iter().enumerate() {
+            let (field, value) = match (field, found.get(index + 1)) {
+                (Some(field), Some(value)) => (field, value.as_str()),
+                _ => continue,
+            };
+            if value.is_empty() || value == "-" {
+                continue;
+            }
+            match kind {
+                FieldKind::String => output.push((field.clone(), RecordValue::from(value))),
+                FieldKind::Int => output.push((field.clone(), value.parse::<i64>().map(RecordValue::Int).unwrap_or(RecordValue::from(value)))),
+                FieldKind::Float => output.push((field.clone(), value.parse::<f64>().map(RecordValue::Float).unwrap_or(RecordValue::from(value)))),
+                FieldKind::Request => {
+                    let parts: Vec<&str> = value.splitn(3, ' ').collect();
+                    for (name, part) in ["method", "path", "protocol"].iter().zip(parts.iter()) {
+                        output.push((name.to_string(), RecordValue::from(*part)));
+                    }
+                }
+            }
+        }
+        return Some(output);
+    }
+}
+
+/// The field and type for an nginx variable
+fn nginx_field(variable: &str) -> (Option<String>, FieldKind) {
+    let (name, kind) = match variable {
+        "remote_addr" => ("client_ip", FieldKind::String),
+        "remote_user" => ("user", FieldKind::String),
+        "time_local" | "time_iso8601" => ("time", FieldKind::String),
+        "request" => ("request", FieldKind::Request),
+        "request_method" => ("method", FieldKind::String),
+        "request_uri" => ("path", FieldKind::String),
+        "server_protocol" => ("protocol", FieldKind::String),
+        "status" => ("status", FieldKind::Int),
+        "body_bytes_sent" | "bytes_sent" => ("bytes", FieldKind::Int),
+        "http_referer" => ("referer", FieldKind::String),
+        "http_user_agent" => ("user_agent", FieldKind::String),
+        "request_time" => ("request_time", FieldKind::Float),
+        "upstream_response_time" | "upstream_connect_time" | "upstream_header_time" => (variable, FieldKind::Float),
+        "request_length" | "connection" | "connection_requests" | "upstream_status" => (variable, FieldKind::Int),
+        other => (other, FieldKind::String),
+    };
+    return (Some(name.to_string()), kind);
+}
+
+fn tokenize_nginx(format: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut literal = String::new();
+    let mut rest = format;
+    while let Some(start) = rest.find('$') {
+        literal.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+        let (name, length) = if rest.starts_with('{') {
+            match rest.find('}') {
+                Some(end) => (&rest[1..end], end + 1),
+                None => {
+                    return Err(format!("unclosed '${{' in '{}'", format));
+                }
+            }
+        } else {
+            let end = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len());
+            (&rest[..end], end)
+        };
+        if name.is_empty() {
+            return Err(format!("'$' without a variable name in '{}'", format));
+        }
+        rest = &rest[length..];
+        if !literal.is_empty() {
+            tokens.push(Token::Literal(literal.clone()));
+            literal.clear();
+        }
+        let (field, kind) = nginx_field(name);
+        tokens.push(Token::Variable(field, kind));
+    }
+    literal.push_str(rest);
+    if !literal.is_empty() {
+        tokens.push(Token::Literal(literal));
+    }
+    return Ok(tokens);
+}
+
+fn tokenize_apache(format: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut literal = String::new();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            literal.push(c);
+            continue;
+        }
+        // Skip status code conditions and the < / > request selectors, eg. %>s or %400,501{User-agent}i
+        while let Some(&m) = chars.peek() {
+            if m == '<' || m == '>' || m == '!' || m == ',' || m.is_ascii_digit() {
+                chars.next();
+            } else {
+                break;
+            }
+        }
+        let argument = if chars.peek() == Some(&'{') {
+            chars.next();
+            let argument: String = chars.by_ref().take_while(|&c| c != '}').collect();
+            Some(argument)
+        } else {
+            None
+        };
+        let directive = match chars.next() {
+            Some(d) => d,
+            None => {
+                return Err(format!("'%' without a directive at the end of '{}'", format));
+            }
+        };
+        if directive == '%' {
+            literal.push('%');
+            continue;
+        }
+        let (field, kind) = match (directive, argument.as_ref()) {
+            ('h', _) | ('a', _) => (Some("client_ip".to_string()), FieldKind::String),
+            ('l', _) => (None, FieldKind::String),
+            ('u', _) => (Some("user".to_string()), FieldKind::String),
+            ('t', None) => {
+                // %t writes its own brackets
+                literal.push('[');
+                tokens.push(Token::Literal(literal.clone()));
+                literal = String::from("]");
+                tokens.push(Token::Variable(Some("time".to_string()), FieldKind::String));
+                continue;
+            }
+            ('t', Some(_)) => (Some("time".to_string()), FieldKind::String),
+            ('r', _) => (Some("request".to_string()), FieldKind::Request),
+            ('m', _) => (Some("method".to_string()), FieldKind::String),
+            ('U', _) => (Some("path".to_string()), FieldKind::String),
+            ('H', _) => (Some("protocol".to_string()), FieldKind::String),
+            ('s', _) => (Some("status".to_string()), FieldKind::Int),
+            ('b', _) | ('B', _) | ('O', _) => (Some("bytes".to_string()), FieldKind::Int),
+            ('T', _) => (Some("request_time".to_string()), FieldKind::Float),
+            ('D', _) => (Some("request_time_us".to_string()), FieldKind::Int),
+            ('v', _) | ('V', _) => (Some("server_name".to_string()), FieldKind::String),
+            ('i', Some(header)) => {
+                let name = match header.to_ascii_lowercase().as_str() {
+                    "referer" => "referer".to_string(),
+                    "user-agent" => "user_agent".to_string(),
+                    other => format!("http_{}", other.replace('-', "_")),
+                };
+                (Some(name), FieldKind::String)
+            }
+            (other, _) => {
+                return Err(format!("unsupported directive '%{}' in '{}'", other, format));
+            }
+        };
+        if !literal.is_empty() {
+            tokens.push(Token::Literal(literal.clone()));
+            literal.clear();
+        }
+        tokens.push(Token::Variable(field, kind));
+    }
+    if !literal.is_empty() {
+        tokens.push(Token::Literal(literal));
+    }
+    return Ok(tokens);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AccessLogFormat;
+    use loghaul::RecordValue;
+
+    fn fields(pairs: Vec<(&str, RecordValue)>) -> Option<Vec<(String, RecordValue)>> {
+        return Some(pairs.into_iter().map(|(k, v)| (k.to_string(), v)).collect());
+    }
+
+    #[test]
+    fn test_parse_combined() {
+        let combined = AccessLogFormat::combined();
+        assert_eq!(combined.parse("203.0.113.9 - alice [10/Oct/2023:13:55:36 -0700] \"GET /index.html?q=\\\"x\\\" HTTP/1.1\" 200 2326 \"http://example.com/\" \"Mozilla/5.0 (X11; Linux x86_64)\""), fields(vec!(
+            ("client_ip", RecordValue::from("203.0.113.9")),
+            ("user", RecordValue::from("alice")),
+            ("time", RecordValue::from("10/Oct/2023:13:55:36 -0700")),
+            ("method", RecordValue::from("GET")),
+            ("path", RecordValue::from("/index.html?q=\\\"x\\\"")),
+            ("protocol", RecordValue::from("HTTP/1.1")),
+            ("status", RecordValue::Int(200)),
+            ("bytes", RecordValue::Int(2326)),
+            ("referer", RecordValue::from("http://example.com/")),
+            ("user_agent", RecordValue::from("Mozilla/5.0 (X11; Linux x86_64)")),
+        )));
+
+        // Dashes are missing values, and a garbage request line is kept as a method
+        assert_eq!(combined.parse("2001:db8::1 - - [10/Oct/2023:13:55:36 +0000] \"\\x16\\x03\" 400 0 \"-\" \"-\""), fields(vec!(
+            ("client_ip", RecordValue::from("2001:db8::1")),
+            ("time", RecordValue::from("10/Oct/2023:13:55:36 +0000")),
+            ("method", RecordValue::from("\\x16\\x03")),
+            ("status", RecordValue::Int(400)),
+            ("bytes", RecordValue::Int(0)),
+        )));
+        assert_eq!(combined.parse("not an access log"), None);
+    }
+
+    #[test]
+    fn test_compile_custom_formats() {
+        let nginx = AccessLogFormat::nginx("$remote_addr [$time_iso8601] \"$request\" $status ${request_time}s upstream=$upstream_response_time host=$host").unwrap();
+        assert_eq!(nginx.parse("10.0.0.1 [2023-10-10T13:55:36+00:00] \"POST /api HTTP/2.0\" 502 1.250s upstream=1.249 host=api.example.com"), fields(vec!(
+            ("client_ip", RecordValue::from("10.0.0.1")),
+            ("time", RecordValue::from("2023-10-10T13:55:36+00:00")),
+            ("method", RecordValue::from("POST")),
+            ("path", RecordValue::from("/api")),
+            ("protocol", RecordValue::from("HTTP/2.0")),
+            ("status", RecordValue::Int(502)),
+            ("request_time", RecordValue::Float(1.25)),
+            ("upstream_response_time", RecordValue::Float(1.249)),
+            ("host", RecordValue::from("api.example.com")),
+        )));
+
+        let apache = AccessLogFormat::apache("%h %l %u %t \"%r\" %>s %b \"%{Referer}i\" \"%{User-agent}i\" %D %{X-Request-Id}i").unwrap();
+        assert_eq!(apache.parse("192.0.2.1 - - [10/Oct/2000:13:55:36 -0700] \"GET /a.gif HTTP/1.0\" 304 - \"-\" \"curl/8.0\" 5120 abc-123"), fields(vec!(
+            ("client_ip", RecordValue::from("192.0.2.1")),
+            ("time", RecordValue::from("10/Oct/2000:13:55:36 -0700")),
+            ("method", RecordValue::from("GET")),
+            ("path", RecordValue::from("/a.gif")),
+            ("protocol", RecordValue::from("HTTP/1.0")),
+            ("status", RecordValue::Int(304)),
+            ("user_agent", RecordValue::from("curl/8.0")),
+            ("request_time_us", RecordValue::Int(5120)),
+            ("http_x_request_id", RecordValue::from("abc-123")),
+        )));
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        assert_eq!(AccessLogFormat::nginx("$status$body_bytes_sent").unwrap_err(), "two variables must be separated by some text");
+        assert_eq!(AccessLogFormat::nginx("${status").unwrap_err(), "unclosed '${' in '${status'");
+        assert_eq!(AccessLogFormat::nginx("cost $ 5").unwrap_err(), "'$' without a variable name in 'cost $ 5'");
+        assert_eq!(AccessLogFormat::apache("%h %Z").unwrap_err(), "unsupported directive '%Z' in '%h %Z'");
+        assert!(AccessLogFormat::apache("100%% %h").is_ok());
+    }
+}
@@ -0,0 +1,82 @@
+use loghaul::Processor;
+use loghaul::Record;
+use loghaul::LoghaulError;
+use access_log_format::AccessLogFormat;
+use internal::parse_helpers::payload_line;
+use internal::parse_helpers::parse_error;
+
+/// Parses web server access log lines into typed fields.
+///
+/// Parses the combined format by default; other layouts are described with an
+/// nginx `log_format` or Apache `LogFormat` string, see `AccessLogFormat`. Lines
+/// that don't match the format fail.
+///
+/// ```
+///     extern crate loghaul;
+///     extern crate loghaul_parse;
+///     use loghaul::Stream;
+///     use loghaul_parse::{AccessLogFormat, AccessLogProcessor};
+///     let format = AccessLogFormat::nginx("$remote_addr - $remote_user [$time_local] \"$request\" $status $body_bytes_sent $request_time").unwrap();
+///     let stream = Stream::new().with_processor(AccessLogProcessor::with_format(format));
+/// ```
+pub struct AccessLogProcessor {
+    format: AccessLogFormat,
+}
+
+impl AccessLogProcessor {
+    /// A processor for the combined format
+    pub fn new() -> AccessLogProcessor {
+        return AccessLogProcessor::with_format(AccessLogFormat::combined());
+    }
+
+    pub fn with_format(format: AccessLogFormat) -> AccessLogProcessor {
+        return AccessLogProcessor {
+            format,
+        };
+    }
+}
+
+impl Processor for AccessLogProcessor {
+    fn process(&mut self, mut record: Record, output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        let fields = {
+            let text = payload_line(&record.payload)?;
+            match self.format.parse(text) {
+                Some(fields) => fields,
+                None => {
+                    return Err(parse_error("line does not match the access log format"));
+                }
+            }
+        };
+        for (key, value) in fields.into_iter() {
+            record.fields.insert(key, value);
+        }
+        output.push(record);
+        return Ok(());
+    }
+
+    fn id(&self) -> Option<String> {
+        Some("access_log".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AccessLogProcessor;
+    use loghaul::Processor;
+    use loghaul::Record;
+    use loghaul::RecordValue;
+
+    #[test]
+    fn test_process_combined_line() {
+        let mut processor = AccessLogProcessor::new();
+        let mut output = Vec::new();
+        processor.process(Record::new("nginx", b"127.0.0.1 - - [01/May/2024:10:00:00 +0000] \"GET /health HTTP/1.1\" 200 2 \"-\" \"kube-probe/1.29\"\n".to_vec()), &mut output).unwrap();
+        assert_eq!(output[0].field("path"), Some(&RecordValue::from("/health")));
+        assert_eq!(output[0].field("status"), Some(&RecordValue::Int(200)));
+        assert_eq!(output[0].field("user"), None);
+        assert_eq!(output[0].payload_str(), Some("127.0.0.1 - - [01/May/2024:10:00:00 +0000] \"GET /health HTTP/1.1\" 200 2 \"-\" \"kube-probe/1.29\"\n"));
+
+        assert!(processor.process(Record::new("nginx", b"2024-05-01 upstream timed out".to_vec()), &mut output).is_err());
+        assert_eq!(output.len(), 1);
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::BTreeMap;
+use regex::Regex;
+use loghaul::RecordValue;
+
+/// Prefix of the capture groups generated for `%{NAME:field}`, kept apart from user named groups
+const GROUP_PREFIX: &str = "__grok";
+
+/// Guards against definitions that expand forever without referring to themselves directly
+const MAX_EXPANSION_DEPTH: usize = 32;
+
+/// The patterns every library starts with. Definitions may refer to each other with `%{NAME}`.
+const BUILTIN_PATTERNS: &[(&str, &str)] = &[
+    ("INT", r"[+-]?\d+"),
+    ("NUMBER", r"[+-]?(?:\d+(?:\.\d*)?|\.\d+)(?:[eE][+-]?\d+)?"),
+    ("WORD", r"\w+"),
+    ("NOTSPACE", r"\S+"),
+    ("SPACE", r"\s*"),
+    ("DATA", r".*?"),
+    ("GREEDYDATA", r".*"),
+    ("QUOTEDSTRING", r#""(?:[^"\\]|\\.)*"|'(?:[^'\\]|\\.)*'"#),
+    ("UUID", r"[A-Fa-f0-9]{8}-(?:[A-Fa-f0-9]{4}-){3}[A-Fa-f0-9]{12}"),
+    ("IPV4", r"(?:(?:25[0-5]|2[0-4]\d|1\d\d|[1-9]?\d)\.){3}(?:25[0-5]|2[0-4]\d|1\d\d|[1-9]?\d)"),
+    ("IPV6", r"(?:[A-Fa-f0-9]{1,4}:){7}[A-Fa-f0-9]{1,4}|(?:[A-Fa-f0-9]{1,4}:){1,7}:|(?:[A-Fa-f0-9]{1,4}:){1,6}(?::[A-Fa-f0-9]{1,4}){1,6}|:(?::[A-Fa-f0-9]{1,4}){1,7}|::"),
+    ("IP", r"%{IPV6}|%{IPV4}"),
+    ("HOSTNAME", r"[A-Za-z0-9](?:[A-Za-z0-9-]{0,62}[A-Za-z0-9])?(?:\.[A-Za-z0-9](?:[A-Za-z0-9-]{0,62}[A-Za-z0-9])?)*\.?"),
+    ("LOGLEVEL", r"(?i:trace|debug|info(?:rmation)?|notice|warn(?:ing)?|err(?:or)?|crit(?:ical)?|fatal|severe|alert|emerg(?:ency)?)"),
+    ("YEAR", r"\d{4}"),
+    ("MONTHNUM", r"0?[1-9]|1[0-2]"),
+    ("MONTHDAY", r"0[1-9]|[12]\d|3[01]|[1-9]"),
+    ("HOUR", r"2[0-3]|[01]?\d"),
+    ("MINUTE", r"[0-5]\d"),
+    ("SECOND", r"(?:[0-5]?\d|60)(?:[:.,]\d+)?"),
+    ("ISO8601_TIMEZONE", r"Z|[+-]%{HOUR}(?::?%{MINUTE})?"),
+    ("TIMESTAMP_ISO8601", r"%{YEAR}-%{MONTHNUM}-%{MONTHDAY}[T ]%{HOUR}:?%{MINUTE}(?::?%{SECOND})?%{ISO8601_TIMEZONE}?"),
+];
+
+/// How a captured value is typed when it is written to a field
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum GrokType {
+    String,
+    Int,
+    Float,
+}
+
+/// A set of named, reusable regex fragments that grok patterns are built from.
+///
+/// A grok pattern is a regex that may also contain `%{NAME}` to match a named
+/// pattern, `%{NAME:field}` to capture it into a field, or `%{NAME:field:int}`
+/// (or `:float`) to capture it as a number. Plain `(?P<field>...)` groups also
+/// become fields.
+///
+/// ```
+///     extern crate loghaul_parse;
+///     use loghaul_parse::GrokLibrary;
+///     let library = GrokLibrary::new().with_pattern("REQUEST_ID", "req-%{INT}");
+///     let pattern = library.compile("%{TIMESTAMP_ISO8601:time} %{LOGLEVEL:level} \\[%{REQUEST_ID:request}\\] %{GREEDYDATA:message}").unwrap();
+///     let fields = pattern.extract("2024-05-01T10:00:00Z WARN [req-42] disk almost full").unwrap();
+///     assert_eq!(fields[1].0, "level");
+/// ```
+#[derive(Debug, Clone)]
+pub struct GrokLibrary {
+    patterns: BTreeMap<String, String>,
+}
+
+/// A compiled grok pattern
+#[derive(Debug, Clone)]
+pub struct GrokPattern {
+    regex: Regex,
+    captures: Vec<(String, String, GrokType)>,
+}
+
+impl GrokLibrary {
+    /// A library holding the builtin patterns
+    pub fn new() -> GrokLibrary {
+        let mut library = GrokLibrary::empty();
+        for &(name, pattern) in BUILTIN_PATTERNS.iter() {
+            library.add_pattern(name, pattern);
+        }
+        return library;
+    }
+
+    /// A library without any patterns
+    pub fn empty() -> GrokLibrary {
+        return GrokLibrary {
+            patterns: BTreeMap::new(),
+        };
+    }
+
+    pub fn with_pattern(mut self, name: &str, pattern: &str) -> GrokLibrary {
+        self.add_pattern(name, pattern);
+        return self;
+    }
+
+    /// Define a named pattern, replacing any existing pattern with the same name
+    pub fn add_pattern(&mut self, name: &str, pattern: &str) {
+        self.patterns.insert(name.to_string(), pattern.to_string());
+    }
+
+    /// Expand every `%{...}` reference in a pattern and compile the result
+    pub fn compile(&self, pattern: &str) -> Result<GrokPattern, String> {
+        let mut captures = Vec::new();
+        let mut stack = Vec::new();
+        let expanded = self.expand(pattern, &mut stack, &mut captures)?;
+        let regex = Regex::new(&expanded).map_err(|e| {
+            let error = e.to_string();
+            format!("invalid pattern '{}': {}", pattern, error.lines().last().unwrap_or("").trim_start_matches("error: "))
+        })?;
+        let named: Vec<String> = regex.capture_names()
+            .filter_map(|n| n)
+            .filter(|n| !n.starts_with(GROUP_PREFIX))
+            .map(|n| n.to_string())
+            .collect();
+        for name in named.into_iter() {
+            captures.push((name.clone(), name, GrokType::String));
+        }
+        return Ok(GrokPattern {
+            regex,
+            captures,
+        });
+    }
+
+    fn expand(&self, pattern: &str, stack: &mut Vec<String>, captures: &mut Vec<(String, String, GrokType)>) -> Result<String, String> {
+        let mut output = String::with_capacity(pattern.len());
+        let mut rest = pattern;
+        while let Some(start) = rest.find("%{") {
+            output.push_str(&rest[..start]);
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                None => {
+                    return Err(format!("unclosed '%{{' in '{}'", pattern));
+                }
+            };
+            let reference = &rest[start + 2..end];
+            rest = &rest[end + 1..];
+
+            let mut parts = reference.splitn(3, ':');
+            let name = parts.next().unwrap_or("");
+            let field = parts.next();
+            let kind = match parts.next() {
+                None | Some("string") => GrokType::String,
+                Some("int") => GrokType::Int,
+                Some("float") => GrokType::Float,
+                Some(other) => {
+                    return Err(format!("unknown type '{}' in '%{{{}}}', expected int, float or string", other, reference));
+                }
+            };
+            let definition = match self.patterns.get(name) {
+                Some(d) => d,
+                None => {
+                    return Err(format!("unknown pattern '{}'", name));
+                }
+            };
+            if stack.iter().any(|s| s == name) {
+                return Err(format!("pattern '{}' refers to itself", name));
+            }
+            if stack.len() >= MAX_EXPANSION_DEPTH {
+                return Err(format!("pattern '{}' nests more than {} levels deep", name, MAX_EXPANSION_DEPTH));
+            }
+            stack.push(name.to_string());
+            let inner = self.expand(definition, stack, captures)?;
+            stack.pop();
+
+            match field {
+                Some(field) if !field.is_empty() => {
+                    let group = format!("{}{}", GROUP_PREFIX, captures.len());
+                    output.push_str(&format!("(?P<{}>{})", group, inner));
+                    captures.push((group, field.to_string(), kind));
+                }
+                _ => output.push_str(&format!("(?:{})", inner)),
+            }
+        }
+        output.push_str(rest);
+        return Ok(output);
+    }
+}
+
+impl GrokPattern {
+    /// Match some text, returning the captured fields in the order they appear
+    /// in the pattern, or None if it doesn't match. Groups that took no part in
+    /// the match are left out.
+    pub fn extract(&self, text: &str) -> Option<Vec<(String, RecordValue)>> {
+        let found = self.regex.captures(text)?;
+        let mut fields = Vec::with_capacity(self.captures.len());
+        for &(ref group, ref field, kind) in self.captures.iter() {
+            let value = match found.name(group) {
+                Some(m) => m.as_str(),
+                None => continue,
+            };
+            let typed = match kind {
+                GrokType::Int => value.parse::<i64>().map(RecordValue::Int).ok(),
+                GrokType::Float => value.parse::<f64>().map(RecordValue::Float).ok(),
+                GrokType::String => None,
+            };
+            fields.push((field.clone(), typed.unwrap_or(RecordValue::from(value))));
+        }
+        return Some(fields);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GrokLibrary;
+    use loghaul::RecordValue;
+
+    #[test]
+    fn test_builtin_patterns() {
+        let library = GrokLibrary::new();
+        let matches = |name: &str, text: &str| library.compile(&format!("^%{{{}}}$", name)).unwrap().extract(text).is_some();
+        assert!(matches("IP", "192.168.0.1"));
+        assert!(matches("IP", "2001:db8::ff00:42:8329"));
+        assert!(matches("IP", "::1"));
+        assert!(!matches("IPV4", "256.1.1.1"));
+        assert!(matches("TIMESTAMP_ISO8601", "2024-05-01T10:00:00.123+02:00"));
+        assert!(matches("TIMESTAMP_ISO8601", "2024-05-01 10:00:00Z"));
+        assert!(!matches("TIMESTAMP_ISO8601", "May 1 10:00:00"));
+        assert!(matches("LOGLEVEL", "WARNING"));
+        assert!(matches("LOGLEVEL", "err"));
+        assert!(!matches("LOGLEVEL", "loud"));
+        assert!(matches("NUMBER", "-1.5e3"));
+        assert!(matches("QUOTEDSTRING", "\"say \\\"hi\\\"\""));
+        assert!(!matches("QUOTEDSTRING", "\"unterminated"));
+    }
+
+    #[test]
+    fn test_compile_captures_typed_fields() {
+        let library = GrokLibrary::new().with_pattern("STATUS", "%{INT}");
+        let pattern = library.compile("%{IP:client} (?P<method>[A-Z]+) %{STATUS:status:int} %{NUMBER:took:float}s(?: %{QUOTEDSTRING:agent})?").unwrap();
+        assert_eq!(pattern.extract("10.0.0.1 GET 200 0.25s"), Some(vec!(
+            ("client".to_string(), RecordValue::from("10.0.0.1")),
+            ("status".to_string(), RecordValue::Int(200)),
+            ("took".to_string(), RecordValue::Float(0.25)),
+            ("method".to_string(), RecordValue::from("GET")),
+        )));
+        assert_eq!(pattern.extract("nothing to see"), None);
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        let library = GrokLibrary::new()
+            .with_pattern("LOOP", "a%{LOOP}")
+            .with_pattern("PING", "%{PONG}")
+            .with_pattern("PONG", "%{PING}");
+        assert_eq!(library.compile("%{NOPE:x}").unwrap_err(), "unknown pattern 'NOPE'");
+        assert_eq!(library.compile("%{INT:x:hex}").unwrap_err(), "unknown type 'hex' in '%{INT:x:hex}', expected int, float or string");
+        assert_eq!(library.compile("%{INT").unwrap_err(), "unclosed '%{' in '%{INT'");
+        assert_eq!(library.compile("%{LOOP}").unwrap_err(), "pattern 'LOOP' refers to itself");
+        assert_eq!(library.compile("%{PING}").unwrap_err(), "pattern 'PING' refers to itself");
+        assert_eq!(library.compile("(%{INT}").unwrap_err(), "invalid pattern '(%{INT}': unclosed group");
+    }
+}
@@ -0,0 +1,166 @@
+use loghaul::Processor;
+use loghaul::Record;
+use loghaul::LoghaulError;
+use loghaul::MetricsRegistry;
+use grok_library::GrokLibrary;
+use grok_library::GrokPattern;
+use internal::parse_helpers::payload_line;
+use internal::parse_helpers::parse_error;
+
+/// Extracts fields with grok patterns, trying each pattern in order until one matches.
+///
+/// Records no pattern matches fail, leaving them to the chain's error policy. The
+/// records each pattern matched are counted as `match:<label>`, and the others as
+/// `no_match`.
+///
+/// ```
+///     extern crate loghaul;
+///     extern crate loghaul_parse;
+///     use loghaul::{Stream, ProcessorErrorPolicy};
+///     use loghaul_parse::GrokProcessor;
+///     let grok = GrokProcessor::new()
+///         .with_pattern("app", "^%{TIMESTAMP_ISO8601:time} %{LOGLEVEL:level} %{GREEDYDATA:message}").unwrap()
+///         .with_pattern("bare", "^%{LOGLEVEL:level}: %{GREEDYDATA:message}").unwrap();
+///     let mut stream = Stream::new();
+///     stream.add_boxed_processor(None, ProcessorErrorPolicy::PassThrough, Box::new(grok));
+/// ```
+pub struct GrokProcessor {
+    library: GrokLibrary,
+    patterns: Vec<(String, GrokPattern)>,
+    field: Option<String>,
+    metrics: Option<(String, MetricsRegistry)>,
+}
+
+impl GrokProcessor {
+    /// A processor compiling its patterns against the builtin library
+    pub fn new() -> GrokProcessor {
+        return GrokProcessor::with_library(GrokLibrary::new());
+    }
+
+    /// A processor compiling its patterns against a custom library
+    pub fn with_library(library: GrokLibrary) -> GrokProcessor {
+        return GrokProcessor {
+            library,
+            patterns: Vec::new(),
+            field: None,
+            metrics: None,
+        };
+    }
+
+    /// Add a pattern to try after the ones already added, labelled for metrics
+    pub fn with_pattern(mut self, label: &str, pattern: &str) -> Result<GrokProcessor, LoghaulError> {
+        self.add_pattern(label, pattern)?;
+        return Ok(self);
+    }
+
+    pub fn add_pattern(&mut self, label: &str, pattern: &str) -> Result<(), LoghaulError> {
+        let compiled = self.library.compile(pattern).map_err(|e| parse_error(&e))?;
+        self.patterns.push((label.to_string(), compiled));
+        return Ok(());
+    }
+
+    /// Match against the string value of a field instead of the payload
+    pub fn with_field(mut self, field: &str) -> GrokProcessor {
+        self.field = Some(field.to_string());
+        return self;
+    }
+
+    fn count(&self, counter: &str) {
+        match self.metrics {
+            Some((ref id, ref metrics)) => metrics.processor_counter(id, counter, 1),
+            None => {}
+        }
+    }
+}
+
+impl Processor for GrokProcessor {
+    fn process(&mut self, mut record: Record, output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        let found = {
+            let text = match self.field {
+                Some(ref field) => match record.field(field) {
+                    Some(value) => value.to_string(),
+                    None => {
+                        return Err(parse_error(&format!("field '{}' is not set", field)));
+                    }
+                },
+                None => payload_line(&record.payload)?.to_string(),
+            };
+            self.patterns.iter().filter_map(|&(ref label, ref pattern)| pattern.extract(&text).map(|f| (label.clone(), f))).next()
+        };
+        match found {
+            Some((label, fields)) => {
+                self.count(&format!("match:{}", label));
+                for (key, value) in fields.into_iter() {
+                    record.fields.insert(key, value);
+                }
+                output.push(record);
+                return Ok(());
+            }
+            None => {
+                self.count("no_match");
+                return Err(parse_error("no grok pattern matched"));
+            }
+        }
+    }
+
+    fn id(&self) -> Option<String> {
+        Some("grok".to_string())
+    }
+
+    fn attach(&mut self, id: &str, metrics: &MetricsRegistry) {
+        self.metrics = Some((id.to_string(), metrics.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GrokProcessor;
+    use loghaul::Processor;
+    use loghaul::Record;
+    use loghaul::RecordValue;
+    use loghaul::MetricsRegistry;
+
+    #[test]
+    fn test_first_matching_pattern_wins() {
+        let metrics = MetricsRegistry::new();
+        let mut grok = GrokProcessor::new()
+            .with_pattern("full", "^%{TIMESTAMP_ISO8601:time} %{LOGLEVEL:level} %{GREEDYDATA:message}$").unwrap()
+            .with_pattern("level", "^%{LOGLEVEL:level}").unwrap()
+            .with_pattern("anything", "^%{GREEDYDATA:message}$").unwrap();
+        grok.attach("grok", &metrics);
+
+        let mut output = Vec::new();
+        grok.process(Record::new("app", b"2024-05-01T10:00:00Z ERROR disk full\n".to_vec()), &mut output).unwrap();
+        grok.process(Record::new("app", b"INFO 2024-05-01 ready".to_vec()), &mut output).unwrap();
+        grok.process(Record::new("app", b"ERROR and more".to_vec()), &mut output).unwrap();
+        assert_eq!(output[0].field("time"), Some(&RecordValue::from("2024-05-01T10:00:00Z")));
+        assert_eq!(output[0].field("message"), Some(&RecordValue::from("disk full")));
+        assert_eq!(output[1].field("level"), Some(&RecordValue::from("INFO")));
+        assert_eq!(output[1].field("message"), None);
+
+        let snapshot = metrics.snapshot();
+        let counters = &snapshot.processor("grok").unwrap().counters;
+        assert_eq!(counters.get("match:full"), Some(&1));
+        assert_eq!(counters.get("match:level"), Some(&2));
+        assert_eq!(counters.get("match:anything"), None);
+    }
+
+    #[test]
+    fn test_no_match_fails_record() {
+        let metrics = MetricsRegistry::new();
+        let mut grok = GrokProcessor::new().with_pattern("ip", "^%{IP:client}").unwrap().with_field("peer");
+        grok.attach("peers", &metrics);
+
+        let mut output = Vec::new();
+        let mut record = Record::new("app", b"payload is ignored".to_vec());
+        record.set_field("peer", "10.1.2.3:443");
+        grok.process(record.clone(), &mut output).unwrap();
+        assert_eq!(output[0].field("client"), Some(&RecordValue::from("10.1.2.3")));
+
+        record.set_field("peer", "localhost");
+        assert!(grok.process(record, &mut output).is_err());
+        assert!(grok.process(Record::new("app", vec!()), &mut output).is_err());
+        assert_eq!(metrics.snapshot().processor("peers").unwrap().counters.get("no_match"), Some(&1));
+        assert!(GrokProcessor::new().with_pattern("bad", "%{MISSING}").is_err());
+    }
+}
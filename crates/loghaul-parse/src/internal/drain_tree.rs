@@ -0,0 +1,199 @@
+use std::collections::BTreeMap;
+
+/// The token standing for any value in a template
+pub const WILDCARD: &str = "<*>";
+
+/// Learns message templates online with the Drain algorithm: messages are routed
+/// by their token count and first few tokens to a leaf, and join the most similar
+/// template there, which turns the tokens they differ in into wildcards.
+pub struct DrainTree {
+    depth: usize,
+    similarity: f64,
+    max_children: usize,
+    roots: BTreeMap<usize, DrainNode>,
+    templates: BTreeMap<u64, DrainTemplate>,
+    next_id: u64,
+}
+
+#[derive(Default)]
+struct DrainNode {
+    children: BTreeMap<String, DrainNode>,
+    templates: Vec<u64>,
+}
+
+/// A learned template and how many messages have matched it
+#[derive(Debug, Clone, PartialEq)]
+pub struct DrainTemplate {
+    pub tokens: Vec<String>,
+    pub count: u64,
+}
+
+impl DrainTemplate {
+    pub fn text(&self) -> String {
+        return self.tokens.join(" ");
+    }
+}
+
+impl DrainTree {
+    pub fn new(depth: usize, similarity: f64, max_children: usize) -> DrainTree {
+        return DrainTree {
+            depth: depth.max(3),
+            similarity,
+            max_children: max_children.max(2),
+            roots: BTreeMap::new(),
+            templates: BTreeMap::new(),
+            next_id: 1,
+        };
+    }
+
+    pub fn len(&self) -> usize {
+        return self.templates.len();
+    }
+
+    pub fn template(&self, id: u64) -> Option<&DrainTemplate> {
+        return self.templates.get(&id);
+    }
+
+    pub fn templates(&self) -> &BTreeMap<u64, DrainTemplate> {
+        return &self.templates;
+    }
+
+    /// Match a message to a template, learning a new one if none is similar enough
+    /// and `learn` is set. Returns the template id and whether it is new.
+    pub fn add(&mut self, tokens: &[&str], learn: bool) -> Option<(u64, bool)> {
+        let (depth, max_children) = (self.depth, self.max_children);
+        let leaf = match learn {
+            true => Some(route(self.roots.entry(tokens.len()).or_insert_with(DrainNode::default), tokens, depth, max_children)),
+            false => self.roots.get_mut(&tokens.len()).and_then(|root| find(root, tokens, depth)),
+        };
+        let leaf = match leaf {
+            Some(leaf) => leaf,
+            None => return None,
+        };
+
+        let mut best: Option<(u64, f64)> = None;
+        for id in leaf.templates.iter() {
+            let score = similarity(&self.templates[id].tokens, tokens);
+            if score >= self.similarity && best.map(|(_, b)| score > b).unwrap_or(true) {
+                best = Some((*id, score));
+            }
+        }
+        match best {
+            Some((id, _)) => {
+                let template = self.templates.get_mut(&id).unwrap();
+                for (known, token) in template.tokens.iter_mut().zip(tokens.iter()) {
+                    if known != token {
+                        *known = WILDCARD.to_string();
+                    }
+                }
+                template.count += 1;
+                return Some((id, false));
+            }
+            None if learn => {
+                let id = self.next_id;
+                self.next_id += 1;
+                leaf.templates.push(id);
+                self.templates.insert(id, DrainTemplate { tokens: tokens.iter().map(|t| t.to_string()).collect(), count: 1 });
+                return Some((id, true));
+            }
+            None => return None,
+        }
+    }
+
+    /// Put back a template learned earlier, such as one loaded from disk
+    pub fn restore(&mut self, id: u64, template: DrainTemplate) {
+        let (depth, max_children) = (self.depth, self.max_children);
+        let tokens: Vec<&str> = template.tokens.iter().map(|t| t.as_str()).collect();
+        let leaf = route(self.roots.entry(tokens.len()).or_insert_with(DrainNode::default), &tokens, depth, max_children);
+        leaf.templates.push(id);
+        self.templates.insert(id, template);
+        self.next_id = self.next_id.max(id + 1);
+    }
+}
+
+/// The key a token is routed by; tokens holding digits are likely values, so they share a wildcard
+fn route_key(token: &str) -> &str {
+    return match token.bytes().any(|b| b.is_ascii_digit()) {
+        true => WILDCARD,
+        false => token,
+    };
+}
+
+/// The leaf for a message, adding nodes on the way; a node that is full sends new tokens to its wildcard child
+fn route<'a>(root: &'a mut DrainNode, tokens: &[&str], depth: usize, max_children: usize) -> &'a mut DrainNode {
+    let mut node = root;
+    for token in tokens.iter().take(depth - 3) {
+        let mut key = route_key(token);
+        if !node.children.contains_key(key) {
+            let room = node.children.len() + if node.children.contains_key(WILDCARD) { 0 } else { 1 };
+            if room >= max_children {
+                key = WILDCARD;
+            }
+        }
+        node = node.children.entry(key.to_string()).or_insert_with(DrainNode::default);
+    }
+    return node;
+}
+
+/// The leaf for a message, if it exists
+fn find<'a>(root: &'a mut DrainNode, tokens: &[&str], depth: usize) -> Option<&'a mut DrainNode> {
+    let mut node = root;
+    for token in tokens.iter().take(depth - 3) {
+        let key = match node.children.contains_key(route_key(token)) {
+            true => route_key(token),
+            false => WILDCARD,
+        };
+        node = match node.children.get_mut(key) {
+            Some(child) => child,
+            None => return None,
+        };
+    }
+    return Some(node);
+}
+
+/// The share of a template's tokens a message matches exactly; wildcards don't count
+fn similarity(template: &[String], tokens: &[&str]) -> f64 {
+    if tokens.is_empty() {
+        return 1.0;
+    }
+    let same = template.iter().zip(tokens.iter()).filter(|&(known, token)| known == token).count();
+    return same as f64 / tokens.len() as f64;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DrainTree;
+
+    fn add(tree: &mut DrainTree, line: &str) -> (u64, bool) {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        return tree.add(&tokens, true).unwrap();
+    }
+
+    #[test]
+    fn test_learns_templates() {
+        let mut tree = DrainTree::new(4, 0.4, 100);
+        assert_eq!(add(&mut tree, "User alice logged in from 10.0.0.1"), (1, true));
+        assert_eq!(add(&mut tree, "User bob logged in from 10.0.0.2"), (1, false));
+        assert_eq!(add(&mut tree, "Disk /dev/sda1 is full"), (2, true));
+        assert_eq!(add(&mut tree, "User carol logged out"), (3, true));
+        assert_eq!(tree.template(1).unwrap().text(), "User <*> logged in from <*>");
+        assert_eq!(tree.template(1).unwrap().count, 2);
+        assert_eq!(tree.template(3).unwrap().text(), "User carol logged out");
+
+        let tokens: Vec<&str> = "Connection reset by peer".split_whitespace().collect();
+        assert_eq!(tree.add(&tokens, false), None);
+        let tokens: Vec<&str> = "User dave logged in from 10.0.0.3".split_whitespace().collect();
+        assert_eq!(tree.add(&tokens, false), Some((1, false)));
+        assert_eq!(tree.len(), 3);
+    }
+
+    #[test]
+    fn test_full_nodes_share_a_wildcard_child() {
+        let mut tree = DrainTree::new(4, 0.5, 2);
+        assert_eq!(add(&mut tree, "alpha ok"), (1, true));
+        // No room for a third child, so both share the wildcard child and merge
+        assert_eq!(add(&mut tree, "beta ok"), (2, true));
+        assert_eq!(add(&mut tree, "gamma ok"), (2, false));
+        assert_eq!(tree.template(2).unwrap().text(), "<*> ok");
+    }
+}
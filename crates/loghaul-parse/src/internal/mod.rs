@@ -0,0 +1,2 @@
+pub mod parse_helpers;
+pub mod drain_tree;
@@ -0,0 +1,14 @@
+use loghaul::LoghaulError;
+use loghaul::LoghaulErrorCode;
+
+/// The payload as a string without its line terminator, or an error if it isn't utf8
+pub fn payload_line(payload: &[u8]) -> Result<&str, LoghaulError> {
+    return match ::std::str::from_utf8(payload) {
+        Ok(text) => Ok(text.trim_end_matches(|c| c == '\n' || c == '\r')),
+        Err(e) => Err(parse_error(&format!("payload is not valid utf8: {}", e)))
+    };
+}
+
+pub fn parse_error(message: &str) -> LoghaulError {
+    return LoghaulError::from(LoghaulErrorCode::ProcessorErr(message.to_string()));
+}
@@ -0,0 +1,278 @@
+use serde_json;
+use serde_json::Value;
+use loghaul::Processor;
+use loghaul::Record;
+use loghaul::RecordValue;
+use loghaul::LoghaulError;
+use internal::parse_helpers::payload_line;
+use internal::parse_helpers::parse_error;
+
+/// Guards against hostile input nesting objects deep enough to exhaust the stack
+const DEFAULT_MAX_DEPTH: usize = 32;
+
+/// What the json processor does with a payload that isn't a valid json object
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum JsonInvalidPolicy {
+    /// Fail the record, leaving it to the chain's error policy (drop, pass through or dead letter)
+    Fail,
+    /// Pass the record on untouched
+    Pass,
+    /// Pass the record on untouched, with the parse error in this field
+    Tag(String),
+}
+
+/// Parses each payload as a json object into the record's fields.
+/// Nested objects and arrays are flattened into dotted keys, so
+/// `{"http": {"status": 200}, "tags": ["a"]}` sets `http.status` and `tags.0`.
+///
+/// ```
+///     extern crate loghaul;
+///     extern crate loghaul_parse;
+///     use loghaul::{Stream, ProcessorErrorPolicy};
+///     use loghaul_parse::{JsonProcessor, JsonInvalidPolicy};
+///     let mut stream = Stream::new();
+///     stream.add_boxed_processor(None, ProcessorErrorPolicy::DeadLetter("rejects".to_string()),
+///                                Box::new(JsonProcessor::new().with_max_depth(8)));
+///     stream.add_processor(JsonProcessor::new()
+///         .with_invalid(JsonInvalidPolicy::Tag("json_error".to_string()))
+///         .without_flatten());
+/// ```
+pub struct JsonProcessor {
+    invalid: JsonInvalidPolicy,
+    separator: Option<String>,
+    max_depth: usize,
+}
+
+impl JsonProcessor {
+    pub fn new() -> JsonProcessor {
+        return JsonProcessor {
+            invalid: JsonInvalidPolicy::Fail,
+            separator: Some(".".to_string()),
+            max_depth: DEFAULT_MAX_DEPTH,
+        };
+    }
+
+    pub fn with_invalid(mut self, invalid: JsonInvalidPolicy) -> JsonProcessor {
+        self.invalid = invalid;
+        return self;
+    }
+
+    /// Join nested keys with this separator instead of `.`
+    pub fn with_separator(mut self, separator: &str) -> JsonProcessor {
+        self.separator = Some(separator.to_string());
+        return self;
+    }
+
+    /// Keep nested objects and arrays as json text in a single field
+    pub fn without_flatten(mut self) -> JsonProcessor {
+        self.separator = None;
+        return self;
+    }
+
+    /// Reject payloads with objects or arrays nested deeper than this
+    pub fn with_max_depth(mut self, max_depth: usize) -> JsonProcessor {
+        self.max_depth = max_depth;
+        return self;
+    }
+
+    /// Parse a payload into a list of fields
+    fn parse(&self, payload: &[u8]) -> Result<Vec<(String, RecordValue)>, String> {
+        let text = payload_line(payload).map_err(|_| "payload is not valid utf8".to_string())?;
+        let depth = nesting_depth(text);
+        if depth > self.max_depth {
+            return Err(format!("json nested deeper than {} levels", self.max_depth));
+        }
+        let value: Value = serde_json::from_str(text).map_err(|e| format!("invalid json: {}", e))?;
+        let object = match value {
+            Value::Object(object) => object,
+            other => {
+                return Err(format!("expected a json object, found {}", type_name(&other)));
+            }
+        };
+        let mut fields = Vec::with_capacity(object.len());
+        for (key, value) in object.into_iter() {
+            self.flatten_into(key, value, &mut fields);
+        }
+        return Ok(fields);
+    }
+
+    fn flatten_into(&self, key: String, value: Value, fields: &mut Vec<(String, RecordValue)>) {
+        let separator = match self.separator {
+            Some(ref s) => s,
+            None => {
+                fields.push((key, to_record_value(value)));
+                return;
+            }
+        };
+        match value {
+            // Empty containers have nothing to flatten, so they are kept as json text
+            Value::Object(ref object) if object.is_empty() => fields.push((key, RecordValue::from("{}"))),
+            Value::Array(ref array) if array.is_empty() => fields.push((key, RecordValue::from("[]"))),
+            Value::Object(object) => {
+                for (child, value) in object.into_iter() {
+                    self.flatten_into(format!("{}{}{}", key, separator, child), value, fields);
+                }
+            }
+            Value::Array(array) => {
+                for (index, value) in array.into_iter().enumerate() {
+                    self.flatten_into(format!("{}{}{}", key, separator, index), value, fields);
+                }
+            }
+            other => fields.push((key, to_record_value(other))),
+        }
+    }
+}
+
+impl Processor for JsonProcessor {
+    fn process(&mut self, mut record: Record, output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        match self.parse(&record.payload) {
+            Ok(fields) => {
+                for (key, value) in fields.into_iter() {
+                    record.fields.insert(key, value);
+                }
+            }
+            Err(message) => match self.invalid {
+                JsonInvalidPolicy::Fail => {
+                    return Err(parse_error(&message));
+                }
+                JsonInvalidPolicy::Pass => {}
+                JsonInvalidPolicy::Tag(ref field) => record.set_field(field, message),
+            }
+        }
+        output.push(record);
+        return Ok(());
+    }
+
+    fn id(&self) -> Option<String> {
+        Some("json".to_string())
+    }
+}
+
+/// Convert a json value to a record value; objects and arrays become their json text
+fn to_record_value(value: Value) -> RecordValue {
+    return match value {
+        Value::Null => RecordValue::Null,
+        Value::Bool(b) => RecordValue::Bool(b),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => RecordValue::Int(i),
+            None => RecordValue::Float(n.as_f64().unwrap_or(0.0)),
+        },
+        Value::String(s) => RecordValue::String(s),
+        other => RecordValue::String(other.to_string()),
+    };
+}
+
+fn type_name(value: &Value) -> &'static str {
+    return match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    };
+}
+
+/// The deepest nesting of objects and arrays in some json text, ignoring brackets inside strings.
+/// Checked before parsing, so hostile input is rejected without building the nested values.
+fn nesting_depth(text: &str) -> usize {
+    let mut depth = 0;
+    let mut deepest = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    for byte in text.bytes() {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'{' | b'[' => {
+                depth += 1;
+                if depth > deepest {
+                    deepest = depth;
+                }
+            }
+            b'}' | b']' => depth = if depth > 0 { depth - 1 } else { 0 },
+            _ => {}
+        }
+    }
+    return deepest;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{JsonProcessor, JsonInvalidPolicy, nesting_depth};
+    use loghaul::Processor;
+    use loghaul::Record;
+    use loghaul::RecordValue;
+
+    fn parse(processor: &mut JsonProcessor, payload: &str) -> Result<Record, ()> {
+        let mut output = Vec::new();
+        processor.process(Record::new("test", payload.as_bytes().to_vec()), &mut output).map_err(|_| ())?;
+        assert_eq!(output.len(), 1);
+        return Ok(output.remove(0));
+    }
+
+    #[test]
+    fn test_parse_flattens_nested_values() {
+        let mut json = JsonProcessor::new();
+        let record = parse(&mut json, "{\"level\":\"warn\",\"latency\":1.5,\"retries\":3,\"ok\":false,\"user\":null,\
+                                       \"http\":{\"status\":503,\"headers\":{}},\"tags\":[\"a\",{\"b\":\"\\u00e9\"}]}\n").unwrap();
+        assert_eq!(record.field("level"), Some(&RecordValue::from("warn")));
+        assert_eq!(record.field("latency"), Some(&RecordValue::Float(1.5)));
+        assert_eq!(record.field("retries"), Some(&RecordValue::Int(3)));
+        assert_eq!(record.field("ok"), Some(&RecordValue::Bool(false)));
+        assert_eq!(record.field("user"), Some(&RecordValue::Null));
+        assert_eq!(record.field("http.status"), Some(&RecordValue::Int(503)));
+        assert_eq!(record.field("http.headers"), Some(&RecordValue::from("{}")));
+        assert_eq!(record.field("tags.0"), Some(&RecordValue::from("a")));
+        assert_eq!(record.field("tags.1.b"), Some(&RecordValue::from("é")));
+        assert_eq!(record.payload_str(), Some("{\"level\":\"warn\",\"latency\":1.5,\"retries\":3,\"ok\":false,\"user\":null,\
+                                               \"http\":{\"status\":503,\"headers\":{}},\"tags\":[\"a\",{\"b\":\"\\u00e9\"}]}\n"));
+
+        let mut json = JsonProcessor::new().with_separator("_");
+        assert_eq!(parse(&mut json, "{\"a\":{\"b\":1}}").unwrap().field("a_b"), Some(&RecordValue::Int(1)));
+
+        let mut json = JsonProcessor::new().without_flatten();
+        let record = parse(&mut json, "{\"a\":{\"b\":[1,2]},\"big\":18446744073709551615}").unwrap();
+        assert_eq!(record.field("a"), Some(&RecordValue::from("{\"b\":[1,2]}")));
+        assert_eq!(record.field("big"), Some(&RecordValue::Float(18446744073709551615.0)));
+    }
+
+    #[test]
+    fn test_invalid_json_policies() {
+        let mut json = JsonProcessor::new();
+        assert!(parse(&mut json, "not json").is_err());
+        assert!(parse(&mut json, "[1, 2]").is_err());
+        assert!(parse(&mut json, "{\"a\": 1").is_err());
+
+        let mut json = JsonProcessor::new().with_invalid(JsonInvalidPolicy::Pass);
+        let record = parse(&mut json, "not json").unwrap();
+        assert!(record.fields.is_empty());
+        assert_eq!(record.payload_str(), Some("not json"));
+
+        let mut json = JsonProcessor::new().with_invalid(JsonInvalidPolicy::Tag("json_error".to_string()));
+        let record = parse(&mut json, "\"just a string\"").unwrap();
+        assert_eq!(record.field("json_error"), Some(&RecordValue::from("expected a json object, found a string")));
+    }
+
+    #[test]
+    fn test_max_depth_rejects_hostile_input() {
+        assert_eq!(nesting_depth("{\"a\": \"[[[{{\\\"\", \"b\": [[1]]}"), 3);
+
+        let hostile = format!("{{\"a\":{}{}}}", "[".repeat(100_000), "]".repeat(100_000));
+        let mut json = JsonProcessor::new().with_invalid(JsonInvalidPolicy::Tag("error".to_string()));
+        let record = parse(&mut json, &hostile).unwrap();
+        assert_eq!(record.field("error"), Some(&RecordValue::from("json nested deeper than 32 levels")));
+
+        let mut json = JsonProcessor::new().with_max_depth(2);
+        assert!(parse(&mut json, "{\"a\":{\"b\":1}}").is_ok());
+        assert!(parse(&mut json, "{\"a\":{\"b\":[1]}}").is_err());
+    }
+}
@@ -0,0 +1,41 @@
+#[cfg(test)]
+extern crate rand;
+extern crate loghaul;
+extern crate serde_json;
+extern crate regex;
+extern crate chrono;
+extern crate chrono_tz;
+extern crate hmac;
+extern crate sha2;
+
+mod json_processor;
+mod logfmt_processor;
+mod grok_library;
+mod grok_processor;
+mod syslog_processor;
+mod access_log_format;
+mod access_log_processor;
+mod timestamp_format;
+mod timestamp_processor;
+mod redact_detector;
+mod redact_processor;
+mod template_processor;
+mod internal;
+
+pub use json_processor::JsonProcessor;
+pub use json_processor::JsonInvalidPolicy;
+pub use logfmt_processor::LogfmtProcessor;
+pub use grok_library::GrokLibrary;
+pub use grok_library::GrokPattern;
+pub use grok_library::GrokType;
+pub use grok_processor::GrokProcessor;
+pub use syslog_processor::SyslogProcessor;
+pub use access_log_format::AccessLogFormat;
+pub use access_log_processor::AccessLogProcessor;
+pub use timestamp_format::TimestampFormat;
+pub use timestamp_format::TimestampZone;
+pub use timestamp_processor::TimestampProcessor;
+pub use redact_detector::RedactDetector;
+pub use redact_processor::RedactMode;
+pub use redact_processor::RedactProcessor;
+pub use template_processor::TemplateProcessor;
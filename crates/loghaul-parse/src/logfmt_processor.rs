@@ -0,0 +1,326 @@
+use loghaul::Processor;
+use loghaul::Record;
+use loghaul::RecordValue;
+use loghaul::LoghaulError;
+use internal::parse_helpers::payload_line;
+use internal::parse_helpers::parse_error;
+
+/// Parses logfmt, and other `key=value` formats, into the record's fields.
+///
+/// Pairs are separated by whitespace, plus an optional extra separator such as
+/// `,` or `&`. Values may be double quoted, with `\"`, `\\`, `\n`, `\r` and `\t`
+/// escapes. A key without a value, like `debug` in `level=info debug`, is set to
+/// true. Unquoted values that look like integers, floats or booleans are typed;
+/// quoted values are always strings.
+///
+/// ```
+///     extern crate loghaul;
+///     extern crate loghaul_parse;
+///     use loghaul::Stream;
+///     use loghaul_parse::LogfmtProcessor;
+///     let stream = Stream::new()
+///         .with_processor(LogfmtProcessor::new())
+///         .with_processor(LogfmtProcessor::new().with_delimiter(':').with_pair_separator(','));
+/// ```
+pub struct LogfmtProcessor {
+    delimiter: char,
+    pair_separator: Option<char>,
+    infer_types: bool,
+}
+
+impl LogfmtProcessor {
+    pub fn new() -> LogfmtProcessor {
+        return LogfmtProcessor {
+            delimiter: '=',
+            pair_separator: None,
+            infer_types: true,
+        };
+    }
+
+    /// Split keys from values on this character instead of `=`
+    pub fn with_delimiter(mut self, delimiter: char) -> LogfmtProcessor {
+        self.delimiter = delimiter;
+        return self;
+    }
+
+    /// Also separate pairs with this character, as well as whitespace
+    pub fn with_pair_separator(mut self, separator: char) -> LogfmtProcessor {
+        self.pair_separator = Some(separator);
+        return self;
+    }
+
+    /// Keep every value as a string
+    pub fn without_type_inference(mut self) -> LogfmtProcessor {
+        self.infer_types = false;
+        return self;
+    }
+
+    fn is_separator(&self, c: char) -> bool {
+        return c.is_whitespace() || Some(c) == self.pair_separator;
+    }
+
+    /// Parse a line into a list of fields, in the order they appear
+    fn parse(&self, text: &str) -> Result<Vec<(String, RecordValue)>, String> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut fields = Vec::new();
+        let mut i = 0;
+        loop {
+            while i < chars.len() && self.is_separator(chars[i]) {
+                i += 1;
+            }
+            if i >= chars.len() {
+                break;
+            }
+
+            let start = i;
+            while i < chars.len() && !self.is_separator(chars[i]) && chars[i] != self.delimiter && chars[i] != '"' {
+                i += 1;
+            }
+            if i == start {
+                return Err(format!("expected a key at position {}, found '{}'", i, chars[i]));
+            }
+            let key: String = chars[start..i].iter().collect();
+
+            if i >= chars.len() || chars[i] != self.delimiter {
+                if i < chars.len() && chars[i] == '"' {
+                    return Err(format!("unexpected quote in key '{}' at position {}", key, i));
+                }
+                fields.push((key, RecordValue::Bool(true)));
+                continue;
+            }
+            i += 1;
+
+            if i < chars.len() && chars[i] == '"' {
+                let (value, end) = self.parse_quoted(&chars, i + 1).ok_or(format!("unterminated quoted value for key '{}'", key))?;
+                i = end;
+                if i < chars.len() && !self.is_separator(chars[i]) {
+                    return Err(format!("unexpected '{}' after quoted value for key '{}' at position {}", chars[i], key, i));
+                }
+                fields.push((key, RecordValue::String(value)));
+                continue;
+            }
+
+            let start = i;
+            while i < chars.len() && !self.is_separator(chars[i]) {
+                if chars[i] == '"' {
+                    return Err(format!("unexpected quote in value for key '{}' at position {}", key, i));
+                }
+                i += 1;
+            }
+            let value: String = chars[start..i].iter().collect();
+            fields.push((key, match self.infer_types {
+                true => infer_type(value),
+                false => RecordValue::String(value),
+            }));
+        }
+        return Ok(fields);
+    }
+
+    /// Read a quoted value starting just after the opening quote.
+    /// Returns the unescaped value and the position just after the closing quote.
+    fn parse_quoted(&self, chars: &[char], start: usize) -> Option<(String, usize)> {
+        let mut value = String::new();
+        let mut i = start;
+        while i < chars.len() {
+            match chars[i] {
+                '"' => {
+                    return Some((value, i + 1));
+                }
+                '\\' if i + 1 < chars.len() => {
+                    match chars[i + 1] {
+                        'n' => value.push('\n'),
+                        'r' => value.push('\r'),
+                        't' => value.push('\t'),
+                        '"' => value.push('"'),
+                        '\\' => value.push('\\'),
+                        // Unknown escapes are kept as written
+                        other => {
+                            value.push('\\');
+                            value.push(other);
+                        }
+                    }
+                    i += 2;
+                }
+                c => {
+                    value.push(c);
+                    i += 1;
+                }
+            }
+        }
+        return None;
+    }
+}
+
+impl Processor for LogfmtProcessor {
+    fn process(&mut self, mut record: Record, output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        let fields = {
+            let text = payload_line(&record.payload)?;
+            self.parse(text).map_err(|e| parse_error(&e))?
+        };
+        for (key, value) in fields.into_iter() {
+            record.fields.insert(key, value);
+        }
+        output.push(record);
+        return Ok(());
+    }
+
+    fn id(&self) -> Option<String> {
+        Some("logfmt".to_string())
+    }
+}
+
+/// Type an unquoted value. Only plain decimal numbers are converted, so values
+/// like `12ms`, `inf` or `0x1f` stay strings, as do integers with leading zeros,
+/// which are usually identifiers rather than quantities.
+fn infer_type(value: String) -> RecordValue {
+    match value.as_str() {
+        "true" => return RecordValue::Bool(true),
+        "false" => return RecordValue::Bool(false),
+        _ => {}
+    }
+    let digits = value.trim_start_matches('-');
+    let numeric = !digits.is_empty()
+        && digits.starts_with(|c: char| c.is_ascii_digit())
+        && digits.chars().all(|c| c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E' || c == '+' || c == '-');
+    if !numeric {
+        return RecordValue::String(value);
+    }
+    let leading_zero = digits.len() > 1 && digits.starts_with('0') && !digits.starts_with("0.");
+    if digits.chars().all(|c| c.is_ascii_digit()) {
+        return match (leading_zero, value.parse::<i64>()) {
+            (false, Ok(i)) => RecordValue::Int(i),
+            _ => RecordValue::String(value),
+        };
+    }
+    return match (leading_zero, value.parse::<f64>()) {
+        (false, Ok(f)) if f.is_finite() => RecordValue::Float(f),
+        _ => RecordValue::String(value),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use rand::{Rng, SeedableRng, StdRng};
+    use super::LogfmtProcessor;
+    use loghaul::Processor;
+    use loghaul::Record;
+    use loghaul::RecordValue;
+
+    fn parse(processor: &mut LogfmtProcessor, payload: &str) -> Result<BTreeMap<String, RecordValue>, ()> {
+        let mut output = Vec::new();
+        processor.process(Record::new("test", payload.as_bytes().to_vec()), &mut output).map_err(|_| ())?;
+        return Ok(output.remove(0).fields);
+    }
+
+    fn fields(pairs: Vec<(&str, RecordValue)>) -> BTreeMap<String, RecordValue> {
+        return pairs.into_iter().map(|(k, v)| (k.to_string(), v)).collect();
+    }
+
+    #[test]
+    fn test_parse_logfmt() {
+        let mut logfmt = LogfmtProcessor::new();
+        assert_eq!(parse(&mut logfmt, "level=info msg=\"started \\\"api\\\"\\n\" dur=12ms port=8080 ratio=0.25 ok=true cached\n"), Ok(fields(vec!(
+            ("level", RecordValue::from("info")),
+            ("msg", RecordValue::from("started \"api\"\n")),
+            ("dur", RecordValue::from("12ms")),
+            ("port", RecordValue::Int(8080)),
+            ("ratio", RecordValue::Float(0.25)),
+            ("ok", RecordValue::Bool(true)),
+            ("cached", RecordValue::Bool(true)),
+        ))));
+
+        let mut kv = LogfmtProcessor::new().with_delimiter(':').with_pair_separator(',').without_type_inference();
+        assert_eq!(parse(&mut kv, "user:bob,id:7, path:\"a,b\""), Ok(fields(vec!(
+            ("user", RecordValue::from("bob")),
+            ("id", RecordValue::from("7")),
+            ("path", RecordValue::from("a,b")),
+        ))));
+    }
+
+    /// Tricky inputs and what they should parse to; None means the line is rejected
+    #[test]
+    fn test_logfmt_corpus() {
+        let corpus: Vec<(&str, Option<Vec<(&str, RecordValue)>>)> = vec!(
+            ("", Some(vec!())),
+            ("   \t ", Some(vec!())),
+            ("a=", Some(vec!(("a", RecordValue::from(""))))),
+            ("a=\"\"", Some(vec!(("a", RecordValue::from(""))))),
+            ("a=1 a=2", Some(vec!(("a", RecordValue::Int(2))))),
+            ("a==b", Some(vec!(("a", RecordValue::from("=b"))))),
+            ("a=b=c", Some(vec!(("a", RecordValue::from("b=c"))))),
+            ("url=http://x/?q=1&r=2", Some(vec!(("url", RecordValue::from("http://x/?q=1&r=2"))))),
+            ("a=\"\\q\"", Some(vec!(("a", RecordValue::from("\\q"))))),
+            ("a=\"tab\\there\"", Some(vec!(("a", RecordValue::from("tab\there"))))),
+            ("emoji=\"😀 ok\" ключ=значение", Some(vec!(("emoji", RecordValue::from("😀 ok")), ("ключ", RecordValue::from("значение"))))),
+            ("n=-3 f=1e3 z=007 h=0x1f i=inf nan=NaN big=99999999999999999999", Some(vec!(
+                ("n", RecordValue::Int(-3)),
+                ("f", RecordValue::Float(1000.0)),
+                ("z", RecordValue::from("007")),
+                ("h", RecordValue::from("0x1f")),
+                ("i", RecordValue::from("inf")),
+                ("nan", RecordValue::from("NaN")),
+                ("big", RecordValue::from("99999999999999999999")),
+            ))),
+            ("x=0 y=0.5 dash=- v=1.2.3", Some(vec!(
+                ("x", RecordValue::Int(0)),
+                ("y", RecordValue::Float(0.5)),
+                ("dash", RecordValue::from("-")),
+                ("v", RecordValue::from("1.2.3")),
+            ))),
+            ("=value", None),
+            ("a=\"unterminated", None),
+            ("a=\"ends in escape\\\"", None),
+            ("a=\"quoted\"tail", None),
+            ("a=half\"quote", None),
+            ("\"quoted key\"=1", None),
+            ("key\"=1", None),
+        );
+        let mut logfmt = LogfmtProcessor::new();
+        for (input, expected) in corpus.into_iter() {
+            assert_eq!(parse(&mut logfmt, input).ok(), expected.map(fields), "input: {:?}", input);
+        }
+    }
+
+    /// Random lines built from logfmt's special characters must never panic
+    #[test]
+    fn test_logfmt_fuzz_never_panics() {
+        let alphabet: Vec<char> = "ab=\" \\\tn0-.,:é\u{0}😀".chars().collect();
+        let mut rng = StdRng::from_seed([7; 32]);
+        let mut processors = vec!(LogfmtProcessor::new(), LogfmtProcessor::new().with_delimiter(':').with_pair_separator(','));
+        for _ in 0..20_000 {
+            let length = rng.gen_range(0, 24);
+            let line: String = (0..length).map(|_| alphabet[rng.gen_range(0, alphabet.len())]).collect();
+            for processor in processors.iter_mut() {
+                let _ = parse(processor, &line);
+            }
+        }
+
+        // Arbitrary bytes, including invalid utf8, are rejected rather than panicking
+        for _ in 0..2_000 {
+            let bytes: Vec<u8> = (0..rng.gen_range(0, 24)).map(|_| rng.gen::<u8>()).collect();
+            let mut output = Vec::new();
+            let _ = processors[0].process(Record::new("fuzz", bytes), &mut output);
+        }
+    }
+
+    /// Any set of string fields survives being written out as logfmt and parsed back
+    #[test]
+    fn test_logfmt_fuzz_round_trip() {
+        let alphabet: Vec<char> = "ab=\" \\\n\t,é😀".chars().collect();
+        let mut rng = StdRng::from_seed([11; 32]);
+        let mut logfmt = LogfmtProcessor::new().without_type_inference();
+        for _ in 0..5_000 {
+            let mut expected = BTreeMap::new();
+            let mut line = String::new();
+            for index in 0..rng.gen_range(0, 5) {
+                let key = format!("k{}", index);
+                let value: String = (0..rng.gen_range(0, 12)).map(|_| alphabet[rng.gen_range(0, alphabet.len())]).collect();
+                let escaped = value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n").replace('\t', "\\t");
+                line.push_str(&format!("{}=\"{}\" ", key, escaped));
+                expected.insert(key, RecordValue::String(value));
+            }
+            assert_eq!(parse(&mut logfmt, &line), Ok(expected), "line: {:?}", line);
+        }
+    }
+}
@@ -0,0 +1,437 @@
+use loghaul::Processor;
+use loghaul::Record;
+use loghaul::RecordValue;
+use loghaul::LoghaulError;
+use internal::parse_helpers::payload_line;
+use internal::parse_helpers::parse_error;
+
+const FACILITIES: [&str; 24] = [
+    "kern", "user", "mail", "daemon", "auth", "syslog", "lpr", "news",
+    "uucp", "cron", "authpriv", "ftp", "ntp", "security", "console", "solaris-cron",
+    "local0", "local1", "local2", "local3", "local4", "local5", "local6", "local7",
+];
+
+const SEVERITIES: [&str; 8] = ["emerg", "alert", "crit", "err", "warning", "notice", "info", "debug"];
+
+const MONTHS: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+
+/// Parses syslog lines in either RFC 5424 or RFC 3164 (BSD) format.
+///
+/// Sets `priority`, `facility` and `severity` from the PRI, then `timestamp`,
+/// `hostname`, `app_name`, `procid`, `msgid` and `message` where present. RFC 5424
+/// structured data is kept in `structured_data`, and each parameter is also set as
+/// `sd.<id>.<name>`. Timestamps are kept as written.
+///
+/// Common deviations are accepted: a missing PRI or hostname, a year, fractional
+/// seconds or an ISO 8601 timestamp in BSD lines, Cisco style sequence numbers and
+/// `*` timestamp markers, and a tag without the trailing colon. Only a malformed
+/// PRI fails a record.
+///
+/// ```
+///     extern crate loghaul;
+///     extern crate loghaul_parse;
+///     use loghaul::Stream;
+///     use loghaul_parse::SyslogProcessor;
+///     let stream = Stream::new().with_processor(SyslogProcessor::new());
+/// ```
+pub struct SyslogProcessor {}
+
+impl SyslogProcessor {
+    pub fn new() -> SyslogProcessor {
+        return SyslogProcessor {};
+    }
+}
+
+impl Processor for SyslogProcessor {
+    fn process(&mut self, mut record: Record, output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        let fields = {
+            let text = payload_line(&record.payload)?;
+            parse_syslog(text).map_err(|e| parse_error(&e))?
+        };
+        for (key, value) in fields.into_iter() {
+            record.fields.insert(key, value);
+        }
+        output.push(record);
+        return Ok(());
+    }
+
+    fn id(&self) -> Option<String> {
+        Some("syslog".to_string())
+    }
+}
+
+/// A cursor over a syslog line
+struct Cursor<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn rest(&self) -> &'a str {
+        return &self.text[self.position..];
+    }
+
+    fn advance(&mut self, bytes: usize) {
+        self.position = (self.position + bytes).min(self.text.len());
+    }
+
+    fn skip_spaces(&mut self) {
+        let rest = self.rest();
+        self.advance(rest.len() - rest.trim_start_matches(' ').len());
+    }
+
+    /// Take everything up to the next space, leaving the cursor on the space
+    fn token(&mut self) -> &'a str {
+        let rest = self.rest();
+        let end = rest.find(' ').unwrap_or(rest.len());
+        self.advance(end);
+        return &rest[..end];
+    }
+}
+
+fn parse_syslog(text: &str) -> Result<Vec<(String, RecordValue)>, String> {
+    let mut fields = Vec::new();
+    let mut cursor = Cursor { text, position: 0 };
+
+    if cursor.rest().starts_with('<') {
+        let end = match cursor.rest().find('>') {
+            Some(end) if end > 1 && end <= 4 => end,
+            _ => {
+                return Err("invalid PRI, expected <0> to <191>".to_string());
+            }
+        };
+        let priority: usize = match cursor.rest()[1..end].parse() {
+            Ok(p) if p <= 191 && cursor.rest()[1..end].bytes().all(|b| b.is_ascii_digit()) => p,
+            _ => {
+                return Err(format!("invalid PRI '{}', expected <0> to <191>", &cursor.rest()[..end + 1]));
+            }
+        };
+        cursor.advance(end + 1);
+        fields.push(("priority".to_string(), RecordValue::Int(priority as i64)));
+        fields.push(("facility".to_string(), RecordValue::from(FACILITIES[priority / 8])));
+        fields.push(("severity".to_string(), RecordValue::from(SEVERITIES[priority % 8])));
+    }
+
+    // RFC 5424 puts a version straight after the PRI
+    let rest = cursor.rest();
+    let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if digits > 0 && digits <= 2 && rest[digits..].starts_with(' ') && !fields.is_empty() {
+        let version: i64 = rest[..digits].parse().unwrap_or(1);
+        cursor.advance(digits + 1);
+        fields.push(("version".to_string(), RecordValue::Int(version)));
+        parse_rfc5424(&mut cursor, &mut fields);
+    } else {
+        parse_rfc3164(&mut cursor, &mut fields);
+    }
+    return Ok(fields);
+}
+
+fn parse_rfc5424(cursor: &mut Cursor, fields: &mut Vec<(String, RecordValue)>) {
+    for name in ["timestamp", "hostname", "app_name", "procid", "msgid"].iter() {
+        cursor.skip_spaces();
+        let value = cursor.token();
+        if value != "-" && !value.is_empty() {
+            fields.push((name.to_string(), RecordValue::from(value)));
+        }
+    }
+    cursor.skip_spaces();
+
+    if cursor.rest().starts_with('[') {
+        match parse_structured_data(cursor.rest()) {
+            Some((length, params)) => {
+                fields.push(("structured_data".to_string(), RecordValue::from(&cursor.rest()[..length])));
+                fields.extend(params.into_iter());
+                cursor.advance(length);
+            }
+            // Keep malformed structured data as part of the message rather than losing it
+            None => {}
+        }
+    } else if cursor.rest() == "-" || cursor.rest().starts_with("- ") {
+        cursor.advance(1);
+    }
+
+    let message = cursor.rest().strip_prefix(' ').unwrap_or(cursor.rest());
+    let message = message.trim_start_matches('\u{feff}');
+    if !message.is_empty() {
+        fields.push(("message".to_string(), RecordValue::from(message)));
+    }
+}
+
+/// Parse `[id name="value" ...]...`, returning the length consumed and a field per parameter
+fn parse_structured_data(text: &str) -> Option<(usize, Vec<(String, RecordValue)>)> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let mut params = Vec::new();
+    let mut i = 0;
+    while i < chars.len() && chars[i].1 == '[' {
+        i += 1;
+        let start = i;
+        while i < chars.len() && chars[i].1 != ' ' && chars[i].1 != ']' {
+            i += 1;
+        }
+        if i == start || i >= chars.len() {
+            return None;
+        }
+        let id = &text[chars[start].0..chars[i].0];
+        loop {
+            while i < chars.len() && chars[i].1 == ' ' {
+                i += 1;
+            }
+            if i >= chars.len() {
+                return None;
+            }
+            if chars[i].1 == ']' {
+                i += 1;
+                break;
+            }
+            let start = i;
+            while i < chars.len() && chars[i].1 != '=' && chars[i].1 != ' ' && chars[i].1 != ']' {
+                i += 1;
+            }
+            if i + 1 >= chars.len() || chars[i].1 != '=' || chars[i + 1].1 != '"' || i == start {
+                return None;
+            }
+            let name = &text[chars[start].0..chars[i].0];
+            i += 2;
+            let mut value = String::new();
+            loop {
+                if i >= chars.len() {
+                    return None;
+                }
+                match chars[i].1 {
+                    '"' => break,
+                    '\\' if i + 1 < chars.len() && (chars[i + 1].1 == '"' || chars[i + 1].1 == '\\' || chars[i + 1].1 == ']') => {
+                        value.push(chars[i + 1].1);
+                        i += 2;
+                        continue;
+                    }
+                    c => value.push(c),
+                }
+                i += 1;
+            }
+            i += 1;
+            params.push((format!("sd.{}.{}", id, name), RecordValue::String(value)));
+        }
+    }
+    let length = if i < chars.len() { chars[i].0 } else { text.len() };
+    return Some((length, params));
+}
+
+fn parse_rfc3164(cursor: &mut Cursor, fields: &mut Vec<(String, RecordValue)>) {
+    // Cisco devices prefix a sequence number, and mark unsynchronised clocks with * or .
+    let rest = cursor.rest();
+    let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if digits > 0 && rest[digits..].starts_with(": ") {
+        cursor.advance(digits + 2);
+    }
+    cursor.skip_spaces();
+    if cursor.rest().starts_with('*') || cursor.rest().starts_with('.') {
+        cursor.advance(1);
+    }
+
+    let timestamp = timestamp_length(cursor.rest());
+    match timestamp {
+        Some(length) => {
+            fields.push(("timestamp".to_string(), RecordValue::from(&cursor.rest()[..length])));
+            cursor.advance(length);
+            if cursor.rest().starts_with(':') {
+                cursor.advance(1);
+            }
+            cursor.skip_spaces();
+        }
+        None => {}
+    }
+
+    // The hostname follows the timestamp, but is often left out; a token that looks like a tag means it was
+    let checkpoint = cursor.position;
+    let token = cursor.token();
+    if timestamp.is_some() && !token.is_empty() && !is_tag(token) && cursor.rest().starts_with(' ') {
+        fields.push(("hostname".to_string(), RecordValue::from(token)));
+        cursor.skip_spaces();
+    } else {
+        cursor.position = checkpoint;
+    }
+
+    let checkpoint = cursor.position;
+    let token = cursor.token();
+    if is_tag(token) {
+        let tag = token.trim_end_matches(':');
+        match (tag.find('['), tag.ends_with(']')) {
+            (Some(open), true) => {
+                fields.push(("app_name".to_string(), RecordValue::from(&tag[..open])));
+                fields.push(("procid".to_string(), RecordValue::from(&tag[open + 1..tag.len() - 1])));
+            }
+            _ => fields.push(("app_name".to_string(), RecordValue::from(tag))),
+        }
+        if cursor.rest().starts_with(' ') {
+            cursor.advance(1);
+        }
+    } else {
+        cursor.position = checkpoint;
+    }
+
+    let message = cursor.rest();
+    if !message.is_empty() {
+        fields.push(("message".to_string(), RecordValue::from(message)));
+    }
+}
+
+/// A tag is `name:`, `name[pid]` or `name[pid]:`
+fn is_tag(token: &str) -> bool {
+    let name = token.trim_end_matches(':');
+    if name.is_empty() {
+        return false;
+    }
+    let name = match (name.find('['), name.ends_with(']')) {
+        (Some(open), true) => &name[..open],
+        (Some(_), false) => {
+            return false;
+        }
+        (None, _) if token.ends_with(':') => name,
+        (None, _) => {
+            return false;
+        }
+    };
+    return !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || "-_./%".contains(c));
+}
+
+/// The length of a BSD style `Mmm dd [yyyy] hh:mm:ss[.frac]` or ISO 8601 timestamp at the start of some text
+fn timestamp_length(text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    if bytes.len() >= 10 && bytes[..4].iter().all(|b| b.is_ascii_digit()) && bytes[4] == b'-' {
+        return Some(text.find(' ').unwrap_or(text.len()));
+    }
+    match text.get(..3) {
+        Some(month) if MONTHS.contains(&month.to_ascii_lowercase().as_str()) => {}
+        _ => {
+            return None;
+        }
+    }
+    let mut position = 3;
+    let mut parts = 0;
+    let mut saw_clock = false;
+    // Day, optional year and clock, each separated by one or more spaces
+    while parts < 3 && !saw_clock {
+        let spaces = text[position..].len() - text[position..].trim_start_matches(' ').len();
+        if spaces == 0 {
+            return None;
+        }
+        let start = position + spaces;
+        let length = text[start..].find(' ').unwrap_or(text.len() - start);
+        let part = text[start..start + length].trim_end_matches(':');
+        if part.contains(':') {
+            if !part.split(|c| c == ':' || c == '.').all(|p| !p.is_empty() && p.bytes().all(|b| b.is_ascii_digit())) {
+                return None;
+            }
+            saw_clock = true;
+        } else if part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        position = start + part.len();
+        parts += 1;
+    }
+    return match saw_clock {
+        true => Some(position),
+        false => None,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use super::SyslogProcessor;
+    use loghaul::Processor;
+    use loghaul::Record;
+
+    fn parse(payload: &str) -> Result<BTreeMap<String, String>, ()> {
+        let mut output = Vec::new();
+        SyslogProcessor::new().process(Record::new("syslog", payload.as_bytes().to_vec()), &mut output).map_err(|_| ())?;
+        return Ok(output.remove(0).fields.into_iter().map(|(k, v)| (k, v.to_string())).collect());
+    }
+
+    fn expect(pairs: Vec<(&str, &str)>) -> Result<BTreeMap<String, String>, ()> {
+        return Ok(pairs.into_iter().map(|(k, v)| (k.to_string(), v.to_string())).collect());
+    }
+
+    #[test]
+    fn test_parse_rfc5424() {
+        assert_eq!(parse("<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 \
+                          [exampleSDID@32473 iut=\"3\" eventSource=\"App \\\"lication\\\"\"][meta seq=\"1\"] \u{feff}An application event\n"), expect(vec!(
+            ("priority", "165"), ("facility", "local4"), ("severity", "notice"), ("version", "1"),
+            ("timestamp", "2003-10-11T22:14:15.003Z"), ("hostname", "mymachine.example.com"),
+            ("app_name", "evntslog"), ("msgid", "ID47"),
+            ("structured_data", "[exampleSDID@32473 iut=\"3\" eventSource=\"App \\\"lication\\\"\"][meta seq=\"1\"]"),
+            ("sd.exampleSDID@32473.iut", "3"), ("sd.exampleSDID@32473.eventSource", "App \"lication\""), ("sd.meta.seq", "1"),
+            ("message", "An application event"),
+        )));
+
+        assert_eq!(parse("<34>1 - - su 42 - - 'su root' failed"), expect(vec!(
+            ("priority", "34"), ("facility", "auth"), ("severity", "crit"), ("version", "1"),
+            ("app_name", "su"), ("procid", "42"), ("message", "'su root' failed"),
+        )));
+
+        // Malformed structured data stays in the message
+        assert_eq!(parse("<13>1 - host app - - [broken sd"), expect(vec!(
+            ("priority", "13"), ("facility", "user"), ("severity", "notice"), ("version", "1"),
+            ("hostname", "host"), ("app_name", "app"), ("message", "[broken sd"),
+        )));
+    }
+
+    #[test]
+    fn test_parse_rfc3164() {
+        assert_eq!(parse("<34>Oct 11 22:14:15 mymachine su[230]: 'su root' failed for lonvick on /dev/pts/8"), expect(vec!(
+            ("priority", "34"), ("facility", "auth"), ("severity", "crit"), ("timestamp", "Oct 11 22:14:15"),
+            ("hostname", "mymachine"), ("app_name", "su"), ("procid", "230"),
+            ("message", "'su root' failed for lonvick on /dev/pts/8"),
+        )));
+
+        // Single digit day padded with a space, no PRI, no pid
+        assert_eq!(parse("Feb  5 01:02:03 web-1 nginx: started"), expect(vec!(
+            ("timestamp", "Feb  5 01:02:03"), ("hostname", "web-1"), ("app_name", "nginx"), ("message", "started"),
+        )));
+    }
+
+    #[test]
+    fn test_parse_vendor_deviations() {
+        // Year and milliseconds in the timestamp, no hostname
+        assert_eq!(parse("<14>Mar 3 2024 10:00:00.123 cron[7]: job done"), expect(vec!(
+            ("priority", "14"), ("facility", "user"), ("severity", "info"),
+            ("timestamp", "Mar 3 2024 10:00:00.123"), ("app_name", "cron"), ("procid", "7"), ("message", "job done"),
+        )));
+
+        // rsyslog high precision timestamps
+        assert_eq!(parse("<30>2024-03-03T10:00:00.123456+01:00 db01 postgres[99]: checkpoint complete"), expect(vec!(
+            ("priority", "30"), ("facility", "daemon"), ("severity", "info"),
+            ("timestamp", "2024-03-03T10:00:00.123456+01:00"), ("hostname", "db01"),
+            ("app_name", "postgres"), ("procid", "99"), ("message", "checkpoint complete"),
+        )));
+
+        // Cisco sequence numbers and unsynchronised clock marker
+        assert_eq!(parse("<189>52: *Mar  1 00:01:02.345: %LINK-3-UPDOWN: Interface Gi0/1, changed state to up"), expect(vec!(
+            ("priority", "189"), ("facility", "local7"), ("severity", "notice"),
+            ("timestamp", "Mar  1 00:01:02.345"), ("app_name", "%LINK-3-UPDOWN"),
+            ("message", "Interface Gi0/1, changed state to up"),
+        )));
+
+        // Nothing recognisable beyond the PRI is still a message
+        assert_eq!(parse("<0>kernel panic - not syncing"), expect(vec!(
+            ("priority", "0"), ("facility", "kern"), ("severity", "emerg"), ("message", "kernel panic - not syncing"),
+        )));
+        assert_eq!(parse("just text"), expect(vec!(("message", "just text"))));
+
+        assert!(parse("<192>Oct 11 22:14:15 host app: too high").is_err());
+        assert!(parse("<1a>Oct 11 22:14:15 host app: bad").is_err());
+        assert!(parse("<>nothing").is_err());
+    }
+
+    #[test]
+    fn test_parse_never_panics_on_truncated_lines() {
+        let line = "<165>1 2003-10-11T22:14:15.003Z host app 1 ID [id a=\"é\\\"\"] msg";
+        let other = "<189>52: *Mar  1 00:01:02.345: %LINK-3-UPDOWN: up";
+        let accented = "<13>Maé 1 12:00:00 hôst tâg[1]: é";
+        for source in [line, other, accented, "abé test", "<13>abé test", "é"].iter() {
+            for (end, _) in source.char_indices() {
+                let _ = parse(&source[..end]);
+            }
+        }
+    }
+}
@@ -0,0 +1,405 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::Instant;
+use serde_json;
+use serde_json::Value;
+use loghaul::Processor;
+use loghaul::Record;
+use loghaul::RecordValue;
+use loghaul::LoghaulError;
+use loghaul::MetricsRegistry;
+use internal::drain_tree::DrainTemplate;
+use internal::drain_tree::DrainTree;
+use internal::parse_helpers::parse_error;
+use internal::parse_helpers::payload_line;
+
+const STATE_VERSION: u64 = 1;
+const DEFAULT_DEPTH: usize = 4;
+const DEFAULT_SIMILARITY: f64 = 0.4;
+const DEFAULT_MAX_CHILDREN: usize = 100;
+const DEFAULT_MAX_TEMPLATES: usize = 10000;
+const DEFAULT_SAVE_SECS: u64 = 10;
+
+/// Learns the templates of log messages as they arrive, with the Drain algorithm,
+/// and tags each record with the id of its template in `template_id` and the
+/// template in `template`, eg. `User <*> logged in from <*>`.
+///
+/// Messages are split on whitespace and compared with the templates learned from
+/// messages with as many tokens and the same first `depth - 3` tokens; tokens holding
+/// digits are treated as values. A message joins the template it shares the most
+/// tokens with, if that is at least `similarity` of them, turning the tokens it
+/// differs in into `<*>`; otherwise it starts a new template. The template written
+/// to a record is the template as it was at the time.
+///
+/// When a new template is learned, an event record is emitted from the source
+/// `template` after the record, with the payload `new template <id>: <template>` and
+/// a newline, and the fields `template_id`, `template` and `sample`, the message
+/// it was learned from.
+///
+/// With a state file, the templates are loaded when the processor is built and
+/// saved every save interval while they change, and when a source ends, so ids
+/// stay the same across restarts. Once `max_templates` are known, messages that
+/// match none are passed on untagged and counted as `unmatched`; new templates are
+/// counted as `new_templates`.
+///
+/// ```
+///     extern crate loghaul;
+///     extern crate loghaul_parse;
+///     use loghaul::Stream;
+///     use loghaul_parse::TemplateProcessor;
+///     let templates = TemplateProcessor::new()
+///         .with_field("message")
+///         .with_similarity(0.5)
+///         .with_target("new_templates");
+///     let stream = Stream::new().with_processor(templates);
+/// ```
+pub struct TemplateProcessor {
+    tree: DrainTree,
+    depth: usize,
+    similarity: f64,
+    max_children: usize,
+    max_templates: usize,
+    field: Option<String>,
+    events: bool,
+    source: String,
+    target: Option<String>,
+    state_file: Option<PathBuf>,
+    save_interval: Duration,
+    saved: Option<Instant>,
+    dirty: bool,
+    metrics: Option<(String, MetricsRegistry)>,
+}
+
+impl TemplateProcessor {
+    pub fn new() -> TemplateProcessor {
+        return TemplateProcessor {
+            tree: DrainTree::new(DEFAULT_DEPTH, DEFAULT_SIMILARITY, DEFAULT_MAX_CHILDREN),
+            depth: DEFAULT_DEPTH,
+            similarity: DEFAULT_SIMILARITY,
+            max_children: DEFAULT_MAX_CHILDREN,
+            max_templates: DEFAULT_MAX_TEMPLATES,
+            field: None,
+            events: true,
+            source: "template".to_string(),
+            target: None,
+            state_file: None,
+            save_interval: Duration::from_secs(DEFAULT_SAVE_SECS),
+            saved: None,
+            dirty: false,
+            metrics: None,
+        };
+    }
+
+    /// Read the message from this field instead of the payload; records without it are passed on untagged
+    pub fn with_field(mut self, field: &str) -> TemplateProcessor {
+        self.field = Some(field.to_string());
+        return self;
+    }
+
+    /// The depth of the parse tree, at least 3; messages are routed by their first `depth - 3` tokens, 4 by default
+    pub fn with_depth(mut self, depth: usize) -> TemplateProcessor {
+        self.depth = depth.max(3);
+        self.rebuild();
+        return self;
+    }
+
+    /// The share of tokens a message must have in common with a template to join it, 0.4 by default
+    pub fn with_similarity(mut self, similarity: f64) -> TemplateProcessor {
+        self.similarity = similarity.max(0.0).min(1.0);
+        self.rebuild();
+        return self;
+    }
+
+    /// The most distinct tokens routed separately at each level of the tree, 100 by default
+    pub fn with_max_children(mut self, max_children: usize) -> TemplateProcessor {
+        self.max_children = max_children.max(2);
+        self.rebuild();
+        return self;
+    }
+
+    /// Stop learning new templates once this many are known, 10000 by default
+    pub fn with_max_templates(mut self, max_templates: usize) -> TemplateProcessor {
+        self.max_templates = max_templates.max(1);
+        return self;
+    }
+
+    /// Emit new template events from this source instead of `template`
+    pub fn with_source(mut self, source: &str) -> TemplateProcessor {
+        self.source = source.to_string();
+        return self;
+    }
+
+    /// Send new template events only to the target with this id, instead of routing them
+    pub fn with_target(mut self, target: &str) -> TemplateProcessor {
+        self.target = Some(target.to_string());
+        return self;
+    }
+
+    /// Don't emit new template events
+    pub fn without_events(mut self) -> TemplateProcessor {
+        self.events = false;
+        return self;
+    }
+
+    /// Load templates from this file, if it exists, and save them to it as they change
+    pub fn with_state_file(mut self, path: impl AsRef<Path>) -> Result<TemplateProcessor, String> {
+        let path = path.as_ref().to_path_buf();
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => Some(text),
+            Err(ref e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => return Err(format!("can't read {}: {}", path.display(), e)),
+        };
+        match text {
+            Some(text) => {
+                for (id, template) in load_state(&text).map_err(|e| format!("invalid state file {}: {}", path.display(), e))? {
+                    self.tree.restore(id, template);
+                }
+            }
+            None => {}
+        }
+        self.state_file = Some(path);
+        return Ok(self);
+    }
+
+    /// How often to save changed templates, every ten seconds by default
+    pub fn with_save_interval(mut self, interval: Duration) -> TemplateProcessor {
+        self.save_interval = interval;
+        return self;
+    }
+
+    /// Route the known templates again after a tree setting changed
+    fn rebuild(&mut self) {
+        let templates = self.tree.templates().clone();
+        self.tree = DrainTree::new(self.depth, self.similarity, self.max_children);
+        for (id, template) in templates.into_iter() {
+            self.tree.restore(id, template);
+        }
+    }
+
+    fn save(&mut self, now: Instant) -> Result<(), LoghaulError> {
+        let path = match self.state_file {
+            Some(ref path) if self.dirty => path.clone(),
+            _ => return Ok(()),
+        };
+        self.dirty = false;
+        self.saved = Some(now);
+        let templates: Vec<Value> = self.tree.templates().iter().map(|(id, template)| {
+            let mut entry = serde_json::Map::new();
+            entry.insert("id".to_string(), Value::from(*id));
+            entry.insert("template".to_string(), Value::from(template.text()));
+            entry.insert("count".to_string(), Value::from(template.count));
+            Value::Object(entry)
+        }).collect();
+        let mut state = serde_json::Map::new();
+        state.insert("version".to_string(), Value::from(STATE_VERSION));
+        state.insert("templates".to_string(), Value::Array(templates));
+
+        // Write a new file and move it into place, so a crash never leaves half a state file
+        let mut partial = path.clone().into_os_string();
+        partial.push(".tmp");
+        return fs::write(&partial, Value::Object(state).to_string())
+            .and_then(|_| fs::rename(&partial, &path))
+            .map_err(|e| parse_error(&format!("can't save templates to {}: {}", path.display(), e)));
+    }
+
+    fn count(&self, name: &str, n: u64) {
+        match self.metrics {
+            Some((ref id, ref metrics)) => metrics.processor_counter(id, name, n),
+            None => {}
+        }
+    }
+}
+
+impl Processor for TemplateProcessor {
+    fn process(&mut self, mut record: Record, output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        let learned = {
+            let text = match self.field {
+                Some(ref field) => record.field(field).and_then(|value| value.as_str()),
+                None => Some(payload_line(&record.payload)?),
+            };
+            match text {
+                Some(text) => {
+                    let tokens: Vec<&str> = text.split_whitespace().collect();
+                    let learn = self.tree.len() < self.max_templates;
+                    self.tree.add(&tokens, learn).map(|(id, new)| (id, new, text.to_string()))
+                }
+                None => {
+                    output.push(record);
+                    return Ok(());
+                }
+            }
+        };
+        let (id, new, text) = match learned {
+            Some(learned) => learned,
+            None => {
+                self.count("unmatched", 1);
+                output.push(record);
+                return Ok(());
+            }
+        };
+        self.dirty = true;
+        let template = self.tree.template(id).map(|t| t.text()).unwrap_or(String::new());
+        record.set_field("template_id", RecordValue::Int(id as i64));
+        record.set_field("template", template.as_str());
+        output.push(record);
+        if !new {
+            return Ok(());
+        }
+
+        self.count("new_templates", 1);
+        if self.events {
+            let mut event = Record::new(&self.source, format!("new template {}: {}\n", id, template).into_bytes());
+            event.set_field("template_id", RecordValue::Int(id as i64));
+            event.set_field("template", template);
+            event.set_field("sample", text);
+            match self.target {
+                Some(ref target) => event.route_to(target),
+                None => {}
+            }
+            output.push(event);
+        }
+        return Ok(());
+    }
+
+    fn id(&self) -> Option<String> {
+        Some("template".to_string())
+    }
+
+    fn attach(&mut self, id: &str, metrics: &MetricsRegistry) {
+        self.metrics = Some((id.to_string(), metrics.clone()));
+    }
+
+    fn flush(&mut self, now: Instant, _output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        match self.saved {
+            Some(saved) if now < saved + self.save_interval => return Ok(()),
+            _ => {}
+        }
+        return self.save(now);
+    }
+
+    fn end_source(&mut self, _source: &str, _output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        return self.save(Instant::now());
+    }
+}
+
+/// The templates in a state file, by id
+fn load_state(text: &str) -> Result<Vec<(u64, DrainTemplate)>, String> {
+    let state: Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    match state.get("version").and_then(|v| v.as_u64()) {
+        Some(STATE_VERSION) => {}
+        Some(other) => return Err(format!("unsupported version {}", other)),
+        None => return Err("missing version".to_string()),
+    }
+    let entries = match state.get("templates").and_then(|t| t.as_array()) {
+        Some(entries) => entries,
+        None => return Err("missing templates".to_string()),
+    };
+    let mut templates = Vec::new();
+    for (index, entry) in entries.iter().enumerate() {
+        let id = entry.get("id").and_then(|v| v.as_u64());
+        let text = entry.get("template").and_then(|v| v.as_str());
+        let count = entry.get("count").and_then(|v| v.as_u64()).unwrap_or(0);
+        match (id, text) {
+            (Some(id), Some(text)) => templates.push((id, DrainTemplate {
+                tokens: text.split_whitespace().map(|t| t.to_string()).collect(),
+                count,
+            })),
+            _ => return Err(format!("templates[{}] needs an id and a template", index)),
+        }
+    }
+    return Ok(templates);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::time::Duration;
+    use std::time::Instant;
+    use loghaul::MetricsRegistry;
+    use loghaul::Processor;
+    use loghaul::Record;
+    use loghaul::RecordValue;
+    use super::TemplateProcessor;
+
+    fn run(processor: &mut TemplateProcessor, lines: &[&str]) -> Vec<Record> {
+        let mut output = Vec::new();
+        for line in lines.iter() {
+            processor.process(Record::new("auth", format!("{}\n", line).into_bytes()), &mut output).unwrap();
+        }
+        return output;
+    }
+
+    #[test]
+    fn test_tags_records_and_announces_new_templates() {
+        let metrics = MetricsRegistry::new();
+        let mut templates = TemplateProcessor::new().with_target("events");
+        templates.attach("template", &metrics);
+        let output = run(&mut templates, &[
+            "User alice logged in from 10.0.0.1",
+            "User bob logged in from 10.0.0.2",
+            "Disk full on /var",
+        ]);
+        assert_eq!(output.len(), 5);
+        assert_eq!(output[0].field("template_id"), Some(&RecordValue::Int(1)));
+        assert_eq!(output[0].field("template"), Some(&RecordValue::from("User alice logged in from 10.0.0.1")));
+        assert_eq!(output[1].source, "template");
+        assert_eq!(output[1].payload_str(), Some("new template 1: User alice logged in from 10.0.0.1\n"));
+        assert_eq!(output[1].field("sample"), Some(&RecordValue::from("User alice logged in from 10.0.0.1")));
+        assert_eq!(output[1].route, Some("events".to_string()));
+        assert_eq!(output[2].field("template"), Some(&RecordValue::from("User <*> logged in from <*>")));
+        assert_eq!(output[2].payload_str(), Some("User bob logged in from 10.0.0.2\n"));
+        assert_eq!(output[3].field("template_id"), Some(&RecordValue::Int(2)));
+        assert_eq!(output[4].payload_str(), Some("new template 2: Disk full on /var\n"));
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.processor("template").unwrap().counters.get("new_templates"), Some(&2));
+
+        let mut limited = TemplateProcessor::new().with_field("message").with_max_templates(1).without_events();
+        let mut output = Vec::new();
+        for message in ["Connected to db", "Cache miss"].iter() {
+            let mut record = Record::new("app", b"-\n".to_vec());
+            record.set_field("message", *message);
+            limited.process(record, &mut output).unwrap();
+        }
+        limited.process(Record::new("app", b"Connected to db\n".to_vec()), &mut output).unwrap();
+        assert_eq!(output.len(), 3);
+        assert_eq!(output[0].field("template_id"), Some(&RecordValue::Int(1)));
+        assert_eq!(output[1].field("template_id"), None);
+        assert_eq!(output[2].field("template_id"), None);
+    }
+
+    #[test]
+    fn test_templates_persist_across_restarts() {
+        let dir = env::temp_dir().join(format!("loghaul_template_test_{}", ::std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("templates.json");
+        let _ = fs::remove_file(&path);
+
+        let mut first = TemplateProcessor::new().with_state_file(&path).unwrap().with_save_interval(Duration::from_secs(60));
+        run(&mut first, &["User alice logged in", "User bob logged in", "Disk full on /var"]);
+        let start = Instant::now();
+        first.flush(start, &mut Vec::new()).unwrap();
+        let saved = fs::read_to_string(&path).unwrap();
+        assert!(saved.contains(r#""template":"User <*> logged in""#));
+
+        // Not saved again until the interval has passed
+        run(&mut first, &["Cache warmed"]);
+        first.flush(start + Duration::from_secs(30), &mut Vec::new()).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), saved);
+        first.end_source("auth", &mut Vec::new()).unwrap();
+
+        let mut second = TemplateProcessor::new().with_state_file(&path).unwrap();
+        let output = run(&mut second, &["User carol logged in", "Cache warmed", "Queue drained"]);
+        let ids: Vec<Option<&RecordValue>> = output.iter().filter(|r| r.source == "auth").map(|r| r.field("template_id")).collect();
+        assert_eq!(ids, vec!(Some(&RecordValue::Int(1)), Some(&RecordValue::Int(3)), Some(&RecordValue::Int(4))));
+
+        fs::write(&path, r#"{"version":2,"templates":[]}"#).unwrap();
+        let err = TemplateProcessor::new().with_state_file(&path).err().unwrap();
+        assert!(err.ends_with("templates.json: unsupported version 2"), "{}", err);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
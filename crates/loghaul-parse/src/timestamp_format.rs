@@ -0,0 +1,238 @@
+use chrono::DateTime;
+use chrono::Datelike;
+use chrono::Duration;
+use chrono::FixedOffset;
+use chrono::NaiveDate;
+use chrono::NaiveDateTime;
+use chrono::TimeZone;
+use chrono::Utc;
+use chrono::format::Item;
+use chrono::format::StrftimeItems;
+use chrono_tz::Tz;
+use regex::Regex;
+
+/// A way of writing timestamps that `TimestampProcessor` recognises
+#[derive(Debug, Clone)]
+pub struct TimestampFormat {
+    kind: FormatKind,
+    prefix: Option<Regex>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum FormatKind {
+    Rfc3339,
+    EpochSeconds,
+    EpochMillis,
+    Syslog,
+    Strftime(String),
+}
+
+/// The zone timestamps without an offset are read in: an IANA zone, which
+/// follows daylight saving changes, or a fixed offset
+#[derive(Debug, Clone)]
+pub enum TimestampZone {
+    Fixed(FixedOffset),
+    Named(Tz),
+}
+
+impl TimestampFormat {
+    /// `2024-05-01T10:00:00.123+02:00`, also with a space instead of the `T`, or
+    /// without an offset
+    pub fn rfc3339() -> TimestampFormat {
+        return TimestampFormat::with_prefix(FormatKind::Rfc3339, r"^\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}(?:\.\d{1,9})?(?:[Zz]|[+-]\d{2}:?\d{2})?");
+    }
+
+    /// Seconds since the unix epoch, ten digits with an optional fraction
+    pub fn epoch_seconds() -> TimestampFormat {
+        return TimestampFormat::with_prefix(FormatKind::EpochSeconds, r"^\d{9,10}(?:\.\d{1,9})?");
+    }
+
+    /// Milliseconds since the unix epoch, thirteen digits
+    pub fn epoch_millis() -> TimestampFormat {
+        return TimestampFormat::with_prefix(FormatKind::EpochMillis, r"^\d{12,13}");
+    }
+
+    /// The BSD syslog `May  1 10:00:00`, which has no year; the most recent year
+    /// that doesn't put it in the future is assumed
+    pub fn syslog() -> TimestampFormat {
+        return TimestampFormat::with_prefix(FormatKind::Syslog, r"^[A-Z][a-z]{2} +\d{1,2} \d{2}:\d{2}:\d{2}(?:\.\d{1,9})?");
+    }
+
+    /// A chrono strftime pattern, eg. `%d/%b/%Y:%H:%M:%S %z`
+    pub fn strftime(pattern: &str) -> Result<TimestampFormat, String> {
+        if StrftimeItems::new(pattern).any(|item| match item { Item::Error => true, _ => false }) {
+            return Err(format!("invalid strftime pattern '{}'", pattern));
+        }
+        return Ok(TimestampFormat {
+            kind: FormatKind::Strftime(pattern.to_string()),
+            prefix: None,
+        });
+    }
+
+    /// A format from its name, `rfc3339`, `epoch_seconds`, `epoch_millis` or
+    /// `syslog`, or from a strftime pattern
+    pub fn from_name(name: &str) -> Result<TimestampFormat, String> {
+        return match name {
+            "rfc3339" => Ok(TimestampFormat::rfc3339()),
+            "epoch_seconds" => Ok(TimestampFormat::epoch_seconds()),
+            "epoch_millis" => Ok(TimestampFormat::epoch_millis()),
+            "syslog" => Ok(TimestampFormat::syslog()),
+            pattern if pattern.contains('%') => TimestampFormat::strftime(pattern),
+            other => Err(format!("unknown timestamp format '{}', expected rfc3339, epoch_seconds, epoch_millis, syslog or a strftime pattern", other)),
+        };
+    }
+
+    fn with_prefix(kind: FormatKind, prefix: &str) -> TimestampFormat {
+        return TimestampFormat {
+            kind,
+            prefix: Some(Regex::new(prefix).expect("builtin timestamp pattern compiles")),
+        };
+    }
+
+    /// Parse a timestamp at the start of some text, returning it with the rest of the text
+    pub fn parse_prefix<'a>(&self, text: &'a str, zone: &TimestampZone, now: DateTime<Utc>) -> Option<(DateTime<Utc>, &'a str)> {
+        let (found, rest) = match self.prefix {
+            Some(ref prefix) => {
+                let end = prefix.find(text)?.end();
+                // A number that keeps going is some other number
+                if self.kind != FormatKind::Rfc3339 && text[end..].starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+                    return None;
+                }
+                (&text[..end], &text[end..])
+            }
+            None => ("", text),
+        };
+        return match self.kind {
+            FormatKind::Rfc3339 => {
+                let normalized = found.replacen(' ', "T", 1);
+                match DateTime::parse_from_rfc3339(&normalized) {
+                    Ok(parsed) => Some(parsed.with_timezone(&Utc)),
+                    Err(_) => {
+                        match DateTime::parse_from_str(&normalized, "%Y-%m-%dT%H:%M:%S%.f%z") {
+                            Ok(parsed) => Some(parsed.with_timezone(&Utc)),
+                            Err(_) => NaiveDateTime::parse_from_str(&normalized, "%Y-%m-%dT%H:%M:%S%.f").ok().and_then(|naive| zone.resolve(&naive)),
+                        }
+                    }
+                }
+            }
+            FormatKind::EpochSeconds => {
+                let mut parts = found.splitn(2, '.');
+                let seconds: i64 = parts.next()?.parse().ok()?;
+                let nanos = match parts.next() {
+                    Some(fraction) => format!("{:0<9}", fraction).parse::<u32>().ok()?,
+                    None => 0,
+                };
+                Utc.timestamp_opt(seconds, nanos).single()
+            }
+            FormatKind::EpochMillis => Utc.timestamp_millis_opt(found.parse().ok()?).single(),
+            FormatKind::Syslog => {
+                let local_now = zone.local_year(now);
+                let parse_in = |year: i32| {
+                    NaiveDateTime::parse_from_str(&format!("{} {}", year, found), "%Y %b %e %H:%M:%S%.f").ok().and_then(|naive| zone.resolve(&naive))
+                };
+                match parse_in(local_now) {
+                    Some(parsed) if parsed <= now + Duration::days(1) => Some(parsed),
+                    _ => parse_in(local_now - 1),
+                }
+            }
+            FormatKind::Strftime(ref pattern) => {
+                return match DateTime::parse_and_remainder(text, pattern) {
+                    Ok((parsed, rest)) => Some((parsed.with_timezone(&Utc), rest)),
+                    Err(_) => match NaiveDateTime::parse_and_remainder(text, pattern) {
+                        Ok((naive, rest)) => zone.resolve(&naive).map(|parsed| (parsed, rest)),
+                        Err(_) => {
+                            let (date, rest) = NaiveDate::parse_and_remainder(text, pattern).ok()?;
+                            zone.resolve(&date.and_hms_opt(0, 0, 0)?).map(|parsed| (parsed, rest))
+                        }
+                    },
+                };
+            }
+        }.map(|parsed| (parsed, rest));
+    }
+}
+
+impl TimestampZone {
+    /// A zone from an IANA name such as `Europe/Berlin`, `UTC`, or a fixed offset such as `+02:00`
+    pub fn from_name(name: &str) -> Result<TimestampZone, String> {
+        if name.starts_with('+') || name.starts_with('-') {
+            return match DateTime::parse_from_str(&format!("2000-01-01T00:00:00{}", name), "%Y-%m-%dT%H:%M:%S%z") {
+                Ok(parsed) => Ok(TimestampZone::Fixed(*parsed.offset())),
+                Err(_) => Err(format!("invalid offset '{}', expected eg. +02:00", name)),
+            };
+        }
+        return name.parse::<Tz>().map(TimestampZone::Named).map_err(|_| format!("unknown timezone '{}'", name));
+    }
+
+    /// Place a local time in this zone. Times repeated by a daylight saving change
+    /// resolve to their first occurrence; times skipped by one don't resolve.
+    fn resolve(&self, naive: &NaiveDateTime) -> Option<DateTime<Utc>> {
+        return match *self {
+            TimestampZone::Fixed(ref offset) => offset.from_local_datetime(naive).earliest().map(|d| d.with_timezone(&Utc)),
+            TimestampZone::Named(ref zone) => zone.from_local_datetime(naive).earliest().map(|d| d.with_timezone(&Utc)),
+        };
+    }
+
+    fn local_year(&self, now: DateTime<Utc>) -> i32 {
+        return match *self {
+            TimestampZone::Fixed(ref offset) => now.with_timezone(offset).year(),
+            TimestampZone::Named(ref zone) => now.with_timezone(zone).year(),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+    use chrono::Utc;
+    use super::TimestampZone;
+    use super::TimestampFormat;
+
+    fn utc(text: &str) -> DateTime<Utc> {
+        return DateTime::parse_from_rfc3339(text).unwrap().with_timezone(&Utc);
+    }
+
+    fn parse(format: &TimestampFormat, text: &str, zone: &str) -> Option<String> {
+        let zone = TimestampZone::from_name(zone).unwrap();
+        return format.parse_prefix(text, &zone, utc("2024-01-10T12:00:00Z")).map(|(parsed, _)| parsed.to_rfc3339());
+    }
+
+    #[test]
+    fn test_builtin_formats() {
+        let rfc3339 = TimestampFormat::rfc3339();
+        assert_eq!(parse(&rfc3339, "2024-05-01T10:00:00.5+02:00 rest", "UTC"), Some("2024-05-01T08:00:00.500+00:00".to_string()));
+        assert_eq!(parse(&rfc3339, "2024-05-01 10:00:00Z", "UTC"), Some("2024-05-01T10:00:00+00:00".to_string()));
+        assert_eq!(parse(&rfc3339, "2024-05-01T10:00:00+0530", "UTC"), Some("2024-05-01T04:30:00+00:00".to_string()));
+        assert_eq!(parse(&rfc3339, "2024-05-01T10:00:00", "Europe/Berlin"), Some("2024-05-01T08:00:00+00:00".to_string()));
+        assert_eq!(parse(&rfc3339, "2024-13-01T10:00:00Z", "UTC"), None);
+
+        assert_eq!(parse(&TimestampFormat::epoch_seconds(), "1714557600.25", "UTC"), Some("2024-05-01T10:00:00.250+00:00".to_string()));
+        assert_eq!(parse(&TimestampFormat::epoch_seconds(), "1714557600123", "UTC"), None);
+        assert_eq!(parse(&TimestampFormat::epoch_millis(), "1714557600123 GET", "UTC"), Some("2024-05-01T10:00:00.123+00:00".to_string()));
+        assert_eq!(parse(&TimestampFormat::epoch_millis(), "200", "UTC"), None);
+    }
+
+    #[test]
+    fn test_syslog_year_is_inferred() {
+        let syslog = TimestampFormat::syslog();
+        assert_eq!(parse(&syslog, "Jan  9 23:59:59 host app: hi", "UTC"), Some("2024-01-09T23:59:59+00:00".to_string()));
+        // Later in the year than now, so written last year
+        assert_eq!(parse(&syslog, "Dec 31 23:00:00", "UTC"), Some("2023-12-31T23:00:00+00:00".to_string()));
+        assert_eq!(parse(&syslog, "Jan 10 13:00:00", "+01:00"), Some("2024-01-10T12:00:00+00:00".to_string()));
+    }
+
+    #[test]
+    fn test_strftime_formats() {
+        let access = TimestampFormat::from_name("%d/%b/%Y:%H:%M:%S %z").unwrap();
+        assert_eq!(parse(&access, "10/Oct/2023:13:55:36 -0700] \"GET /\"", "UTC"), Some("2023-10-10T20:55:36+00:00".to_string()));
+        let local = TimestampFormat::from_name("%Y/%m/%d %H:%M:%S").unwrap();
+        assert_eq!(parse(&local, "2024/03/31 02:30:00", "Europe/Berlin"), None);
+        assert_eq!(parse(&local, "2024/10/27 02:30:00", "Europe/Berlin"), Some("2024-10-27T00:30:00+00:00".to_string()));
+        let date = TimestampFormat::from_name("%Y%m%d").unwrap();
+        assert_eq!(parse(&date, "20240501", "UTC"), Some("2024-05-01T00:00:00+00:00".to_string()));
+
+        assert_eq!(TimestampFormat::from_name("%Q").unwrap_err(), "invalid strftime pattern '%Q'");
+        assert!(TimestampFormat::from_name("iso").is_err());
+        assert_eq!(TimestampZone::from_name("Mars/Olympus").unwrap_err(), "unknown timezone 'Mars/Olympus'");
+        assert!(TimestampZone::from_name("+25:00").is_err());
+    }
+}
@@ -0,0 +1,203 @@
+use chrono::DateTime;
+use chrono::SecondsFormat;
+use chrono::Utc;
+use chrono_tz::Tz;
+use loghaul::Processor;
+use loghaul::Record;
+use loghaul::RecordValue;
+use loghaul::LoghaulError;
+use loghaul::MetricsRegistry;
+use timestamp_format::TimestampFormat;
+use timestamp_format::TimestampZone;
+use internal::parse_helpers::payload_line;
+
+/// How far into a payload a timestamp is looked for
+const MAX_SCAN_BYTES: usize = 256;
+
+/// Finds the time an event happened and writes it, normalized to UTC, to a field.
+///
+/// Each format is tried in order against a field, which must hold nothing but the
+/// timestamp, or against the payload, where the timestamp may follow a space, `[`,
+/// `"`, `(`, `,`, `=` or `>` near the start of the line. Timestamps without an offset
+/// are read in the configured timezone, UTC by default.
+///
+/// The result is written as RFC 3339 with milliseconds, eg. `2024-05-01T10:00:00.000Z`,
+/// to the `timestamp` field by default. When no format matches, the time the record
+/// was processed is written instead; the two cases are counted as `parsed` and `fallback`.
+///
+/// ```
+///     extern crate loghaul;
+///     extern crate loghaul_parse;
+///     use loghaul::Stream;
+///     use loghaul_parse::{TimestampFormat, TimestampProcessor, TimestampZone};
+///     let timestamps = TimestampProcessor::new()
+///         .with_formats(vec!(TimestampFormat::rfc3339(), TimestampFormat::from_name("%d/%b/%Y:%H:%M:%S %z").unwrap()))
+///         .with_zone(TimestampZone::from_name("Europe/Berlin").unwrap());
+///     let stream = Stream::new().with_processor(timestamps);
+/// ```
+pub struct TimestampProcessor {
+    formats: Vec<TimestampFormat>,
+    field: Option<String>,
+    target: String,
+    zone: TimestampZone,
+    metrics: Option<(String, MetricsRegistry)>,
+}
+
+impl TimestampProcessor {
+    /// A processor trying RFC 3339, epoch milliseconds, epoch seconds then syslog timestamps
+    pub fn new() -> TimestampProcessor {
+        return TimestampProcessor {
+            formats: vec!(
+                TimestampFormat::rfc3339(),
+                TimestampFormat::epoch_millis(),
+                TimestampFormat::epoch_seconds(),
+                TimestampFormat::syslog(),
+            ),
+            field: None,
+            target: "timestamp".to_string(),
+            zone: TimestampZone::Named(Tz::UTC),
+            metrics: None,
+        };
+    }
+
+    /// Replace the formats to try, in order
+    pub fn with_formats(mut self, formats: Vec<TimestampFormat>) -> TimestampProcessor {
+        self.formats = formats;
+        return self;
+    }
+
+    /// Read the timestamp from this field instead of the payload
+    pub fn with_field(mut self, field: &str) -> TimestampProcessor {
+        self.field = Some(field.to_string());
+        return self;
+    }
+
+    /// Write the normalized timestamp to this field instead of `timestamp`
+    pub fn with_target(mut self, field: &str) -> TimestampProcessor {
+        self.target = field.to_string();
+        return self;
+    }
+
+    /// Read timestamps without an offset in this zone instead of UTC
+    pub fn with_zone(mut self, zone: TimestampZone) -> TimestampProcessor {
+        self.zone = zone;
+        return self;
+    }
+
+    /// The first timestamp found in some text, by the first format that finds one
+    fn find(&self, text: &str, whole: bool, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let text = text.trim();
+        let mut starts = vec!(0);
+        if !whole {
+            for (offset, c) in text.char_indices().take_while(|&(offset, _)| offset < MAX_SCAN_BYTES) {
+                match c {
+                    ' ' | '[' | '"' | '(' | ',' | '=' | '>' => starts.push(offset + 1),
+                    _ => {}
+                }
+            }
+        }
+        for start in starts.into_iter() {
+            for format in self.formats.iter() {
+                match format.parse_prefix(&text[start..], &self.zone, now) {
+                    Some((parsed, rest)) if !whole || rest.trim().is_empty() => {
+                        return Some(parsed);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        return None;
+    }
+
+    fn normalize(&self, record: &mut Record, now: DateTime<Utc>) {
+        let found = match self.field {
+            Some(ref field) => match record.field(field) {
+                Some(value) => self.find(&value.to_string(), true, now),
+                None => None,
+            },
+            None => match payload_line(&record.payload) {
+                Ok(text) => self.find(text, false, now),
+                Err(_) => None,
+            },
+        };
+        let (time, counter) = match found {
+            Some(time) => (time, "parsed"),
+            None => (now, "fallback"),
+        };
+        record.set_field(&self.target, RecordValue::from(time.to_rfc3339_opts(SecondsFormat::Millis, true)));
+        match self.metrics {
+            Some((ref id, ref metrics)) => metrics.processor_counter(id, counter, 1),
+            None => {}
+        }
+    }
+}
+
+impl Processor for TimestampProcessor {
+    fn process(&mut self, mut record: Record, output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        self.normalize(&mut record, Utc::now());
+        output.push(record);
+        return Ok(());
+    }
+
+    fn id(&self) -> Option<String> {
+        Some("timestamp".to_string())
+    }
+
+    fn attach(&mut self, id: &str, metrics: &MetricsRegistry) {
+        self.metrics = Some((id.to_string(), metrics.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+    use chrono::Utc;
+    use super::TimestampProcessor;
+    use timestamp_format::TimestampFormat;
+    use timestamp_format::TimestampZone;
+    use loghaul::Processor;
+    use loghaul::Record;
+    use loghaul::RecordValue;
+    use loghaul::MetricsRegistry;
+
+    fn normalized(processor: &TimestampProcessor, mut record: Record) -> Option<RecordValue> {
+        let now = DateTime::parse_from_rfc3339("2024-05-02T00:00:00Z").unwrap().with_timezone(&Utc);
+        processor.normalize(&mut record, now);
+        return record.field("timestamp").cloned();
+    }
+
+    #[test]
+    fn test_finds_timestamp_in_payload() {
+        let processor = TimestampProcessor::new()
+            .with_formats(vec!(TimestampFormat::from_name("%d/%b/%Y:%H:%M:%S %z").unwrap(), TimestampFormat::syslog()))
+            .with_zone(TimestampZone::from_name("America/New_York").unwrap());
+        let line = b"10.0.0.1 - - [01/May/2024:10:00:00 +0200] \"GET / HTTP/1.1\" 200 5\n".to_vec();
+        assert_eq!(normalized(&processor, Record::new("web", line)), Some(RecordValue::from("2024-05-01T08:00:00.000Z")));
+        let line = b"<13>May  1 10:00:00 host app: started".to_vec();
+        assert_eq!(normalized(&processor, Record::new("syslog", line)), Some(RecordValue::from("2024-05-01T14:00:00.000Z")));
+    }
+
+    #[test]
+    fn test_reads_field_and_falls_back() {
+        let metrics = MetricsRegistry::new();
+        let mut processor = TimestampProcessor::new().with_field("ts").with_target("event_time");
+        processor.attach("timestamp", &metrics);
+
+        let mut record = Record::new("app", b"1714557600 is not where it is read".to_vec());
+        record.set_field("ts", RecordValue::Int(1714557600123));
+        let mut output = Vec::new();
+        processor.process(record.clone(), &mut output).unwrap();
+        assert_eq!(output[0].field("event_time"), Some(&RecordValue::from("2024-05-01T10:00:00.123Z")));
+
+        // Only a whole field value counts as a timestamp
+        record.set_field("ts", "2024-05-01T10:00:00Z and more");
+        let before = Utc::now();
+        processor.process(record, &mut output).unwrap();
+        let fallback = output[1].field("event_time").unwrap().to_string();
+        assert!(DateTime::parse_from_rfc3339(&fallback).unwrap() >= before - ::chrono::Duration::milliseconds(1));
+
+        let counters = metrics.snapshot().processor("timestamp").unwrap().counters.clone();
+        assert_eq!(counters.get("parsed"), Some(&1));
+        assert_eq!(counters.get("fallback"), Some(&1));
+    }
+}
@@ -0,0 +1,6 @@
+extern crate loghaul;
+extern crate rhai;
+
+mod script_processor;
+
+pub use script_processor::ScriptProcessor;
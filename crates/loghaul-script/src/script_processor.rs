@@ -0,0 +1,393 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::Mutex;
+use rhai::AST;
+use rhai::Dynamic;
+use rhai::Engine;
+use rhai::EvalAltResult;
+use rhai::Map;
+use rhai::Scope;
+use rhai::module_resolvers::DummyModuleResolver;
+use loghaul::Processor;
+use loghaul::Record;
+use loghaul::RecordValue;
+use loghaul::LoghaulError;
+use loghaul::LoghaulErrorCode;
+use loghaul::MetricsRegistry;
+
+const DEFAULT_MAX_OPERATIONS: u64 = 100000;
+const DEFAULT_MAX_STRING_SIZE: usize = 1024 * 1024;
+const DEFAULT_MAX_COLLECTION_SIZE: usize = 10000;
+const DEFAULT_MAX_EMITTED: usize = 1000;
+const DEFAULT_MAX_EMITTED_BYTES: usize = 16 * 1024 * 1024;
+const MAX_CALL_LEVELS: usize = 32;
+
+/// Runs a [Rhai](https://rhai.rs) script on every record.
+///
+/// The script sees the record as the variables `payload` (a string), `fields` (a map),
+/// `route` (the target the record is sent to, or `()` to use the routes) and the
+/// constant `source`; whatever it leaves in them is written back to the record. A
+/// payload that isn't valid UTF-8 is shown with replacement characters, and only
+/// written back, as text, if the script changes it. It
+/// can also call `drop()` to discard the record and `emit(payload)` or
+/// `emit(payload, fields)` to send more records from the same source after it.
+///
+/// Scripts can't reach the file system or network, and `print` and `debug` are
+/// ignored. Each run is limited in how many operations it performs, and strings,
+/// arrays and maps are limited in size, as are the number and total size of the
+/// records it emits; a script going over a limit, or failing in any other way, fails
+/// the record with a processor error.
+///
+/// Records scripts drop are counted as `dropped`, and records they emit as `emitted`.
+///
+/// ```
+///     extern crate loghaul;
+///     extern crate loghaul_script;
+///     use loghaul::Stream;
+///     use loghaul_script::ScriptProcessor;
+///     let script = ScriptProcessor::new("inline", r#"
+///         if fields.level == "debug" { drop(); }
+///         fields.length = payload.len();
+///     "#).unwrap().with_max_operations(10000);
+///     let stream = Stream::new().with_processor(script);
+/// ```
+pub struct ScriptProcessor {
+    name: String,
+    engine: Engine,
+    ast: AST,
+    actions: Arc<Mutex<ScriptActions>>,
+    max_emitted: usize,
+    max_emitted_bytes: usize,
+    metrics: Option<(String, MetricsRegistry)>,
+}
+
+/// What a script asked for, besides changing the record
+struct ScriptActions {
+    dropped: bool,
+    emitted: Vec<(String, Map)>,
+    emitted_bytes: usize,
+    max_emitted: usize,
+    max_emitted_bytes: usize,
+}
+
+impl ScriptProcessor {
+    /// Compile a script, naming it in errors
+    pub fn new(name: &str, script: &str) -> Result<ScriptProcessor, String> {
+        let actions = Arc::new(Mutex::new(ScriptActions::new(DEFAULT_MAX_EMITTED, DEFAULT_MAX_EMITTED_BYTES)));
+        let mut engine = Engine::new();
+        // The default resolver loads `import`ed modules from disk
+        engine.set_module_resolver(DummyModuleResolver::new());
+        engine.on_print(|_| {});
+        engine.on_debug(|_, _, _| {});
+        engine.set_max_call_levels(MAX_CALL_LEVELS);
+        engine.set_max_operations(DEFAULT_MAX_OPERATIONS);
+        engine.set_max_string_size(DEFAULT_MAX_STRING_SIZE);
+        engine.set_max_array_size(DEFAULT_MAX_COLLECTION_SIZE);
+        engine.set_max_map_size(DEFAULT_MAX_COLLECTION_SIZE);
+
+        let dropped = actions.clone();
+        engine.register_fn("drop", move || {
+            dropped.lock().expect("script actions lock").dropped = true;
+        });
+        let emitted = actions.clone();
+        engine.register_fn("emit", move |payload: &str| -> Result<(), Box<EvalAltResult>> {
+            return emitted.lock().expect("script actions lock").emit(payload, Map::new());
+        });
+        let emitted = actions.clone();
+        engine.register_fn("emit", move |payload: &str, fields: Map| -> Result<(), Box<EvalAltResult>> {
+            return emitted.lock().expect("script actions lock").emit(payload, fields);
+        });
+
+        let ast = engine.compile(script).map_err(|e| format!("{}: {}", name, e))?;
+        return Ok(ScriptProcessor {
+            name: name.to_string(),
+            engine,
+            ast,
+            actions,
+            max_emitted: DEFAULT_MAX_EMITTED,
+            max_emitted_bytes: DEFAULT_MAX_EMITTED_BYTES,
+            metrics: None,
+        });
+    }
+
+    /// Read and compile a script file
+    pub fn from_file(path: impl AsRef<Path>) -> Result<ScriptProcessor, String> {
+        let path = path.as_ref();
+        let script = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        return ScriptProcessor::new(&path.display().to_string(), &script);
+    }
+
+    /// Fail a record once its script has performed this many operations, 100000 by default
+    pub fn with_max_operations(mut self, operations: u64) -> ScriptProcessor {
+        self.engine.set_max_operations(operations.max(1));
+        return self;
+    }
+
+    /// Fail a record once its script builds a string longer than this many bytes, 1MiB by default
+    pub fn with_max_string_size(mut self, bytes: usize) -> ScriptProcessor {
+        self.engine.set_max_string_size(bytes.max(1));
+        return self;
+    }
+
+    /// Fail a record once its script builds an array or map with more than this many
+    /// entries, 10000 by default
+    pub fn with_max_collection_size(mut self, entries: usize) -> ScriptProcessor {
+        self.engine.set_max_array_size(entries.max(1));
+        self.engine.set_max_map_size(entries.max(1));
+        return self;
+    }
+
+    /// Fail a record once its script emits more than this many records, or records
+    /// totalling more than this many bytes, 1000 records and 16MiB by default
+    pub fn with_max_emitted(mut self, records: usize, bytes: usize) -> ScriptProcessor {
+        self.max_emitted = records;
+        self.max_emitted_bytes = bytes;
+        return self;
+    }
+
+    fn error(&self, message: String) -> LoghaulError {
+        return LoghaulError::from(LoghaulErrorCode::ProcessorErr(format!("{}: {}", self.name, message)));
+    }
+
+    fn count(&self, name: &str, count: usize) {
+        match self.metrics {
+            Some((ref id, ref metrics)) if count > 0 => metrics.processor_counter(id, name, count as u64),
+            _ => {}
+        }
+    }
+}
+
+impl Processor for ScriptProcessor {
+    fn process(&mut self, mut record: Record, output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        let mut scope = Scope::new();
+        scope.push_constant("source", record.source.clone());
+        let original = String::from_utf8_lossy(&record.payload).to_string();
+        scope.push("payload", original.clone());
+        scope.push("fields", to_map(&record));
+        scope.push("route", record.route.clone().map(Dynamic::from).unwrap_or(Dynamic::UNIT));
+
+        *self.actions.lock().expect("script actions lock") = ScriptActions::new(self.max_emitted, self.max_emitted_bytes);
+        let result = self.engine.run_ast_with_scope(&mut scope, &self.ast);
+        let actions = ::std::mem::replace(&mut *self.actions.lock().expect("script actions lock"), ScriptActions::new(0, 0));
+        match result {
+            Ok(_) => {}
+            Err(e) => return Err(self.error(e.to_string())),
+        }
+
+        let payload = scope.get_value::<Dynamic>("payload").unwrap_or(Dynamic::UNIT);
+        if !payload.is_string() {
+            return Err(self.error(format!("payload must be a string, found {}", payload.type_name())));
+        }
+        let payload = payload.into_string().expect("payload is a string");
+        if payload != original {
+            record.set_payload_str(&payload);
+        }
+        record.fields = match scope.get_value::<Dynamic>("fields").and_then(|fields| fields.try_cast::<Map>()) {
+            Some(fields) => from_map(fields),
+            None => return Err(self.error("fields must be a map".to_string())),
+        };
+        let route = scope.get_value::<Dynamic>("route").unwrap_or(Dynamic::UNIT);
+        record.route = match route {
+            ref route if route.is_unit() => None,
+            ref route if route.is_string() => Some(route.to_string()),
+            route => return Err(self.error(format!("route must be a string or (), found {}", route.type_name()))),
+        };
+
+        let source = record.source.clone();
+        self.count("dropped", actions.dropped as usize);
+        self.count("emitted", actions.emitted.len());
+        if !actions.dropped {
+            output.push(record);
+        }
+        for (payload, fields) in actions.emitted.into_iter() {
+            let mut emitted = Record::new(&source, payload.into_bytes());
+            emitted.fields = from_map(fields);
+            output.push(emitted);
+        }
+        return Ok(());
+    }
+
+    fn id(&self) -> Option<String> {
+        Some("script".to_string())
+    }
+
+    fn attach(&mut self, id: &str, metrics: &MetricsRegistry) {
+        self.metrics = Some((id.to_string(), metrics.clone()));
+    }
+}
+
+impl ScriptActions {
+    fn new(max_emitted: usize, max_emitted_bytes: usize) -> ScriptActions {
+        return ScriptActions {
+            dropped: false,
+            emitted: Vec::new(),
+            emitted_bytes: 0,
+            max_emitted,
+            max_emitted_bytes,
+        };
+    }
+
+    /// Queue a record, failing the script once it has emitted too much
+    fn emit(&mut self, payload: &str, fields: Map) -> Result<(), Box<EvalAltResult>> {
+        if self.emitted.len() >= self.max_emitted {
+            return Err(format!("emitted more than {} records", self.max_emitted).into());
+        }
+        let size = payload.len() + fields.iter().map(|(key, value)| key.len() + value.to_string().len()).sum::<usize>();
+        if self.emitted_bytes + size > self.max_emitted_bytes {
+            return Err(format!("emitted more than {} bytes", self.max_emitted_bytes).into());
+        }
+        self.emitted_bytes += size;
+        self.emitted.push((payload.to_string(), fields));
+        return Ok(());
+    }
+}
+
+/// The fields of a record as a script map
+fn to_map(record: &Record) -> Map {
+    return record.fields.iter().map(|(key, value)| {
+        let value = match *value {
+            RecordValue::Null => Dynamic::UNIT,
+            RecordValue::Bool(v) => Dynamic::from(v),
+            RecordValue::Int(v) => Dynamic::from(v),
+            RecordValue::Float(v) => Dynamic::from(v),
+            RecordValue::String(ref v) => Dynamic::from(v.clone()),
+        };
+        (key.as_str().into(), value)
+    }).collect();
+}
+
+/// Record fields from a script map; arrays, maps and other values become strings
+fn from_map(map: Map) -> ::std::collections::BTreeMap<String, RecordValue> {
+    return map.into_iter().map(|(key, value)| {
+        let value = if value.is_unit() {
+            RecordValue::Null
+        } else if let Ok(v) = value.as_bool() {
+            RecordValue::Bool(v)
+        } else if let Ok(v) = value.as_int() {
+            RecordValue::Int(v)
+        } else if let Ok(v) = value.as_float() {
+            RecordValue::Float(v)
+        } else {
+            RecordValue::String(value.to_string())
+        };
+        (key.to_string(), value)
+    }).collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ScriptProcessor;
+    use loghaul::Processor;
+    use loghaul::Record;
+    use loghaul::RecordValue;
+    use loghaul::MetricsRegistry;
+
+    #[test]
+    fn test_script_changes_drops_emits_and_routes() {
+        let metrics = MetricsRegistry::new();
+        let mut script = ScriptProcessor::new("test", r#"
+            if fields.level == "debug" {
+                drop();
+                return;
+            }
+            payload = payload.to_upper();
+            fields.length = payload.len();
+            fields.ratio = 0.5;
+            fields.remove("secret");
+            if fields.status >= 500 {
+                route = "pager";
+                emit("alert for " + source, #{ status: fields.status, tags: [1, 2] });
+                emit("second");
+            }
+        "#).unwrap();
+        script.attach("script", &metrics);
+
+        let mut output = Vec::new();
+        let mut record = Record::new("api", b"failed".to_vec());
+        record.set_field("status", RecordValue::Int(503));
+        record.set_field("secret", "hunter2");
+        script.process(record, &mut output).unwrap();
+        let mut debug = Record::new("api", b"noise".to_vec());
+        debug.set_field("level", "debug");
+        script.process(debug, &mut output).unwrap();
+
+        assert_eq!(output.len(), 3);
+        assert_eq!(output[0].payload_str(), Some("FAILED"));
+        assert_eq!(output[0].route, Some("pager".to_string()));
+        assert_eq!(output[0].field("length"), Some(&RecordValue::Int(6)));
+        assert_eq!(output[0].field("ratio"), Some(&RecordValue::Float(0.5)));
+        assert_eq!(output[0].field("secret"), None);
+        assert_eq!(output[1].source, "api");
+        assert_eq!(output[1].payload_str(), Some("alert for api"));
+        assert_eq!(output[1].field("status"), Some(&RecordValue::Int(503)));
+        assert_eq!(output[1].field("tags"), Some(&RecordValue::from("[1, 2]")));
+        assert_eq!(output[2].payload_str(), Some("second"));
+        assert!(output[2].fields.is_empty());
+
+        let counters = metrics.snapshot().processor("script").unwrap().counters.clone();
+        assert_eq!(counters.get("dropped"), Some(&1));
+        assert_eq!(counters.get("emitted"), Some(&2));
+    }
+
+    #[test]
+    fn test_script_errors_and_limits() {
+        let err = ScriptProcessor::new("broken.rhai", "let x = ;").err().unwrap();
+        assert!(err.starts_with("broken.rhai: "), "{}", err);
+        assert!(err.contains("line 1"), "{}", err);
+
+        let mut output = Vec::new();
+        let mut spin = ScriptProcessor::new("spin", "loop { }").unwrap().with_max_operations(1000);
+        let err = spin.process(Record::new("a", b"x".to_vec()), &mut output).unwrap_err();
+        assert!(err.to_string().contains("spin: Too many operations"), "{}", err);
+
+        let mut grow = ScriptProcessor::new("grow", "let s = payload; loop { s += s; }").unwrap().with_max_string_size(64);
+        assert!(grow.process(Record::new("a", b"x".to_vec()), &mut output).is_err());
+
+        let mut wrong = ScriptProcessor::new("wrong", "payload = 1;").unwrap();
+        let err = wrong.process(Record::new("a", b"x".to_vec()), &mut output).unwrap_err();
+        assert!(err.to_string().contains("payload must be a string, found i64"), "{}", err);
+        assert!(output.is_empty());
+
+        // A failed record leaves nothing behind for the next one
+        let mut partial = ScriptProcessor::new("partial", r#"emit("early"); if payload == "bad" { throw "bad record"; }"#).unwrap();
+        assert!(partial.process(Record::new("a", b"bad".to_vec()), &mut output).is_err());
+        partial.process(Record::new("a", b"good".to_vec()), &mut output).unwrap();
+        assert_eq!(output.len(), 2);
+
+        output.clear();
+        let mut flood = ScriptProcessor::new("flood", "loop { emit(payload); }").unwrap().with_max_emitted(10, 1000);
+        let err = flood.process(Record::new("a", b"x".to_vec()), &mut output).unwrap_err();
+        assert!(err.to_string().contains("emitted more than 10 records"), "{}", err);
+        let mut large = ScriptProcessor::new("large", "emit(payload, #{ size: 1 }); emit(payload);").unwrap().with_max_emitted(10, 10);
+        let err = large.process(Record::new("a", b"123456".to_vec()), &mut output).unwrap_err();
+        assert!(err.to_string().contains("emitted more than 10 bytes"), "{}", err);
+        assert!(output.is_empty());
+        assert!(ScriptProcessor::from_file("/nonexistent/script.rhai").is_err());
+    }
+
+    #[test]
+    fn test_script_keeps_payload_that_is_not_utf8() {
+        let mut output = Vec::new();
+        let mut tag = ScriptProcessor::new("tag", "fields.seen = true;").unwrap();
+        tag.process(Record::new("a", b"caf\xe9\n".to_vec()), &mut output).unwrap();
+        assert_eq!(output[0].payload, b"caf\xe9\n".to_vec());
+        assert_eq!(output[0].field("seen"), Some(&RecordValue::Bool(true)));
+
+        let mut upper = ScriptProcessor::new("upper", "payload = payload.to_upper();").unwrap();
+        upper.process(Record::new("a", b"caf\xe9\n".to_vec()), &mut output).unwrap();
+        assert_eq!(output[1].payload_str(), Some("CAF\u{fffd}\n"));
+    }
+
+    #[test]
+    fn test_script_cannot_import_files() {
+        let path = ::std::env::temp_dir().join(format!("loghaul_script_module_{}.rhai", ::std::process::id()));
+        ::std::fs::write(&path, "fn secret() { 42 }").unwrap();
+        let mut output = Vec::new();
+        let mut import = ScriptProcessor::new("import", &format!("import \"{}\" as m; fields.secret = m::secret();", path.with_extension("").display())).unwrap();
+        let err = import.process(Record::new("a", b"x".to_vec()), &mut output).unwrap_err();
+        ::std::fs::remove_file(&path).unwrap();
+        assert!(err.to_string().contains("Module not found"), "{}", err);
+        assert!(output.is_empty());
+    }
+}
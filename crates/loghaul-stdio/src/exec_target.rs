@@ -0,0 +1,292 @@
+use loghaul::Target;
+use loghaul::LoghaulError;
+use loghaul::LoghaulErrorCode;
+use loghaul::StreamEntry;
+use loghaul::Record;
+use loghaul::RecordFormat;
+use std::io::Write;
+use std::mem;
+use std::process::Child;
+use std::process::Command;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::mpsc;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::mpsc::SyncSender;
+use std::sync::mpsc::TrySendError;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+/// How many commands may run at once; further records wait in the queue
+const MAX_RUNNING: usize = 16;
+
+/// How many records may wait for a command before new ones are dropped
+const MAX_QUEUED: usize = 1000;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+/// How often running commands are checked on
+const REAP_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Runs a command for every record, writing the record to its standard input.
+///
+/// Records are written as JSON by default. Commands are started and waited for on a
+/// background thread, so a slow command never holds up the stream: at most 16 run
+/// at once, up to 1000 records wait for them, and records arriving while the queue
+/// is full are dropped. A command still running after the timeout is killed. One
+/// that can't be started, is killed or exits unsuccessfully is reported as an error
+/// by the next record. The commands' output is discarded.
+///
+/// ```
+///     extern crate loghaul;
+///     extern crate loghaul_stdio;
+///     use std::time::Duration;
+///     use loghaul::Stream;
+///     use loghaul_stdio::ExecTarget;
+///     let pager = ExecTarget::new(&["/usr/local/bin/page", "--team", "ops"])
+///         .with_timeout(Duration::from_secs(10));
+///     let stream = Stream::new().with_target(pager);
+/// ```
+pub struct ExecTarget {
+    command: Vec<String>,
+    format: RecordFormat,
+    timeout: Duration,
+    queue: Option<SyncSender<Vec<u8>>>,
+    failures: Arc<Mutex<Vec<String>>>,
+}
+
+/// Starts commands for queued records and collects them once they exit
+struct ExecWorker {
+    command: Vec<String>,
+    timeout: Duration,
+    running: Vec<(Child, Instant)>,
+    failures: Arc<Mutex<Vec<String>>>,
+}
+
+impl ExecTarget {
+    /// Run this program with these arguments, without a shell
+    pub fn new(command: &[&str]) -> ExecTarget {
+        return ExecTarget {
+            command: command.iter().map(|c| c.to_string()).collect(),
+            format: RecordFormat::Json,
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECS),
+            queue: None,
+            failures: Arc::new(Mutex::new(Vec::new())),
+        };
+    }
+
+    /// Write records in this format instead of JSON
+    pub fn with_format(mut self, format: RecordFormat) -> ExecTarget {
+        self.format = format;
+        return self;
+    }
+
+    /// Kill a command still running after this long, 30 seconds by default
+    pub fn with_timeout(mut self, timeout: Duration) -> ExecTarget {
+        self.timeout = timeout;
+        return self;
+    }
+
+    /// Queue a record for the worker, starting it on first use
+    fn send(&mut self, data: &[u8]) -> Result<(), String> {
+        if self.queue.is_none() {
+            let (sender, receiver) = mpsc::sync_channel(MAX_QUEUED);
+            let mut worker = ExecWorker {
+                command: self.command.clone(),
+                timeout: self.timeout,
+                running: Vec::new(),
+                failures: self.failures.clone(),
+            };
+            thread::spawn(move || worker.run(receiver));
+            self.queue = Some(sender);
+        }
+        return match self.queue.as_ref().expect("queue is started").try_send(data.to_vec()) {
+            Ok(_) => Ok(()),
+            Err(TrySendError::Full(_)) => Err("too many records waiting for the command, record dropped".to_string()),
+            Err(TrySendError::Disconnected(_)) => Err("the command runner stopped, record dropped".to_string()),
+        };
+    }
+
+    /// Report what went wrong in the background since the last call
+    fn failures(&self) -> Result<(), LoghaulError> {
+        let failures = mem::replace(&mut *self.failures.lock().expect("exec failures lock"), Vec::new());
+        return match failures.is_empty() {
+            true => Ok(()),
+            false => Err(self.error(&failures.join(", "))),
+        };
+    }
+
+    fn error(&self, message: &str) -> LoghaulError {
+        return LoghaulError::from(LoghaulErrorCode::TargetErr(format!("{}: {}", self.command.join(" "), message)));
+    }
+}
+
+impl ExecWorker {
+    fn run(&mut self, queue: mpsc::Receiver<Vec<u8>>) {
+        loop {
+            self.reap();
+            if self.running.len() >= MAX_RUNNING {
+                thread::sleep(REAP_INTERVAL);
+                continue;
+            }
+            match queue.recv_timeout(REAP_INTERVAL) {
+                Ok(data) => self.start(data),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        // The target is gone; wait for, or kill, what is still running so nothing is left behind
+        while !self.running.is_empty() {
+            thread::sleep(REAP_INTERVAL);
+            self.reap();
+        }
+    }
+
+    fn start(&mut self, data: Vec<u8>) {
+        let spawned = Command::new(&self.command[0])
+            .args(&self.command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn();
+        let mut child = match spawned {
+            Ok(child) => child,
+            Err(e) => return self.fail(e.to_string()),
+        };
+        // Written from its own thread so a command that doesn't read its input can't block
+        // this one; it gives up once the command exits or is killed. Closing standard input
+        // afterwards lets the command see the end of the record.
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        thread::spawn(move || {
+            let _ = stdin.write_all(&data);
+        });
+        self.running.push((child, Instant::now()));
+    }
+
+    /// Collect the commands that have exited, and kill those running too long
+    fn reap(&mut self) {
+        let running = mem::replace(&mut self.running, Vec::new());
+        for (mut child, started) in running.into_iter() {
+            match child.try_wait() {
+                Ok(Some(status)) if status.success() => {}
+                Ok(Some(status)) => self.fail(status.to_string()),
+                Ok(None) if started.elapsed() >= self.timeout => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    self.fail(format!("killed after running for {:?}", self.timeout));
+                }
+                Ok(None) => self.running.push((child, started)),
+                Err(e) => self.fail(e.to_string()),
+            }
+        }
+    }
+
+    fn fail(&self, message: String) {
+        self.failures.lock().expect("exec failures lock").push(message);
+    }
+}
+
+impl Drop for ExecTarget {
+    fn drop(&mut self) {
+        // Closing the queue stops the worker once the commands still running have exited or
+        // been killed, so a removed target leaves no zombies behind; it doesn't wait for them here
+        self.queue = None;
+    }
+}
+
+impl Target for ExecTarget {
+    fn consume(&mut self, entry: StreamEntry, data: &Vec<u8>) -> Result<(), LoghaulError> {
+        match entry {
+            StreamEntry::NoData => {}
+            StreamEntry::EOF => {}
+            StreamEntry::Data => {
+                if self.command.first().map(|program| program.is_empty()).unwrap_or(true) {
+                    return Err(self.error("no command to run"));
+                }
+                match self.send(data) {
+                    Ok(_) => {}
+                    Err(e) => return Err(self.error(&e)),
+                }
+            }
+        };
+        return self.failures();
+    }
+
+    fn consume_record(&mut self, record: &Record) -> Result<(), LoghaulError> {
+        let data = self.format.encode(record);
+        return self.consume(StreamEntry::Data, &data);
+    }
+
+    fn id(&self) -> Option<String> {
+        Some(self.command.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ExecTarget;
+    use loghaul::Target;
+    use loghaul::Record;
+    use loghaul::RecordFormat;
+    use loghaul::StreamEntry;
+    use std::env;
+    use std::fs;
+    use std::process;
+    use std::thread::sleep;
+    use std::time::Duration;
+    use std::time::Instant;
+
+    /// Poll an exec target until it reports a failure, or give up after a few seconds
+    fn next_failure(target: &mut ExecTarget) -> Option<String> {
+        let started = Instant::now();
+        while started.elapsed() < Duration::from_secs(5) {
+            match target.consume(StreamEntry::NoData, &Vec::new()) {
+                Ok(_) => sleep(Duration::from_millis(10)),
+                Err(e) => return Some(e.to_string()),
+            }
+        }
+        return None;
+    }
+
+    #[test]
+    fn test_runs_command_per_record() {
+        let path = env::temp_dir().join(format!("loghaul-exec-{}.log", process::id()));
+        let script = format!("cat >> '{}'", path.display());
+        let mut target = ExecTarget::new(&["sh", "-c", &script]).with_format(RecordFormat::Raw);
+        target.consume_record(&Record::new("alert", b"first\n".to_vec())).unwrap();
+        let started = Instant::now();
+        while fs::read_to_string(&path).map(|c| c.len() < 6).unwrap_or(true) && started.elapsed() < Duration::from_secs(5) {
+            sleep(Duration::from_millis(10));
+        }
+        target.consume_record(&Record::new("alert", b"second\n".to_vec())).unwrap();
+        drop(target);
+        let started = Instant::now();
+        while fs::read_to_string(&path).map(|c| c.len() < 13).unwrap_or(true) && started.elapsed() < Duration::from_secs(5) {
+            sleep(Duration::from_millis(10));
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "first\nsecond\n");
+        fs::remove_file(&path).unwrap();
+
+        let mut failing = ExecTarget::new(&["sh", "-c", "cat > /dev/null; exit 3"]);
+        failing.consume_record(&Record::new("alert", b"alert\n".to_vec())).unwrap();
+        assert!(next_failure(&mut failing).unwrap().contains("sh -c cat > /dev/null; exit 3: exit status: 3"));
+
+        let mut missing = ExecTarget::new(&["/nonexistent/loghaul-page"]);
+        missing.consume_record(&Record::new("alert", b"alert\n".to_vec())).unwrap();
+        assert!(next_failure(&mut missing).unwrap().contains("/nonexistent/loghaul-page: "));
+    }
+
+    #[test]
+    fn test_hung_commands_do_not_block() {
+        // Neither reads its input nor exits; a record larger than a pipe holds would block a writer
+        let mut hung = ExecTarget::new(&["sleep", "10"]).with_format(RecordFormat::Raw).with_timeout(Duration::from_millis(500));
+        let large = Record::new("alert", vec!(b'x'; 1024 * 1024));
+        let started = Instant::now();
+        for _ in 0..20 {
+            hung.consume_record(&large).unwrap();
+        }
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(next_failure(&mut hung).unwrap().contains("sleep 10: killed after running for 500ms"));
+    }
+}
@@ -1,5 +1,7 @@
 extern crate loghaul;
 
 mod stdout_target;
+mod exec_target;
 
-pub use stdout_target::StdoutTarget;
\ No newline at end of file
+pub use stdout_target::StdoutTarget;
+pub use exec_target::ExecTarget;
\ No newline at end of file
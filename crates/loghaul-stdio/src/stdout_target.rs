@@ -1,14 +1,26 @@
 use loghaul::Target;
 use loghaul::LoghaulError;
 use loghaul::StreamEntry;
+use loghaul::Record;
+use loghaul::RecordFormat;
 use std::io::Write;
 use std::io;
 
-pub struct StdoutTarget {}
+pub struct StdoutTarget {
+    format: RecordFormat,
+}
 
 impl StdoutTarget {
     pub fn new() -> StdoutTarget {
-        return StdoutTarget {};
+        return StdoutTarget {
+            format: RecordFormat::Raw,
+        };
+    }
+
+    /// Write records in this format instead of their raw payload
+    pub fn with_format(mut self, format: RecordFormat) -> StdoutTarget {
+        self.format = format;
+        return self;
     }
 
     fn write(&mut self, data: &Vec<u8>) {
@@ -27,6 +39,13 @@ impl Target for StdoutTarget {
         };
         Ok(())
     }
+
+    fn consume_record(&mut self, record: &Record) -> Result<(), LoghaulError> {
+        return match self.format {
+            RecordFormat::Raw => self.consume(StreamEntry::Data, &record.payload),
+            format => self.consume(StreamEntry::Data, &format.encode(record)),
+        };
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,9 @@
+#[cfg(test)]
+extern crate wat;
+extern crate loghaul;
+extern crate wasmi;
+
+mod wasm_abi;
+mod wasm_processor;
+
+pub use wasm_processor::WasmProcessor;
@@ -0,0 +1,153 @@
+use loghaul::Record;
+use loghaul::RecordValue;
+
+/// The ABI version plugins must report
+pub const ABI_VERSION: i32 = 1;
+
+const TYPE_NULL: u8 = 0;
+const TYPE_BOOL: u8 = 1;
+const TYPE_INT: u8 = 2;
+const TYPE_FLOAT: u8 = 3;
+const TYPE_STRING: u8 = 4;
+
+/// Encode a record to pass it to a plugin; the layout is described on `WasmProcessor`
+pub fn encode_record(record: &Record) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(record.payload.len() + record.source.len() + 64);
+    put_bytes(&mut bytes, record.source.as_bytes());
+    put_bytes(&mut bytes, &record.payload);
+    put_bytes(&mut bytes, record.route.as_ref().map(|r| r.as_bytes()).unwrap_or(b""));
+    bytes.extend_from_slice(&(record.fields.len() as u32).to_le_bytes());
+    for (name, value) in record.fields.iter() {
+        put_bytes(&mut bytes, name.as_bytes());
+        match *value {
+            RecordValue::Null => bytes.push(TYPE_NULL),
+            RecordValue::Bool(v) => {
+                bytes.push(TYPE_BOOL);
+                bytes.push(v as u8);
+            }
+            RecordValue::Int(v) => {
+                bytes.push(TYPE_INT);
+                bytes.extend_from_slice(&v.to_le_bytes());
+            }
+            RecordValue::Float(v) => {
+                bytes.push(TYPE_FLOAT);
+                bytes.extend_from_slice(&v.to_le_bytes());
+            }
+            RecordValue::String(ref v) => {
+                bytes.push(TYPE_STRING);
+                put_bytes(&mut bytes, v.as_bytes());
+            }
+        }
+    }
+    return bytes;
+}
+
+/// Decode a record a plugin emitted
+pub fn decode_record(bytes: &[u8]) -> Result<Record, String> {
+    let mut reader = Reader { bytes, position: 0 };
+    let source = reader.string("source")?;
+    let payload = reader.bytes("payload")?.to_vec();
+    let route = reader.string("route")?;
+    let mut record = Record::new(&source, payload);
+    if !route.is_empty() {
+        record.route_to(&route);
+    }
+    let count = reader.u32("field count")?;
+    for _ in 0..count {
+        let name = reader.string("field name")?;
+        let value = match reader.take(1, &name)?[0] {
+            TYPE_NULL => RecordValue::Null,
+            TYPE_BOOL => RecordValue::Bool(reader.take(1, &name)?[0] != 0),
+            TYPE_INT => RecordValue::Int(i64::from_le_bytes(reader.array(&name)?)),
+            TYPE_FLOAT => RecordValue::Float(f64::from_le_bytes(reader.array(&name)?)),
+            TYPE_STRING => RecordValue::String(reader.string(&name)?),
+            other => return Err(format!("field '{}' has unknown type {}", name, other)),
+        };
+        record.fields.insert(name, value);
+    }
+    if reader.position != bytes.len() {
+        return Err(format!("{} bytes after the record", bytes.len() - reader.position));
+    }
+    return Ok(record);
+}
+
+fn put_bytes(bytes: &mut Vec<u8>, value: &[u8]) {
+    bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(value);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize, what: &str) -> Result<&'a [u8], String> {
+        if self.bytes.len() - self.position < length {
+            return Err(format!("record ends in {}", what));
+        }
+        let taken = &self.bytes[self.position..self.position + length];
+        self.position += length;
+        return Ok(taken);
+    }
+
+    fn array(&mut self, what: &str) -> Result<[u8; 8], String> {
+        let mut array = [0; 8];
+        array.copy_from_slice(self.take(8, what)?);
+        return Ok(array);
+    }
+
+    fn u32(&mut self, what: &str) -> Result<u32, String> {
+        let mut array = [0; 4];
+        array.copy_from_slice(self.take(4, what)?);
+        return Ok(u32::from_le_bytes(array));
+    }
+
+    fn bytes(&mut self, what: &str) -> Result<&'a [u8], String> {
+        let length = self.u32(what)? as usize;
+        return self.take(length, what);
+    }
+
+    fn string(&mut self, what: &str) -> Result<String, String> {
+        let bytes = self.bytes(what)?;
+        return String::from_utf8(bytes.to_vec()).map_err(|_| format!("{} is not valid utf8", what));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode_record;
+    use super::encode_record;
+    use loghaul::Record;
+    use loghaul::RecordValue;
+
+    #[test]
+    fn test_records_round_trip() {
+        let mut record = Record::new("api", b"GET /\n".to_vec());
+        record.route_to("archive");
+        record.set_field("status", RecordValue::Int(-200));
+        record.set_field("took", RecordValue::Float(0.25));
+        record.set_field("cached", RecordValue::Bool(true));
+        record.set_field("user", "bob");
+        record.set_field("trace", RecordValue::Null);
+        let bytes = encode_record(&record);
+        assert_eq!(&bytes[..11], b"\x03\x00\x00\x00api\x06\x00\x00\x00");
+        assert_eq!(decode_record(&bytes), Ok(record));
+
+        let plain = Record::new("", Vec::new());
+        assert_eq!(decode_record(&encode_record(&plain)), Ok(plain));
+    }
+
+    #[test]
+    fn test_rejects_malformed_records() {
+        let bytes = encode_record(&Record::new("api", b"x".to_vec()));
+        assert_eq!(decode_record(&bytes[..bytes.len() - 1]), Err("record ends in field count".to_string()));
+        let mut extra = bytes.clone();
+        extra.push(0);
+        assert_eq!(decode_record(&extra), Err("1 bytes after the record".to_string()));
+        let mut bad_type = bytes[..bytes.len() - 4].to_vec();
+        bad_type.extend_from_slice(b"\x01\x00\x00\x00\x01\x00\x00\x00k\x09");
+        assert_eq!(decode_record(&bad_type), Err("field 'k' has unknown type 9".to_string()));
+        assert_eq!(decode_record(b"\x02\x00\x00\x00\xff\xfe"), Err("source is not valid utf8".to_string()));
+    }
+}
@@ -0,0 +1,574 @@
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+use wasmi::core::Trap;
+use wasmi::Caller;
+use wasmi::Config;
+use wasmi::Engine;
+use wasmi::Extern;
+use wasmi::Linker;
+use wasmi::Memory;
+use wasmi::Module;
+use wasmi::Store;
+use wasmi::StoreLimits;
+use wasmi::StoreLimitsBuilder;
+use wasmi::TypedFunc;
+use loghaul::Processor;
+use loghaul::Record;
+use loghaul::LoghaulError;
+use loghaul::LoghaulErrorCode;
+use loghaul::MetricsRegistry;
+use wasm_abi::ABI_VERSION;
+use wasm_abi::decode_record;
+use wasm_abi::encode_record;
+
+const DEFAULT_FUEL: u64 = 10000000;
+const DEFAULT_MAX_MEMORY: usize = 64 * 1024 * 1024;
+const DEFAULT_TIME_LIMIT_MS: u64 = 1000;
+const DEFAULT_RELOAD_INTERVAL_MS: u64 = 1000;
+const DEFAULT_MAX_EMITTED: usize = 1000;
+const DEFAULT_MAX_EMITTED_BYTES: usize = 16 * 1024 * 1024;
+
+/// Runs every record through a WebAssembly plugin, which sends on any number of
+/// records in its place.
+///
+/// A plugin is a module exporting:
+///
+/// - `memory`, its linear memory
+/// - `loghaul_abi_version() -> i32`, returning `1`
+/// - `loghaul_alloc(len: i32) -> i32`, returning the address of `len` free bytes
+/// - `loghaul_process(ptr: i32, len: i32) -> i32`, handling the record encoded at
+///   `ptr` and returning `0`, or any other value to fail the record
+/// - optionally `loghaul_dealloc(ptr: i32, len: i32)`, called once a record is handled
+///
+/// and importing from the `loghaul` module:
+///
+/// - `emit(ptr: i32, len: i32)`, sending on the record encoded at `ptr`; emitting
+///   nothing drops the record
+/// - `fail(ptr: i32, len: i32)`, failing the record with the utf8 message at `ptr`
+///
+/// Records are encoded as follows, with integers little endian; an empty route sends
+/// the record wherever the routes do:
+///
+/// ```text
+/// record = string(source) bytes(payload) string(route) u32(field count) field*
+/// field  = string(name) u8(type) value
+/// value  = nothing (type 0, null) | u8 (type 1, bool) | i64 (type 2, int)
+///        | f64 (type 3, float) | string (type 4, string)
+/// string = bytes, utf8
+/// bytes  = u32(length) u8*
+/// ```
+///
+/// Plugins only reach the host through these imports. Each record may use a limited
+/// amount of fuel, roughly one unit per instruction, and emit a limited number of records
+/// and bytes, and the plugin's memory may only grow to a limited size. Records taking longer than the time limit fail too, though
+/// the limit is only checked when the plugin emits and when it returns, so fuel is what
+/// stops a plugin that loops. After a record fails inside the plugin, the plugin is
+/// started again for the next one.
+///
+/// A plugin loaded from a file is swapped for the new module when the file changes,
+/// without stopping the stream; if the new module fails to load, the old one keeps
+/// running. Swaps are counted as `reloaded`, failed swaps as `reload_failed`, and the
+/// fuel plugins use as `fuel`.
+///
+/// ```no_run
+///     extern crate loghaul;
+///     extern crate loghaul_wasm;
+///     use std::time::Duration;
+///     use loghaul::Stream;
+///     use loghaul_wasm::WasmProcessor;
+///     let plugin = WasmProcessor::from_file("plugins/parse_orders.wasm").unwrap()
+///         .with_fuel(1000000)
+///         .with_time_limit(Duration::from_millis(50));
+///     let stream = Stream::new().with_processor(plugin);
+/// ```
+pub struct WasmProcessor {
+    name: String,
+    engine: Engine,
+    module: Module,
+    plugin: Option<Plugin>,
+    fuel: u64,
+    max_memory: usize,
+    max_emitted: usize,
+    max_emitted_bytes: usize,
+    time_limit: Option<Duration>,
+    watch: Option<Watch>,
+    metrics: Option<(String, MetricsRegistry)>,
+}
+
+/// A running instance of the plugin
+struct Plugin {
+    store: Store<HostState>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    process: TypedFunc<(i32, i32), i32>,
+    dealloc: Option<TypedFunc<(i32, i32), ()>>,
+}
+
+/// What the host functions share with the processor
+struct HostState {
+    limits: StoreLimits,
+    emitted: Vec<Vec<u8>>,
+    emitted_bytes: usize,
+    max_emitted: usize,
+    max_emitted_bytes: usize,
+    deadline: Option<Instant>,
+}
+
+/// The plugin file, checked for changes every interval
+struct Watch {
+    path: PathBuf,
+    interval: Duration,
+    next_check: Option<Instant>,
+    version: Option<(SystemTime, u64)>,
+}
+
+impl WasmProcessor {
+    /// Load a plugin from a compiled module, naming it in errors
+    pub fn new(name: &str, wasm: &[u8]) -> Result<WasmProcessor, String> {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let mut processor = WasmProcessor {
+            name: name.to_string(),
+            module: Module::new(&engine, wasm).map_err(|e| format!("{}: {}", name, e))?,
+            engine,
+            plugin: None,
+            fuel: DEFAULT_FUEL,
+            max_memory: DEFAULT_MAX_MEMORY,
+            max_emitted: DEFAULT_MAX_EMITTED,
+            max_emitted_bytes: DEFAULT_MAX_EMITTED_BYTES,
+            time_limit: Some(Duration::from_millis(DEFAULT_TIME_LIMIT_MS)),
+            watch: None,
+            metrics: None,
+        };
+        // Start the plugin now, so a module that isn't one fails to load
+        processor.plugin = Some(processor.start(&processor.module).map_err(|e| format!("{}: {}", name, e))?);
+        return Ok(processor);
+    }
+
+    /// Load a plugin from a file, swapping it when the file changes
+    pub fn from_file(path: impl AsRef<Path>) -> Result<WasmProcessor, String> {
+        let path = path.as_ref();
+        let wasm = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut processor = WasmProcessor::new(&path.display().to_string(), &wasm)?;
+        processor.watch = Some(Watch {
+            path: path.to_path_buf(),
+            interval: Duration::from_millis(DEFAULT_RELOAD_INTERVAL_MS),
+            next_check: None,
+            version: file_version(path),
+        });
+        return Ok(processor);
+    }
+
+    /// The fuel each record may use, ten million by default
+    pub fn with_fuel(mut self, fuel: u64) -> WasmProcessor {
+        self.fuel = fuel.max(1);
+        return self;
+    }
+
+    /// The most bytes the plugin's memory may grow to, 64MiB by default
+    pub fn with_max_memory(mut self, bytes: usize) -> WasmProcessor {
+        self.max_memory = bytes;
+        // Started again with the new limit
+        self.plugin = None;
+        return self;
+    }
+
+    /// Fail records for which the plugin emits more than this many records or bytes,
+    /// 1000 records and 16MiB by default
+    pub fn with_max_emitted(mut self, records: usize, bytes: usize) -> WasmProcessor {
+        self.max_emitted = records;
+        self.max_emitted_bytes = bytes;
+        // Started again with the new limits
+        self.plugin = None;
+        return self;
+    }
+
+    /// Fail records the plugin takes longer than this to handle, one second by default
+    pub fn with_time_limit(mut self, limit: Duration) -> WasmProcessor {
+        self.time_limit = Some(limit);
+        return self;
+    }
+
+    /// Don't limit the time records take, only their fuel
+    pub fn without_time_limit(mut self) -> WasmProcessor {
+        self.time_limit = None;
+        return self;
+    }
+
+    /// How often to check the plugin file for changes, every second by default
+    pub fn with_reload_interval(mut self, interval: Duration) -> WasmProcessor {
+        match self.watch {
+            Some(ref mut watch) => watch.interval = interval,
+            None => {}
+        }
+        return self;
+    }
+
+    /// Never swap the plugin when its file changes
+    pub fn without_reload(mut self) -> WasmProcessor {
+        self.watch = None;
+        return self;
+    }
+
+    /// Replace the plugin with another module; if it fails to load, the current one is kept
+    pub fn swap(&mut self, wasm: &[u8]) -> Result<(), String> {
+        let module = Module::new(&self.engine, wasm).map_err(|e| format!("{}: {}", self.name, e))?;
+        let plugin = self.start(&module).map_err(|e| format!("{}: {}", self.name, e))?;
+        self.module = module;
+        self.plugin = Some(plugin);
+        return Ok(());
+    }
+
+    fn start(&self, module: &Module) -> Result<Plugin, String> {
+        let limits = StoreLimitsBuilder::new().memory_size(self.max_memory).trap_on_grow_failure(true).build();
+        let mut store = Store::new(&self.engine, HostState {
+            limits,
+            emitted: Vec::new(),
+            emitted_bytes: 0,
+            max_emitted: self.max_emitted,
+            max_emitted_bytes: self.max_emitted_bytes,
+            deadline: None,
+        });
+        store.limiter(|state| &mut state.limits);
+        refuel(&mut store, self.fuel)?;
+
+        let mut linker = Linker::<HostState>::new(&self.engine);
+        linker.func_wrap("loghaul", "emit", |mut caller: Caller<HostState>, ptr: i32, len: i32| -> Result<(), Trap> {
+            // Checked before copying, so a flood of records can't exhaust the host's memory
+            let state = caller.data();
+            if state.emitted.len() >= state.max_emitted {
+                return Err(Trap::new(format!("emitted more than {} records", state.max_emitted)));
+            }
+            if state.emitted_bytes.saturating_add(len as u32 as usize) > state.max_emitted_bytes {
+                return Err(Trap::new(format!("emitted more than {} bytes", state.max_emitted_bytes)));
+            }
+            let bytes = read_memory(&caller, ptr, len)?;
+            match caller.data().deadline {
+                Some(deadline) if Instant::now() > deadline => return Err(Trap::new("time limit exceeded")),
+                _ => {}
+            }
+            caller.data_mut().emitted_bytes += bytes.len();
+            caller.data_mut().emitted.push(bytes);
+            return Ok(());
+        }).map_err(|e| e.to_string())?;
+        linker.func_wrap("loghaul", "fail", |caller: Caller<HostState>, ptr: i32, len: i32| -> Result<(), Trap> {
+            let bytes = read_memory(&caller, ptr, len)?;
+            return Err(Trap::new(String::from_utf8_lossy(&bytes).to_string()));
+        }).map_err(|e| e.to_string())?;
+
+        let instance = linker.instantiate(&mut store, module).and_then(|pre| pre.start(&mut store)).map_err(|e| e.to_string())?;
+        let memory = instance.get_memory(&store, "memory").ok_or("the module does not export memory".to_string())?;
+        let version = instance.get_typed_func::<(), i32>(&store, "loghaul_abi_version").map_err(|e| format!("loghaul_abi_version: {}", e))?
+            .call(&mut store, ()).map_err(|e| format!("loghaul_abi_version: {}", e))?;
+        if version != ABI_VERSION {
+            return Err(format!("ABI version {} is not supported, expected {}", version, ABI_VERSION));
+        }
+        let dealloc = match instance.get_export(&store, "loghaul_dealloc") {
+            Some(_) => Some(instance.get_typed_func(&store, "loghaul_dealloc").map_err(|e| format!("loghaul_dealloc: {}", e))?),
+            None => None,
+        };
+        return Ok(Plugin {
+            alloc: instance.get_typed_func(&store, "loghaul_alloc").map_err(|e| format!("loghaul_alloc: {}", e))?,
+            process: instance.get_typed_func(&store, "loghaul_process").map_err(|e| format!("loghaul_process: {}", e))?,
+            dealloc,
+            memory,
+            store,
+        });
+    }
+
+    /// Pass a record to the plugin, returning the records it emitted
+    fn call(&mut self, record: &Record) -> Result<Vec<Vec<u8>>, String> {
+        if self.plugin.is_none() {
+            self.plugin = Some(self.start(&self.module)?);
+        }
+        let plugin = self.plugin.as_mut().expect("plugin started");
+        refuel(&mut plugin.store, self.fuel)?;
+        let deadline = self.time_limit.map(|limit| Instant::now() + limit);
+        plugin.store.data_mut().deadline = deadline;
+        plugin.store.data_mut().emitted.clear();
+        plugin.store.data_mut().emitted_bytes = 0;
+
+        let input = encode_record(record);
+        let len = input.len() as i32;
+        let ptr = plugin.alloc.call(&mut plugin.store, len).map_err(|e| format!("loghaul_alloc: {}", e))?;
+        plugin.memory.write(&mut plugin.store, ptr as u32 as usize, &input).map_err(|_| "loghaul_alloc returned an address outside memory".to_string())?;
+        let status = plugin.process.call(&mut plugin.store, (ptr, len)).map_err(|e| e.to_string())?;
+        match plugin.dealloc {
+            Some(ref dealloc) => dealloc.call(&mut plugin.store, (ptr, len)).map_err(|e| format!("loghaul_dealloc: {}", e))?,
+            None => {}
+        }
+        match deadline {
+            Some(deadline) if Instant::now() > deadline => return Err("time limit exceeded".to_string()),
+            _ => {}
+        }
+        if status != 0 {
+            return Err(format!("loghaul_process returned {}", status));
+        }
+        return Ok(::std::mem::replace(&mut plugin.store.data_mut().emitted, Vec::new()));
+    }
+
+    fn fuel_used(&self) -> u64 {
+        return match self.plugin {
+            Some(ref plugin) => plugin.store.fuel_consumed().unwrap_or(0),
+            None => 0,
+        };
+    }
+
+    fn count(&self, name: &str, count: u64) {
+        match self.metrics {
+            Some((ref id, ref metrics)) if count > 0 => metrics.processor_counter(id, name, count),
+            _ => {}
+        }
+    }
+
+    /// Swap the plugin if its file changed since it was loaded
+    fn reload(&mut self, now: Instant) -> Result<(), LoghaulError> {
+        let path = match self.watch {
+            Some(ref mut watch) if watch.next_check.map(|next| now >= next).unwrap_or(true) => {
+                watch.next_check = Some(now + watch.interval);
+                let version = file_version(&watch.path);
+                if version.is_none() || version == watch.version {
+                    return Ok(());
+                }
+                // A module that fails to load is only retried once the file changes again
+                watch.version = version;
+                watch.path.clone()
+            }
+            _ => return Ok(()),
+        };
+        let result = fs::read(&path).map_err(|e| e.to_string()).and_then(|wasm| self.swap(&wasm));
+        return match result {
+            Ok(_) => {
+                self.count("reloaded", 1);
+                Ok(())
+            }
+            Err(e) => {
+                self.count("reload_failed", 1);
+                Err(LoghaulError::from(LoghaulErrorCode::ProcessorErr(format!("{}: reloading failed, keeping the loaded plugin: {}", path.display(), e))))
+            }
+        };
+    }
+}
+
+impl Processor for WasmProcessor {
+    fn process(&mut self, record: Record, output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        let used = self.fuel_used();
+        let result = self.call(&record);
+        self.count("fuel", self.fuel_used().saturating_sub(used));
+        let emitted = match result {
+            Ok(emitted) => emitted,
+            Err(e) => {
+                // The plugin stopped part way, so its state can't be trusted
+                self.plugin = None;
+                return Err(LoghaulError::from(LoghaulErrorCode::ProcessorErr(format!("{}: {}", self.name, e))));
+            }
+        };
+        for bytes in emitted.into_iter() {
+            let emitted = decode_record(&bytes)
+                .map_err(|e| LoghaulError::from(LoghaulErrorCode::ProcessorErr(format!("{}: emitted an invalid record: {}", self.name, e))))?;
+            output.push(emitted);
+        }
+        return Ok(());
+    }
+
+    fn id(&self) -> Option<String> {
+        Some("wasm".to_string())
+    }
+
+    fn attach(&mut self, id: &str, metrics: &MetricsRegistry) {
+        self.metrics = Some((id.to_string(), metrics.clone()));
+    }
+
+    fn flush(&mut self, now: Instant, _output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        return self.reload(now);
+    }
+}
+
+/// Set the fuel left in a store to exactly `fuel`
+fn refuel(store: &mut Store<HostState>, fuel: u64) -> Result<(), String> {
+    let remaining = store.consume_fuel(0).map_err(|e| e.to_string())?;
+    return match remaining < fuel {
+        true => store.add_fuel(fuel - remaining),
+        false => store.consume_fuel(remaining - fuel).map(|_| ()),
+    }.map_err(|e| e.to_string());
+}
+
+fn read_memory(caller: &Caller<HostState>, ptr: i32, len: i32) -> Result<Vec<u8>, Trap> {
+    let memory = match caller.get_export("memory").and_then(Extern::into_memory) {
+        Some(memory) => memory,
+        None => return Err(Trap::new("the module does not export memory")),
+    };
+    // Both are addresses, so a negative value is a large one rather than an error
+    let start = ptr as u32 as usize;
+    let end = start.checked_add(len as u32 as usize);
+    return match end.and_then(|end| memory.data(caller).get(start..end)) {
+        Some(bytes) => Ok(bytes.to_vec()),
+        None => Err(Trap::new("address outside memory")),
+    };
+}
+
+/// When a file was modified and how long it is, to notice when it is replaced
+fn file_version(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    return Some((metadata.modified().ok()?, metadata.len()));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::time::Duration;
+    use std::time::Instant;
+    use std::time::SystemTime;
+    use wat;
+    use super::WasmProcessor;
+    use loghaul::Processor;
+    use loghaul::Record;
+    use loghaul::RecordValue;
+    use loghaul::MetricsRegistry;
+
+    /// A plugin reading records at address 1024, with `process` as the body of loghaul_process
+    fn plugin(process: &str) -> Vec<u8> {
+        return wat::parse_str(format!(r#"
+            (module
+                (import "loghaul" "emit" (func $emit (param i32 i32)))
+                (import "loghaul" "fail" (func $fail (param i32 i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 16) "bad input")
+                (func (export "loghaul_abi_version") (result i32) i32.const 1)
+                (func (export "loghaul_alloc") (param i32) (result i32) i32.const 1024)
+                (func (export "loghaul_process") (param $ptr i32) (param $len i32) (result i32)
+                    {}))
+        "#, process)).unwrap();
+    }
+
+    fn run(processor: &mut WasmProcessor, record: Record) -> Result<Vec<Record>, String> {
+        let mut output = Vec::new();
+        return processor.process(record, &mut output).map(|_| output).map_err(|e| e.to_string());
+    }
+
+    #[test]
+    fn test_plugin_emits_changed_records() {
+        // Upper case the first byte of the payload, which follows the source, and emit the record twice
+        let mut processor = WasmProcessor::new("upper", &plugin(r#"
+            (local $payload i32)
+            (local.set $payload (i32.add (i32.add (local.get $ptr) (i32.const 8)) (i32.load (local.get $ptr))))
+            (i32.store8 (local.get $payload) (i32.sub (i32.load8_u (local.get $payload)) (i32.const 32)))
+            (call $emit (local.get $ptr) (local.get $len))
+            (call $emit (local.get $ptr) (local.get $len))
+            i32.const 0
+        "#)).unwrap();
+        let metrics = MetricsRegistry::new();
+        processor.attach("wasm", &metrics);
+
+        let mut record = Record::new("api", b"hello".to_vec());
+        record.set_field("status", RecordValue::Int(200));
+        let output = run(&mut processor, record).unwrap();
+        assert_eq!(output.len(), 2);
+        assert_eq!(output[0].payload_str(), Some("Hello"));
+        assert_eq!(output[0].source, "api");
+        assert_eq!(output[0].field("status"), Some(&RecordValue::Int(200)));
+        assert!(metrics.snapshot().processor("wasm").unwrap().counters.get("fuel").cloned().unwrap_or(0) > 0);
+
+        let mut drop = WasmProcessor::new("drop", &plugin("i32.const 0")).unwrap();
+        assert_eq!(run(&mut drop, Record::new("api", b"x".to_vec())), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn test_plugin_failures_and_limits() {
+        let mut status = WasmProcessor::new("status", &plugin("i32.const 3")).unwrap();
+        let err = run(&mut status, Record::new("a", b"x".to_vec())).unwrap_err();
+        assert!(err.contains("status: loghaul_process returned 3"), "{}", err);
+
+        let mut fail = WasmProcessor::new("fail", &plugin("(call $fail (i32.const 16) (i32.const 9)) i32.const 0")).unwrap();
+        let err = run(&mut fail, Record::new("a", b"x".to_vec())).unwrap_err();
+        assert!(err.contains("fail: bad input"), "{}", err);
+
+        let mut garbage = WasmProcessor::new("garbage", &plugin("(call $emit (i32.const 16) (i32.const 9)) i32.const 0")).unwrap();
+        let err = run(&mut garbage, Record::new("a", b"x".to_vec())).unwrap_err();
+        assert!(err.contains("garbage: emitted an invalid record"), "{}", err);
+
+        let mut spin = WasmProcessor::new("spin", &plugin("(loop $spin (br $spin)) i32.const 0")).unwrap().with_fuel(10000);
+        let err = run(&mut spin, Record::new("a", b"x".to_vec())).unwrap_err();
+        assert!(err.contains("fuel"), "{}", err);
+
+        // Ranges past the end of memory are refused before anything is copied
+        let mut outside = WasmProcessor::new("outside", &plugin("(call $fail (i32.const 65000) (i32.const 1000)) i32.const 0")).unwrap();
+        let err = run(&mut outside, Record::new("a", b"x".to_vec())).unwrap_err();
+        assert!(err.contains("address outside memory"), "{}", err);
+        let mut huge = WasmProcessor::new("huge", &plugin("(call $fail (i32.const 16) (i32.const -1)) i32.const 0")).unwrap();
+        let err = run(&mut huge, Record::new("a", b"x".to_vec())).unwrap_err();
+        assert!(err.contains("address outside memory"), "{}", err);
+
+        let flood = plugin("(loop $again (call $emit (local.get $ptr) (local.get $len)) (br $again)) i32.const 0");
+        let mut records = WasmProcessor::new("flood", &flood).unwrap().with_max_emitted(10, 1024 * 1024);
+        let err = run(&mut records, Record::new("a", b"x".to_vec())).unwrap_err();
+        assert!(err.contains("flood: emitted more than 10 records"), "{}", err);
+        let mut bytes = WasmProcessor::new("flood", &flood).unwrap().with_max_emitted(1000, 100);
+        let err = run(&mut bytes, Record::new("a", b"x".to_vec())).unwrap_err();
+        assert!(err.contains("flood: emitted more than 100 bytes"), "{}", err);
+        let mut within = WasmProcessor::new("twice", &plugin("(call $emit (local.get $ptr) (local.get $len)) (call $emit (local.get $ptr) (local.get $len)) i32.const 0")).unwrap().with_max_emitted(2, 1024);
+        assert_eq!(run(&mut within, Record::new("a", b"x".to_vec())).unwrap().len(), 2);
+
+        let grow = plugin("(drop (memory.grow (i32.const 16))) i32.const 0");
+        let mut small = WasmProcessor::new("grow", &grow).unwrap().with_max_memory(4 * 65536);
+        assert!(run(&mut small, Record::new("a", b"x".to_vec())).is_err());
+        let mut large = WasmProcessor::new("grow", &grow).unwrap().with_max_memory(32 * 65536);
+        assert!(run(&mut large, Record::new("a", b"x".to_vec())).is_ok());
+
+        // A plugin is started again after failing
+        let mut once = WasmProcessor::new("once", &plugin(r#"
+            (if (i32.load (i32.const 0)) (then (return (i32.const 0))))
+            (i32.store (i32.const 0) (i32.const 1))
+            unreachable
+        "#)).unwrap();
+        assert!(run(&mut once, Record::new("a", b"x".to_vec())).is_err());
+        assert!(run(&mut once, Record::new("a", b"x".to_vec())).is_err());
+
+        let wrong_version = wat::parse_str(r#"(module (memory (export "memory") 1) (func (export "loghaul_abi_version") (result i32) i32.const 2))"#).unwrap();
+        assert_eq!(WasmProcessor::new("v2", &wrong_version).err(), Some("v2: ABI version 2 is not supported, expected 1".to_string()));
+        assert!(WasmProcessor::new("text", b"not wasm").is_err());
+    }
+
+    #[test]
+    fn test_plugin_file_is_swapped_when_changed() {
+        let dir = env::temp_dir().join(format!("loghaul_wasm_test_{}", ::std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("plugin.wasm");
+        fs::write(&path, plugin("(call $emit (local.get $ptr) (local.get $len)) i32.const 0")).unwrap();
+
+        let metrics = MetricsRegistry::new();
+        let mut processor = WasmProcessor::from_file(&path).unwrap().with_reload_interval(Duration::from_secs(5));
+        processor.attach("wasm", &metrics);
+        let start = Instant::now();
+        processor.flush(start, &mut Vec::new()).unwrap();
+        assert_eq!(run(&mut processor, Record::new("a", b"x".to_vec())).unwrap().len(), 1);
+
+        let touch = |contents: &[u8], offset: u64| {
+            fs::write(&path, contents).unwrap();
+            let file = fs::OpenOptions::new().write(true).open(&path).unwrap();
+            file.set_modified(SystemTime::now() + Duration::from_secs(offset)).unwrap();
+        };
+        touch(&plugin("i32.const 0"), 10);
+        // Not checked again until the interval passed
+        processor.flush(start + Duration::from_secs(1), &mut Vec::new()).unwrap();
+        assert_eq!(run(&mut processor, Record::new("a", b"x".to_vec())).unwrap().len(), 1);
+        processor.flush(start + Duration::from_secs(5), &mut Vec::new()).unwrap();
+        assert_eq!(run(&mut processor, Record::new("a", b"x".to_vec())).unwrap().len(), 0);
+
+        touch(b"broken", 20);
+        let err = processor.flush(start + Duration::from_secs(10), &mut Vec::new()).unwrap_err();
+        assert!(err.to_string().contains("reloading failed, keeping the loaded plugin"), "{}", err);
+        assert_eq!(run(&mut processor, Record::new("a", b"x".to_vec())).unwrap().len(), 0);
+
+        let counters = metrics.snapshot().processor("wasm").unwrap().counters.clone();
+        assert_eq!(counters.get("reloaded"), Some(&1));
+        assert_eq!(counters.get("reload_failed"), Some(&1));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
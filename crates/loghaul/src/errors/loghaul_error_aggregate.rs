@@ -25,6 +25,11 @@ impl LoghaulErrorAggregate {
         return self.errors.len();
     }
 
+    /// Return every error, in the order they were added
+    pub fn errors(&self) -> &Vec<LoghaulError> {
+        return &self.errors;
+    }
+
     /// Convert into a result statement
     pub fn to_result(self) -> Result<(), LoghaulErrorAggregate> {
         if self.len() > 0 {
@@ -0,0 +1,221 @@
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use std::io::Read;
+use std::io::Write;
+use std::io::ErrorKind;
+use MetricsRegistry;
+use LoghaulError;
+use LoghaulErrorCode;
+use exporter::prometheus_format::render_prometheus;
+
+/// Requests larger than this are rejected rather than buffered
+const MAX_REQUEST_BYTES: usize = 8192;
+
+/// How long the listener sleeps between accept attempts while idle
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(25);
+
+/// How long a client may take to send its request or read the response
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Connections beyond this many at once are closed without a response
+const MAX_CONNECTIONS: usize = 16;
+
+/// Serves `/metrics` and `/health` for a metrics registry on a background thread.
+/// Each connection is answered on its own short-lived thread, so a slow client
+/// doesn't hold up other scrapes or stopping the exporter.
+pub struct MetricsHttpExporter {
+    address: SocketAddr,
+    running: Arc<AtomicBool>,
+    join_handle: Option<JoinHandle<()>>,
+}
+
+impl MetricsHttpExporter {
+    /// Bind to the given address and start serving; use port 0 to pick any free port.
+    pub fn start(address: &str, metrics: MetricsRegistry) -> Result<MetricsHttpExporter, LoghaulError> {
+        let listener = TcpListener::bind(address).map_err(|e| exporter_error(&format!("{}: {}", address, e)))?;
+        listener.set_nonblocking(true).map_err(|e| exporter_error(&e.to_string()))?;
+        let local_address = listener.local_addr().map_err(|e| exporter_error(&e.to_string()))?;
+
+        let running = Arc::new(AtomicBool::new(true));
+        let running_remote = running.clone();
+        let handle = thread::spawn(move || {
+            let connections = Arc::new(AtomicUsize::new(0));
+            while running_remote.load(Ordering::SeqCst) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        if connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                            connections.fetch_sub(1, Ordering::SeqCst);
+                            continue;
+                        }
+                        let metrics = metrics.clone();
+                        let connections = connections.clone();
+                        thread::spawn(move || {
+                            MetricsHttpExporter::serve(stream, &metrics);
+                            connections.fetch_sub(1, Ordering::SeqCst);
+                        });
+                    }
+                    Err(ref e) if e.kind() == ErrorKind::WouldBlock => {
+                        thread::sleep(ACCEPT_INTERVAL);
+                    }
+                    Err(_) => {
+                        thread::sleep(ACCEPT_INTERVAL);
+                    }
+                }
+            }
+        });
+
+        return Ok(MetricsHttpExporter {
+            address: local_address,
+            running,
+            join_handle: Some(handle),
+        });
+    }
+
+    /// The address the exporter is actually listening on
+    pub fn address(&self) -> SocketAddr {
+        return self.address;
+    }
+
+    /// Stop listening and wait for the listener thread to exit
+    pub fn halt(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        match self.join_handle.take() {
+            Some(handle) => {
+                let _ = handle.join();
+            }
+            None => {}
+        }
+    }
+
+    fn serve(mut stream: TcpStream, metrics: &MetricsRegistry) {
+        let _ = stream.set_nonblocking(false);
+        let _ = stream.set_read_timeout(Some(CLIENT_TIMEOUT));
+        let _ = stream.set_write_timeout(Some(CLIENT_TIMEOUT));
+        let response = match MetricsHttpExporter::read_request_path(&mut stream) {
+            Some((ref method, _)) if method != "GET" => response(405, "Method Not Allowed", "text/plain", "method not allowed\n"),
+            Some((_, ref path)) if path == "/metrics" => {
+                response(200, "OK", "text/plain; version=0.0.4", &render_prometheus(&metrics.snapshot()))
+            }
+            Some((_, ref path)) if path == "/health" => {
+                let snapshot = metrics.snapshot();
+                let mut body = String::new();
+                for (id, source) in snapshot.sources.iter() {
+                    body.push_str(&format!("source {} {}\n", id, if source.healthy { "ok" } else { "failing" }));
+                }
+                for (id, target) in snapshot.targets.iter() {
+                    body.push_str(&format!("target {} {}\n", id, if target.healthy { "ok" } else { "failing" }));
+                }
+                if snapshot.healthy() {
+                    response(200, "OK", "text/plain", &format!("ok\n{}", body))
+                } else {
+                    response(503, "Service Unavailable", "text/plain", &format!("failing\n{}", body))
+                }
+            }
+            Some(_) => response(404, "Not Found", "text/plain", "not found\n"),
+            None => response(400, "Bad Request", "text/plain", "bad request\n"),
+        };
+        let _ = stream.write_all(response.as_bytes());
+        let _ = stream.flush();
+    }
+
+    /// Read the request head and return the method and path from the request line
+    fn read_request_path(stream: &mut TcpStream) -> Option<(String, String)> {
+        let mut request = Vec::new();
+        let mut chunk = [0u8; 1024];
+        while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+            if request.len() > MAX_REQUEST_BYTES {
+                return None;
+            }
+            match stream.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(size) => request.extend_from_slice(&chunk[..size]),
+                Err(_) => return None,
+            }
+        }
+        let head = String::from_utf8_lossy(&request);
+        let mut parts = head.lines().next()?.split_whitespace();
+        let method = parts.next()?.to_string();
+        let target = parts.next()?;
+        let path = target.split('?').next().unwrap_or(target).to_string();
+        return Some((method, path));
+    }
+}
+
+impl Drop for MetricsHttpExporter {
+    fn drop(&mut self) {
+        self.halt();
+    }
+}
+
+fn response(status: u16, reason: &str, content_type: &str, body: &str) -> String {
+    return format!("HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                   status, reason, content_type, body.len(), body);
+}
+
+fn exporter_error(message: &str) -> LoghaulError {
+    return LoghaulError::from(LoghaulErrorCode::ExporterErr(message.to_string()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MetricsHttpExporter;
+    use MetricsRegistry;
+    use StreamEntry;
+    use std::net::TcpStream;
+    use std::net::SocketAddr;
+    use std::io::Read;
+    use std::io::Write;
+    use std::time::Duration;
+    use std::time::Instant;
+
+    fn get(address: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        return response;
+    }
+
+    #[test]
+    fn test_exporter_serves_metrics_and_health() {
+        let registry = MetricsRegistry::new();
+        registry.source_polled("app", StreamEntry::Data, 5);
+        let mut exporter = MetricsHttpExporter::start("127.0.0.1:0", registry.clone()).unwrap();
+
+        let metrics = get(exporter.address(), "/metrics");
+        assert!(metrics.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(metrics.contains("loghaul_source_records_total{source=\"app\"} 1\n"));
+
+        let health = get(exporter.address(), "/health");
+        assert!(health.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(health.contains("source app ok\n"));
+
+        registry.target_error("archive");
+        let health = get(exporter.address(), "/health");
+        assert!(health.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+        assert!(health.contains("target archive failing\n"));
+
+        assert!(get(exporter.address(), "/nope").starts_with("HTTP/1.1 404 Not Found\r\n"));
+        exporter.halt();
+    }
+
+    #[test]
+    fn test_slow_client_does_not_block_others() {
+        let mut exporter = MetricsHttpExporter::start("127.0.0.1:0", MetricsRegistry::new()).unwrap();
+        let started = Instant::now();
+        // Connects and never sends a request
+        let _idle = TcpStream::connect(exporter.address()).unwrap();
+        ::std::thread::sleep(Duration::from_millis(100));
+        assert!(get(exporter.address(), "/health").starts_with("HTTP/1.1 200 OK\r\n"));
+        exporter.halt();
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,2 @@
+pub mod prometheus_format;
+pub mod metrics_http_exporter;
@@ -0,0 +1,109 @@
+use std::fmt::Write;
+use std::time::Duration;
+use MetricsSnapshot;
+
+/// Render a snapshot in the prometheus text exposition format (version 0.0.4)
+pub fn render_prometheus(snapshot: &MetricsSnapshot) -> String {
+    let mut output = String::new();
+
+    write_family(&mut output, "loghaul_source_records_total", "counter", "Records read from a source.",
+                 snapshot.sources.iter().map(|(id, m)| (label("source", id), m.records_in as f64)).collect());
+    write_family(&mut output, "loghaul_source_bytes_total", "counter", "Bytes read from a source.",
+                 snapshot.sources.iter().map(|(id, m)| (label("source", id), m.bytes_in as f64)).collect());
+    write_family(&mut output, "loghaul_source_errors_total", "counter", "Failed polls of a source.",
+                 snapshot.sources.iter().map(|(id, m)| (label("source", id), m.errors as f64)).collect());
+    write_family(&mut output, "loghaul_source_eofs_total", "counter", "Times a source reached EOF.",
+                 snapshot.sources.iter().map(|(id, m)| (label("source", id), m.eofs as f64)).collect());
+    write_family(&mut output, "loghaul_source_resumes_total", "counter", "Times a source was resumed after EOF.",
+                 snapshot.sources.iter().map(|(id, m)| (label("source", id), m.resumes as f64)).collect());
+    write_family(&mut output, "loghaul_source_up", "gauge", "1 if the last poll of a source succeeded.",
+                 snapshot.sources.iter().map(|(id, m)| (label("source", id), if m.healthy { 1.0 } else { 0.0 })).collect());
+
+    write_family(&mut output, "loghaul_target_records_total", "counter", "Records written to a target.",
+                 snapshot.targets.iter().map(|(id, m)| (label("target", id), m.records_out as f64)).collect());
+    write_family(&mut output, "loghaul_target_bytes_total", "counter", "Bytes written to a target.",
+                 snapshot.targets.iter().map(|(id, m)| (label("target", id), m.bytes_out as f64)).collect());
+    write_family(&mut output, "loghaul_target_errors_total", "counter", "Failed writes to a target.",
+                 snapshot.targets.iter().map(|(id, m)| (label("target", id), m.errors as f64)).collect());
+    write_family(&mut output, "loghaul_target_up", "gauge", "1 if the last write to a target succeeded.",
+                 snapshot.targets.iter().map(|(id, m)| (label("target", id), if m.healthy { 1.0 } else { 0.0 })).collect());
+
+    write_family(&mut output, "loghaul_processor_records_in_total", "counter", "Records passed to a processor.",
+                 snapshot.processors.iter().map(|(id, m)| (label("processor", id), m.records_in as f64)).collect());
+    write_family(&mut output, "loghaul_processor_records_out_total", "counter", "Records produced by a processor.",
+                 snapshot.processors.iter().map(|(id, m)| (label("processor", id), m.records_out as f64)).collect());
+    write_family(&mut output, "loghaul_processor_errors_total", "counter", "Records a processor failed on.",
+                 snapshot.processors.iter().map(|(id, m)| (label("processor", id), m.errors as f64)).collect());
+
+    write_family(&mut output, "loghaul_processor_counter_total", "counter", "Counters published by a processor.",
+                 snapshot.processors.iter()
+                     .flat_map(|(id, m)| m.counters.iter().map(move |(name, value)| (labels(&[("processor", id), ("counter", name)]), *value as f64)))
+                     .collect());
+    write_family(&mut output, "loghaul_unrouted_records_total", "counter", "Records that matched no route.",
+                 vec!((String::new(), snapshot.unrouted as f64)));
+    write_family(&mut output, "loghaul_cooler_sources", "gauge", "Sources waiting in the cooler to be resumed.",
+                 vec!((String::new(), snapshot.cooler_size as f64)));
+
+    let name = "loghaul_step_duration_seconds";
+    let _ = writeln!(output, "# HELP {} Time taken by a single stream step.", name);
+    let _ = writeln!(output, "# TYPE {} histogram", name);
+    for (bound, count) in snapshot.step_latency.cumulative() {
+        let _ = writeln!(output, "{}_bucket{{le=\"{}\"}} {}", name, seconds(bound), count);
+    }
+    let _ = writeln!(output, "{}_bucket{{le=\"+Inf\"}} {}", name, snapshot.step_latency.count());
+    let _ = writeln!(output, "{}_sum {}", name, seconds(snapshot.step_latency.sum()));
+    let _ = writeln!(output, "{}_count {}", name, snapshot.step_latency.count());
+
+    return output;
+}
+
+fn write_family(output: &mut String, name: &str, kind: &str, help: &str, values: Vec<(String, f64)>) {
+    let _ = writeln!(output, "# HELP {} {}", name, help);
+    let _ = writeln!(output, "# TYPE {} {}", name, kind);
+    for (labels, value) in values {
+        let _ = writeln!(output, "{}{} {}", name, labels, value);
+    }
+}
+
+fn label(key: &str, value: &str) -> String {
+    return labels(&[(key, value)]);
+}
+
+fn labels(pairs: &[(&str, &str)]) -> String {
+    let rendered: Vec<String> = pairs.iter().map(|&(key, value)| {
+        let escaped = value.replace("\\", "\\\\").replace("\"", "\\\"").replace("\n", "\\n");
+        format!("{}=\"{}\"", key, escaped)
+    }).collect();
+    return format!("{{{}}}", rendered.join(","));
+}
+
+fn seconds(value: Duration) -> f64 {
+    return value.as_secs() as f64 + value.subsec_nanos() as f64 / 1_000_000_000.0;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render_prometheus;
+    use MetricsRegistry;
+    use StreamEntry;
+    use std::time::Duration;
+
+    #[test]
+    fn test_render_prometheus() {
+        let registry = MetricsRegistry::new();
+        registry.source_polled("/var/log/\"app\".log", StreamEntry::Data, 12);
+        registry.target_consumed("archive", 12);
+        registry.observe_step(Duration::from_micros(75));
+        registry.processor_counter("grok", "match:access", 2);
+
+        let output = render_prometheus(&registry.snapshot());
+        assert!(output.contains("# TYPE loghaul_source_records_total counter\n"));
+        assert!(output.contains("loghaul_source_bytes_total{source=\"/var/log/\\\"app\\\".log\"} 12\n"));
+        assert!(output.contains("loghaul_target_records_total{target=\"archive\"} 1\n"));
+        assert!(output.contains("loghaul_step_duration_seconds_bucket{le=\"0.00005\"} 0\n"));
+        assert!(output.contains("loghaul_step_duration_seconds_bucket{le=\"0.0001\"} 1\n"));
+        assert!(output.contains("loghaul_step_duration_seconds_count 1\n"));
+        assert!(output.contains("loghaul_cooler_sources 0\n"));
+        assert!(output.contains("loghaul_processor_counter_total{processor=\"grok\",counter=\"match:access\"} 2\n"));
+    }
+}
@@ -0,0 +1,28 @@
+use std::time::Duration;
+use KeeperEofStrategy;
+use Source;
+use Target;
+use ProcessorChain;
+use Router;
+
+/// Instructions sent from a keeper to its worker thread
+pub enum InternalKeeperCommand {
+    Halt,
+    AddSource(Box<Source + Send + 'static>),
+    RemoveSource(String),
+    AddTarget(String, Box<Target + Send + 'static>),
+    RemoveTarget(String),
+    UpdateSchedule(Duration, KeeperEofStrategy),
+    SetProcessors(ProcessorChain),
+    SetRouter(Router),
+    Reconfigure {
+        processors: Option<ProcessorChain>,
+        transition_router: Option<Router>,
+        targets_removed: Vec<String>,
+        targets_added: Vec<(String, Box<Target + Send + 'static>)>,
+        sources_removed: Vec<String>,
+        sources_added: Vec<Box<Source + Send + 'static>>,
+        router: Option<Router>,
+        schedule: Option<(Duration, KeeperEofStrategy)>,
+    },
+}
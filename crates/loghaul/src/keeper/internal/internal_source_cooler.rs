@@ -39,6 +39,26 @@ impl InternalSourceCooler {
         }
     }
 
+    /// Return the number of sources waiting to be resumed
+    pub fn len(&self) -> usize {
+        return self.cooler.len();
+    }
+
+    /// Discard every waiting source with the given id, returning the number removed
+    pub fn remove(&mut self, id: &str) -> usize {
+        let before = self.cooler.len();
+        self.cooler.retain(|i| match i.source {
+            Some(ref source) => source.id().map(|v| v != id).unwrap_or(true),
+            None => true
+        });
+        return before - self.cooler.len();
+    }
+
+    /// Change how future EOF sources are handled; sources already waiting stay in the cooler.
+    pub fn set_eof_strategy(&mut self, eof: KeeperEofStrategy) {
+        self.eof = eof;
+    }
+
     /// Fetch a set of resumed sources
     pub fn resume(&mut self) -> Option<Vec<Box<Source + Send + 'static>>> {
         if self.reheat_cold_items() {
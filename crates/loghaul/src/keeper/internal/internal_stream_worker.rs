@@ -6,16 +6,18 @@ use std::sync::mpsc::TryRecvError;
 use Stream;
 use std::sync::mpsc::Receiver;
 use KeeperConfig;
-use LoghaulError;
-use LoghaulErrorCode;
+use LoghaulErrorAggregate;
 use keeper::internal::internal_source_cooler::InternalSourceCooler;
 use Source;
-use std::error::Error;
+use std::time::Instant;
+use metrics::metrics_registry::MetricsRegistry;
+use keeper::internal::internal_keeper_command::InternalKeeperCommand;
 
 pub struct InternalStreamWorker {
     config: KeeperConfig,
     cooler: InternalSourceCooler,
     logger: InternalKeeperLogSender,
+    metrics: MetricsRegistry,
     stream: Stream,
 }
 
@@ -25,29 +27,28 @@ impl InternalStreamWorker {
             cooler: InternalSourceCooler::new(config.eof_strategy),
             config,
             logger,
+            metrics: stream.metrics(),
             stream,
         };
     }
 
-    pub fn run(&mut self, halt_channel: Receiver<()>) {
+    pub fn run(&mut self, command_channel: Receiver<InternalKeeperCommand>) {
         self.logger.log(KeeperLogEntry::KeeperWorkerThreadStarted);
         let mut eof:Vec<Box<Source + Send + 'static>> = Vec::new();
 
         // Poll each source forever, pushing to each target for every input.
         loop {
             thread::sleep(self.config.interval);
+            let started = Instant::now();
             match self.stream.step(&mut eof) {
                 Ok(_) => {}
-                Err(err) => {
-                    self.logger.log(KeeperLogEntry::KeeperError(LoghaulError::from(LoghaulErrorCode::SourceErr(err.description().to_string()))));
-                }
+                Err(err) => self.log_errors(err),
             }
+            self.metrics.observe_step(started.elapsed());
 
             // If we got an EOF sources, deal with them.
             if eof.len() > 0 {
-                println!("Got an EOF source");
                 eof.into_iter().for_each(|i| {
-                    println!("Item PUSH!");
                     self.cooler.push(i);
                 });
                 eof = Vec::new();
@@ -56,19 +57,126 @@ impl InternalStreamWorker {
             // If we have any new resumed streams, load them
             match self.cooler.resume() {
                 Some(resumed) => {
-                    println!("Reloaded some source");
-                    resumed.into_iter().for_each(|i| self.stream.add_boxed_source(i));
+                    for source in resumed.into_iter() {
+                        match source.id() {
+                            Some(id) => self.metrics.source_resumed(&id),
+                            None => {}
+                        }
+                        self.stream.add_boxed_source(source);
+                    }
                 },
                 None => {}
             }
+            self.metrics.set_cooler_size(self.cooler.len());
+
+            // Apply any topology changes, and check if we received a halt signal
+            if !self.apply_commands(&command_channel) {
+                // Deliver whatever processors are holding back before the stream is dropped
+                match self.stream.finish() {
+                    Ok(_) => {}
+                    Err(err) => self.log_errors(err),
+                }
+                self.logger.log(KeeperLogEntry::KeeperWorkerThreadHalted);
+                break;
+            }
+        }
+    }
+
+    /// Apply every pending command; returns false if the worker should halt.
+    fn apply_commands(&mut self, command_channel: &Receiver<InternalKeeperCommand>) -> bool {
+        loop {
+            match command_channel.try_recv() {
+                Ok(InternalKeeperCommand::Halt) | Err(TryRecvError::Disconnected) => {
+                    return false;
+                }
+                Ok(command) => {
+                    self.apply(command);
+                }
+                Err(TryRecvError::Empty) => {
+                    return true;
+                }
+            }
+        }
+    }
+
+    fn log_errors(&mut self, err: LoghaulErrorAggregate) {
+        for error in err.errors().iter() {
+            self.logger.log(KeeperLogEntry::KeeperError(error.clone()));
+        }
+    }
 
-            // Check if we received a halt signal
-            match halt_channel.try_recv() {
-                Ok(_) | Err(TryRecvError::Disconnected) => {
-                    self.logger.log(KeeperLogEntry::KeeperWorkerThreadHalted);
-                    break;
+    fn apply(&mut self, command: InternalKeeperCommand) {
+        match command {
+            InternalKeeperCommand::Halt => {}
+            InternalKeeperCommand::AddSource(source) => {
+                self.stream.add_boxed_source(source);
+                let id = self.stream.source_ids().pop().unwrap_or(String::new());
+                self.logger.log(KeeperLogEntry::KeeperSourceAdded(id));
+            }
+            InternalKeeperCommand::RemoveSource(id) => {
+                match self.stream.remove_source(&id) {
+                    Ok(_) => {}
+                    Err(err) => self.log_errors(err),
+                }
+                self.cooler.remove(&id);
+                self.metrics.set_cooler_size(self.cooler.len());
+                self.logger.log(KeeperLogEntry::KeeperSourceRemoved(id));
+            }
+            InternalKeeperCommand::AddTarget(id, target) => {
+                self.stream.add_boxed_target(Some(id.clone()), target);
+                self.logger.log(KeeperLogEntry::KeeperTargetAdded(id));
+            }
+            InternalKeeperCommand::RemoveTarget(id) => {
+                self.stream.remove_target(&id);
+                self.logger.log(KeeperLogEntry::KeeperTargetRemoved(id));
+            }
+            InternalKeeperCommand::UpdateSchedule(interval, eof_strategy) => {
+                self.config.interval = interval;
+                self.config.eof_strategy = eof_strategy;
+                self.cooler.set_eof_strategy(eof_strategy);
+                self.logger.log(KeeperLogEntry::KeeperScheduleUpdated);
+            }
+            InternalKeeperCommand::SetProcessors(processors) => {
+                let previous = self.stream.set_processors(processors);
+                match self.stream.drain_processors(previous) {
+                    Ok(_) => {}
+                    Err(err) => self.log_errors(err),
+                }
+                self.logger.log(KeeperLogEntry::KeeperProcessorsUpdated);
+            }
+            InternalKeeperCommand::SetRouter(router) => {
+                self.stream.set_router(router);
+                self.logger.log(KeeperLogEntry::KeeperRoutesUpdated);
+            }
+            InternalKeeperCommand::Reconfigure { processors, transition_router, targets_removed, targets_added, sources_removed, sources_added, router, schedule } => {
+                match processors {
+                    Some(processors) => self.apply(InternalKeeperCommand::SetProcessors(processors)),
+                    None => {}
+                }
+                match transition_router {
+                    Some(router) => self.apply(InternalKeeperCommand::SetRouter(router)),
+                    None => {}
+                }
+                for id in targets_removed.into_iter() {
+                    self.apply(InternalKeeperCommand::RemoveTarget(id));
+                }
+                for (id, target) in targets_added.into_iter() {
+                    self.apply(InternalKeeperCommand::AddTarget(id, target));
+                }
+                for id in sources_removed.into_iter() {
+                    self.apply(InternalKeeperCommand::RemoveSource(id));
+                }
+                for source in sources_added.into_iter() {
+                    self.apply(InternalKeeperCommand::AddSource(source));
+                }
+                match router {
+                    Some(router) => self.apply(InternalKeeperCommand::SetRouter(router)),
+                    None => {}
+                }
+                match schedule {
+                    Some((interval, eof_strategy)) => self.apply(InternalKeeperCommand::UpdateSchedule(interval, eof_strategy)),
+                    None => {}
                 }
-                Err(TryRecvError::Empty) => {}
             }
         }
     }
@@ -1,4 +1,5 @@
 pub mod internal_stream_worker;
 pub mod internal_log_channel;
 pub mod internal_noop_log;
-pub mod internal_source_cooler;
\ No newline at end of file
+pub mod internal_source_cooler;
+pub mod internal_keeper_command;
@@ -12,22 +12,43 @@ use keeper::internal::internal_log_channel::InternalKeeperLogSender;
 use keeper::internal::internal_log_channel::InternalKeeperLogReceiver;
 use keeper::internal::internal_noop_log::InternalNoOpKeeperLog;
 use keeper::internal::internal_stream_worker::InternalStreamWorker;
+use keeper::internal::internal_keeper_command::InternalKeeperCommand;
+use LoghaulErrorCode;
+use Source;
+use Target;
+use KeeperEofStrategy;
+use ProcessorChain;
+use Router;
+use KeeperChanges;
+use std::time::Duration;
+use metrics::metrics_registry::MetricsRegistry;
+use metrics::metrics_snapshot::MetricsSnapshot;
+#[cfg(feature = "prometheus")]
+use exporter::metrics_http_exporter::MetricsHttpExporter;
+#[cfg(feature = "prometheus")]
+use std::net::SocketAddr;
 
 /// Keeper looks after a stream, and acts as a managed runtime to dispatch events through the stream.
 pub struct Keeper {
-    halt: Option<Sender<()>>,
+    commands: Option<Sender<InternalKeeperCommand>>,
     logger: Option<InternalKeeperLogSender>,
     log_keeper: Option<InternalKeeperLogReceiver>,
     join_handle: Option<JoinHandle<()>>,
+    metrics: MetricsRegistry,
+    #[cfg(feature = "prometheus")]
+    exporter: Option<MetricsHttpExporter>,
 }
 
 impl Keeper {
     pub fn new(stream: Stream, config: Option<KeeperConfig>) -> Keeper {
         let mut rtn = Keeper {
-            halt: None,
+            commands: None,
             logger: None,
             log_keeper: None,
             join_handle: None,
+            metrics: stream.metrics(),
+            #[cfg(feature = "prometheus")]
+            exporter: None,
         };
         rtn.start(stream, config.unwrap_or(Default::default()));
         return rtn;
@@ -45,12 +66,97 @@ impl Keeper {
         return Ok(());
     }
 
+    /// Return a copy of the current metrics for the stream this keeper is running.
+    /// Metrics remain readable after the keeper has been halted.
+    pub fn metrics(&self) -> MetricsSnapshot {
+        return self.metrics.snapshot();
+    }
+
+    /// Serve the keeper metrics in prometheus format on `/metrics`, and source and
+    /// target health on `/health`, until the keeper is halted.
+    /// Returns the bound address, which is useful when binding to port 0.
+    #[cfg(feature = "prometheus")]
+    pub fn expose_metrics(&mut self, address: &str) -> Result<SocketAddr, LoghaulError> {
+        match self.exporter.take() {
+            Some(mut previous) => previous.halt(),
+            None => {}
+        }
+        let exporter = MetricsHttpExporter::start(address, self.metrics.clone())?;
+        let bound = exporter.address();
+        self.exporter = Some(exporter);
+        return Ok(bound);
+    }
+
+    /// Add a source to the running stream
+    pub fn add_source(&mut self, source: Box<Source + Send + 'static>) -> Result<(), LoghaulError> {
+        return self.send(InternalKeeperCommand::AddSource(source));
+    }
+
+    /// Remove every source with the given id from the running stream.
+    /// Pending data in the source is drained to the targets before it is closed.
+    pub fn remove_source(&mut self, id: &str) -> Result<(), LoghaulError> {
+        return self.send(InternalKeeperCommand::RemoveSource(id.to_string()));
+    }
+
+    /// Add a target to the running stream
+    pub fn add_target(&mut self, id: &str, target: Box<Target + Send + 'static>) -> Result<(), LoghaulError> {
+        return self.send(InternalKeeperCommand::AddTarget(id.to_string(), target));
+    }
+
+    /// Remove every target with the given id from the running stream
+    pub fn remove_target(&mut self, id: &str) -> Result<(), LoghaulError> {
+        return self.send(InternalKeeperCommand::RemoveTarget(id.to_string()));
+    }
+
+    /// Change the step interval and eof strategy of the running stream
+    pub fn update_schedule(&mut self, interval: Duration, eof_strategy: KeeperEofStrategy) -> Result<(), LoghaulError> {
+        return self.send(InternalKeeperCommand::UpdateSchedule(interval, eof_strategy));
+    }
+
+    /// Replace the processor chain of the running stream
+    pub fn set_processors(&mut self, processors: ProcessorChain) -> Result<(), LoghaulError> {
+        return self.send(InternalKeeperCommand::SetProcessors(processors));
+    }
+
+    /// Replace the routing rules of the running stream
+    pub fn set_router(&mut self, router: Router) -> Result<(), LoghaulError> {
+        return self.send(InternalKeeperCommand::SetRouter(router));
+    }
+
+    /// Apply several changes to the running stream at once, between two steps
+    pub fn reconfigure(&mut self, changes: KeeperChanges) -> Result<(), LoghaulError> {
+        return self.send(InternalKeeperCommand::Reconfigure {
+            processors: changes.processors,
+            transition_router: changes.transition_router,
+            targets_removed: changes.targets_removed,
+            targets_added: changes.targets_added,
+            sources_removed: changes.sources_removed,
+            sources_added: changes.sources_added,
+            router: changes.router,
+            schedule: changes.schedule,
+        });
+    }
+
+    /// Whether the worker is still running the stream; false once halted, or if it
+    /// stopped on its own, eg. because a source or target panicked
+    pub fn is_running(&self) -> bool {
+        return match self.join_handle {
+            Some(ref handle) => !handle.is_finished(),
+            None => false,
+        };
+    }
+
+    /// Push an entry into the keeper log from outside the keeper, eg. to report a rejected reload
+    pub fn notify(&mut self, entry: KeeperLogEntry) {
+        self.log(entry);
+    }
+
     pub fn halt(&mut self) {
         // Stop the worker
-        if self.halt.is_some() {
+        if self.commands.is_some() {
             self.log(KeeperLogEntry::KeeperHaltStarted);
-            let halter = self.halt.take().unwrap();
-            match halter.send(()) {
+            let halter = self.commands.take().unwrap();
+            match halter.send(InternalKeeperCommand::Halt) {
                 Ok(_) => {}
                 Err(_) => {
                     self.log(KeeperLogEntry::KeeperSendWorkerHaltError);
@@ -72,6 +178,15 @@ impl Keeper {
             None => {}
         }
 
+        // Stop serving metrics
+        #[cfg(feature = "prometheus")]
+        {
+            match self.exporter.take() {
+                Some(mut exporter) => exporter.halt(),
+                None => {}
+            }
+        }
+
         // Flush the logger
         match self.log_keeper {
             Some(ref mut k) => {
@@ -95,7 +210,7 @@ impl Keeper {
         });
 
         self.join_handle = Some(handle);
-        self.halt = Some(tx);
+        self.commands = Some(tx);
     }
 
     fn setup_logger(&mut self, config: &mut KeeperConfig) -> InternalKeeperLogSender {
@@ -107,6 +222,15 @@ impl Keeper {
         return remote_logger;
     }
 
+    fn send(&mut self, command: InternalKeeperCommand) -> Result<(), LoghaulError> {
+        match self.commands.as_ref() {
+            Some(commands) => {
+                commands.send(command).map_err(|_| LoghaulError::from(LoghaulErrorCode::WorkerUnavailable))
+            }
+            None => Err(LoghaulError::from(LoghaulErrorCode::WorkerUnavailable))
+        }
+    }
+
     fn log(&mut self, entry: KeeperLogEntry) {
         match self.logger.as_mut() {
             Some(ref mut l) => { l.log(entry); }
@@ -231,5 +355,156 @@ mod tests {
         assert_eq!(results.lock().unwrap().iter().filter(|i| *i == "1").count(), 3);
         assert_eq!(results.lock().unwrap().iter().filter(|i| *i == "5").count(), 3);
         assert_eq!(results.lock().unwrap().len(), 15);
+
+        let metrics = keeper.metrics();
+        let source = metrics.source("source-0").unwrap();
+        assert_eq!(source.records_in, 15);
+        assert!(source.eofs >= 2);
+        assert!(source.resumes >= 2);
+        assert_eq!(metrics.target("target-0").unwrap().records_out, 15);
+        assert!(metrics.step_latency.count() > 0);
+    }
+
+    #[test]
+    fn test_keeper_topology_changes() {
+        use std::thread::sleep;
+        let results = Arc::new(Mutex::new(Vec::<String>::new()));
+        let results_bucket = results.clone();
+
+        let stream = Stream::new()
+            .with_named_source("old", MockSource::new(vec!("1", "2")))
+            .with_named_target("sink", MockTarget::new(move |value, buffer| -> Result<(), LoghaulError> {
+                match value {
+                    StreamEntry::Data => results_bucket.lock().unwrap().push(from_utf8(buffer).unwrap().to_string()),
+                    _ => {}
+                }
+                Ok(())
+            }));
+
+        let keeper_log = Arc::new(Mutex::new(Vec::new()));
+        let mut keeper = Keeper::new(stream, Some(KeeperConfig {
+            interval: Duration::from_millis(1),
+            eof_strategy: KeeperEofStrategy::ResumeSourceAfterCooldown(Duration::from_millis(1)),
+            logger: Some(Box::new(MockKeeperLog::new(keeper_log.clone()))),
+        }));
+        sleep(Duration::from_millis(50));
+
+        keeper.remove_source("old").unwrap();
+        keeper.add_source(Box::new(::NamedSource::new("new", Box::new(MockSource::closed(vec!("3")))))).unwrap();
+        keeper.update_schedule(Duration::from_millis(2), KeeperEofStrategy::DropSource).unwrap();
+        keeper.set_processors(::ProcessorChain::new()).unwrap();
+        keeper.set_router(::Router::new()).unwrap();
+        sleep(Duration::from_millis(50));
+        keeper.remove_target("sink").unwrap();
+        keeper.halt();
+
+        assert!(keeper.add_source(Box::new(MockSource::empty())).is_err());
+        assert_eq!(*results.lock().unwrap(), vec!("1", "2", "3"));
+        assert_eq!(keeper.metrics().cooler_size, 0);
+        let logs: Vec<KeeperLogEntry> = MockKeeperLog::convert_to_vec(keeper_log);
+        assert!(logs.contains(&KeeperLogEntry::KeeperSourceRemoved("old".to_string())));
+        assert!(logs.contains(&KeeperLogEntry::KeeperSourceAdded("new".to_string())));
+        assert!(logs.contains(&KeeperLogEntry::KeeperTargetRemoved("sink".to_string())));
+        assert!(logs.contains(&KeeperLogEntry::KeeperScheduleUpdated));
+        assert!(logs.contains(&KeeperLogEntry::KeeperProcessorsUpdated));
+        assert!(logs.contains(&KeeperLogEntry::KeeperRoutesUpdated));
+    }
+
+    #[test]
+    fn test_keeper_is_running_until_worker_stops() {
+        use std::thread::sleep;
+        let mut keeper = Keeper::new(Stream::new().with_source(MockSource::new(vec!("1"))), Some(KeeperConfig {
+            interval: Duration::from_millis(1),
+            eof_strategy: KeeperEofStrategy::DropSource,
+            logger: None,
+        }));
+        sleep(Duration::from_millis(20));
+        assert!(keeper.is_running());
+        keeper.halt();
+        assert!(!keeper.is_running());
+
+        let stream = Stream::new()
+            .with_source(MockSource::new(vec!("1")))
+            .with_target(MockTarget::new(|_, _| -> Result<(), LoghaulError> { panic!("target failed") }));
+        let mut keeper = Keeper::new(stream, Some(KeeperConfig {
+            interval: Duration::from_millis(1),
+            eof_strategy: KeeperEofStrategy::DropSource,
+            logger: None,
+        }));
+        sleep(Duration::from_millis(50));
+        assert!(!keeper.is_running());
+        keeper.halt();
+    }
+
+    #[test]
+    fn test_keeper_reconfigure_replaces_target_without_losing_records() {
+        use std::thread::sleep;
+        use keeper::KeeperChanges;
+        let results = Arc::new(Mutex::new(Vec::<String>::new()));
+        let sink = |results: Arc<Mutex<Vec<String>>>, name: &'static str| MockTarget::new(move |value, buffer| -> Result<(), LoghaulError> {
+            match value {
+                StreamEntry::Data => results.lock().unwrap().push(format!("{}:{}", name, from_utf8(buffer).unwrap())),
+                _ => {}
+            }
+            Ok(())
+        });
+        let values: Vec<String> = (0..200).map(|i| i.to_string()).collect();
+        let stream = Stream::new()
+            .with_named_source("numbers", MockSource::new(values.iter().map(|v| &*Box::leak(v.clone().into_boxed_str())).collect()))
+            .with_named_target("sink", sink(results.clone(), "old"));
+
+        let keeper_log = Arc::new(Mutex::new(Vec::new()));
+        let mut keeper = Keeper::new(stream, Some(KeeperConfig {
+            interval: Duration::from_millis(1),
+            eof_strategy: KeeperEofStrategy::DropSource,
+            logger: Some(Box::new(MockKeeperLog::new(keeper_log.clone()))),
+        }));
+        sleep(Duration::from_millis(20));
+
+        let mut changes = KeeperChanges::default();
+        changes.targets_removed = vec!("sink".to_string());
+        changes.targets_added = vec!(("sink".to_string(), Box::new(sink(results.clone(), "new"))));
+        changes.schedule = Some((Duration::from_millis(1), KeeperEofStrategy::DropSource));
+        keeper.reconfigure(changes).unwrap();
+        sleep(Duration::from_millis(400));
+        keeper.halt();
+
+        // Every record reached exactly one of the two targets, in order
+        let results = results.lock().unwrap();
+        assert_eq!(results.iter().map(|r| r.splitn(2, ':').nth(1).unwrap().to_string()).collect::<Vec<_>>(), values);
+        assert!(results.iter().any(|r| r.starts_with("new:")));
+        let logs: Vec<KeeperLogEntry> = MockKeeperLog::convert_to_vec(keeper_log);
+        assert!(logs.contains(&KeeperLogEntry::KeeperTargetRemoved("sink".to_string())));
+        assert!(logs.contains(&KeeperLogEntry::KeeperTargetAdded("sink".to_string())));
+        assert!(logs.contains(&KeeperLogEntry::KeeperScheduleUpdated));
+    }
+
+    #[cfg(feature = "prometheus")]
+    #[test]
+    fn test_keeper_expose_metrics() {
+        use std::net::TcpStream;
+        use std::io::Read;
+        use std::io::Write;
+        use std::thread::sleep;
+
+        let stream = Stream::new()
+            .with_named_source("numbers", MockSource::new(vec!("1", "2", "3")))
+            .with_target(MockTarget::new(|_, _| -> Result<(), LoghaulError> { Ok(()) }));
+        let mut keeper = Keeper::new(stream, Some(KeeperConfig {
+            interval: Duration::from_millis(1),
+            eof_strategy: KeeperEofStrategy::DropSource,
+            logger: None,
+        }));
+        let address = keeper.expose_metrics("127.0.0.1:0").unwrap();
+        sleep(Duration::from_millis(50));
+
+        let mut client = TcpStream::connect(address).unwrap();
+        client.write_all(b"GET /metrics HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        keeper.halt();
+
+        assert!(response.contains("loghaul_source_records_total{source=\"numbers\"} 3\n"));
+        assert!(TcpStream::connect(address).is_err());
     }
 }
\ No newline at end of file
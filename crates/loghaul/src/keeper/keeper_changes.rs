@@ -0,0 +1,24 @@
+use std::time::Duration;
+use KeeperEofStrategy;
+use Source;
+use Target;
+use ProcessorChain;
+use Router;
+
+/// Changes to a running stream that are applied together, between two steps,
+/// so no record ever passes through a half changed pipeline.
+///
+/// They are applied in the order the fields are listed: data drained from removed
+/// sources goes through the new processors, the transition router and the new targets.
+#[derive(Default)]
+pub struct KeeperChanges {
+    pub processors: Option<ProcessorChain>,
+    /// The routes used while removed sources are drained, eg. still naming their ids
+    pub transition_router: Option<Router>,
+    pub targets_removed: Vec<String>,
+    pub targets_added: Vec<(String, Box<Target + Send + 'static>)>,
+    pub sources_removed: Vec<String>,
+    pub sources_added: Vec<Box<Source + Send + 'static>>,
+    pub router: Option<Router>,
+    pub schedule: Option<(Duration, KeeperEofStrategy)>,
+}
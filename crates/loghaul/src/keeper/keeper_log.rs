@@ -13,6 +13,15 @@ pub enum KeeperLogEntry {
     KeeperWaitWorkerError,
     KeeperSendWorkerHaltError,
     KeeperError(LoghaulError),
+    KeeperSourceAdded(String),
+    KeeperSourceRemoved(String),
+    KeeperTargetAdded(String),
+    KeeperTargetRemoved(String),
+    KeeperScheduleUpdated,
+    KeeperProcessorsUpdated,
+    KeeperRoutesUpdated,
+    KeeperReloaded,
+    KeeperReloadRejected(String),
 }
 
 pub trait KeeperLog {
@@ -1,9 +1,11 @@
 pub mod keeper;
 pub mod keeper_config;
+pub mod keeper_changes;
 pub mod keeper_log;
 pub mod internal;
 
 pub use self::keeper::Keeper;
 pub use self::keeper_config::KeeperConfig;
+pub use self::keeper_changes::KeeperChanges;
 pub use self::keeper_log::KeeperLog;
 pub use self::keeper_log::KeeperLogEntry;
\ No newline at end of file
@@ -1,24 +1,75 @@
+extern crate regex;
+extern crate hostname;
+
 mod source;
 mod target;
+mod processor;
+mod records;
+mod processors;
 mod streams;
+mod routing;
 mod errors;
 mod keeper;
+mod metrics;
+#[cfg(feature = "prometheus")]
+mod exporter;
 
 pub mod mock;
 
 pub use source::Source;
 pub use target::Target;
+pub use processor::Processor;
+pub use processor::ProcessorErrorPolicy;
+
+pub use records::record::Record;
+pub use records::record_value::RecordValue;
+pub use records::record_format::RecordFormat;
 
 pub use streams::stream::Stream;
 pub use streams::stream_entry::StreamEntry;
 pub use streams::stream_buffer::StreamBuffer;
+pub use streams::stream_named_source::NamedSource;
+pub use streams::stream_processor_chain::ProcessorChain;
+
+pub use routing::route::Route;
+pub use routing::route::RouteSelector;
+pub use routing::router::Router;
+
+pub use processors::split_lines_processor::SplitLinesProcessor;
+pub use processors::fn_processor::FnProcessor;
+pub use processors::multiline_processor::MultilineProcessor;
+pub use processors::enrich_processor::EnrichProcessor;
+pub use processors::sample_processor::SampleProcessor;
+pub use processors::rate_limit_processor::RateLimitProcessor;
+pub use processors::dedup_processor::DedupProcessor;
+pub use processors::aggregate_metric::AggregateMetric;
+pub use processors::aggregate_metric::AggregateKind;
+pub use processors::aggregate_processor::AggregateProcessor;
+pub use processors::alert_rule::AlertRule;
+pub use processors::alert_processor::AlertProcessor;
+pub use processors::order_processor::OrderProcessor;
+pub use processors::order_processor::OrderLatePolicy;
+pub use processors::session_processor::SessionProcessor;
 
 pub use keeper::keeper::Keeper;
 pub use keeper::keeper_config::KeeperConfig;
 pub use keeper::keeper_config::KeeperEofStrategy;
+pub use keeper::keeper_changes::KeeperChanges;
 pub use keeper::keeper_log::KeeperLogEntry;
 pub use keeper::keeper_log::KeeperLog;
 
+pub use metrics::metrics_registry::MetricsRegistry;
+pub use metrics::metrics_snapshot::MetricsSnapshot;
+pub use metrics::metrics_snapshot::SourceMetrics;
+pub use metrics::metrics_snapshot::TargetMetrics;
+pub use metrics::metrics_snapshot::ProcessorMetrics;
+pub use metrics::metrics_histogram::MetricsHistogram;
+
+#[cfg(feature = "prometheus")]
+pub use exporter::metrics_http_exporter::MetricsHttpExporter;
+#[cfg(feature = "prometheus")]
+pub use exporter::prometheus_format::render_prometheus;
+
 pub use errors::loghaul_error::LoghaulError;
 pub use errors::loghaul_error::LoghaulErrorCode;
 pub use errors::loghaul_error_aggregate::LoghaulErrorAggregate;
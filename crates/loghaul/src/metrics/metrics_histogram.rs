@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+/// Upper bounds, in microseconds, of the default latency buckets.
+const DEFAULT_BOUNDS_US: [u64; 12] = [50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000, 25_000, 50_000, 100_000, 1_000_000];
+
+/// A fixed bucket latency histogram.
+/// Each bucket counts observations less than or equal to its bound; anything
+/// larger than the last bound is only counted in the totals.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MetricsHistogram {
+    bounds: Vec<Duration>,
+    buckets: Vec<u64>,
+    count: u64,
+    sum: Duration,
+}
+
+impl MetricsHistogram {
+    pub fn new() -> MetricsHistogram {
+        let bounds: Vec<Duration> = DEFAULT_BOUNDS_US.iter().map(|us| Duration::from_micros(*us)).collect();
+        return MetricsHistogram {
+            buckets: vec![0; bounds.len()],
+            bounds,
+            count: 0,
+            sum: Duration::from_millis(0),
+        };
+    }
+
+    /// Record a single observation
+    pub fn observe(&mut self, value: Duration) {
+        match self.bounds.iter().position(|bound| value <= *bound) {
+            Some(offset) => {
+                self.buckets[offset] += 1;
+            }
+            None => {}
+        }
+        self.count += 1;
+        self.sum += value;
+    }
+
+    /// The bucket bounds paired with the cumulative number of observations at or below each bound
+    pub fn cumulative(&self) -> Vec<(Duration, u64)> {
+        let mut total = 0;
+        return self.bounds.iter().zip(self.buckets.iter()).map(|(bound, count)| {
+            total += *count;
+            (*bound, total)
+        }).collect();
+    }
+
+    /// Total number of observations
+    pub fn count(&self) -> u64 {
+        return self.count;
+    }
+
+    /// Sum of every observation
+    pub fn sum(&self) -> Duration {
+        return self.sum;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MetricsHistogram;
+    use std::time::Duration;
+
+    #[test]
+    fn test_histogram_buckets_are_cumulative() {
+        let mut histogram = MetricsHistogram::new();
+        histogram.observe(Duration::from_micros(10));
+        histogram.observe(Duration::from_micros(400));
+        histogram.observe(Duration::from_secs(5));
+
+        let buckets = histogram.cumulative();
+        assert_eq!(buckets[0], (Duration::from_micros(50), 1));
+        assert_eq!(buckets[3], (Duration::from_micros(500), 2));
+        assert_eq!(buckets[buckets.len() - 1].1, 2);
+        assert_eq!(histogram.count(), 3);
+        assert_eq!(histogram.sum(), Duration::from_micros(5_000_410));
+    }
+}
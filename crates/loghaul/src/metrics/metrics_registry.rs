@@ -0,0 +1,209 @@
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use metrics::metrics_snapshot::MetricsSnapshot;
+use metrics::metrics_snapshot::SourceMetrics;
+use metrics::metrics_snapshot::TargetMetrics;
+use metrics::metrics_snapshot::ProcessorMetrics;
+use StreamEntry;
+
+/// MetricsRegistry is a shared handle to the counters of a stream.
+/// Cloning the registry is cheap and every clone updates the same counters, so a
+/// stream can be moved to a worker thread while its owner keeps reading snapshots.
+#[derive(Clone)]
+pub struct MetricsRegistry {
+    state: Arc<Mutex<MetricsSnapshot>>,
+}
+
+impl MetricsRegistry {
+    pub fn new() -> MetricsRegistry {
+        return MetricsRegistry {
+            state: Arc::new(Mutex::new(MetricsSnapshot::new())),
+        };
+    }
+
+    /// Return a copy of the current state of every metric
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        match self.state.lock() {
+            Ok(state) => state.clone(),
+            Err(_) => MetricsSnapshot::new()
+        }
+    }
+
+    /// Record the result of polling a source
+    pub fn source_polled(&self, id: &str, entry: StreamEntry, bytes: usize) {
+        self.with_source(id, |source| {
+            source.healthy = true;
+            match entry {
+                StreamEntry::Data => {
+                    source.records_in += 1;
+                    source.bytes_in += bytes as u64;
+                }
+                StreamEntry::EOF => {
+                    source.eofs += 1;
+                }
+                StreamEntry::NoData => {}
+            }
+        });
+    }
+
+    /// Record a failed poll on a source
+    pub fn source_error(&self, id: &str) {
+        self.with_source(id, |source| {
+            source.errors += 1;
+            source.healthy = false;
+        });
+    }
+
+    /// Record a source coming back out of the cooler
+    pub fn source_resumed(&self, id: &str) {
+        self.with_source(id, |source| {
+            source.resumes += 1;
+            source.healthy = true;
+        });
+    }
+
+    /// Record a record successfully written to a target
+    pub fn target_consumed(&self, id: &str, bytes: usize) {
+        self.with_target(id, |target| {
+            target.records_out += 1;
+            target.bytes_out += bytes as u64;
+            target.healthy = true;
+        });
+    }
+
+    /// Record a target failing to consume a record
+    pub fn target_error(&self, id: &str) {
+        self.with_target(id, |target| {
+            target.errors += 1;
+            target.healthy = false;
+        });
+    }
+
+    /// Forget a source that is no longer part of the stream
+    pub fn remove_source(&self, id: &str) {
+        self.with_state(|state| {
+            state.sources.remove(id);
+        });
+    }
+
+    /// Forget a target that is no longer part of the stream, so it no longer counts against health
+    pub fn remove_target(&self, id: &str) {
+        self.with_state(|state| {
+            state.targets.remove(id);
+        });
+    }
+
+    /// Record a processor handling one record, producing `produced` records
+    pub fn processor_processed(&self, id: &str, produced: usize) {
+        self.with_processor(id, |processor| {
+            processor.records_in += 1;
+            processor.records_out += produced as u64;
+        });
+    }
+
+    /// Record a processor failing on a record
+    pub fn processor_error(&self, id: &str) {
+        self.with_processor(id, |processor| {
+            processor.records_in += 1;
+            processor.errors += 1;
+        });
+    }
+
+    /// Record a record that no route sent to any target
+    pub fn record_unrouted(&self) {
+        self.with_state(|state| state.unrouted += 1);
+    }
+
+    /// Add to a counter published by a processor
+    pub fn processor_counter(&self, id: &str, counter: &str, amount: u64) {
+        self.with_processor(id, |processor| {
+            *processor.counters.entry(counter.to_string()).or_insert(0) += amount;
+        });
+    }
+
+    /// Record how long a single stream step took
+    pub fn observe_step(&self, elapsed: Duration) {
+        self.with_state(|state| state.step_latency.observe(elapsed));
+    }
+
+    /// Update the number of sources currently waiting in the cooler
+    pub fn set_cooler_size(&self, size: usize) {
+        self.with_state(|state| state.cooler_size = size);
+    }
+
+    fn with_source(&self, id: &str, update: impl FnOnce(&mut SourceMetrics)) {
+        self.with_state(|state| {
+            if !state.sources.contains_key(id) {
+                state.sources.insert(id.to_string(), SourceMetrics::default());
+            }
+            update(state.sources.get_mut(id).unwrap());
+        });
+    }
+
+    fn with_target(&self, id: &str, update: impl FnOnce(&mut TargetMetrics)) {
+        self.with_state(|state| {
+            if !state.targets.contains_key(id) {
+                state.targets.insert(id.to_string(), TargetMetrics::default());
+            }
+            update(state.targets.get_mut(id).unwrap());
+        });
+    }
+
+    fn with_processor(&self, id: &str, update: impl FnOnce(&mut ProcessorMetrics)) {
+        self.with_state(|state| {
+            if !state.processors.contains_key(id) {
+                state.processors.insert(id.to_string(), ProcessorMetrics::default());
+            }
+            update(state.processors.get_mut(id).unwrap());
+        });
+    }
+
+    fn with_state(&self, update: impl FnOnce(&mut MetricsSnapshot)) {
+        match self.state.lock() {
+            Ok(ref mut state) => {
+                update(state);
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MetricsRegistry;
+    use StreamEntry;
+
+    #[test]
+    fn test_registry_clones_share_counters() {
+        let registry = MetricsRegistry::new();
+        let remote = registry.clone();
+
+        remote.source_polled("a", StreamEntry::Data, 10);
+        remote.source_polled("a", StreamEntry::NoData, 0);
+        remote.source_polled("a", StreamEntry::EOF, 0);
+        remote.source_resumed("a");
+        remote.target_consumed("b", 10);
+        remote.target_error("b");
+
+        let snapshot = registry.snapshot();
+        let source = snapshot.source("a").unwrap();
+        assert_eq!(source.records_in, 1);
+        assert_eq!(source.bytes_in, 10);
+        assert_eq!(source.eofs, 1);
+        assert_eq!(source.resumes, 1);
+        let target = snapshot.target("b").unwrap();
+        assert_eq!(target.records_out, 1);
+        assert_eq!(target.errors, 1);
+        assert!(source.healthy);
+        assert!(!target.healthy);
+        assert!(!snapshot.healthy());
+
+        registry.remove_target("b");
+        registry.remove_source("a");
+        let snapshot = registry.snapshot();
+        assert!(snapshot.target("b").is_none());
+        assert!(snapshot.source("a").is_none());
+        assert!(snapshot.healthy());
+    }
+}
@@ -0,0 +1,79 @@
+use std::collections::BTreeMap;
+use metrics::metrics_histogram::MetricsHistogram;
+
+/// Counters for a single source
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct SourceMetrics {
+    pub records_in: u64,
+    pub bytes_in: u64,
+    pub errors: u64,
+    pub eofs: u64,
+    pub resumes: u64,
+    /// False if the most recent poll of this source failed
+    pub healthy: bool,
+}
+
+/// Counters for a single target
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct TargetMetrics {
+    pub records_out: u64,
+    pub bytes_out: u64,
+    pub errors: u64,
+    /// False if the most recent write to this target failed
+    pub healthy: bool,
+}
+
+/// Counters for a single processor
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ProcessorMetrics {
+    pub records_in: u64,
+    pub records_out: u64,
+    pub errors: u64,
+    /// Counters published by the processor itself, eg. matches per pattern
+    pub counters: BTreeMap<String, u64>,
+}
+
+/// A point in time copy of every metric tracked for a stream.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MetricsSnapshot {
+    pub sources: BTreeMap<String, SourceMetrics>,
+    pub targets: BTreeMap<String, TargetMetrics>,
+    pub processors: BTreeMap<String, ProcessorMetrics>,
+    pub step_latency: MetricsHistogram,
+    pub cooler_size: usize,
+    /// Records that matched no route and had no default route to fall back on
+    pub unrouted: u64,
+}
+
+impl MetricsSnapshot {
+    pub fn new() -> MetricsSnapshot {
+        return MetricsSnapshot {
+            sources: BTreeMap::new(),
+            targets: BTreeMap::new(),
+            processors: BTreeMap::new(),
+            step_latency: MetricsHistogram::new(),
+            cooler_size: 0,
+            unrouted: 0,
+        };
+    }
+
+    /// Fetch the counters for a source, if it has ever been seen
+    pub fn source(&self, id: &str) -> Option<&SourceMetrics> {
+        return self.sources.get(id);
+    }
+
+    /// Fetch the counters for a target, if it has ever been seen
+    pub fn target(&self, id: &str) -> Option<&TargetMetrics> {
+        return self.targets.get(id);
+    }
+
+    /// Fetch the counters for a processor, if it has ever been seen
+    pub fn processor(&self, id: &str) -> Option<&ProcessorMetrics> {
+        return self.processors.get(id);
+    }
+
+    /// True if every source and target completed its last operation without error
+    pub fn healthy(&self) -> bool {
+        return self.sources.values().all(|i| i.healthy) && self.targets.values().all(|i| i.healthy);
+    }
+}
@@ -0,0 +1,3 @@
+pub mod metrics_registry;
+pub mod metrics_snapshot;
+pub mod metrics_histogram;
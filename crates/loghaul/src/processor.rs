@@ -0,0 +1,48 @@
+use std::time::Instant;
+use records::record::Record;
+use metrics::metrics_registry::MetricsRegistry;
+use LoghaulError;
+
+pub trait Processor {
+    /// Process a single record, pushing zero or more records into the output.
+    /// A processor can modify the record and push it, drop it by pushing
+    /// nothing, or split it by pushing several records.
+    fn process(&mut self, record: Record, output: &mut Vec<Record>) -> Result<(), LoghaulError>;
+
+    /// A stable name for this processor, used to label metrics.
+    /// Processors that return None are given a positional id by the stream.
+    fn id(&self) -> Option<String> {
+        None
+    }
+
+    /// Called when the processor becomes part of a stream, with the id it was
+    /// given there and the stream's metrics, so it can publish its own counters
+    /// with `MetricsRegistry::processor_counter`. The counters a processor documents
+    /// are only kept once it is attached; used on its own, it counts nothing.
+    fn attach(&mut self, _id: &str, _metrics: &MetricsRegistry) {}
+
+    /// Called on every step of the stream, so a processor holding records back
+    /// can release those that are due. Records pushed here continue down the chain
+    /// from the next processor.
+    fn flush(&mut self, _now: Instant, _output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        Ok(())
+    }
+
+    /// Called when a source reaches EOF or is removed from the stream, so a
+    /// processor can release anything it still holds for that source.
+    fn end_source(&mut self, _source: &str, _output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        Ok(())
+    }
+}
+
+/// What a stream does with a record when a processor fails on it
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ProcessorErrorPolicy {
+    /// Discard the record
+    Drop,
+    /// Pass the record on to the next stage unchanged, as if the processor wasn't there
+    PassThrough,
+    /// Skip the rest of the chain and send the record, as it was before the failing
+    /// processor, only to the target with this id
+    DeadLetter(String),
+}
@@ -0,0 +1,96 @@
+use records::record::Record;
+use routing::route::RouteSelector;
+
+/// What an aggregated metric measures
+#[derive(Debug, Clone, PartialEq)]
+pub enum AggregateKind {
+    /// The number of matching records
+    Count,
+    /// The distribution of a numeric field of matching records
+    Histogram(String),
+}
+
+/// A metric derived from records by an `AggregateProcessor`: a count of records, or a
+/// histogram of a numeric field, over the records meeting every selector, kept
+/// separately for each combination of values of the group by fields.
+///
+/// ```
+///     use loghaul::{AggregateMetric, RouteSelector};
+///     let errors = AggregateMetric::count("http_5xx")
+///         .with_selector(RouteSelector::field_matches("status", "^5\\d\\d$").unwrap())
+///         .with_group_by(&["path"]);
+///     let latency = AggregateMetric::histogram("latency", "dur").with_group_by(&["service"]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct AggregateMetric {
+    name: String,
+    kind: AggregateKind,
+    group_by: Vec<String>,
+    selectors: Vec<RouteSelector>,
+}
+
+impl AggregateMetric {
+    /// Count matching records
+    pub fn count(name: &str) -> AggregateMetric {
+        return AggregateMetric::new(name, AggregateKind::Count);
+    }
+
+    /// Summarize the values of a numeric field of matching records; records without
+    /// the field, or where it isn't a number, are left out
+    pub fn histogram(name: &str, field: &str) -> AggregateMetric {
+        return AggregateMetric::new(name, AggregateKind::Histogram(field.to_string()));
+    }
+
+    fn new(name: &str, kind: AggregateKind) -> AggregateMetric {
+        return AggregateMetric {
+            name: name.to_string(),
+            kind: kind,
+            group_by: Vec::new(),
+            selectors: Vec::new(),
+        };
+    }
+
+    /// Keep the metric separately for each combination of values of these fields
+    pub fn with_group_by(mut self, fields: &[&str]) -> AggregateMetric {
+        self.group_by = fields.iter().map(|f| f.to_string()).collect();
+        return self;
+    }
+
+    /// Only measure records meeting this selector, as well as any others
+    pub fn with_selector(mut self, selector: RouteSelector) -> AggregateMetric {
+        self.selectors.push(selector);
+        return self;
+    }
+
+    pub fn name(&self) -> &str {
+        return &self.name;
+    }
+
+    pub fn kind(&self) -> &AggregateKind {
+        return &self.kind;
+    }
+
+    pub fn group_by(&self) -> &Vec<String> {
+        return &self.group_by;
+    }
+
+    pub fn matches(&self, record: &Record) -> bool {
+        return self.selectors.iter().all(|s| s.matches(record));
+    }
+
+    /// The values of the group by fields of a record; missing fields group as empty strings
+    pub fn group_of(&self, record: &Record) -> Vec<String> {
+        return self.group_by.iter().map(|field| record.field(field).map(|v| v.to_string()).unwrap_or(String::new())).collect();
+    }
+
+    /// The value a histogram observes for a record, if it has one
+    pub fn value_of(&self, record: &Record) -> Option<f64> {
+        return match self.kind {
+            AggregateKind::Count => None,
+            AggregateKind::Histogram(ref field) => match record.field(field) {
+                Some(value) => value.as_f64().or_else(|| value.as_str().and_then(|s| s.trim().parse().ok())),
+                None => None,
+            },
+        };
+    }
+}
@@ -0,0 +1,480 @@
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::time::Duration;
+use std::time::Instant;
+use Processor;
+use LoghaulError;
+use records::record::Record;
+use records::record_value::RecordValue;
+use metrics::metrics_registry::MetricsRegistry;
+use processors::aggregate_metric::AggregateKind;
+use processors::aggregate_metric::AggregateMetric;
+use processors::internal::internal_event_time;
+use processors::internal::internal_sketch::InternalSketch;
+
+const DEFAULT_MAX_GROUPS: usize = 10000;
+
+/// Derives counters and histograms from records, over tumbling windows of event
+/// time, and emits them as summary records. Records pass through unchanged.
+///
+/// The event time is read from the `timestamp` field by default, as RFC 3339 like
+/// the `TimestampProcessor` writes it, or as milliseconds since the epoch. Records
+/// without one, or with one too near the limits of an i64 for its window to fit, are
+/// placed by the time they are processed. Windows are aligned to
+/// the epoch, so one minute windows start on the minute.
+///
+/// A window closes once records at least `lateness` past its end have been seen,
+/// or once it has been open for its length plus `lateness` of wall clock time, so
+/// a quiet stream still produces summaries. All windows close when the last source
+/// ends. Records that belong to a window which has already closed are late, and
+/// are not measured.
+///
+/// When a window closes, one summary record is emitted per metric and group with
+/// the fields `metric`, `window_start`, `window_end`, the group by fields and
+/// `count`; histograms add `sum`, `min`, `max`, `p50`, `p95` and `p99`. Quantiles
+/// are estimated within 1%. The payload holds the same fields as a logfmt line.
+/// Summaries come from the source `aggregate`, and can be routed to any target.
+///
+/// At most `max_groups` groups are kept across the open windows; records that
+/// would start another group are not measured and counted as `groups_dropped`. Late
+/// records are counted as `late`, and the summaries emitted as `summaries`.
+///
+/// ```
+///     use std::time::Duration;
+///     use loghaul::{Stream, AggregateProcessor, AggregateMetric, RouteSelector};
+///     let metrics = AggregateProcessor::new(Duration::from_secs(60))
+///         .with_lateness(Duration::from_secs(10))
+///         .with_metric(AggregateMetric::count("http_5xx")
+///             .with_selector(RouteSelector::field_matches("status", "^5").unwrap())
+///             .with_group_by(&["path"]))
+///         .with_metric(AggregateMetric::histogram("latency", "dur").with_group_by(&["service"]))
+///         .with_target("metrics");
+///     let stream = Stream::new().with_processor(metrics);
+/// ```
+pub struct AggregateProcessor {
+    window: i64,
+    lateness: i64,
+    metrics_defined: Vec<AggregateMetric>,
+    time_field: Option<String>,
+    source: String,
+    target: Option<String>,
+    max_groups: usize,
+    windows: BTreeMap<i64, AggregateWindow>,
+    groups: usize,
+    max_event_time: Option<i64>,
+    closed_until: Option<i64>,
+    sources: BTreeSet<String>,
+    clock: (Instant, i64),
+    metrics: Option<(String, MetricsRegistry)>,
+}
+
+struct AggregateWindow {
+    opened: Instant,
+    groups: BTreeMap<(usize, Vec<String>), AggregateGroup>,
+}
+
+struct AggregateGroup {
+    count: u64,
+    sketch: Option<InternalSketch>,
+}
+
+impl AggregateProcessor {
+    /// Aggregate over windows of this length, rounded to milliseconds
+    pub fn new(window: Duration) -> AggregateProcessor {
+        return AggregateProcessor {
+            window: internal_event_time::millis_of(window).max(1),
+            lateness: 0,
+            metrics_defined: Vec::new(),
+            time_field: Some("timestamp".to_string()),
+            source: "aggregate".to_string(),
+            target: None,
+            max_groups: DEFAULT_MAX_GROUPS,
+            windows: BTreeMap::new(),
+            groups: 0,
+            max_event_time: None,
+            closed_until: None,
+            sources: BTreeSet::new(),
+            clock: (Instant::now(), internal_event_time::now_millis()),
+            metrics: None,
+        };
+    }
+
+    pub fn with_metric(mut self, metric: AggregateMetric) -> AggregateProcessor {
+        self.metrics_defined.push(metric);
+        return self;
+    }
+
+    /// How long after a window ends records for it are still accepted, none by default
+    pub fn with_lateness(mut self, lateness: Duration) -> AggregateProcessor {
+        self.lateness = internal_event_time::millis_of(lateness);
+        return self;
+    }
+
+    /// Read the event time from this field instead of `timestamp`
+    pub fn with_time_field(mut self, field: &str) -> AggregateProcessor {
+        self.time_field = Some(field.to_string());
+        return self;
+    }
+
+    /// Place every record by the time it is processed, ignoring event times
+    pub fn with_processing_time(mut self) -> AggregateProcessor {
+        self.time_field = None;
+        return self;
+    }
+
+    /// Emit summaries from this source instead of `aggregate`
+    pub fn with_source(mut self, source: &str) -> AggregateProcessor {
+        self.source = source.to_string();
+        return self;
+    }
+
+    /// Send summaries only to the target with this id, instead of routing them
+    pub fn with_target(mut self, target: &str) -> AggregateProcessor {
+        self.target = Some(target.to_string());
+        return self;
+    }
+
+    /// Keep at most this many groups across the open windows, 10000 by default
+    pub fn with_max_groups(mut self, max_groups: usize) -> AggregateProcessor {
+        self.max_groups = max_groups.max(1);
+        return self;
+    }
+
+    fn event_time(&self, record: &Record, now: Instant) -> i64 {
+        let found = match self.time_field {
+            Some(ref field) => internal_event_time::field_time(record, field),
+            None => None,
+        };
+        // Times too close to the limits of i64 to have a window are treated as missing
+        let found = found.filter(|&time| self.window_of(time).is_some());
+        return match found {
+            Some(millis) => millis,
+            None => internal_event_time::clock_time(self.clock, now),
+        };
+    }
+
+    /// The start and end of the window holding a time, if both fit in an i64
+    fn window_of(&self, time: i64) -> Option<(i64, i64)> {
+        let start = time.checked_sub(time.rem_euclid(self.window))?;
+        return Some((start, start.checked_add(self.window)?));
+    }
+
+    fn watermark(&self) -> Option<i64> {
+        return self.max_event_time.map(|t| t.saturating_sub(self.lateness));
+    }
+
+    fn process_at(&mut self, record: Record, now: Instant, output: &mut Vec<Record>) {
+        self.sources.insert(record.source.clone());
+        let time = self.event_time(&record, now);
+        let (start, end) = match self.window_of(time) {
+            Some(bounds) => bounds,
+            None => {
+                output.push(record);
+                return;
+            }
+        };
+        let closed = self.closed_until.map(|t| end <= t).unwrap_or(false) || self.watermark().map(|t| end <= t).unwrap_or(false);
+        if closed && !self.windows.contains_key(&start) {
+            self.count("late", 1);
+            output.push(record);
+            return;
+        }
+
+        let mut dropped = 0;
+        {
+            let window = self.windows.entry(start).or_insert_with(|| AggregateWindow { opened: now, groups: BTreeMap::new() });
+            for (index, metric) in self.metrics_defined.iter().enumerate() {
+                if !metric.matches(&record) {
+                    continue;
+                }
+                let value = metric.value_of(&record);
+                if *metric.kind() != AggregateKind::Count && value.is_none() {
+                    continue;
+                }
+                let key = (index, metric.group_of(&record));
+                if !window.groups.contains_key(&key) {
+                    if self.groups >= self.max_groups {
+                        dropped += 1;
+                        continue;
+                    }
+                    self.groups += 1;
+                }
+                let group = window.groups.entry(key).or_insert_with(|| AggregateGroup {
+                    count: 0,
+                    sketch: value.map(|_| InternalSketch::new()),
+                });
+                group.count += 1;
+                match (group.sketch.as_mut(), value) {
+                    (Some(sketch), Some(value)) => sketch.add(value),
+                    _ => {}
+                }
+            }
+        }
+        if dropped > 0 {
+            self.count("groups_dropped", dropped);
+        }
+        output.push(record);
+
+        if self.max_event_time.map(|t| time > t).unwrap_or(true) {
+            self.max_event_time = Some(time);
+            let watermark = time.saturating_sub(self.lateness);
+            let due: Vec<i64> = self.windows.keys().cloned().take_while(|start| start.saturating_add(self.window) <= watermark).collect();
+            for start in due {
+                self.close(start, output);
+            }
+        }
+    }
+
+    fn flush_at(&mut self, now: Instant, output: &mut Vec<Record>) {
+        let open_for = Duration::from_millis(self.window as u64) + Duration::from_millis(self.lateness as u64);
+        let due: Vec<i64> = self.windows.iter()
+            .filter(|&(_, w)| w.opened.checked_add(open_for).map(|due| now >= due).unwrap_or(false))
+            .map(|(start, _)| *start)
+            .collect();
+        for start in due {
+            self.close(start, output);
+        }
+    }
+
+    /// Emit the summaries of a window and forget it
+    fn close(&mut self, start: i64, output: &mut Vec<Record>) {
+        let window = match self.windows.remove(&start) {
+            Some(window) => window,
+            None => return,
+        };
+        let end = start.saturating_add(self.window);
+        self.closed_until = Some(self.closed_until.map(|t| t.max(end)).unwrap_or(end));
+        self.groups -= window.groups.len();
+        let summaries = window.groups.len();
+        for ((index, values), group) in window.groups {
+            let metric = &self.metrics_defined[index];
+            let mut fields = vec!(
+                ("metric".to_string(), RecordValue::from(metric.name())),
+                ("window_start".to_string(), RecordValue::from(internal_event_time::format_rfc3339(start))),
+                ("window_end".to_string(), RecordValue::from(internal_event_time::format_rfc3339(end))),
+            );
+            for (field, value) in metric.group_by().iter().zip(values) {
+                fields.push((field.clone(), RecordValue::from(value)));
+            }
+            fields.push(("count".to_string(), RecordValue::Int(group.count as i64)));
+            match group.sketch {
+                Some(ref sketch) => {
+                    fields.push(("sum".to_string(), RecordValue::Float(sketch.sum())));
+                    fields.push(("min".to_string(), RecordValue::Float(sketch.min())));
+                    fields.push(("max".to_string(), RecordValue::Float(sketch.max())));
+                    for &(name, q) in [("p50", 0.5), ("p95", 0.95), ("p99", 0.99)].iter() {
+                        let estimate = sketch.quantile(q).unwrap_or(0.0);
+                        fields.push((name.to_string(), RecordValue::Float((estimate * 1000.0).round() / 1000.0)));
+                    }
+                }
+                None => {}
+            }
+
+            let line: Vec<String> = fields.iter().map(|&(ref name, ref value)| format!("{}={}", name, logfmt_value(&value.to_string()))).collect();
+            let mut summary = Record::new(&self.source, format!("{}\n", line.join(" ")).into_bytes());
+            for (name, value) in fields {
+                summary.set_field(&name, value);
+            }
+            match self.target {
+                Some(ref target) => summary.route_to(target),
+                None => {}
+            }
+            output.push(summary);
+        }
+        self.count("summaries", summaries as u64);
+    }
+
+    fn count(&self, name: &str, n: u64) {
+        match self.metrics {
+            Some((ref id, ref metrics)) => metrics.processor_counter(id, name, n),
+            None => {}
+        }
+    }
+}
+
+impl Processor for AggregateProcessor {
+    fn process(&mut self, record: Record, output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        self.process_at(record, Instant::now(), output);
+        return Ok(());
+    }
+
+    fn id(&self) -> Option<String> {
+        Some("aggregate".to_string())
+    }
+
+    fn attach(&mut self, id: &str, metrics: &MetricsRegistry) {
+        self.metrics = Some((id.to_string(), metrics.clone()));
+    }
+
+    fn flush(&mut self, now: Instant, output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        self.flush_at(now, output);
+        return Ok(());
+    }
+
+    fn end_source(&mut self, source: &str, output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        self.sources.remove(source);
+        if self.sources.is_empty() {
+            let open: Vec<i64> = self.windows.keys().cloned().collect();
+            for start in open {
+                self.close(start, output);
+            }
+        }
+        return Ok(());
+    }
+}
+
+/// Quote a logfmt value when it is empty or holds spaces, quotes or `=`
+fn logfmt_value(value: &str) -> String {
+    if !value.is_empty() && !value.contains(|c: char| c.is_whitespace() || c == '"' || c == '=') {
+        return value.to_string();
+    }
+    return format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use std::time::Instant;
+    use super::AggregateProcessor;
+    use Processor;
+    use records::record::Record;
+    use records::record_value::RecordValue;
+    use routing::route::RouteSelector;
+    use metrics::metrics_registry::MetricsRegistry;
+    use processors::aggregate_metric::AggregateMetric;
+
+    fn request(time: &str, path: &str, status: i64, dur: f64) -> Record {
+        let mut record = Record::new("api", b"request\n".to_vec());
+        record.set_field("timestamp", time);
+        record.set_field("path", path);
+        record.set_field("status", status);
+        record.set_field("dur", dur);
+        return record;
+    }
+
+    #[test]
+    fn test_counts_per_group_and_event_time_window() {
+        let metrics = MetricsRegistry::new();
+        let mut aggregate = AggregateProcessor::new(Duration::from_secs(60))
+            .with_lateness(Duration::from_secs(10))
+            .with_metric(AggregateMetric::count("http_5xx")
+                .with_selector(RouteSelector::field_matches("status", "^5").unwrap())
+                .with_group_by(&["path"]))
+            .with_target("metrics");
+        aggregate.attach("aggregate", &metrics);
+        let now = Instant::now();
+        let mut output = Vec::new();
+        aggregate.process_at(request("2024-05-01T10:00:05Z", "/a", 500, 1.0), now, &mut output);
+        aggregate.process_at(request("2024-05-01T10:00:50Z", "/b", 503, 1.0), now, &mut output);
+        aggregate.process_at(request("2024-05-01T10:00:20Z", "/a", 200, 1.0), now, &mut output);
+        // Within the lateness, so the first window is still open
+        aggregate.process_at(request("2024-05-01T10:01:05Z", "/a", 500, 1.0), now, &mut output);
+        aggregate.process_at(request("2024-05-01T10:00:55Z", "/a", 502, 1.0), now, &mut output);
+        assert_eq!(output.len(), 5);
+        assert!(output.iter().all(|r| r.source == "api"));
+
+        output.clear();
+        aggregate.process_at(request("2024-05-01T10:01:10Z", "/b", 200, 1.0), now, &mut output);
+        assert_eq!(output.len(), 3);
+        let summaries: Vec<&str> = output[1..].iter().map(|r| r.payload_str().unwrap()).collect();
+        assert_eq!(summaries, vec!(
+            "metric=http_5xx window_start=2024-05-01T10:00:00.000Z window_end=2024-05-01T10:01:00.000Z path=/a count=2\n",
+            "metric=http_5xx window_start=2024-05-01T10:00:00.000Z window_end=2024-05-01T10:01:00.000Z path=/b count=1\n",
+        ));
+        assert_eq!(output[1].source, "aggregate");
+        assert_eq!(output[1].route, Some("metrics".to_string()));
+        assert_eq!(output[1].field("count"), Some(&RecordValue::Int(2)));
+        assert_eq!(output[1].field("path"), Some(&RecordValue::from("/a")));
+
+        // The first window has closed, so this one is late
+        output.clear();
+        aggregate.process_at(request("2024-05-01T10:00:59Z", "/a", 500, 1.0), now, &mut output);
+        assert_eq!(output.len(), 1);
+        aggregate.end_source("api", &mut output).unwrap();
+        assert_eq!(output.len(), 2);
+        assert_eq!(output[1].field("window_start"), Some(&RecordValue::from("2024-05-01T10:01:00.000Z")));
+        assert_eq!(output[1].field("count"), Some(&RecordValue::Int(1)));
+
+        let snapshot = metrics.snapshot();
+        let counters = &snapshot.processor("aggregate").unwrap().counters;
+        assert_eq!(counters.get("late"), Some(&1));
+        assert_eq!(counters.get("summaries"), Some(&3));
+    }
+
+    #[test]
+    fn test_histograms_and_idle_windows() {
+        let mut aggregate = AggregateProcessor::new(Duration::from_secs(60))
+            .with_lateness(Duration::from_secs(5))
+            .with_metric(AggregateMetric::histogram("latency", "dur").with_group_by(&["path"]))
+            .with_max_groups(1);
+        let now = Instant::now();
+        let mut output = Vec::new();
+        for millis in 1..101 {
+            aggregate.process_at(request("2024-05-01T10:00:00Z", "/a", 200, millis as f64), now, &mut output);
+        }
+        let mut missing = request("2024-05-01T10:00:00Z", "/a", 200, 0.0);
+        missing.remove_field("dur");
+        aggregate.process_at(missing, now, &mut output);
+        // There is no room for another group
+        aggregate.process_at(request("2024-05-01T10:00:00Z", "/b", 200, 1.0), now, &mut output);
+
+        output.clear();
+        aggregate.flush_at(now + Duration::from_secs(64), &mut output);
+        assert!(output.is_empty());
+        aggregate.flush_at(now + Duration::from_secs(65), &mut output);
+        assert_eq!(output.len(), 1);
+        let summary = &output[0];
+        assert_eq!(summary.field("count"), Some(&RecordValue::Int(100)));
+        assert_eq!(summary.field("sum"), Some(&RecordValue::Float(5050.0)));
+        assert_eq!(summary.field("min"), Some(&RecordValue::Float(1.0)));
+        assert_eq!(summary.field("max"), Some(&RecordValue::Float(100.0)));
+        for &(name, expected) in [("p50", 50.0), ("p95", 95.0), ("p99", 99.0)].iter() {
+            let found = summary.field(name).and_then(|v| v.as_f64()).unwrap();
+            assert!((found - expected).abs() / expected <= 0.02, "{} was {}", name, found);
+        }
+        assert!(summary.payload_str().unwrap().starts_with("metric=latency window_start=2024-05-01T10:00:00.000Z "));
+        assert!(summary.payload_str().unwrap().contains(" path=/a count=100 sum=5050 min=1 max=100 p50="));
+    }
+
+    #[test]
+    fn test_records_without_event_time_use_processing_time() {
+        let mut aggregate = AggregateProcessor::new(Duration::from_secs(1))
+            .with_metric(AggregateMetric::count("lines"));
+        let mut output = Vec::new();
+        let now = Instant::now();
+        aggregate.process_at(Record::new("a", b"one\n".to_vec()), now, &mut output);
+        aggregate.process_at(Record::new("b", b"two\n".to_vec()), now, &mut output);
+        aggregate.end_source("a", &mut output).unwrap();
+        assert_eq!(output.len(), 2);
+        aggregate.end_source("b", &mut output).unwrap();
+        assert_eq!(output.len(), 3);
+        assert_eq!(output[2].field("count"), Some(&RecordValue::Int(2)));
+    }
+    #[test]
+    fn test_out_of_range_event_times_use_processing_time() {
+        let mut aggregate = AggregateProcessor::new(Duration::from_secs(60))
+            .with_lateness(Duration::from_secs(10))
+            .with_metric(AggregateMetric::count("lines"));
+        let mut output = Vec::new();
+        let now = Instant::now();
+        for &millis in [::std::i64::MIN, ::std::i64::MAX].iter() {
+            let mut record = Record::new("a", b"edge\n".to_vec());
+            record.set_field("timestamp", RecordValue::Int(millis));
+            aggregate.process_at(record, now, &mut output);
+        }
+        aggregate.process_at(Record::new("a", b"plain\n".to_vec()), now, &mut output);
+        aggregate.end_source("a", &mut output).unwrap();
+        assert_eq!(output.len(), 4);
+        assert_eq!(output[3].field("count"), Some(&RecordValue::Int(3)));
+
+        let mut forever = AggregateProcessor::new(Duration::from_secs(::std::u64::MAX))
+            .with_lateness(Duration::from_secs(::std::u64::MAX))
+            .with_metric(AggregateMetric::count("lines"));
+        output.clear();
+        let mut record = Record::new("a", b"late\n".to_vec());
+        record.set_field("timestamp", RecordValue::Int(-5));
+        forever.process_at(record, now, &mut output);
+        forever.flush_at(now + Duration::from_secs(60), &mut output);
+        assert_eq!(output.len(), 1);
+    }
+}
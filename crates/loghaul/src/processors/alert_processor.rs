@@ -0,0 +1,279 @@
+use std::collections::BTreeMap;
+use std::collections::VecDeque;
+use std::time::Instant;
+use Processor;
+use LoghaulError;
+use records::record::Record;
+use records::record_value::RecordValue;
+use metrics::metrics_registry::MetricsRegistry;
+use processors::alert_rule::AlertRule;
+
+const DEFAULT_MAX_GROUPS: usize = 10000;
+
+/// Raises alerts when records match a rule more often than its threshold within
+/// its window. Records pass through unchanged.
+///
+/// When a rule fires, an alert record is emitted from the source `alert`, with the
+/// payload `alert <rule>: <N> matching records within <seconds>s`, the group if the
+/// rule has one, and a newline. Its fields are `alert`, `matches`, `threshold`,
+/// `window_secs`, the group by fields, `last_source` and `last_payload` from the
+/// record that fired it, and `suppressed`, the times the rule would have fired for
+/// the group during its cooldown since the previous alert. Alerts are routed like
+/// any other record, or sent only to a single target.
+///
+/// Windows slide with the time records are processed. At most `max_groups` rule and
+/// group pairs are tracked; records that would start another are not counted, but
+/// are tallied as `groups_dropped`. Alerts are counted as `alerts`, and alerts held
+/// back by a cooldown as `suppressed`.
+///
+/// ```
+///     use std::time::Duration;
+///     use loghaul::{Stream, AlertProcessor, AlertRule, RouteSelector};
+///     let alerts = AlertProcessor::new()
+///         .with_rule(AlertRule::new("out_of_memory", 20, Duration::from_secs(300))
+///             .with_selector(RouteSelector::payload("OutOfMemoryError").unwrap()))
+///         .with_target("pager");
+///     let stream = Stream::new().with_processor(alerts);
+/// ```
+pub struct AlertProcessor {
+    rules: Vec<AlertRule>,
+    source: String,
+    target: Option<String>,
+    max_groups: usize,
+    states: BTreeMap<(usize, Vec<String>), AlertState>,
+    metrics: Option<(String, MetricsRegistry)>,
+}
+
+struct AlertState {
+    matches: VecDeque<Instant>,
+    quiet_until: Option<Instant>,
+    suppressed: u64,
+}
+
+impl AlertProcessor {
+    pub fn new() -> AlertProcessor {
+        return AlertProcessor {
+            rules: Vec::new(),
+            source: "alert".to_string(),
+            target: None,
+            max_groups: DEFAULT_MAX_GROUPS,
+            states: BTreeMap::new(),
+            metrics: None,
+        };
+    }
+
+    pub fn with_rule(mut self, rule: AlertRule) -> AlertProcessor {
+        self.rules.push(rule);
+        return self;
+    }
+
+    /// Emit alerts from this source instead of `alert`
+    pub fn with_source(mut self, source: &str) -> AlertProcessor {
+        self.source = source.to_string();
+        return self;
+    }
+
+    /// Send alerts only to the target with this id, instead of routing them
+    pub fn with_target(mut self, target: &str) -> AlertProcessor {
+        self.target = Some(target.to_string());
+        return self;
+    }
+
+    /// Track at most this many rule and group pairs, 10000 by default
+    pub fn with_max_groups(mut self, max_groups: usize) -> AlertProcessor {
+        self.max_groups = max_groups.max(1);
+        return self;
+    }
+
+    fn process_at(&mut self, record: Record, now: Instant, output: &mut Vec<Record>) {
+        let (mut fired, mut suppressed, mut dropped) = (Vec::new(), 0, 0);
+        for (index, rule) in self.rules.iter().enumerate() {
+            if !rule.matches(&record) {
+                continue;
+            }
+            let key = (index, rule.group_of(&record));
+            if !self.states.contains_key(&key) && self.states.len() >= self.max_groups {
+                dropped += 1;
+                continue;
+            }
+            let state = self.states.entry(key.clone()).or_insert_with(|| AlertState {
+                matches: VecDeque::new(),
+                quiet_until: None,
+                suppressed: 0,
+            });
+            while state.matches.front().map(|t| *t + rule.window() <= now).unwrap_or(false) {
+                state.matches.pop_front();
+            }
+            state.matches.push_back(now);
+            if (state.matches.len() as u64) <= rule.threshold() {
+                continue;
+            }
+            let matches = state.matches.len() as u64;
+            state.matches.clear();
+            if state.quiet_until.map(|t| now < t).unwrap_or(false) {
+                state.suppressed += 1;
+                suppressed += 1;
+                continue;
+            }
+            state.quiet_until = Some(now + rule.cooldown());
+            fired.push((key, matches, state.suppressed));
+            state.suppressed = 0;
+        }
+
+        for ((index, values), matches, suppressed) in fired.iter() {
+            let alert = self.alert(&self.rules[*index], values, *matches, *suppressed, &record);
+            output.push(alert);
+        }
+        self.count("alerts", fired.len() as u64);
+        self.count("suppressed", suppressed);
+        self.count("groups_dropped", dropped);
+        output.push(record);
+    }
+
+    /// Forget groups which are neither counting matches nor cooling down
+    fn flush_at(&mut self, now: Instant) {
+        let rules = &self.rules;
+        self.states.retain(|&(index, _), state| {
+            let window = rules[index].window();
+            return state.quiet_until.map(|t| now < t).unwrap_or(false) || state.matches.back().map(|t| now < *t + window).unwrap_or(false);
+        });
+    }
+
+    fn alert(&self, rule: &AlertRule, values: &Vec<String>, matches: u64, suppressed: u64, record: &Record) -> Record {
+        let group: Vec<String> = rule.group_by().iter().zip(values.iter()).map(|(field, value)| format!("{}={}", field, value)).collect();
+        let mut payload = format!("alert {}: {} matching records within {}s", rule.name(), matches, rule.window().as_secs());
+        if !group.is_empty() {
+            payload.push_str(&format!(" ({})", group.join(" ")));
+        }
+        payload.push('\n');
+
+        let mut alert = Record::new(&self.source, payload.into_bytes());
+        alert.set_field("alert", rule.name());
+        alert.set_field("matches", RecordValue::Int(matches as i64));
+        alert.set_field("threshold", RecordValue::Int(rule.threshold() as i64));
+        alert.set_field("window_secs", RecordValue::Int(rule.window().as_secs() as i64));
+        for (field, value) in rule.group_by().iter().zip(values.iter()) {
+            alert.set_field(field, value.as_str());
+        }
+        alert.set_field("last_source", record.source.as_str());
+        let last_payload = String::from_utf8_lossy(&record.payload);
+        alert.set_field("last_payload", last_payload.trim_end_matches(|c| c == '\n' || c == '\r'));
+        alert.set_field("suppressed", RecordValue::Int(suppressed as i64));
+        match self.target {
+            Some(ref target) => alert.route_to(target),
+            None => {}
+        }
+        return alert;
+    }
+
+    fn count(&self, name: &str, n: u64) {
+        match self.metrics {
+            Some((ref id, ref metrics)) if n > 0 => metrics.processor_counter(id, name, n),
+            _ => {}
+        }
+    }
+}
+
+impl Processor for AlertProcessor {
+    fn process(&mut self, record: Record, output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        self.process_at(record, Instant::now(), output);
+        return Ok(());
+    }
+
+    fn id(&self) -> Option<String> {
+        Some("alert".to_string())
+    }
+
+    fn attach(&mut self, id: &str, metrics: &MetricsRegistry) {
+        self.metrics = Some((id.to_string(), metrics.clone()));
+    }
+
+    fn flush(&mut self, now: Instant, _output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        self.flush_at(now);
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use std::time::Instant;
+    use super::AlertProcessor;
+    use Processor;
+    use records::record::Record;
+    use records::record_value::RecordValue;
+    use routing::route::RouteSelector;
+    use metrics::metrics_registry::MetricsRegistry;
+    use processors::alert_rule::AlertRule;
+
+    fn line(host: &str, text: &str) -> Record {
+        let mut record = Record::new("app", format!("{}\n", text).into_bytes());
+        record.set_field("hostname", host);
+        return record;
+    }
+
+    #[test]
+    fn test_fires_over_threshold_within_window() {
+        let metrics = MetricsRegistry::new();
+        let mut alerts = AlertProcessor::new()
+            .with_rule(AlertRule::new("oom", 2, Duration::from_secs(60))
+                .with_selector(RouteSelector::payload("OutOfMemoryError").unwrap())
+                .with_group_by(&["hostname"]))
+            .with_target("pager");
+        alerts.attach("alert", &metrics);
+        let start = Instant::now();
+        let mut output = Vec::new();
+        alerts.process_at(line("web-1", "java.lang.OutOfMemoryError"), start, &mut output);
+        alerts.process_at(line("web-2", "java.lang.OutOfMemoryError"), start, &mut output);
+        alerts.process_at(line("web-1", "all good"), start, &mut output);
+        alerts.process_at(line("web-1", "java.lang.OutOfMemoryError"), start + Duration::from_secs(30), &mut output);
+        // The first match has left the window
+        alerts.process_at(line("web-1", "java.lang.OutOfMemoryError"), start + Duration::from_secs(60), &mut output);
+        assert_eq!(output.len(), 5);
+        alerts.process_at(line("web-1", "java.lang.OutOfMemoryError again"), start + Duration::from_secs(61), &mut output);
+        assert_eq!(output.len(), 7);
+
+        let alert = &output[5];
+        assert_eq!(alert.source, "alert");
+        assert_eq!(alert.route, Some("pager".to_string()));
+        assert_eq!(alert.payload_str(), Some("alert oom: 3 matching records within 60s (hostname=web-1)\n"));
+        assert_eq!(alert.field("matches"), Some(&RecordValue::Int(3)));
+        assert_eq!(alert.field("threshold"), Some(&RecordValue::Int(2)));
+        assert_eq!(alert.field("hostname"), Some(&RecordValue::from("web-1")));
+        assert_eq!(alert.field("last_source"), Some(&RecordValue::from("app")));
+        assert_eq!(alert.field("last_payload"), Some(&RecordValue::from("java.lang.OutOfMemoryError again")));
+        assert_eq!(alert.field("suppressed"), Some(&RecordValue::Int(0)));
+        assert_eq!(output[6].payload_str(), Some("java.lang.OutOfMemoryError again\n"));
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.processor("alert").unwrap().counters.get("alerts"), Some(&1));
+    }
+
+    #[test]
+    fn test_cooldown_holds_back_repeated_alerts() {
+        let metrics = MetricsRegistry::new();
+        let mut alerts = AlertProcessor::new()
+            .with_rule(AlertRule::new("errors", 0, Duration::from_secs(10)).with_cooldown(Duration::from_secs(100)))
+            .with_max_groups(1);
+        alerts.attach("alert", &metrics);
+        let start = Instant::now();
+        let mut output = Vec::new();
+        for seconds in [0, 1, 2, 99, 100].iter() {
+            alerts.process_at(line("web-1", "error"), start + Duration::from_secs(*seconds), &mut output);
+        }
+        let fired: Vec<Option<&RecordValue>> = output.iter().filter(|r| r.source == "alert").map(|r| r.field("suppressed")).collect();
+        assert_eq!(fired, vec!(Some(&RecordValue::Int(0)), Some(&RecordValue::Int(3))));
+        assert_eq!(output[0].payload_str(), Some("alert errors: 1 matching records within 10s\n"));
+
+        // Cooling down keeps the group, then it is forgotten
+        alerts.flush_at(start + Duration::from_secs(150));
+        assert_eq!(alerts.states.len(), 1);
+        alerts.flush_at(start + Duration::from_secs(200));
+        assert!(alerts.states.is_empty());
+
+        let snapshot = metrics.snapshot();
+        let counters = &snapshot.processor("alert").unwrap().counters;
+        assert_eq!(counters.get("alerts"), Some(&2));
+        assert_eq!(counters.get("suppressed"), Some(&3));
+    }
+}
@@ -0,0 +1,90 @@
+use std::time::Duration;
+use records::record::Record;
+use routing::route::RouteSelector;
+
+/// A condition an `AlertProcessor` raises an alert for: more than `threshold` records
+/// meeting every selector within a sliding `window`, counted separately for each
+/// combination of values of the group by fields.
+///
+/// Once a rule fires for a group it stays quiet for that group for the cooldown,
+/// the length of the window by default, and then needs more than `threshold` new
+/// matches to fire again.
+///
+/// ```
+///     use std::time::Duration;
+///     use loghaul::{AlertRule, RouteSelector};
+///     let oom = AlertRule::new("out_of_memory", 20, Duration::from_secs(300))
+///         .with_selector(RouteSelector::payload("OutOfMemoryError").unwrap())
+///         .with_group_by(&["hostname"])
+///         .with_cooldown(Duration::from_secs(900));
+/// ```
+#[derive(Debug, Clone)]
+pub struct AlertRule {
+    name: String,
+    threshold: u64,
+    window: Duration,
+    cooldown: Duration,
+    group_by: Vec<String>,
+    selectors: Vec<RouteSelector>,
+}
+
+impl AlertRule {
+    /// Fire when more than `threshold` matching records arrive within `window`
+    pub fn new(name: &str, threshold: u64, window: Duration) -> AlertRule {
+        return AlertRule {
+            name: name.to_string(),
+            threshold: threshold,
+            window: window,
+            cooldown: window,
+            group_by: Vec::new(),
+            selectors: Vec::new(),
+        };
+    }
+
+    /// Only count records meeting this selector, as well as any others
+    pub fn with_selector(mut self, selector: RouteSelector) -> AlertRule {
+        self.selectors.push(selector);
+        return self;
+    }
+
+    /// Count and alert separately for each combination of values of these fields
+    pub fn with_group_by(mut self, fields: &[&str]) -> AlertRule {
+        self.group_by = fields.iter().map(|f| f.to_string()).collect();
+        return self;
+    }
+
+    /// How long to stay quiet after firing, the length of the window by default
+    pub fn with_cooldown(mut self, cooldown: Duration) -> AlertRule {
+        self.cooldown = cooldown;
+        return self;
+    }
+
+    pub fn name(&self) -> &str {
+        return &self.name;
+    }
+
+    pub fn threshold(&self) -> u64 {
+        return self.threshold;
+    }
+
+    pub fn window(&self) -> Duration {
+        return self.window;
+    }
+
+    pub fn cooldown(&self) -> Duration {
+        return self.cooldown;
+    }
+
+    pub fn group_by(&self) -> &Vec<String> {
+        return &self.group_by;
+    }
+
+    pub fn matches(&self, record: &Record) -> bool {
+        return self.selectors.iter().all(|s| s.matches(record));
+    }
+
+    /// The values of the group by fields of a record; missing fields group as empty strings
+    pub fn group_of(&self, record: &Record) -> Vec<String> {
+        return self.group_by.iter().map(|field| record.field(field).map(|v| v.to_string()).unwrap_or(String::new())).collect();
+    }
+}
@@ -0,0 +1,348 @@
+use std::collections::BTreeMap;
+use std::collections::VecDeque;
+use std::time::Duration;
+use std::time::Instant;
+use Processor;
+use LoghaulError;
+use records::record::Record;
+use records::record_value::RecordValue;
+use metrics::metrics_registry::MetricsRegistry;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 5;
+const DEFAULT_MAX_KEYS: usize = 10000;
+
+/// Collapses repeated records from the same source into the first of them and a
+/// record counting the repeats.
+///
+/// Records are the same when their payloads are, or, with key fields set, when
+/// those fields are. By default only consecutive records are compared: a run of
+/// repeats ends when a different record arrives, when the source ends, or when no
+/// repeat has arrived for the timeout. With a window, a record is a repeat of any
+/// record first seen less than the window ago, and the run ends when the window
+/// closes.
+///
+/// When a run with repeats ends, a record with the first record's fields, a
+/// `repeated` field holding the count and the payload `last message repeated <N> times`
+/// is emitted, ending with the first record's line terminator.
+///
+/// Collapsed repeats are counted as `duplicates`.
+///
+/// ```
+///     use std::time::Duration;
+///     use loghaul::{Stream, DedupProcessor};
+///     let dedup = DedupProcessor::new()
+///         .with_fields(&["level", "message"])
+///         .with_window(Duration::from_secs(60));
+///     let stream = Stream::new().with_processor(dedup);
+/// ```
+pub struct DedupProcessor {
+    fields: Option<Vec<String>>,
+    window: Option<Duration>,
+    timeout: Duration,
+    max_keys: usize,
+    /// Runs by key when windowed, otherwise by source
+    runs: BTreeMap<Vec<u8>, Run>,
+    /// When each window closes, oldest first
+    closing: VecDeque<(Instant, Vec<u8>)>,
+    metrics: Option<(String, MetricsRegistry)>,
+}
+
+/// The first record of a run and how many times it was repeated
+struct Run {
+    first: Record,
+    repeats: u64,
+    /// When the run ends if nothing else ends it first
+    closes: Instant,
+}
+
+impl DedupProcessor {
+    /// Collapse consecutive repeats of the same payload
+    pub fn new() -> DedupProcessor {
+        return DedupProcessor {
+            fields: None,
+            window: None,
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECS),
+            max_keys: DEFAULT_MAX_KEYS,
+            runs: BTreeMap::new(),
+            closing: VecDeque::new(),
+            metrics: None,
+        };
+    }
+
+    /// Compare these fields instead of the payload; a missing field only matches
+    /// another missing field
+    pub fn with_fields(mut self, fields: &[&str]) -> DedupProcessor {
+        self.fields = Some(fields.iter().map(|f| f.to_string()).collect());
+        return self;
+    }
+
+    /// Collapse every repeat within this long of the first record, not only consecutive ones
+    pub fn with_window(mut self, window: Duration) -> DedupProcessor {
+        self.window = Some(window);
+        return self;
+    }
+
+    /// End a run of consecutive repeats once none has arrived for this long, five seconds by default
+    pub fn with_timeout(mut self, timeout: Duration) -> DedupProcessor {
+        self.timeout = timeout;
+        return self;
+    }
+
+    /// Track at most this many windows, 10000 by default; when full, the oldest closes early
+    pub fn with_max_keys(mut self, max_keys: usize) -> DedupProcessor {
+        self.max_keys = max_keys.max(1);
+        return self;
+    }
+
+    /// The bytes records are compared by, each part prefixed with its length so
+    /// that different records never share a key. Consecutive runs are tracked per
+    /// source, so only windowed keys include the source.
+    fn key_of(&self, record: &Record) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        match self.window {
+            Some(_) => push_part(&mut bytes, record.source.as_bytes()),
+            None => {}
+        }
+        match self.fields {
+            Some(ref fields) => {
+                for field in fields.iter() {
+                    match record.field(field) {
+                        Some(value) => {
+                            bytes.push(1);
+                            push_part(&mut bytes, value.to_string().as_bytes());
+                        }
+                        None => bytes.push(2),
+                    }
+                }
+            }
+            None => bytes.extend_from_slice(&record.payload),
+        }
+        return bytes;
+    }
+
+    fn process_at(&mut self, record: Record, now: Instant, output: &mut Vec<Record>) {
+        let key = self.key_of(&record);
+        let slot = match self.window {
+            Some(_) => key.clone(),
+            None => record.source.as_bytes().to_vec(),
+        };
+        let repeat = match self.runs.get(&slot) {
+            Some(run) => now < run.closes && (self.window.is_some() || self.key_of(&run.first) == key),
+            None => false,
+        };
+        if repeat {
+            let run = self.runs.get_mut(&slot).expect("run exists");
+            run.repeats += 1;
+            if self.window.is_none() {
+                run.closes = now + self.timeout;
+            }
+            match self.metrics {
+                Some((ref id, ref metrics)) => metrics.processor_counter(id, "duplicates", 1),
+                None => {}
+            }
+            return;
+        }
+
+        match self.runs.remove(&slot) {
+            Some(run) => run.end(output),
+            None => {}
+        }
+        let closes = match self.window {
+            Some(window) => {
+                while self.runs.len() >= self.max_keys {
+                    match self.closing.pop_front() {
+                        Some((closes, oldest)) => self.close(&oldest, Some(closes), output),
+                        None => break,
+                    }
+                }
+                self.closing.push_back((now + window, slot.clone()));
+                now + window
+            }
+            None => now + self.timeout,
+        };
+        self.runs.insert(slot, Run {
+            first: record.clone(),
+            repeats: 0,
+            closes,
+        });
+        output.push(record);
+    }
+
+    /// End a run, unless it was replaced since it was queued to close at `closes`
+    fn close(&mut self, slot: &[u8], closes: Option<Instant>, output: &mut Vec<Record>) {
+        let current = match self.runs.get(slot) {
+            Some(run) => closes.map(|closes| closes == run.closes).unwrap_or(true),
+            None => false,
+        };
+        if current {
+            self.runs.remove(slot).expect("run exists").end(output);
+        }
+    }
+
+    fn flush_at(&mut self, now: Instant, output: &mut Vec<Record>) {
+        if self.window.is_some() {
+            // Windows close in the order they opened
+            while self.closing.front().map(|&(closes, _)| closes <= now).unwrap_or(false) {
+                let (closes, slot) = self.closing.pop_front().expect("front exists");
+                self.close(&slot, Some(closes), output);
+            }
+            return;
+        }
+        // Consecutive runs are pushed back by each repeat, but there is only one per source
+        let ended: Vec<Vec<u8>> = self.runs.iter().filter(|&(_, run)| run.closes <= now).map(|(slot, _)| slot.clone()).collect();
+        for slot in ended.iter() {
+            self.close(slot, None, output);
+        }
+    }
+}
+
+/// Append a part of a key, prefixed with its length
+fn push_part(bytes: &mut Vec<u8>, part: &[u8]) {
+    bytes.extend_from_slice(&(part.len() as u64).to_be_bytes());
+    bytes.extend_from_slice(part);
+}
+
+impl Run {
+    /// Emit the repeat count, if there were any repeats
+    fn end(self, output: &mut Vec<Record>) {
+        if self.repeats == 0 {
+            return;
+        }
+        let terminator: &[u8] = match self.first.payload {
+            ref payload if payload.ends_with(b"\r\n") => b"\r\n",
+            ref payload if payload.ends_with(b"\n") => b"\n",
+            _ => b"",
+        };
+        let mut summary = self.first.clone();
+        summary.payload = format!("last message repeated {} times", self.repeats).into_bytes();
+        summary.payload.extend_from_slice(terminator);
+        summary.set_field("repeated", RecordValue::Int(self.repeats as i64));
+        output.push(summary);
+    }
+}
+
+impl Processor for DedupProcessor {
+    fn process(&mut self, record: Record, output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        self.process_at(record, Instant::now(), output);
+        return Ok(());
+    }
+
+    fn id(&self) -> Option<String> {
+        Some("dedup".to_string())
+    }
+
+    fn attach(&mut self, id: &str, metrics: &MetricsRegistry) {
+        self.metrics = Some((id.to_string(), metrics.clone()));
+    }
+
+    fn flush(&mut self, now: Instant, output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        self.flush_at(now, output);
+        return Ok(());
+    }
+
+    fn end_source(&mut self, source: &str, output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        let slots: Vec<Vec<u8>> = self.runs.iter().filter(|&(_, run)| run.first.source == source).map(|(slot, _)| slot.clone()).collect();
+        for slot in slots.iter() {
+            self.close(slot, None, output);
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use std::time::Instant;
+    use super::DedupProcessor;
+    use Processor;
+    use records::record::Record;
+    use records::record_value::RecordValue;
+    use metrics::metrics_registry::MetricsRegistry;
+
+    fn payloads(output: &Vec<Record>) -> Vec<&str> {
+        return output.iter().map(|r| r.payload_str().unwrap()).collect();
+    }
+
+    #[test]
+    fn test_collapses_consecutive_repeats() {
+        let metrics = MetricsRegistry::new();
+        let mut dedup = DedupProcessor::new().with_timeout(Duration::from_secs(2));
+        dedup.attach("dedup", &metrics);
+        let start = Instant::now();
+        let mut output = Vec::new();
+        for (offset, source, line) in vec!((0, "a", "boom\n"), (0, "b", "boom\n"), (1, "a", "boom\n"), (2, "a", "boom\n"), (2, "b", "ok\n"), (3, "a", "ok\n")).into_iter() {
+            dedup.process_at(Record::new(source, line.as_bytes().to_vec()), start + Duration::from_secs(offset), &mut output);
+        }
+        assert_eq!(payloads(&output), vec!("boom\n", "boom\n", "ok\n", "last message repeated 2 times\n", "ok\n"));
+        assert_eq!(output[3].source, "a");
+        assert_eq!(output[3].field("repeated"), Some(&RecordValue::Int(2)));
+        assert_eq!(metrics.snapshot().processor("dedup").unwrap().counters.get("duplicates"), Some(&2));
+
+        // The run only ends once no repeat arrived for the timeout
+        output.clear();
+        dedup.process_at(Record::new("b", b"ok\n".to_vec()), start + Duration::from_secs(3), &mut output);
+        dedup.flush_at(start + Duration::from_secs(4), &mut output);
+        assert!(output.is_empty());
+        dedup.flush_at(start + Duration::from_secs(5), &mut output);
+        assert_eq!(payloads(&output), vec!("last message repeated 1 times\n"));
+        assert_eq!(output[0].source, "b");
+
+        // After the timeout the same line passes again
+        output.clear();
+        dedup.process_at(Record::new("b", b"ok\n".to_vec()), start + Duration::from_secs(6), &mut output);
+        dedup.process_at(Record::new("b", b"ok\n".to_vec()), start + Duration::from_secs(6), &mut output);
+        dedup.end_source("b", &mut output).unwrap();
+        assert_eq!(payloads(&output), vec!("ok\n", "last message repeated 1 times\n"));
+    }
+
+    #[test]
+    fn test_windowed_dedup_on_fields() {
+        let mut dedup = DedupProcessor::new().with_fields(&["code"]).with_window(Duration::from_secs(10)).with_max_keys(2);
+        let start = Instant::now();
+        let mut output = Vec::new();
+        for (offset, code) in vec!((0, Some(1)), (1, Some(2)), (2, Some(1)), (3, None), (4, Some(1)), (5, None)).into_iter() {
+            let mut record = Record::new("app", format!("code {:?}", code).into_bytes());
+            match code {
+                Some(code) => record.set_field("code", RecordValue::Int(code)),
+                None => {}
+            }
+            dedup.process_at(record, start + Duration::from_secs(offset), &mut output);
+        }
+        // The third key closed the oldest window early
+        assert_eq!(payloads(&output), vec!("code Some(1)", "code Some(2)", "last message repeated 1 times", "code None", "code Some(1)"));
+        assert_eq!(output[2].field("code"), Some(&RecordValue::Int(1)));
+
+        output.clear();
+        dedup.flush_at(start + Duration::from_secs(13), &mut output);
+        assert_eq!(payloads(&output), vec!("last message repeated 1 times"));
+        assert_eq!(output[0].field("code"), None);
+        dedup.flush_at(start + Duration::from_secs(14), &mut output);
+        assert_eq!(output.len(), 1);
+    }
+
+    #[test]
+    fn test_keys_are_compared_exactly() {
+        let start = Instant::now();
+        let mut output = Vec::new();
+        // Values that would run together if the key fields were simply joined
+        let mut dedup = DedupProcessor::new().with_fields(&["a", "b"]).with_window(Duration::from_secs(10));
+        for &(a, b) in [("x\u{0}", "y"), ("x", "\u{0}y"), ("x\u{0}\u{1}y", "")].iter() {
+            let mut record = Record::new("app", format!("{:?} {:?}", a, b).into_bytes());
+            record.set_field("a", a);
+            record.set_field("b", b);
+            dedup.process_at(record, start, &mut output);
+        }
+        assert_eq!(output.len(), 3);
+
+        // Sources whose names only differ slightly keep their own runs
+        let mut consecutive = DedupProcessor::new();
+        output.clear();
+        for source in vec!("a", "a\u{0}", "a", "a\u{0}").into_iter() {
+            consecutive.process_at(Record::new(source, b"boom\n".to_vec()), start, &mut output);
+        }
+        assert_eq!(payloads(&output), vec!("boom\n", "boom\n"));
+        consecutive.end_source("a", &mut output).unwrap();
+        assert_eq!(output[2].source, "a");
+        assert_eq!(output[2].field("repeated"), Some(&RecordValue::Int(1)));
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::BTreeMap;
+use std::env;
+use regex::Regex;
+use hostname;
+use Processor;
+use LoghaulError;
+use LoghaulErrorCode;
+use records::record::Record;
+use records::record_value::RecordValue;
+
+/// Adds fields describing where a record came from.
+///
+/// Static values, the host name and environment variables are resolved once, when
+/// they are added. The source id, which for file sources is the file path, can be
+/// written to a field, and named groups of a pattern matched against it become
+/// fields too, eg. the pod name from `/var/log/pods/<namespace>_<pod>_<uid>/...`.
+/// Fields the record already has are overwritten.
+///
+/// ```
+///     use loghaul::{Stream, EnrichProcessor};
+///     let enrich = EnrichProcessor::new()
+///         .with_hostname("host")
+///         .with_value("env", "production")
+///         .with_source_field("file")
+///         .with_source_pattern(r"^/var/log/pods/(?P<namespace>[^_/]+)_(?P<pod>[^_/]+)_").unwrap();
+///     let stream = Stream::new().with_processor(enrich);
+/// ```
+pub struct EnrichProcessor {
+    values: Vec<(String, RecordValue)>,
+    source_field: Option<String>,
+    patterns: Vec<Regex>,
+    captured: BTreeMap<String, Vec<(String, RecordValue)>>,
+}
+
+impl EnrichProcessor {
+    pub fn new() -> EnrichProcessor {
+        return EnrichProcessor {
+            values: Vec::new(),
+            source_field: None,
+            patterns: Vec::new(),
+            captured: BTreeMap::new(),
+        };
+    }
+
+    /// Set a field to a static value on every record
+    pub fn with_value(mut self, field: &str, value: impl Into<RecordValue>) -> EnrichProcessor {
+        self.values.push((field.to_string(), value.into()));
+        return self;
+    }
+
+    /// Set a field to the name of this host
+    pub fn with_hostname(self, field: &str) -> EnrichProcessor {
+        let name = hostname::get().map(|name| name.to_string_lossy().to_string()).unwrap_or("localhost".to_string());
+        return self.with_value(field, name);
+    }
+
+    /// Set a field to the value of an environment variable, if it is set
+    pub fn with_env(self, field: &str, variable: &str) -> EnrichProcessor {
+        return match env::var(variable) {
+            Ok(value) => self.with_value(field, value),
+            Err(_) => self,
+        };
+    }
+
+    /// Set a field to the id of the source each record was read from
+    pub fn with_source_field(mut self, field: &str) -> EnrichProcessor {
+        self.source_field = Some(field.to_string());
+        return self;
+    }
+
+    /// Match a pattern against the source id, setting each named group that
+    /// matched as a field
+    pub fn with_source_pattern(mut self, pattern: &str) -> Result<EnrichProcessor, LoghaulError> {
+        let regex = Regex::new(pattern).map_err(|e| LoghaulError::from(LoghaulErrorCode::ProcessorErr(e.to_string())))?;
+        self.patterns.push(regex);
+        self.captured.clear();
+        return Ok(self);
+    }
+
+    /// The fields every pattern captures from a source id
+    fn capture(&self, source: &str) -> Vec<(String, RecordValue)> {
+        let mut fields = Vec::new();
+        for pattern in self.patterns.iter() {
+            let found = match pattern.captures(source) {
+                Some(found) => found,
+                None => continue,
+            };
+            for name in pattern.capture_names().filter_map(|n| n) {
+                match found.name(name) {
+                    Some(value) => fields.push((name.to_string(), RecordValue::from(value.as_str()))),
+                    None => {}
+                }
+            }
+        }
+        return fields;
+    }
+}
+
+impl Processor for EnrichProcessor {
+    fn process(&mut self, mut record: Record, output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        for &(ref field, ref value) in self.values.iter() {
+            record.fields.insert(field.clone(), value.clone());
+        }
+        match self.source_field {
+            Some(ref field) => record.set_field(field, record.source.clone()),
+            None => {}
+        }
+        if !self.patterns.is_empty() {
+            // Sources are few and long lived, so each is only matched once
+            if !self.captured.contains_key(&record.source) {
+                let fields = self.capture(&record.source);
+                self.captured.insert(record.source.clone(), fields);
+            }
+            for &(ref field, ref value) in self.captured[&record.source].iter() {
+                record.fields.insert(field.clone(), value.clone());
+            }
+        }
+        output.push(record);
+        return Ok(());
+    }
+
+    fn id(&self) -> Option<String> {
+        Some("enrich".to_string())
+    }
+
+    fn end_source(&mut self, source: &str, _output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        self.captured.remove(source);
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use super::EnrichProcessor;
+    use Processor;
+    use records::record::Record;
+    use records::record_value::RecordValue;
+
+    #[test]
+    fn test_static_and_host_fields() {
+        env::set_var("LOGHAUL_TEST_REGION", "eu-west-1");
+        let mut enrich = EnrichProcessor::new()
+            .with_value("env", "production")
+            .with_value("shard", 3)
+            .with_hostname("host")
+            .with_env("region", "LOGHAUL_TEST_REGION")
+            .with_env("zone", "LOGHAUL_TEST_UNSET");
+
+        let mut record = Record::new("app", b"hello".to_vec());
+        record.set_field("env", "staging");
+        let mut output = Vec::new();
+        enrich.process(record, &mut output).unwrap();
+        assert_eq!(output[0].field("env"), Some(&RecordValue::from("production")));
+        assert_eq!(output[0].field("shard"), Some(&RecordValue::Int(3)));
+        assert_eq!(output[0].field("region"), Some(&RecordValue::from("eu-west-1")));
+        assert_eq!(output[0].field("zone"), None);
+        assert!(output[0].field("host").is_some());
+    }
+
+    #[test]
+    fn test_source_path_fields() {
+        let mut enrich = EnrichProcessor::new()
+            .with_source_field("file")
+            .with_source_pattern(r"^/var/log/pods/(?P<namespace>[^_/]+)_(?P<pod>[^_/]+)_(?P<uid>[^/]+)/(?P<container>[^/]+)/").unwrap()
+            .with_source_pattern(r"\.(?P<rotation>\d+)\.log$").unwrap();
+
+        let mut output = Vec::new();
+        enrich.process(Record::new("/var/log/pods/shop_checkout-7d9f_1234-abcd/api/0.log", b"a".to_vec()), &mut output).unwrap();
+        enrich.process(Record::new("/var/log/syslog", b"b".to_vec()), &mut output).unwrap();
+        enrich.process(Record::new("/var/log/pods/shop_checkout-7d9f_1234-abcd/api/0.log", b"c".to_vec()), &mut output).unwrap();
+        assert_eq!(output[0].field("file"), Some(&RecordValue::from("/var/log/pods/shop_checkout-7d9f_1234-abcd/api/0.log")));
+        assert_eq!(output[0].field("namespace"), Some(&RecordValue::from("shop")));
+        assert_eq!(output[0].field("pod"), Some(&RecordValue::from("checkout-7d9f")));
+        assert_eq!(output[0].field("container"), Some(&RecordValue::from("api")));
+        assert_eq!(output[0].field("rotation"), None);
+        assert_eq!(output[1].fields.len(), 1);
+        assert_eq!(output[2].fields, output[0].fields);
+        assert!(EnrichProcessor::new().with_source_pattern("(?P<x").is_err());
+    }
+}
@@ -0,0 +1,51 @@
+use Processor;
+use LoghaulError;
+use records::record::Record;
+
+/// A processor backed by a closure, for quick map and filter stages.
+///
+/// ```
+///     use loghaul::{Stream, FnProcessor};
+///     let stream = Stream::new()
+///         .with_processor(FnProcessor::filter(|record| !record.payload.starts_with(b"DEBUG")))
+///         .with_processor(FnProcessor::map(|mut record| {
+///             record.set_field("length", record.payload.len() as i64);
+///             record
+///         }));
+/// ```
+pub struct FnProcessor {
+    handler: Box<FnMut(Record, &mut Vec<Record>) -> Result<(), LoghaulError> + Send>,
+}
+
+impl FnProcessor {
+    /// Full control: push zero or more records to the output for each input
+    pub fn new(handler: impl FnMut(Record, &mut Vec<Record>) -> Result<(), LoghaulError> + Send + 'static) -> FnProcessor {
+        return FnProcessor {
+            handler: Box::new(handler),
+        };
+    }
+
+    /// Transform every record
+    pub fn map(mut mapper: impl FnMut(Record) -> Record + Send + 'static) -> FnProcessor {
+        return FnProcessor::new(move |record, output| {
+            output.push(mapper(record));
+            Ok(())
+        });
+    }
+
+    /// Keep only records the predicate returns true for
+    pub fn filter(mut predicate: impl FnMut(&Record) -> bool + Send + 'static) -> FnProcessor {
+        return FnProcessor::new(move |record, output| {
+            if predicate(&record) {
+                output.push(record);
+            }
+            Ok(())
+        });
+    }
+}
+
+impl Processor for FnProcessor {
+    fn process(&mut self, record: Record, output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        (self.handler)(record, output)
+    }
+}
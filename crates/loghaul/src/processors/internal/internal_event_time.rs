@@ -0,0 +1,157 @@
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+use records::record::Record;
+use records::record_value::RecordValue;
+
+const MILLIS_PER_DAY: i64 = 86400000;
+
+/// Milliseconds since the epoch of an RFC 3339 timestamp such as
+/// `2024-05-01T10:00:00.250Z` or `2024-05-01 12:00:00+02:00`
+pub fn parse_rfc3339(text: &str) -> Option<i64> {
+    let bytes = text.as_bytes();
+    if bytes.len() < 20 || bytes[4] != b'-' || bytes[7] != b'-' || bytes[13] != b':' || bytes[16] != b':' {
+        return None;
+    }
+    match bytes[10] {
+        b'T' | b't' | b' ' => {}
+        _ => return None,
+    }
+    let number = |start: usize, end: usize| -> Option<i64> {
+        let digits = text.get(start..end)?;
+        if !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        return digits.parse().ok();
+    };
+    let (year, month, day) = (number(0, 4)?, number(5, 7)?, number(8, 10)?);
+    let (hour, minute, second) = (number(11, 13)?, number(14, 16)?, number(17, 19)?);
+    if month < 1 || month > 12 || day < 1 || day > 31 || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let mut position = 19;
+    let mut millis = 0;
+    if bytes[position] == b'.' {
+        let digits = bytes[position + 1..].iter().take_while(|b| b.is_ascii_digit()).count();
+        if digits == 0 {
+            return None;
+        }
+        let fraction = &text[position + 1..position + 1 + digits.min(3)];
+        millis = fraction.parse::<i64>().ok()? * 10_i64.pow(3 - fraction.len() as u32);
+        position += 1 + digits;
+    }
+    let offset = match text.get(position..)? {
+        "Z" | "z" => 0,
+        zone if zone.len() == 6 && zone.as_bytes()[3] == b':' => {
+            let sign = match zone.as_bytes()[0] {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return None,
+            };
+            sign * (number(position + 1, position + 3)? * 60 + number(position + 4, position + 6)?)
+        }
+        _ => return None,
+    };
+    let seconds = ((days_from_civil(year, month, day) * 24 + hour) * 60 + minute - offset) * 60 + second;
+    return Some(seconds * 1000 + millis);
+}
+
+/// An RFC 3339 timestamp in UTC with milliseconds, eg. `2024-05-01T10:00:00.250Z`
+pub fn format_rfc3339(millis: i64) -> String {
+    let days = millis.div_euclid(MILLIS_PER_DAY);
+    let rest = millis.rem_euclid(MILLIS_PER_DAY);
+    let (year, month, day) = civil_from_days(days);
+    return format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z", year, month, day,
+        rest / 3600000, rest / 60000 % 60, rest / 1000 % 60, rest % 1000);
+}
+
+/// Milliseconds since the epoch now
+pub fn now_millis() -> i64 {
+    return SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64 * 1000 + d.subsec_millis() as i64).unwrap_or(0);
+}
+
+/// The event time a record holds in a field, as RFC 3339 or milliseconds since the epoch
+pub fn field_time(record: &Record, field: &str) -> Option<i64> {
+    return match record.field(field) {
+        Some(RecordValue::String(ref text)) => parse_rfc3339(text),
+        Some(RecordValue::Int(millis)) => Some(*millis),
+        _ => None,
+    };
+}
+
+/// Milliseconds since the epoch at an instant, given the time at an earlier `clock` instant
+pub fn clock_time(clock: (Instant, i64), now: Instant) -> i64 {
+    let (instant, millis) = clock;
+    return match now > instant {
+        true => millis.saturating_add(millis_of(now.duration_since(instant))),
+        false => millis,
+    };
+}
+
+/// A duration in whole milliseconds, saturating at `i64::MAX`
+pub fn millis_of(duration: Duration) -> i64 {
+    return duration.as_millis().min(::std::i64::MAX as u128) as i64;
+}
+
+/// Days since 1970-01-01 of a date in the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    return era * 146097 + day_of_era - 719468;
+}
+
+/// The date of a number of days since 1970-01-01
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    return (year, month, day);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use std::time::Instant;
+    use super::clock_time;
+    use super::format_rfc3339;
+    use super::millis_of;
+    use super::parse_rfc3339;
+
+    #[test]
+    fn test_rfc3339_round_trip() {
+        assert_eq!(parse_rfc3339("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(parse_rfc3339("2024-05-01T10:00:00.250Z"), Some(1714557600250));
+        assert_eq!(parse_rfc3339("2024-05-01 12:00:00.25+02:00"), Some(1714557600250));
+        assert_eq!(parse_rfc3339("2024-05-01T09:30:00.123456-00:30"), Some(1714557600123));
+        assert_eq!(parse_rfc3339("1969-12-31T23:59:59.999Z"), Some(-1));
+        assert_eq!(format_rfc3339(1714557600250), "2024-05-01T10:00:00.250Z");
+        assert_eq!(format_rfc3339(-1), "1969-12-31T23:59:59.999Z");
+        assert_eq!(format_rfc3339(951782400000), "2000-02-29T00:00:00.000Z");
+
+        assert_eq!(parse_rfc3339("2024-05-01T10:00:00"), None);
+        assert_eq!(parse_rfc3339("2024-13-01T10:00:00Z"), None);
+        assert_eq!(parse_rfc3339("2024-05-01T10:00:00.Z"), None);
+        assert_eq!(parse_rfc3339("2024-05-01T10:00:00+0200"), None);
+        assert_eq!(parse_rfc3339("May  1 10:00:00 host"), None);
+    }
+    #[test]
+    fn test_millis_saturate() {
+        assert_eq!(millis_of(Duration::from_millis(1500)), 1500);
+        assert_eq!(millis_of(Duration::from_secs(::std::u64::MAX)), ::std::i64::MAX);
+        let now = Instant::now();
+        assert_eq!(clock_time((now, 5), now + Duration::from_millis(10)), 15);
+        assert_eq!(clock_time((now, 5), now), 5);
+        assert_eq!(clock_time((now, ::std::i64::MAX - 1), now + Duration::from_millis(10)), ::std::i64::MAX);
+    }
+}
@@ -0,0 +1,29 @@
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+/// A small xorshift generator; plenty for sampling decisions, not for anything
+/// that needs to be unpredictable
+pub struct InternalRandom {
+    state: u64,
+}
+
+impl InternalRandom {
+    /// A generator seeded from the clock
+    pub fn new() -> InternalRandom {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos() as u64 ^ d.as_secs()).unwrap_or(0);
+        return InternalRandom::with_seed(nanos);
+    }
+
+    pub fn with_seed(seed: u64) -> InternalRandom {
+        return InternalRandom {
+            state: seed | 1,
+        };
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        return self.state;
+    }
+}
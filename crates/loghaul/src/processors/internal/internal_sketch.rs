@@ -0,0 +1,132 @@
+use std::collections::BTreeMap;
+
+/// The relative error of quantiles
+const ACCURACY: f64 = 0.01;
+
+/// Estimates quantiles of a stream of values in little memory, by counting values
+/// in buckets whose width grows with their magnitude, so any quantile is within
+/// 1% of a value that was actually observed
+#[derive(Debug, Clone)]
+pub struct InternalSketch {
+    gamma: f64,
+    positive: BTreeMap<i32, u64>,
+    negative: BTreeMap<i32, u64>,
+    zero: u64,
+    count: u64,
+    sum: f64,
+    min: f64,
+    max: f64,
+}
+
+impl InternalSketch {
+    pub fn new() -> InternalSketch {
+        return InternalSketch {
+            gamma: (1.0 + ACCURACY) / (1.0 - ACCURACY),
+            positive: BTreeMap::new(),
+            negative: BTreeMap::new(),
+            zero: 0,
+            count: 0,
+            sum: 0.0,
+            min: ::std::f64::INFINITY,
+            max: ::std::f64::NEG_INFINITY,
+        };
+    }
+
+    pub fn add(&mut self, value: f64) {
+        if !value.is_finite() {
+            return;
+        }
+        if value > ::std::f64::MIN_POSITIVE {
+            *self.positive.entry(self.bucket(value)).or_insert(0) += 1;
+        } else if value < -::std::f64::MIN_POSITIVE {
+            *self.negative.entry(self.bucket(-value)).or_insert(0) += 1;
+        } else {
+            self.zero += 1;
+        }
+        self.count += 1;
+        self.sum += value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    pub fn sum(&self) -> f64 {
+        return self.sum;
+    }
+
+    pub fn min(&self) -> f64 {
+        return self.min;
+    }
+
+    pub fn max(&self) -> f64 {
+        return self.max;
+    }
+
+    /// The value below which a fraction `q` of the values fall, or None without values
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = (q.max(0.0).min(1.0) * (self.count - 1) as f64).round() as u64;
+        let mut seen = 0;
+        for (&index, &count) in self.negative.iter().rev() {
+            seen += count;
+            if seen > rank {
+                return Some(self.clamp(-self.value(index)));
+            }
+        }
+        seen += self.zero;
+        if seen > rank {
+            return Some(self.clamp(0.0));
+        }
+        for (&index, &count) in self.positive.iter() {
+            seen += count;
+            if seen > rank {
+                return Some(self.clamp(self.value(index)));
+            }
+        }
+        return Some(self.max);
+    }
+
+    fn bucket(&self, magnitude: f64) -> i32 {
+        return (magnitude.ln() / self.gamma.ln()).ceil() as i32;
+    }
+
+    /// The value a bucket stands for, equally far in relative terms from both its bounds
+    fn value(&self, index: i32) -> f64 {
+        return 2.0 * self.gamma.powi(index) / (self.gamma + 1.0);
+    }
+
+    fn clamp(&self, value: f64) -> f64 {
+        return value.max(self.min).min(self.max);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InternalSketch;
+
+    #[test]
+    fn test_quantiles_are_within_accuracy() {
+        let mut sketch = InternalSketch::new();
+        assert_eq!(sketch.quantile(0.5), None);
+        for value in 1..1001 {
+            sketch.add(value as f64);
+        }
+        for &(q, expected) in [(0.5, 500.0), (0.95, 950.0), (0.99, 990.0)].iter() {
+            let found = sketch.quantile(q).unwrap();
+            assert!((found - expected).abs() / expected <= 0.02, "p{} was {}", q, found);
+        }
+        assert_eq!(sketch.quantile(0.0), Some(1.0));
+        assert_eq!(sketch.quantile(1.0), Some(1000.0));
+        assert_eq!(sketch.sum(), 500500.0);
+
+        let mut mixed = InternalSketch::new();
+        for value in [-10.0, 0.0, 0.0, 5.0, ::std::f64::NAN].iter() {
+            mixed.add(*value);
+        }
+        assert_eq!(mixed.quantile(0.0), Some(-10.0));
+        assert_eq!(mixed.quantile(0.5), Some(0.0));
+        assert_eq!(mixed.min(), -10.0);
+        assert_eq!(mixed.max(), 5.0);
+    }
+}
@@ -0,0 +1,4 @@
+pub mod internal_hash;
+pub mod internal_random;
+pub mod internal_event_time;
+pub mod internal_sketch;
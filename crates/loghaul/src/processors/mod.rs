@@ -0,0 +1,14 @@
+pub mod split_lines_processor;
+pub mod fn_processor;
+pub mod multiline_processor;
+pub mod enrich_processor;
+pub mod internal;
+pub mod sample_processor;
+pub mod rate_limit_processor;
+pub mod dedup_processor;
+pub mod aggregate_metric;
+pub mod aggregate_processor;
+pub mod alert_rule;
+pub mod alert_processor;
+pub mod order_processor;
+pub mod session_processor;
@@ -0,0 +1,331 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+use std::time::Instant;
+use regex::bytes::Regex;
+use Processor;
+use LoghaulError;
+use LoghaulErrorCode;
+use records::record::Record;
+use metrics::metrics_registry::MetricsRegistry;
+
+const DEFAULT_MAX_LINES: usize = 500;
+const DEFAULT_TIMEOUT_MS: u64 = 1000;
+
+/// Merges consecutive lines from the same source into one record, eg. a stack
+/// trace and the log line that introduced it.
+///
+/// Which lines belong together is decided by up to three patterns:
+/// - `start`: a line matching it begins a new event, other lines join the current one
+/// - `continuation`: a line matching it joins the current event, other lines begin a new one
+/// - `end`: a line matching it is the last line of the current event
+///
+/// With both `start` and `continuation`, a line joins the current event if it
+/// matches `continuation` or doesn't match `start`. With only `end`, every line
+/// joins the current event until one matches it.
+///
+/// Lines are joined with `\n`, and the merged record keeps the source, fields and
+/// line terminator of its first line. An event is released as soon as the next one
+/// begins, when it reaches the maximum number of lines, when no line has joined it
+/// for the flush timeout, or when its source ends. Expects one line per record, so
+/// it usually follows `SplitLinesProcessor`.
+///
+/// Events cut short by the line limit are counted as `max_lines`, and events
+/// released by the timeout as `timeout`.
+///
+/// ```
+///     use loghaul::{Stream, SplitLinesProcessor, MultilineProcessor};
+///     let multiline = MultilineProcessor::new()
+///         .with_continuation(r"^(\s|Caused by:)").unwrap()
+///         .with_max_lines(200);
+///     let stream = Stream::new()
+///         .with_processor(SplitLinesProcessor::new())
+///         .with_processor(multiline);
+/// ```
+pub struct MultilineProcessor {
+    start: Option<Regex>,
+    continuation: Option<Regex>,
+    end: Option<Regex>,
+    max_lines: usize,
+    timeout: Duration,
+    pending: BTreeMap<String, PendingEvent>,
+    metrics: Option<(String, MetricsRegistry)>,
+}
+
+struct PendingEvent {
+    head: Record,
+    payload: Vec<u8>,
+    terminator: Vec<u8>,
+    lines: usize,
+    updated: Instant,
+}
+
+impl MultilineProcessor {
+    /// A processor without any patterns, which releases every line on its own
+    /// until a pattern is added
+    pub fn new() -> MultilineProcessor {
+        return MultilineProcessor {
+            start: None,
+            continuation: None,
+            end: None,
+            max_lines: DEFAULT_MAX_LINES,
+            timeout: Duration::from_millis(DEFAULT_TIMEOUT_MS),
+            pending: BTreeMap::new(),
+            metrics: None,
+        };
+    }
+
+    /// Begin a new event on every line matching this pattern
+    pub fn with_start(mut self, pattern: &str) -> Result<MultilineProcessor, LoghaulError> {
+        self.start = Some(compile(pattern)?);
+        return Ok(self);
+    }
+
+    /// Join every line matching this pattern to the current event
+    pub fn with_continuation(mut self, pattern: &str) -> Result<MultilineProcessor, LoghaulError> {
+        self.continuation = Some(compile(pattern)?);
+        return Ok(self);
+    }
+
+    /// End the current event with the first line matching this pattern
+    pub fn with_end(mut self, pattern: &str) -> Result<MultilineProcessor, LoghaulError> {
+        self.end = Some(compile(pattern)?);
+        return Ok(self);
+    }
+
+    /// Release an event once it has this many lines, 500 by default
+    pub fn with_max_lines(mut self, max_lines: usize) -> MultilineProcessor {
+        self.max_lines = max_lines.max(1);
+        return self;
+    }
+
+    /// Release an event once no line has joined it for this long, one second by default
+    pub fn with_timeout(mut self, timeout: Duration) -> MultilineProcessor {
+        self.timeout = timeout;
+        return self;
+    }
+
+    /// Whether a line joins the event currently pending
+    fn joins(&self, line: &[u8]) -> bool {
+        match self.continuation {
+            Some(ref continuation) if continuation.is_match(line) => {
+                return true;
+            }
+            _ => {}
+        }
+        return match (&self.start, &self.continuation, &self.end) {
+            (Some(start), _, _) => !start.is_match(line),
+            (None, None, Some(_)) => true,
+            _ => false,
+        };
+    }
+
+    fn count(&self, counter: &str) {
+        match self.metrics {
+            Some((ref id, ref metrics)) => metrics.processor_counter(id, counter, 1),
+            None => {}
+        }
+    }
+}
+
+impl Processor for MultilineProcessor {
+    fn process(&mut self, record: Record, output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        let (line, terminator) = split_terminator(&record.payload);
+        let ends = self.end.as_ref().map(|end| end.is_match(line)).unwrap_or(false);
+        let joins = self.pending.contains_key(&record.source) && self.joins(line);
+
+        if joins {
+            let full = {
+                let pending = self.pending.get_mut(&record.source).expect("pending event exists");
+                pending.payload.push(b'\n');
+                pending.payload.extend_from_slice(line);
+                pending.lines += 1;
+                pending.updated = Instant::now();
+                pending.lines >= self.max_lines
+            };
+            if full && !ends {
+                self.count("max_lines");
+            }
+            if full || ends {
+                match self.pending.remove(&record.source) {
+                    Some(pending) => output.push(pending.release()),
+                    None => {}
+                }
+            }
+            return Ok(());
+        }
+
+        match self.pending.remove(&record.source) {
+            Some(pending) => output.push(pending.release()),
+            None => {}
+        }
+        let event = PendingEvent {
+            payload: line.to_vec(),
+            terminator: terminator.to_vec(),
+            lines: 1,
+            updated: Instant::now(),
+            head: record,
+        };
+        if ends || self.max_lines == 1 {
+            output.push(event.release());
+        } else {
+            self.pending.insert(event.head.source.clone(), event);
+        }
+        return Ok(());
+    }
+
+    fn id(&self) -> Option<String> {
+        Some("multiline".to_string())
+    }
+
+    fn attach(&mut self, id: &str, metrics: &MetricsRegistry) {
+        self.metrics = Some((id.to_string(), metrics.clone()));
+    }
+
+    fn flush(&mut self, now: Instant, output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        let timeout = self.timeout;
+        let expired: Vec<String> = self.pending.iter()
+            .filter(|&(_, pending)| now.duration_since(pending.updated) >= timeout)
+            .map(|(source, _)| source.clone())
+            .collect();
+        for source in expired.iter() {
+            match self.pending.remove(source) {
+                Some(pending) => {
+                    self.count("timeout");
+                    output.push(pending.release());
+                }
+                None => {}
+            }
+        }
+        return Ok(());
+    }
+
+    fn end_source(&mut self, source: &str, output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        match self.pending.remove(source) {
+            Some(pending) => output.push(pending.release()),
+            None => {}
+        }
+        return Ok(());
+    }
+}
+
+impl PendingEvent {
+    fn release(self) -> Record {
+        let mut record = self.head;
+        record.payload = self.payload;
+        record.payload.extend_from_slice(&self.terminator);
+        return record;
+    }
+}
+
+fn compile(pattern: &str) -> Result<Regex, LoghaulError> {
+    return Regex::new(pattern).map_err(|e| LoghaulError::from(LoghaulErrorCode::ProcessorErr(e.to_string())));
+}
+
+/// Split a line into its content and its `\n` or `\r\n` terminator
+fn split_terminator(payload: &[u8]) -> (&[u8], &[u8]) {
+    let mut end = payload.len();
+    if end > 0 && payload[end - 1] == b'\n' {
+        end -= 1;
+        if end > 0 && payload[end - 1] == b'\r' {
+            end -= 1;
+        }
+    }
+    return payload.split_at(end);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use std::time::Instant;
+    use super::MultilineProcessor;
+    use Processor;
+    use records::record::Record;
+    use metrics::metrics_registry::MetricsRegistry;
+
+    fn feed(processor: &mut MultilineProcessor, source: &str, lines: &[&str]) -> Vec<String> {
+        let mut output = Vec::new();
+        for line in lines.iter() {
+            processor.process(Record::new(source, line.as_bytes().to_vec()), &mut output).unwrap();
+        }
+        return output.iter().map(|r| r.payload_str().unwrap().to_string()).collect();
+    }
+
+    #[test]
+    fn test_continuation_pattern() {
+        let mut multiline = MultilineProcessor::new().with_continuation(r"^(\s|Caused by:)").unwrap();
+        let events = feed(&mut multiline, "app", &[
+            "2024-05-01 ERROR request failed\n",
+            "java.lang.IllegalStateException: boom\n",
+            "\tat com.example.Handler.run(Handler.java:42)\n",
+            "Caused by: java.io.IOException: closed\r\n",
+            "\t... 12 more\n",
+            "2024-05-01 INFO recovered\n",
+        ]);
+        assert_eq!(events, vec!(
+            "2024-05-01 ERROR request failed\n",
+            "java.lang.IllegalStateException: boom\n\tat com.example.Handler.run(Handler.java:42)\nCaused by: java.io.IOException: closed\n\t... 12 more\n",
+        ));
+
+        let mut output = Vec::new();
+        multiline.end_source("app", &mut output).unwrap();
+        assert_eq!(output[0].payload_str(), Some("2024-05-01 INFO recovered\n"));
+    }
+
+    #[test]
+    fn test_start_and_end_patterns() {
+        let mut multiline = MultilineProcessor::new().with_start(r"^Traceback|^\d{4}-").unwrap();
+        let events = feed(&mut multiline, "py", &[
+            "2024-05-01 ERROR failed\n",
+            "Traceback (most recent call last):\n",
+            "  File \"app.py\", line 3, in <module>\n",
+            "ValueError: bad\n",
+            "2024-05-01 INFO next\n",
+        ]);
+        assert_eq!(events, vec!(
+            "2024-05-01 ERROR failed\n",
+            "Traceback (most recent call last):\n  File \"app.py\", line 3, in <module>\nValueError: bad\n",
+        ));
+
+        let mut multiline = MultilineProcessor::new().with_end(r";$").unwrap();
+        let events = feed(&mut multiline, "sql", &["SELECT *", "FROM users", "WHERE id = 1;", "DELETE FROM t;"]);
+        assert_eq!(events, vec!("SELECT *\nFROM users\nWHERE id = 1;", "DELETE FROM t;"));
+    }
+
+    #[test]
+    fn test_sources_are_merged_separately() {
+        let mut multiline = MultilineProcessor::new().with_continuation(r"^\s").unwrap();
+        let mut output = Vec::new();
+        multiline.process(Record::new("a", b"a1".to_vec()), &mut output).unwrap();
+        multiline.process(Record::new("b", b"b1".to_vec()), &mut output).unwrap();
+        multiline.process(Record::new("a", b" a2".to_vec()), &mut output).unwrap();
+        multiline.process(Record::new("b", b" b2".to_vec()), &mut output).unwrap();
+        assert!(output.is_empty());
+        multiline.end_source("b", &mut output).unwrap();
+        assert_eq!(output[0].payload_str(), Some("b1\n b2"));
+        assert_eq!(output[0].source, "b");
+    }
+
+    #[test]
+    fn test_max_lines_and_timeout() {
+        let metrics = MetricsRegistry::new();
+        let mut multiline = MultilineProcessor::new()
+            .with_continuation(r"^\s").unwrap()
+            .with_max_lines(3)
+            .with_timeout(Duration::from_millis(500));
+        multiline.attach("multiline", &metrics);
+
+        let events = feed(&mut multiline, "app", &["head", " 1", " 2", " 3", " 4"]);
+        assert_eq!(events, vec!("head\n 1\n 2"));
+
+        let mut output = Vec::new();
+        multiline.flush(Instant::now(), &mut output).unwrap();
+        assert!(output.is_empty());
+        multiline.flush(Instant::now() + Duration::from_millis(600), &mut output).unwrap();
+        assert_eq!(output[0].payload_str(), Some(" 3\n 4"));
+
+        let counters = metrics.snapshot().processor("multiline").unwrap().counters.clone();
+        assert_eq!(counters.get("max_lines"), Some(&1));
+        assert_eq!(counters.get("timeout"), Some(&1));
+        assert!(MultilineProcessor::new().with_start("(").is_err());
+    }
+}
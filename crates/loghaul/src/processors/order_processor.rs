@@ -0,0 +1,316 @@
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::VecDeque;
+use std::time::Duration;
+use std::time::Instant;
+use Processor;
+use LoghaulError;
+use records::record::Record;
+use records::record_value::RecordValue;
+use metrics::metrics_registry::MetricsRegistry;
+use processors::internal::internal_event_time;
+
+const DEFAULT_MAX_BUFFERED: usize = 100000;
+
+/// What an `OrderProcessor` does with a record older than records it already released
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OrderLatePolicy {
+    /// Release it at once, out of order
+    Pass,
+    /// Discard it
+    Drop,
+    /// Release it at once, only to the target with this id
+    Route(String),
+}
+
+/// Holds records back for a bounded delay and releases them sorted by event time,
+/// so records merged from several sources come out in chronological order.
+///
+/// The event time is read from the `timestamp` field by default, as RFC 3339 like
+/// the `TimestampProcessor` writes it, or as milliseconds since the epoch. A record
+/// without one stays next to the record before it from the same source, or is
+/// released at once if there is none. Records with the same time keep the order
+/// they arrived in.
+///
+/// The watermark is the latest event time seen less the delay: records at or before
+/// it are released. So that a quiet stream isn't held up, a record is also released,
+/// with every record before it, once it has waited the delay, and everything is
+/// released when the last source ends or more than `max_buffered` records are held.
+///
+/// A record older than one already released is late, and is handled by the late
+/// policy, which passes it on at once by default; late records that are passed on
+/// get a `late` field set to true. Late records are counted as `late`, and records
+/// released early because the buffer was full as `overflow`.
+///
+/// ```
+///     use std::time::Duration;
+///     use loghaul::{Stream, OrderProcessor, OrderLatePolicy};
+///     let order = OrderProcessor::new(Duration::from_secs(2))
+///         .with_late_policy(OrderLatePolicy::Route("late".to_string()));
+///     let stream = Stream::new().with_processor(order);
+/// ```
+pub struct OrderProcessor {
+    delay: Duration,
+    time_field: String,
+    late_policy: OrderLatePolicy,
+    max_buffered: usize,
+    buffer: BTreeMap<(i64, u64), Record>,
+    arrivals: VecDeque<(Instant, i64)>,
+    sequence: u64,
+    max_event_time: Option<i64>,
+    released_until: Option<i64>,
+    last_times: BTreeMap<String, i64>,
+    sources: BTreeSet<String>,
+    metrics: Option<(String, MetricsRegistry)>,
+}
+
+impl OrderProcessor {
+    /// Hold records for up to `delay`, rounded to milliseconds
+    pub fn new(delay: Duration) -> OrderProcessor {
+        return OrderProcessor {
+            delay: delay,
+            time_field: "timestamp".to_string(),
+            late_policy: OrderLatePolicy::Pass,
+            max_buffered: DEFAULT_MAX_BUFFERED,
+            buffer: BTreeMap::new(),
+            arrivals: VecDeque::new(),
+            sequence: 0,
+            max_event_time: None,
+            released_until: None,
+            last_times: BTreeMap::new(),
+            sources: BTreeSet::new(),
+            metrics: None,
+        };
+    }
+
+    /// Read the event time from this field instead of `timestamp`
+    pub fn with_time_field(mut self, field: &str) -> OrderProcessor {
+        self.time_field = field.to_string();
+        return self;
+    }
+
+    pub fn with_late_policy(mut self, policy: OrderLatePolicy) -> OrderProcessor {
+        self.late_policy = policy;
+        return self;
+    }
+
+    /// Hold at most this many records, 100000 by default
+    pub fn with_max_buffered(mut self, max_buffered: usize) -> OrderProcessor {
+        self.max_buffered = max_buffered.max(1);
+        return self;
+    }
+
+    fn process_at(&mut self, mut record: Record, now: Instant, output: &mut Vec<Record>) {
+        self.sources.insert(record.source.clone());
+        let time = match internal_event_time::field_time(&record, &self.time_field).or_else(|| self.last_times.get(&record.source).cloned()) {
+            Some(time) => time,
+            None => {
+                output.push(record);
+                return;
+            }
+        };
+        self.last_times.insert(record.source.clone(), time);
+
+        if self.released_until.map(|t| time < t).unwrap_or(false) {
+            self.count("late", 1);
+            match self.late_policy {
+                OrderLatePolicy::Pass => {}
+                OrderLatePolicy::Drop => return,
+                OrderLatePolicy::Route(ref target) => record.route_to(target),
+            }
+            record.set_field("late", RecordValue::Bool(true));
+            output.push(record);
+            return;
+        }
+
+        self.buffer.insert((time, self.sequence), record);
+        self.sequence += 1;
+        self.arrivals.push_back((now, time));
+        if self.max_event_time.map(|t| time > t).unwrap_or(true) {
+            self.max_event_time = Some(time);
+        }
+        let watermark = self.max_event_time.unwrap_or(time).saturating_sub(internal_event_time::millis_of(self.delay));
+        self.release(watermark, output);
+
+        let overflow = self.buffer.len().saturating_sub(self.max_buffered);
+        if overflow > 0 {
+            let cutoff = self.buffer.keys().nth(overflow - 1).map(|&(t, _)| t).unwrap_or(time);
+            let held = self.buffer.len();
+            self.release(cutoff, output);
+            self.count("overflow", (held - self.buffer.len()) as u64);
+        }
+    }
+
+    fn flush_at(&mut self, now: Instant, output: &mut Vec<Record>) {
+        let mut cutoff = None;
+        while self.arrivals.front().and_then(|&(arrived, _)| arrived.checked_add(self.delay)).map(|due| due <= now).unwrap_or(false) {
+            let (_, time) = self.arrivals.pop_front().unwrap();
+            cutoff = Some(cutoff.map(|c: i64| c.max(time)).unwrap_or(time));
+        }
+        match cutoff {
+            Some(cutoff) => self.release(cutoff, output),
+            None => {}
+        }
+    }
+
+    /// Release every held record at or before a time, in order
+    fn release(&mut self, until: i64, output: &mut Vec<Record>) {
+        loop {
+            let key = match self.buffer.keys().next() {
+                Some(&key) if key.0 <= until => key,
+                _ => break,
+            };
+            output.push(self.buffer.remove(&key).unwrap());
+            self.released_until = Some(self.released_until.map(|t| t.max(key.0)).unwrap_or(key.0));
+        }
+        if self.buffer.is_empty() {
+            self.arrivals.clear();
+        }
+    }
+
+    fn count(&self, name: &str, n: u64) {
+        match self.metrics {
+            Some((ref id, ref metrics)) if n > 0 => metrics.processor_counter(id, name, n),
+            _ => {}
+        }
+    }
+}
+
+impl Processor for OrderProcessor {
+    fn process(&mut self, record: Record, output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        self.process_at(record, Instant::now(), output);
+        return Ok(());
+    }
+
+    fn id(&self) -> Option<String> {
+        Some("order".to_string())
+    }
+
+    fn attach(&mut self, id: &str, metrics: &MetricsRegistry) {
+        self.metrics = Some((id.to_string(), metrics.clone()));
+    }
+
+    fn flush(&mut self, now: Instant, output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        self.flush_at(now, output);
+        return Ok(());
+    }
+
+    fn end_source(&mut self, source: &str, output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        self.sources.remove(source);
+        self.last_times.remove(source);
+        if self.sources.is_empty() {
+            self.release(::std::i64::MAX, output);
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use std::time::Instant;
+    use super::OrderLatePolicy;
+    use super::OrderProcessor;
+    use Processor;
+    use records::record::Record;
+    use records::record_value::RecordValue;
+    use metrics::metrics_registry::MetricsRegistry;
+
+    fn event(source: &str, second: u32) -> Record {
+        let mut record = Record::new(source, format!("{}:{}\n", source, second).into_bytes());
+        record.set_field("timestamp", format!("2024-05-01T10:00:{:02}Z", second));
+        return record;
+    }
+
+    fn payloads(records: &[Record]) -> Vec<&str> {
+        return records.iter().map(|r| r.payload_str().unwrap().trim()).collect();
+    }
+
+    #[test]
+    fn test_merges_sources_by_event_time() {
+        let metrics = MetricsRegistry::new();
+        let mut order = OrderProcessor::new(Duration::from_secs(5));
+        order.attach("order", &metrics);
+        let now = Instant::now();
+        let mut output = Vec::new();
+        for &(source, second) in [("a", 1), ("a", 4), ("b", 2), ("b", 3)].iter() {
+            order.process_at(event(source, second), now, &mut output);
+        }
+        assert!(output.is_empty());
+        // 10s less the delay releases everything up to 5s
+        order.process_at(event("b", 10), now, &mut output);
+        order.process_at(event("a", 7), now, &mut output);
+        assert_eq!(payloads(&output), vec!("a:1", "b:2", "b:3", "a:4"));
+
+        // A line without a timestamp stays after the line before it from its source
+        let mut continuation = Record::new("a", b"  at Main.java:7\n".to_vec());
+        continuation.set_field("level", "trace");
+        order.process_at(continuation, now, &mut output);
+        order.process_at(event("b", 3), now, &mut output);
+        assert_eq!(output.len(), 5);
+        assert_eq!(output[4].field("late"), Some(&RecordValue::Bool(true)));
+
+        output.clear();
+        order.end_source("a", &mut output).unwrap();
+        assert!(output.is_empty());
+        order.end_source("b", &mut output).unwrap();
+        assert_eq!(payloads(&output), vec!("a:7", "at Main.java:7", "b:10"));
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.processor("order").unwrap().counters.get("late"), Some(&1));
+    }
+
+    #[test]
+    fn test_releases_after_delay_and_when_full() {
+        let mut order = OrderProcessor::new(Duration::from_secs(5))
+            .with_late_policy(OrderLatePolicy::Route("late".to_string()))
+            .with_max_buffered(3);
+        let start = Instant::now();
+        let mut output = Vec::new();
+        order.process_at(event("a", 3), start, &mut output);
+        order.process_at(event("b", 1), start + Duration::from_secs(2), &mut output);
+        order.flush_at(start + Duration::from_secs(4), &mut output);
+        assert!(output.is_empty());
+        // The first record has waited long enough, and takes the earlier one with it
+        order.flush_at(start + Duration::from_secs(5), &mut output);
+        assert_eq!(payloads(&output), vec!("b:1", "a:3"));
+
+        output.clear();
+        order.process_at(event("a", 2), start, &mut output);
+        assert_eq!(output[0].route, Some("late".to_string()));
+
+        output.clear();
+        for second in [9, 8, 7, 6].iter() {
+            order.process_at(event("a", *second), start, &mut output);
+        }
+        assert_eq!(payloads(&output), vec!("a:6"));
+
+        let mut dropping = OrderProcessor::new(Duration::from_secs(0)).with_late_policy(OrderLatePolicy::Drop);
+        output.clear();
+        dropping.process_at(event("a", 5), start, &mut output);
+        dropping.process_at(event("a", 4), start, &mut output);
+        assert_eq!(payloads(&output), vec!("a:5"));
+    }
+    #[test]
+    fn test_extreme_event_times_do_not_overflow() {
+        let start = Instant::now();
+        let mut output = Vec::new();
+        let mut order = OrderProcessor::new(Duration::from_secs(5));
+        for &millis in [::std::i64::MIN, ::std::i64::MAX].iter() {
+            let mut record = Record::new("a", format!("{}\n", millis).into_bytes());
+            record.set_field("timestamp", RecordValue::Int(millis));
+            order.process_at(record, start, &mut output);
+        }
+        order.end_source("a", &mut output).unwrap();
+        assert_eq!(payloads(&output), vec!(::std::i64::MIN.to_string().as_str(), ::std::i64::MAX.to_string().as_str()));
+
+        // A delay too long to add to an instant never releases records early
+        let mut forever = OrderProcessor::new(Duration::from_secs(::std::u64::MAX));
+        output.clear();
+        forever.process_at(event("a", 1), start, &mut output);
+        forever.flush_at(start + Duration::from_secs(60), &mut output);
+        assert!(output.is_empty());
+        forever.end_source("a", &mut output).unwrap();
+        assert_eq!(payloads(&output), vec!("a:1"));
+    }
+}
@@ -0,0 +1,264 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+use std::time::Instant;
+use Processor;
+use LoghaulError;
+use records::record::Record;
+use records::record_value::RecordValue;
+use metrics::metrics_registry::MetricsRegistry;
+
+const DEFAULT_SUMMARY_SECS: u64 = 10;
+const DEFAULT_MAX_KEYS: usize = 10000;
+
+/// Limits how many records pass per source, or per value of a key field, with a
+/// token bucket: up to `burst` records pass at once, refilled at `rate` per second.
+///
+/// Records over the limit are dropped and counted. While records are being
+/// dropped, a summary record is emitted every summary interval for each key
+/// that dropped any, with the payload `<N> records suppressed by rate limit` and a newline,
+/// and the count in its `suppressed` field; keyed limits also set `rate_limit_key`.
+/// Summaries are emitted from the source of the last dropped record.
+///
+/// At most `max_keys` buckets are kept. When a new key arrives and there is no
+/// room, buckets that are full again are forgotten; if none are, the new key
+/// shares a single overflow bucket.
+///
+/// Dropped records are counted as `suppressed`.
+///
+/// ```
+///     use std::time::Duration;
+///     use loghaul::{Stream, RateLimitProcessor};
+///     let limit = RateLimitProcessor::new(100.0, 500)
+///         .with_key_field("service")
+///         .with_summary_interval(Duration::from_secs(30));
+///     let stream = Stream::new().with_processor(limit);
+/// ```
+pub struct RateLimitProcessor {
+    rate: f64,
+    burst: f64,
+    key: Option<String>,
+    summary_interval: Duration,
+    max_keys: usize,
+    buckets: BTreeMap<String, TokenBucket>,
+    overflow: Option<TokenBucket>,
+    summary_due: Option<Instant>,
+    metrics: Option<(String, MetricsRegistry)>,
+}
+
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+    suppressed: u64,
+    last_source: String,
+}
+
+impl RateLimitProcessor {
+    /// Pass `rate` records per second, and bursts of up to `burst` records
+    pub fn new(rate: f64, burst: u64) -> RateLimitProcessor {
+        return RateLimitProcessor {
+            rate: rate.max(0.0),
+            burst: burst.max(1) as f64,
+            key: None,
+            summary_interval: Duration::from_secs(DEFAULT_SUMMARY_SECS),
+            max_keys: DEFAULT_MAX_KEYS,
+            buckets: BTreeMap::new(),
+            overflow: None,
+            summary_due: None,
+            metrics: None,
+        };
+    }
+
+    /// Limit each value of this field separately, instead of each source
+    pub fn with_key_field(mut self, field: &str) -> RateLimitProcessor {
+        self.key = Some(field.to_string());
+        return self;
+    }
+
+    /// How often to emit suppression summaries, every ten seconds by default
+    pub fn with_summary_interval(mut self, interval: Duration) -> RateLimitProcessor {
+        self.summary_interval = interval;
+        return self;
+    }
+
+    /// Keep at most this many buckets, 10000 by default
+    pub fn with_max_keys(mut self, max_keys: usize) -> RateLimitProcessor {
+        self.max_keys = max_keys.max(1);
+        return self;
+    }
+
+    fn key_of(&self, record: &Record) -> String {
+        return match self.key {
+            Some(ref field) => record.field(field).map(|value| value.to_string()).unwrap_or(String::new()),
+            None => record.source.clone(),
+        };
+    }
+
+    /// Take a token for a record, returning whether it may pass
+    fn admit(&mut self, key: String, source: &str, now: Instant) -> bool {
+        let (rate, burst) = (self.rate, self.burst);
+        if !self.buckets.contains_key(&key) && self.buckets.len() >= self.max_keys {
+            self.forget_full_buckets(now);
+        }
+        let bucket = if self.buckets.contains_key(&key) || self.buckets.len() < self.max_keys {
+            self.buckets.entry(key).or_insert_with(|| TokenBucket::new(burst, now))
+        } else {
+            self.overflow.get_or_insert_with(|| TokenBucket::new(burst, now))
+        };
+        bucket.refill(rate, burst, now);
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return true;
+        }
+        bucket.suppressed += 1;
+        bucket.last_source = source.to_string();
+        if self.summary_due.is_none() {
+            self.summary_due = Some(now + self.summary_interval);
+        }
+        return false;
+    }
+
+    fn forget_full_buckets(&mut self, now: Instant) {
+        let (rate, burst) = (self.rate, self.burst);
+        for bucket in self.buckets.values_mut() {
+            bucket.refill(rate, burst, now);
+        }
+        self.buckets.retain(|_, bucket| bucket.suppressed > 0 || bucket.tokens < burst);
+    }
+
+    /// Emit a summary for every bucket that suppressed records and matches the filter
+    fn summarize(&mut self, output: &mut Vec<Record>, only_source: Option<&str>) {
+        let keyed = self.key.is_some();
+        let buckets = self.buckets.iter_mut().map(|(key, bucket)| (Some(key.as_str()), bucket)).chain(self.overflow.iter_mut().map(|bucket| (None, bucket)));
+        for (key, bucket) in buckets {
+            if bucket.suppressed == 0 || only_source.map(|s| s != bucket.last_source).unwrap_or(false) {
+                continue;
+            }
+            let mut summary = Record::new(&bucket.last_source, format!("{} records suppressed by rate limit\n", bucket.suppressed).into_bytes());
+            summary.set_field("suppressed", RecordValue::Int(bucket.suppressed as i64));
+            match key {
+                Some(key) if keyed => summary.set_field("rate_limit_key", key.to_string()),
+                _ => {}
+            }
+            output.push(summary);
+            bucket.suppressed = 0;
+        }
+    }
+}
+
+impl TokenBucket {
+    fn new(burst: f64, now: Instant) -> TokenBucket {
+        return TokenBucket {
+            tokens: burst,
+            updated: now,
+            suppressed: 0,
+            last_source: String::new(),
+        };
+    }
+
+    fn refill(&mut self, rate: f64, burst: f64, now: Instant) {
+        if now > self.updated {
+            let elapsed = now.duration_since(self.updated);
+            let seconds = elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1e9;
+            self.tokens = (self.tokens + seconds * rate).min(burst);
+            self.updated = now;
+        }
+    }
+}
+
+impl Processor for RateLimitProcessor {
+    fn process(&mut self, record: Record, output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        let key = self.key_of(&record);
+        if self.admit(key, &record.source, Instant::now()) {
+            output.push(record);
+            return Ok(());
+        }
+        match self.metrics {
+            Some((ref id, ref metrics)) => metrics.processor_counter(id, "suppressed", 1),
+            None => {}
+        }
+        return Ok(());
+    }
+
+    fn id(&self) -> Option<String> {
+        Some("rate_limit".to_string())
+    }
+
+    fn attach(&mut self, id: &str, metrics: &MetricsRegistry) {
+        self.metrics = Some((id.to_string(), metrics.clone()));
+    }
+
+    fn flush(&mut self, now: Instant, output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        match self.summary_due {
+            Some(due) if due <= now => {
+                self.summary_due = None;
+                self.summarize(output, None);
+            }
+            _ => {}
+        }
+        return Ok(());
+    }
+
+    fn end_source(&mut self, source: &str, output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        self.summarize(output, Some(source));
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use std::time::Instant;
+    use super::RateLimitProcessor;
+    use Processor;
+    use records::record::Record;
+    use records::record_value::RecordValue;
+
+    #[test]
+    fn test_token_bucket_per_source() {
+        let mut limit = RateLimitProcessor::new(2.0, 3).with_summary_interval(Duration::from_secs(5));
+        let start = Instant::now();
+        let admitted: Vec<bool> = (0..5).map(|_| limit.admit("a".to_string(), "a", start)).collect();
+        assert_eq!(admitted, vec!(true, true, true, false, false));
+        assert!(limit.admit("b".to_string(), "b", start));
+
+        // Half a second refills one token at two per second
+        let later = start + Duration::from_millis(500);
+        assert!(limit.admit("a".to_string(), "a", later));
+        assert!(!limit.admit("a".to_string(), "a", later));
+
+        let mut output = Vec::new();
+        limit.flush(start + Duration::from_secs(4), &mut output).unwrap();
+        assert!(output.is_empty());
+        limit.flush(start + Duration::from_secs(5), &mut output).unwrap();
+        assert_eq!(output.len(), 1);
+        assert_eq!(output[0].source, "a");
+        assert_eq!(output[0].payload_str(), Some("3 records suppressed by rate limit\n"));
+        assert_eq!(output[0].field("suppressed"), Some(&RecordValue::Int(3)));
+        assert_eq!(output[0].field("rate_limit_key"), None);
+
+        limit.flush(start + Duration::from_secs(60), &mut output).unwrap();
+        assert_eq!(output.len(), 1);
+    }
+
+    #[test]
+    fn test_keyed_limits_and_overflow() {
+        let mut limit = RateLimitProcessor::new(0.0, 1).with_key_field("service").with_max_keys(2);
+        let mut output = Vec::new();
+        for service in ["api", "api", "db", "web", "web", "cache"].iter() {
+            let mut record = Record::new("app", service.as_bytes().to_vec());
+            record.set_field("service", *service);
+            limit.process(record, &mut output).unwrap();
+        }
+        // web and cache share the overflow bucket, which only had one token
+        let passed: Vec<&str> = output.iter().map(|r| r.payload_str().unwrap()).collect();
+        assert_eq!(passed, vec!("api", "db", "web"));
+
+        output.clear();
+        limit.end_source("app", &mut output).unwrap();
+        let summaries: Vec<(Option<&RecordValue>, Option<&RecordValue>)> = output.iter().map(|r| (r.field("rate_limit_key"), r.field("suppressed"))).collect();
+        assert_eq!(summaries, vec!(
+            (Some(&RecordValue::from("api")), Some(&RecordValue::Int(1))),
+            (None, Some(&RecordValue::Int(2))),
+        ));
+    }
+}
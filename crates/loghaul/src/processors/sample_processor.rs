@@ -0,0 +1,123 @@
+use Processor;
+use LoghaulError;
+use records::record::Record;
+use metrics::metrics_registry::MetricsRegistry;
+use processors::internal::internal_hash::stable_hash;
+use processors::internal::internal_random::InternalRandom;
+
+/// Keeps one record in every N and drops the rest.
+///
+/// Records are picked at random, unless a key field is set: then the choice
+/// depends only on the key's value, so every record sharing a value, such as a
+/// trace id, is kept or dropped together, run after run. Records without the key
+/// field are picked at random.
+///
+/// Dropped records are counted as `sampled_out`.
+///
+/// ```
+///     use loghaul::{Stream, SampleProcessor};
+///     let stream = Stream::new().with_processor(SampleProcessor::new(100).with_key_field("trace_id"));
+/// ```
+pub struct SampleProcessor {
+    rate: u64,
+    key: Option<String>,
+    random: InternalRandom,
+    metrics: Option<(String, MetricsRegistry)>,
+}
+
+impl SampleProcessor {
+    /// Keep one record in every `rate`; a rate of 1 keeps everything
+    pub fn new(rate: u64) -> SampleProcessor {
+        return SampleProcessor {
+            rate: rate.max(1),
+            key: None,
+            random: InternalRandom::new(),
+            metrics: None,
+        };
+    }
+
+    /// Decide by the value of this field instead of at random
+    pub fn with_key_field(mut self, field: &str) -> SampleProcessor {
+        self.key = Some(field.to_string());
+        return self;
+    }
+
+    fn keep(&mut self, record: &Record) -> bool {
+        let value = match self.key {
+            Some(ref key) => record.field(key).map(|value| value.to_string()),
+            None => None,
+        };
+        return match value {
+            Some(value) => stable_hash(value.as_bytes()) % self.rate == 0,
+            None => self.random.next() % self.rate == 0,
+        };
+    }
+}
+
+impl Processor for SampleProcessor {
+    fn process(&mut self, record: Record, output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        if self.keep(&record) {
+            output.push(record);
+            return Ok(());
+        }
+        match self.metrics {
+            Some((ref id, ref metrics)) => metrics.processor_counter(id, "sampled_out", 1),
+            None => {}
+        }
+        return Ok(());
+    }
+
+    fn id(&self) -> Option<String> {
+        Some("sample".to_string())
+    }
+
+    fn attach(&mut self, id: &str, metrics: &MetricsRegistry) {
+        self.metrics = Some((id.to_string(), metrics.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SampleProcessor;
+    use Processor;
+    use records::record::Record;
+    use metrics::metrics_registry::MetricsRegistry;
+
+    #[test]
+    fn test_random_sampling_keeps_about_one_in_n() {
+        let metrics = MetricsRegistry::new();
+        let mut sample = SampleProcessor::new(10);
+        sample.attach("sample", &metrics);
+        let mut output = Vec::new();
+        for _ in 0..10000 {
+            sample.process(Record::new("app", b"debug".to_vec()), &mut output).unwrap();
+        }
+        assert!(output.len() > 800 && output.len() < 1200, "kept {}", output.len());
+        let dropped = metrics.snapshot().processor("sample").unwrap().counters.get("sampled_out").cloned();
+        assert_eq!(dropped, Some(10000 - output.len() as u64));
+
+        let mut everything = SampleProcessor::new(0);
+        everything.process(Record::new("app", b"x".to_vec()), &mut output).unwrap();
+        assert_eq!(output.last().unwrap().payload, b"x");
+    }
+
+    #[test]
+    fn test_keyed_sampling_is_consistent() {
+        let mut first = SampleProcessor::new(4).with_key_field("trace");
+        let mut second = SampleProcessor::new(4).with_key_field("trace");
+        let mut kept_first = Vec::new();
+        let mut kept_second = Vec::new();
+        for round in 0..3 {
+            for trace in 0..200 {
+                let mut record = Record::new("app", format!("{} {}", round, trace).into_bytes());
+                record.set_field("trace", format!("trace-{}", trace));
+                first.process(record.clone(), &mut kept_first).unwrap();
+                second.process(record, &mut kept_second).unwrap();
+            }
+        }
+        assert_eq!(kept_first, kept_second);
+        // A kept trace is kept in every round
+        assert_eq!(kept_first.len() % 3, 0);
+        assert!(kept_first.len() > 90 && kept_first.len() < 210, "kept {}", kept_first.len());
+    }
+}
@@ -0,0 +1,395 @@
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::time::Duration;
+use std::time::Instant;
+use Processor;
+use LoghaulError;
+use records::record::Record;
+use records::record_value::RecordValue;
+use routing::route::RouteSelector;
+use metrics::metrics_registry::MetricsRegistry;
+use processors::internal::internal_event_time;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_MAX_SESSIONS: usize = 10000;
+const DEFAULT_MAX_EVENTS: usize = 1000;
+
+/// Groups records sharing the value of a key field, such as `request_id`, into
+/// sessions, and emits one combined record per session once no record has joined
+/// it for the inactivity timeout. Records without the key pass through unchanged.
+///
+/// The session record comes from the source `session`, with the payloads of its
+/// records sorted by event time as its payload, each ending in a newline. Its fields
+/// are the key, `events`, `sources` (the sources it was read from, comma separated,
+/// in order of appearance), `started` and `ended` in RFC 3339, `duration_ms`, `error`,
+/// set when any record was an error, and `closed`, which is `timeout`, `evicted` or
+/// `end`. Event times are read from the `timestamp` field like the `TimestampProcessor`
+/// writes it, or as milliseconds since the epoch, and default to the time a record
+/// was processed. A record is an error when it meets any error selector; by default,
+/// when its `level` field is `error`, `fatal` or `critical`.
+///
+/// At most `max_sessions` sessions are kept; a new session evicts the one that has
+/// been inactive longest. A session keeps at most `max_events` records, and counts
+/// any others in its `events_dropped` field. All sessions close when the last source
+/// ends. Sessions emitted are counted as `sessions`, evicted sessions as `evicted`,
+/// and records left out of a full session as `events_dropped`.
+///
+/// ```
+///     use std::time::Duration;
+///     use loghaul::{Stream, SessionProcessor, RouteSelector};
+///     let sessions = SessionProcessor::new("request_id")
+///         .with_timeout(Duration::from_secs(10))
+///         .with_error_selector(RouteSelector::field_matches("status", "^5").unwrap())
+///         .with_max_sessions(50000);
+///     let stream = Stream::new().with_processor(sessions);
+/// ```
+pub struct SessionProcessor {
+    key: String,
+    timeout: Duration,
+    max_sessions: usize,
+    max_events: usize,
+    time_field: String,
+    errors: Vec<RouteSelector>,
+    default_errors: bool,
+    source: String,
+    target: Option<String>,
+    sessions: BTreeMap<String, Session>,
+    activity: BTreeMap<(Instant, u64), String>,
+    sequence: u64,
+    sources: BTreeSet<String>,
+    clock: (Instant, i64),
+    metrics: Option<(String, MetricsRegistry)>,
+}
+
+struct Session {
+    events: Vec<(i64, u64, Record)>,
+    sources: Vec<String>,
+    error: bool,
+    dropped: u64,
+    active: (Instant, u64),
+}
+
+impl SessionProcessor {
+    /// Group records by the value of this field
+    pub fn new(key: &str) -> SessionProcessor {
+        return SessionProcessor {
+            key: key.to_string(),
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECS),
+            max_sessions: DEFAULT_MAX_SESSIONS,
+            max_events: DEFAULT_MAX_EVENTS,
+            time_field: "timestamp".to_string(),
+            errors: vec!(RouteSelector::field_matches("level", "(?i)^(error|fatal|critical)$").unwrap()),
+            default_errors: true,
+            source: "session".to_string(),
+            target: None,
+            sessions: BTreeMap::new(),
+            activity: BTreeMap::new(),
+            sequence: 0,
+            sources: BTreeSet::new(),
+            clock: (Instant::now(), internal_event_time::now_millis()),
+            metrics: None,
+        };
+    }
+
+    /// Close a session once no record has joined it for this long, 30 seconds by default
+    pub fn with_timeout(mut self, timeout: Duration) -> SessionProcessor {
+        self.timeout = timeout;
+        return self;
+    }
+
+    /// Keep at most this many sessions, 10000 by default
+    pub fn with_max_sessions(mut self, max_sessions: usize) -> SessionProcessor {
+        self.max_sessions = max_sessions.max(1);
+        return self;
+    }
+
+    /// Keep at most this many records per session, 1000 by default
+    pub fn with_max_events(mut self, max_events: usize) -> SessionProcessor {
+        self.max_events = max_events.max(1);
+        return self;
+    }
+
+    /// Read the event time from this field instead of `timestamp`
+    pub fn with_time_field(mut self, field: &str) -> SessionProcessor {
+        self.time_field = field.to_string();
+        return self;
+    }
+
+    /// Count records meeting this selector as errors, instead of those with an error `level`;
+    /// a record meeting any of several selectors is an error
+    pub fn with_error_selector(mut self, selector: RouteSelector) -> SessionProcessor {
+        if self.default_errors {
+            self.errors.clear();
+            self.default_errors = false;
+        }
+        self.errors.push(selector);
+        return self;
+    }
+
+    /// Emit sessions from this source instead of `session`
+    pub fn with_source(mut self, source: &str) -> SessionProcessor {
+        self.source = source.to_string();
+        return self;
+    }
+
+    /// Send sessions only to the target with this id, instead of routing them
+    pub fn with_target(mut self, target: &str) -> SessionProcessor {
+        self.target = Some(target.to_string());
+        return self;
+    }
+
+    fn event_time(&self, record: &Record, now: Instant) -> i64 {
+        return internal_event_time::field_time(record, &self.time_field).unwrap_or_else(|| internal_event_time::clock_time(self.clock, now));
+    }
+
+    fn process_at(&mut self, record: Record, now: Instant, output: &mut Vec<Record>) {
+        self.sources.insert(record.source.clone());
+        let key = match record.field(&self.key) {
+            Some(RecordValue::Null) | None => {
+                output.push(record);
+                return;
+            }
+            Some(value) => value.to_string(),
+        };
+        if !self.sessions.contains_key(&key) && self.sessions.len() >= self.max_sessions {
+            let oldest = self.activity.values().next().cloned();
+            match oldest {
+                Some(oldest) => {
+                    self.close(&oldest, "evicted", output);
+                    self.count("evicted", 1);
+                }
+                None => {}
+            }
+        }
+
+        let time = self.event_time(&record, now);
+        let error = self.errors.iter().any(|s| s.matches(&record));
+        let active = (now, self.sequence);
+        self.sequence += 1;
+        let max_events = self.max_events;
+        let session = self.sessions.entry(key.clone()).or_insert_with(|| Session {
+            events: Vec::new(),
+            sources: Vec::new(),
+            error: false,
+            dropped: 0,
+            active: active,
+        });
+        self.activity.remove(&session.active);
+        self.activity.insert(active, key);
+        session.active = active;
+        session.error |= error;
+        if !session.sources.contains(&record.source) {
+            session.sources.push(record.source.clone());
+        }
+        if session.events.len() >= max_events {
+            session.dropped += 1;
+            self.count("events_dropped", 1);
+            return;
+        }
+        let position = session.events.len() as u64;
+        session.events.push((time, position, record));
+    }
+
+    fn flush_at(&mut self, now: Instant, output: &mut Vec<Record>) {
+        let due: Vec<String> = self.activity.iter()
+            .take_while(|&(&(active, _), _)| active.checked_add(self.timeout).map(|due| due <= now).unwrap_or(false))
+            .map(|(_, key)| key.clone())
+            .collect();
+        for key in due {
+            self.close(&key, "timeout", output);
+        }
+    }
+
+    /// Emit the combined record of a session and forget it
+    fn close(&mut self, key: &str, reason: &str, output: &mut Vec<Record>) {
+        let mut session = match self.sessions.remove(key) {
+            Some(session) => session,
+            None => return,
+        };
+        self.activity.remove(&session.active);
+        session.events.sort_by_key(|&(time, position, _)| (time, position));
+
+        let mut payload = Vec::new();
+        for &(_, _, ref record) in session.events.iter() {
+            payload.extend_from_slice(&record.payload);
+            if !record.payload.ends_with(b"\n") {
+                payload.push(b'\n');
+            }
+        }
+        let started = session.events.first().map(|&(time, _, _)| time).unwrap_or(0);
+        let ended = session.events.last().map(|&(time, _, _)| time).unwrap_or(started);
+
+        let mut combined = Record::new(&self.source, payload);
+        combined.set_field(&self.key, key);
+        combined.set_field("events", RecordValue::Int(session.events.len() as i64));
+        combined.set_field("sources", session.sources.join(","));
+        combined.set_field("started", internal_event_time::format_rfc3339(started));
+        combined.set_field("ended", internal_event_time::format_rfc3339(ended));
+        // Saturates for sessions spanning more of the i64 range than an i64 holds
+        combined.set_field("duration_ms", RecordValue::Int(ended.saturating_sub(started)));
+        combined.set_field("error", RecordValue::Bool(session.error));
+        combined.set_field("closed", reason);
+        if session.dropped > 0 {
+            combined.set_field("events_dropped", RecordValue::Int(session.dropped as i64));
+        }
+        match self.target {
+            Some(ref target) => combined.route_to(target),
+            None => {}
+        }
+        output.push(combined);
+        self.count("sessions", 1);
+    }
+
+    fn count(&self, name: &str, n: u64) {
+        match self.metrics {
+            Some((ref id, ref metrics)) if n > 0 => metrics.processor_counter(id, name, n),
+            _ => {}
+        }
+    }
+}
+
+impl Processor for SessionProcessor {
+    fn process(&mut self, record: Record, output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        self.process_at(record, Instant::now(), output);
+        return Ok(());
+    }
+
+    fn id(&self) -> Option<String> {
+        Some("session".to_string())
+    }
+
+    fn attach(&mut self, id: &str, metrics: &MetricsRegistry) {
+        self.metrics = Some((id.to_string(), metrics.clone()));
+    }
+
+    fn flush(&mut self, now: Instant, output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        self.flush_at(now, output);
+        return Ok(());
+    }
+
+    fn end_source(&mut self, source: &str, output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        self.sources.remove(source);
+        if self.sources.is_empty() {
+            let open: Vec<String> = self.activity.values().cloned().collect();
+            for key in open {
+                self.close(&key, "end", output);
+            }
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use std::time::Instant;
+    use super::SessionProcessor;
+    use Processor;
+    use records::record::Record;
+    use records::record_value::RecordValue;
+    use routing::route::RouteSelector;
+    use metrics::metrics_registry::MetricsRegistry;
+
+    fn event(source: &str, request: &str, millis: u32, text: &str) -> Record {
+        let mut record = Record::new(source, format!("{}\n", text).into_bytes());
+        record.set_field("request_id", request);
+        record.set_field("timestamp", format!("2024-05-01T10:00:00.{:03}Z", millis));
+        return record;
+    }
+
+    #[test]
+    fn test_combines_records_after_inactivity() {
+        let metrics = MetricsRegistry::new();
+        let mut sessions = SessionProcessor::new("request_id").with_timeout(Duration::from_secs(10)).with_target("traces");
+        sessions.attach("session", &metrics);
+        let start = Instant::now();
+        let mut output = Vec::new();
+        sessions.process_at(event("gateway", "r1", 100, "GET /orders"), start, &mut output);
+        sessions.process_at(event("orders", "r1", 250, "query took 120ms"), start, &mut output);
+        sessions.process_at(event("gateway", "r2", 300, "GET /health"), start + Duration::from_secs(5), &mut output);
+        // Arrives last, but happened before the query finished
+        let mut failed = event("orders", "r1", 200, "db timeout");
+        failed.set_field("level", "ERROR");
+        sessions.process_at(failed, start + Duration::from_secs(2), &mut output);
+        sessions.process_at(Record::new("gateway", b"no request\n".to_vec()), start, &mut output);
+        assert_eq!(output.len(), 1);
+
+        output.clear();
+        sessions.flush_at(start + Duration::from_secs(11), &mut output);
+        assert!(output.is_empty());
+        sessions.flush_at(start + Duration::from_secs(12), &mut output);
+        assert_eq!(output.len(), 1);
+        let session = &output[0];
+        assert_eq!(session.source, "session");
+        assert_eq!(session.route, Some("traces".to_string()));
+        assert_eq!(session.payload_str(), Some("GET /orders\ndb timeout\nquery took 120ms\n"));
+        assert_eq!(session.field("request_id"), Some(&RecordValue::from("r1")));
+        assert_eq!(session.field("events"), Some(&RecordValue::Int(3)));
+        assert_eq!(session.field("sources"), Some(&RecordValue::from("gateway,orders")));
+        assert_eq!(session.field("started"), Some(&RecordValue::from("2024-05-01T10:00:00.100Z")));
+        assert_eq!(session.field("duration_ms"), Some(&RecordValue::Int(150)));
+        assert_eq!(session.field("error"), Some(&RecordValue::Bool(true)));
+        assert_eq!(session.field("closed"), Some(&RecordValue::from("timeout")));
+
+        output.clear();
+        sessions.end_source("gateway", &mut output).unwrap();
+        sessions.end_source("orders", &mut output).unwrap();
+        assert_eq!(output.len(), 1);
+        assert_eq!(output[0].field("error"), Some(&RecordValue::Bool(false)));
+        assert_eq!(output[0].field("closed"), Some(&RecordValue::from("end")));
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.processor("session").unwrap().counters.get("sessions"), Some(&2));
+    }
+
+    #[test]
+    fn test_memory_caps_evict_and_truncate() {
+        let metrics = MetricsRegistry::new();
+        let mut sessions = SessionProcessor::new("request_id")
+            .with_max_sessions(2)
+            .with_max_events(2)
+            .with_error_selector(RouteSelector::payload("panic").unwrap());
+        sessions.attach("session", &metrics);
+        let start = Instant::now();
+        let mut output = Vec::new();
+        sessions.process_at(event("api", "r1", 1, "one"), start, &mut output);
+        sessions.process_at(event("api", "r2", 2, "two"), start + Duration::from_secs(1), &mut output);
+        sessions.process_at(event("api", "r1", 3, "panic"), start + Duration::from_secs(2), &mut output);
+        sessions.process_at(event("api", "r1", 4, "dropped"), start + Duration::from_secs(2), &mut output);
+        assert!(output.is_empty());
+        // r2 has been inactive longest, so it makes room
+        sessions.process_at(event("api", "r3", 5, "three"), start + Duration::from_secs(3), &mut output);
+        assert_eq!(output.len(), 1);
+        assert_eq!(output[0].field("request_id"), Some(&RecordValue::from("r2")));
+        assert_eq!(output[0].field("closed"), Some(&RecordValue::from("evicted")));
+
+        output.clear();
+        sessions.end_source("api", &mut output).unwrap();
+        assert_eq!(output[0].payload_str(), Some("one\npanic\n"));
+        assert_eq!(output[0].field("events_dropped"), Some(&RecordValue::Int(1)));
+        assert_eq!(output[0].field("error"), Some(&RecordValue::Bool(true)));
+
+        let snapshot = metrics.snapshot();
+        let counters = &snapshot.processor("session").unwrap().counters;
+        assert_eq!(counters.get("evicted"), Some(&1));
+        assert_eq!(counters.get("events_dropped"), Some(&1));
+    }
+    #[test]
+    fn test_extreme_event_times_do_not_overflow() {
+        let mut sessions = SessionProcessor::new("request_id").with_timeout(Duration::from_secs(::std::u64::MAX));
+        let start = Instant::now();
+        let mut output = Vec::new();
+        for &millis in [::std::i64::MAX, ::std::i64::MIN].iter() {
+            let mut record = Record::new("gateway", format!("{}\n", millis).into_bytes());
+            record.set_field("request_id", "r1");
+            record.set_field("timestamp", RecordValue::Int(millis));
+            sessions.process_at(record, start, &mut output);
+        }
+        sessions.flush_at(start + Duration::from_secs(60), &mut output);
+        assert!(output.is_empty());
+        sessions.end_source("gateway", &mut output).unwrap();
+        assert_eq!(output.len(), 1);
+        assert_eq!(output[0].field("duration_ms"), Some(&RecordValue::Int(::std::i64::MAX)));
+        assert_eq!(output[0].payload_str(), Some(format!("{}\n{}\n", ::std::i64::MIN, ::std::i64::MAX).as_str()));
+    }
+}
@@ -0,0 +1,56 @@
+use Processor;
+use LoghaulError;
+use records::record::Record;
+
+/// Splits each record into one record per line.
+/// Line terminators are kept, so targets writing raw payloads reproduce the
+/// input exactly. Each record is split on its own; a line that spans two
+/// reads from a source comes out as two records.
+pub struct SplitLinesProcessor {}
+
+impl SplitLinesProcessor {
+    pub fn new() -> SplitLinesProcessor {
+        return SplitLinesProcessor {};
+    }
+}
+
+impl Processor for SplitLinesProcessor {
+    fn process(&mut self, record: Record, output: &mut Vec<Record>) -> Result<(), LoghaulError> {
+        let mut start = 0;
+        for (offset, byte) in record.payload.iter().enumerate() {
+            if *byte == b'\n' {
+                let mut line = record.clone();
+                line.payload = record.payload[start..offset + 1].to_vec();
+                output.push(line);
+                start = offset + 1;
+            }
+        }
+        if start < record.payload.len() {
+            let mut line = record.clone();
+            line.payload = record.payload[start..].to_vec();
+            output.push(line);
+        }
+        return Ok(());
+    }
+
+    fn id(&self) -> Option<String> {
+        Some("split_lines".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SplitLinesProcessor;
+    use Processor;
+    use records::record::Record;
+
+    #[test]
+    fn test_split_lines_keeps_terminators() {
+        let mut output = Vec::new();
+        let mut splitter = SplitLinesProcessor::new();
+        splitter.process(Record::new("a", b"one\ntwo\r\nthree".to_vec()), &mut output).unwrap();
+        let lines: Vec<&str> = output.iter().map(|r| r.payload_str().unwrap()).collect();
+        assert_eq!(lines, vec!("one\n", "two\r\n", "three"));
+        assert!(output.iter().all(|r| r.source == "a"));
+    }
+}
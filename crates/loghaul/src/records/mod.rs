@@ -0,0 +1,3 @@
+pub mod record;
+pub mod record_value;
+pub mod record_format;
@@ -0,0 +1,54 @@
+use std::collections::BTreeMap;
+use std::str::from_utf8;
+use records::record_value::RecordValue;
+
+/// A single unit of data moving through a stream: the raw payload read from a
+/// source, plus any fields processors have extracted or attached to it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Record {
+    /// The id of the source this record was read from
+    pub source: String,
+    pub payload: Vec<u8>,
+    pub fields: BTreeMap<String, RecordValue>,
+    /// If set, the record is sent only to the target with this id, whatever the routes say
+    pub route: Option<String>,
+}
+
+impl Record {
+    pub fn new(source: &str, payload: Vec<u8>) -> Record {
+        return Record {
+            source: source.to_string(),
+            payload,
+            fields: BTreeMap::new(),
+            route: None,
+        };
+    }
+
+    /// The payload as a string, if it is valid utf8
+    pub fn payload_str(&self) -> Option<&str> {
+        return from_utf8(&self.payload).ok();
+    }
+
+    /// Replace the payload with a string
+    pub fn set_payload_str(&mut self, value: &str) {
+        self.payload.clear();
+        self.payload.extend_from_slice(value.as_bytes());
+    }
+
+    pub fn field(&self, key: &str) -> Option<&RecordValue> {
+        return self.fields.get(key);
+    }
+
+    pub fn set_field(&mut self, key: &str, value: impl Into<RecordValue>) {
+        self.fields.insert(key.to_string(), value.into());
+    }
+
+    /// Send this record only to the given target, bypassing the router
+    pub fn route_to(&mut self, target: &str) {
+        self.route = Some(target.to_string());
+    }
+
+    pub fn remove_field(&mut self, key: &str) -> Option<RecordValue> {
+        return self.fields.remove(key);
+    }
+}
@@ -0,0 +1,97 @@
+use std::fmt::Write;
+use records::record::Record;
+use records::record_value::RecordValue;
+
+/// How a target writes a record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordFormat {
+    /// The payload bytes, unchanged; fields are not written
+    Raw,
+    /// One JSON object per line, with the record's `source`, its `payload` as text without
+    /// the trailing newline, and its `fields`
+    Json,
+}
+
+impl RecordFormat {
+    /// Return the bytes a target writes for a record
+    pub fn encode(&self, record: &Record) -> Vec<u8> {
+        return match self {
+            RecordFormat::Raw => record.payload.clone(),
+            RecordFormat::Json => encode_json(record).into_bytes(),
+        };
+    }
+}
+
+fn encode_json(record: &Record) -> String {
+    let payload = String::from_utf8_lossy(&record.payload);
+    let payload = payload.trim_end_matches('\n').trim_end_matches('\r');
+    let mut line = String::with_capacity(record.payload.len() + 64);
+    line.push_str("{\"source\":");
+    push_json_string(&mut line, &record.source);
+    line.push_str(",\"payload\":");
+    push_json_string(&mut line, payload);
+    line.push_str(",\"fields\":{");
+    for (index, (key, value)) in record.fields.iter().enumerate() {
+        if index > 0 {
+            line.push(',');
+        }
+        push_json_string(&mut line, key);
+        line.push(':');
+        match value {
+            RecordValue::Null => line.push_str("null"),
+            RecordValue::Bool(v) => line.push_str(if *v { "true" } else { "false" }),
+            RecordValue::Int(v) => { let _ = write!(line, "{}", v); }
+            // JSON has no NaN or infinity
+            RecordValue::Float(v) if !v.is_finite() => line.push_str("null"),
+            RecordValue::Float(v) => { let _ = write!(line, "{:?}", v); }
+            RecordValue::String(v) => push_json_string(&mut line, v),
+        }
+    }
+    line.push_str("}}\n");
+    return line;
+}
+
+fn push_json_string(line: &mut String, value: &str) {
+    line.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => line.push_str("\\\""),
+            '\\' => line.push_str("\\\\"),
+            '\n' => line.push_str("\\n"),
+            '\r' => line.push_str("\\r"),
+            '\t' => line.push_str("\\t"),
+            c if (c as u32) < 0x20 => { let _ = write!(line, "\\u{:04x}", c as u32); }
+            c => line.push(c),
+        }
+    }
+    line.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RecordFormat;
+    use records::record::Record;
+    use records::record_value::RecordValue;
+
+    #[test]
+    fn test_encode_json() {
+        let mut record = Record::new("api", b"GET \"/\"\tok\n".to_vec());
+        record.set_field("status", RecordValue::Int(200));
+        record.set_field("took", RecordValue::Float(1.0));
+        record.set_field("cached", RecordValue::Bool(false));
+        record.set_field("user", RecordValue::Null);
+        record.set_field("trace", "a\nb\u{1}");
+        assert_eq!(
+            String::from_utf8(RecordFormat::Json.encode(&record)).unwrap(),
+            "{\"source\":\"api\",\"payload\":\"GET \\\"/\\\"\\tok\",\"fields\":{\"cached\":false,\"status\":200,\
+             \"took\":1.0,\"trace\":\"a\\nb\\u0001\",\"user\":null}}\n"
+        );
+        assert_eq!(RecordFormat::Raw.encode(&record), record.payload);
+
+        let record = Record::new("api", vec!(b'c', b'a', b'f', 0xe9, b'\r', b'\n'));
+        assert_eq!(
+            String::from_utf8(RecordFormat::Json.encode(&record)).unwrap(),
+            "{\"source\":\"api\",\"payload\":\"caf\u{fffd}\",\"fields\":{}}\n"
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use std::fmt;
+
+/// The value of a single record field
+#[derive(Debug, PartialEq, Clone)]
+pub enum RecordValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+impl RecordValue {
+    /// Return the string value, if this is a string
+    pub fn as_str(&self) -> Option<&str> {
+        return match self {
+            RecordValue::String(v) => Some(v),
+            _ => None
+        };
+    }
+
+    /// Return the value as an integer, if it is one
+    pub fn as_i64(&self) -> Option<i64> {
+        return match self {
+            RecordValue::Int(v) => Some(*v),
+            _ => None
+        };
+    }
+
+    /// Return the value as a float; integers are converted
+    pub fn as_f64(&self) -> Option<f64> {
+        return match self {
+            RecordValue::Int(v) => Some(*v as f64),
+            RecordValue::Float(v) => Some(*v),
+            _ => None
+        };
+    }
+
+    /// Return the value as a bool, if it is one
+    pub fn as_bool(&self) -> Option<bool> {
+        return match self {
+            RecordValue::Bool(v) => Some(*v),
+            _ => None
+        };
+    }
+}
+
+impl fmt::Display for RecordValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecordValue::Null => write!(f, "null"),
+            RecordValue::Bool(v) => write!(f, "{}", v),
+            RecordValue::Int(v) => write!(f, "{}", v),
+            RecordValue::Float(v) => write!(f, "{}", v),
+            RecordValue::String(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+impl<'a> From<&'a str> for RecordValue {
+    fn from(value: &'a str) -> Self {
+        RecordValue::String(value.to_string())
+    }
+}
+
+impl From<String> for RecordValue {
+    fn from(value: String) -> Self {
+        RecordValue::String(value)
+    }
+}
+
+impl From<i64> for RecordValue {
+    fn from(value: i64) -> Self {
+        RecordValue::Int(value)
+    }
+}
+
+impl From<f64> for RecordValue {
+    fn from(value: f64) -> Self {
+        RecordValue::Float(value)
+    }
+}
+
+impl From<bool> for RecordValue {
+    fn from(value: bool) -> Self {
+        RecordValue::Bool(value)
+    }
+}
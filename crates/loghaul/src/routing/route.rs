@@ -0,0 +1,113 @@
+use regex;
+use regex::bytes;
+use records::record::Record;
+use LoghaulError;
+use LoghaulErrorCode;
+
+/// A single condition a record must meet for a route to apply
+#[derive(Debug, Clone)]
+pub enum RouteSelector {
+    /// The record was read from any of these sources
+    Source(Vec<String>),
+    /// The field is set, and its value renders as exactly this string
+    FieldEquals(String, String),
+    /// The field is set, and its value renders as a string matching the regex
+    FieldMatches(String, regex::Regex),
+    /// The raw payload matches the regex
+    Payload(bytes::Regex),
+}
+
+impl RouteSelector {
+    pub fn source(id: &str) -> RouteSelector {
+        return RouteSelector::Source(vec!(id.to_string()));
+    }
+
+    pub fn field_equals(key: &str, value: &str) -> RouteSelector {
+        return RouteSelector::FieldEquals(key.to_string(), value.to_string());
+    }
+
+    pub fn field_matches(key: &str, pattern: &str) -> Result<RouteSelector, LoghaulError> {
+        return regex::Regex::new(pattern)
+            .map(|r| RouteSelector::FieldMatches(key.to_string(), r))
+            .map_err(|e| LoghaulError::from(LoghaulErrorCode::InvalidRoute(e.to_string())));
+    }
+
+    pub fn payload(pattern: &str) -> Result<RouteSelector, LoghaulError> {
+        return bytes::Regex::new(pattern)
+            .map(|r| RouteSelector::Payload(r))
+            .map_err(|e| LoghaulError::from(LoghaulErrorCode::InvalidRoute(e.to_string())));
+    }
+
+    pub fn matches(&self, record: &Record) -> bool {
+        return match self {
+            RouteSelector::Source(ids) => ids.iter().any(|id| *id == record.source),
+            RouteSelector::FieldEquals(key, value) => record.field(key).map(|v| v.to_string() == *value).unwrap_or(false),
+            RouteSelector::FieldMatches(key, pattern) => record.field(key).map(|v| pattern.is_match(&v.to_string())).unwrap_or(false),
+            RouteSelector::Payload(pattern) => pattern.is_match(&record.payload),
+        };
+    }
+}
+
+/// Send records meeting every selector to a set of targets.
+/// A route without selectors matches every record.
+///
+/// ```
+///     use loghaul::{Route, RouteSelector};
+///     let pager = Route::new(&["pager"])
+///         .with_selector(RouteSelector::source("/var/log/api.log"))
+///         .with_selector(RouteSelector::payload("ERROR|FATAL").unwrap());
+///     let archive = Route::new(&["archive"]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Route {
+    selectors: Vec<RouteSelector>,
+    targets: Vec<String>,
+}
+
+impl Route {
+    pub fn new(targets: &[&str]) -> Route {
+        return Route {
+            selectors: Vec::new(),
+            targets: targets.iter().map(|t| t.to_string()).collect(),
+        };
+    }
+
+    pub fn with_selector(mut self, selector: RouteSelector) -> Route {
+        self.selectors.push(selector);
+        return self;
+    }
+
+    /// The ids of the targets this route sends to
+    pub fn targets(&self) -> &Vec<String> {
+        return &self.targets;
+    }
+
+    pub fn matches(&self, record: &Record) -> bool {
+        return self.selectors.iter().all(|s| s.matches(record));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Route, RouteSelector};
+    use records::record::Record;
+
+    #[test]
+    fn test_route_requires_every_selector() {
+        let route = Route::new(&["pager"])
+            .with_selector(RouteSelector::source("api"))
+            .with_selector(RouteSelector::field_equals("status", "500"))
+            .with_selector(RouteSelector::payload("(?i)error").unwrap());
+
+        let mut record = Record::new("api", b"Error: upstream timed out".to_vec());
+        assert!(!route.matches(&record));
+        record.set_field("status", 500);
+        assert!(route.matches(&record));
+        record.source = "web".to_string();
+        assert!(!route.matches(&record));
+
+        assert!(Route::new(&["archive"]).matches(&record));
+        assert!(RouteSelector::field_matches("status", "^5\\d\\d$").unwrap().matches(&record));
+        assert!(RouteSelector::payload("[").is_err());
+    }
+}
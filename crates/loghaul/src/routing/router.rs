@@ -0,0 +1,101 @@
+use routing::route::Route;
+use records::record::Record;
+
+/// Decides which targets receive each record.
+/// Every route a record matches contributes its targets; records matching no
+/// route go to the default targets, if any are set, and are otherwise dropped.
+/// A router with no routes and no default sends every record to every target.
+#[derive(Debug, Clone)]
+pub struct Router {
+    routes: Vec<Route>,
+    default: Option<Vec<String>>,
+}
+
+impl Router {
+    pub fn new() -> Router {
+        return Router {
+            routes: Vec::new(),
+            default: None,
+        };
+    }
+
+    pub fn with_route(mut self, route: Route) -> Router {
+        self.add_route(route);
+        return self;
+    }
+
+    pub fn add_route(&mut self, route: Route) {
+        self.routes.push(route);
+    }
+
+    /// Send records that match no route to these targets
+    pub fn with_default(mut self, targets: &[&str]) -> Router {
+        self.default = Some(targets.iter().map(|t| t.to_string()).collect());
+        return self;
+    }
+
+    /// True if this router sends every record to every target
+    pub fn is_broadcast(&self) -> bool {
+        return self.routes.is_empty() && self.default.is_none();
+    }
+
+    /// Return the ids of the targets a record should be sent to, or None if it goes to every target
+    pub fn select<'a>(&'a self, record: &'a Record) -> Option<Vec<&'a str>> {
+        match record.route {
+            Some(ref target) => {
+                return Some(vec!(target.as_str()));
+            }
+            None => {}
+        }
+        if self.is_broadcast() {
+            return None;
+        }
+        let mut selected: Vec<&str> = Vec::new();
+        let mut matched = false;
+        for route in self.routes.iter().filter(|r| r.matches(record)) {
+            matched = true;
+            for target in route.targets().iter() {
+                if !selected.contains(&target.as_str()) {
+                    selected.push(target);
+                }
+            }
+        }
+        if !matched {
+            match self.default {
+                Some(ref targets) => selected.extend(targets.iter().map(|t| t.as_str())),
+                None => {}
+            }
+        }
+        return Some(selected);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Router;
+    use routing::route::{Route, RouteSelector};
+    use records::record::Record;
+
+    #[test]
+    fn test_router_select() {
+        let record = Record::new("api", b"ERROR boom".to_vec());
+        let quiet = Record::new("api", b"INFO ok".to_vec());
+        assert_eq!(Router::new().select(&record), None);
+
+        let router = Router::new()
+            .with_route(Route::new(&["pager", "archive"]).with_selector(RouteSelector::payload("ERROR").unwrap()))
+            .with_route(Route::new(&["archive"]).with_selector(RouteSelector::source("api")));
+        assert_eq!(router.select(&record), Some(vec!("pager", "archive")));
+        assert_eq!(router.select(&quiet), Some(vec!("archive")));
+        assert_eq!(router.select(&Record::new("web", vec!())), Some(vec!()));
+
+        let mut routed = record.clone();
+        routed.route_to("misc");
+        assert_eq!(router.select(&routed), Some(vec!("misc")));
+        assert_eq!(Router::new().select(&routed), Some(vec!("misc")));
+
+        let router = router.with_default(&["misc"]);
+        assert_eq!(router.select(&Record::new("web", vec!())), Some(vec!("misc")));
+        assert_eq!(router.select(&quiet), Some(vec!("archive")));
+    }
+}
@@ -14,4 +14,10 @@ pub trait Source {
     /// may want to periodically attempt to restart, reading from the file
     /// again.
     fn resume(&mut self) -> Result<(), LoghaulError>;
+
+    /// A stable name for this source, used to label metrics.
+    /// Sources that return None are given a positional id by the stream.
+    fn id(&self) -> Option<String> {
+        None
+    }
 }
\ No newline at end of file
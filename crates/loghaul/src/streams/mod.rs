@@ -1,3 +1,5 @@
 pub mod stream;
 pub mod stream_entry;
 pub mod stream_buffer;
+pub mod stream_named_source;
+pub mod stream_processor_chain;
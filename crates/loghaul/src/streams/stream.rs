@@ -2,17 +2,39 @@ use Source;
 use Target;
 use LoghaulErrorAggregate;
 use std::mem;
+use std::time::Instant;
 use StreamEntry;
+use metrics::metrics_registry::MetricsRegistry;
+use streams::stream_named_source::NamedSource;
+use streams::stream_processor_chain::ProcessorChain;
+use records::record::Record;
+use Processor;
+use ProcessorErrorPolicy;
+use routing::route::Route;
+use routing::router::Router;
+
+/// Upper bound on polls when draining a removed source, so an endless source can't stall the stream
+const MAX_DRAIN_POLLS: usize = 64;
 
 pub struct Stream {
     sources: Vec<SourceBucket>,
-    targets: Vec<Box<Target + Send + 'static>>,
+    targets: Vec<TargetBucket>,
+    processors: ProcessorChain,
+    router: Router,
+    metrics: MetricsRegistry,
+    source_count: usize,
 }
 
 struct SourceBucket {
     eof: bool,
     source: Box<Source + Send + 'static>,
     buffer: Vec<u8>,
+    id: String,
+}
+
+struct TargetBucket {
+    target: Box<Target + Send + 'static>,
+    id: String,
 }
 
 impl SourceBucket {
@@ -38,6 +60,10 @@ impl Stream {
         return Stream {
             sources: Vec::new(),
             targets: Vec::new(),
+            processors: ProcessorChain::new(),
+            router: Router::new(),
+            metrics: MetricsRegistry::new(),
+            source_count: 0,
         };
     }
 
@@ -52,10 +78,37 @@ impl Stream {
         self.add_boxed_source(Box::new(source));
     }
 
-    /// Add a new data source to this stream
+    /// Add a new data source to this stream, labelled with an explicit id
+    pub fn with_named_source(mut self, id: &str, source: impl Source + Send + 'static) -> Stream {
+        self.add_boxed_source(Box::new(NamedSource::new(id, Box::new(source))));
+        return self;
+    }
+
+    /// Add a new data source to this stream.
+    /// A source whose id is already taken by an active source gets a `#2`, `#3`, ... suffix,
+    /// so that each source keeps its own metrics.
     pub fn add_boxed_source(&mut self, source: Box<Source + Send + 'static>) {
+        // Anonymous sources are wrapped so their positional id survives an EOF / resume cycle.
+        let source: Box<Source + Send + 'static> = match source.id() {
+            Some(_) => source,
+            None => {
+                self.source_count += 1;
+                Box::new(NamedSource::new(&format!("source-{}", self.source_count - 1), source))
+            }
+        };
+        let source: Box<Source + Send + 'static> = match source.id() {
+            Some(ref id) if self.sources.iter().any(|s| &s.id == id) => {
+                let mut n = 2;
+                while self.sources.iter().any(|s| s.id == format!("{}#{}", id, n)) {
+                    n += 1;
+                }
+                Box::new(NamedSource::new(&format!("{}#{}", id, n), source))
+            }
+            _ => source,
+        };
         self.sources.push(SourceBucket {
             eof: false,
+            id: source.id().unwrap_or(String::new()),
             source: source,
             buffer: Vec::new(),
         });
@@ -67,9 +120,147 @@ impl Stream {
         return self;
     }
 
+    /// Add a new data target to this stream, labelled with an explicit id
+    pub fn with_named_target(mut self, id: &str, target: impl Target + Send + 'static) -> Stream {
+        self.add_boxed_target(Some(id.to_string()), Box::new(target));
+        return self;
+    }
+
     /// Add a new data target to this stream
     pub fn add_target(&mut self, target: impl Target + Send + 'static) {
-        self.targets.push(Box::new(target));
+        self.add_boxed_target(None, Box::new(target));
+    }
+
+    /// Add a new data target to this stream, optionally overriding the id the target reports
+    pub fn add_boxed_target(&mut self, id: Option<String>, target: Box<Target + Send + 'static>) {
+        let id = id.or(target.id()).unwrap_or(format!("target-{}", self.targets.len()));
+        self.targets.push(TargetBucket {
+            target,
+            id,
+        });
+    }
+
+    /// Add a processor to the end of the processor chain.
+    /// Every record read from a source passes through each processor, in the
+    /// order they were added, before it is handed to the targets.
+    pub fn with_processor(mut self, processor: impl Processor + Send + 'static) -> Stream {
+        self.add_processor(processor);
+        return self;
+    }
+
+    /// Add a processor to the end of the processor chain; records it fails on are dropped
+    pub fn add_processor(&mut self, processor: impl Processor + Send + 'static) {
+        self.add_boxed_processor(None, ProcessorErrorPolicy::Drop, Box::new(processor));
+    }
+
+    /// Add a processor to the end of the processor chain, optionally overriding the id it reports
+    pub fn add_boxed_processor(&mut self, id: Option<String>, policy: ProcessorErrorPolicy, processor: Box<Processor + Send + 'static>) {
+        self.processors.push(id, policy, processor);
+        self.processors.attach(&self.metrics);
+    }
+
+    /// Replace the whole processor chain, returning the previous one.
+    /// Pass the previous chain to `drain_processors` to deliver what it still holds.
+    pub fn set_processors(&mut self, mut processors: ProcessorChain) -> ProcessorChain {
+        processors.attach(&self.metrics);
+        return mem::replace(&mut self.processors, processors);
+    }
+
+    /// Deliver everything a processor chain that is no longer part of the stream still holds, then drop it
+    pub fn drain_processors(&mut self, mut processors: ProcessorChain) -> Result<(), LoghaulErrorAggregate> {
+        let mut errors = LoghaulErrorAggregate::new();
+        let mut records = Vec::new();
+        processors.finish(&self.metrics, &mut records, &mut errors);
+        Stream::deliver(&self.router, &mut self.targets, &self.metrics, records, &mut errors);
+        return errors.to_result();
+    }
+
+    /// Deliver everything the processors still hold, as if every source had ended.
+    /// Call this before the stream stops, so records a processor held back aren't lost.
+    pub fn finish(&mut self) -> Result<(), LoghaulErrorAggregate> {
+        let mut errors = LoghaulErrorAggregate::new();
+        let mut records = Vec::new();
+        self.processors.finish(&self.metrics, &mut records, &mut errors);
+        Stream::deliver(&self.router, &mut self.targets, &self.metrics, records, &mut errors);
+        return errors.to_result();
+    }
+
+    /// Add a routing rule. Once any route is set, records only reach the
+    /// targets of the routes they match, or the default route.
+    pub fn with_route(mut self, route: Route) -> Stream {
+        self.router.add_route(route);
+        return self;
+    }
+
+    /// Replace the routing rules, returning the previous ones
+    pub fn set_router(&mut self, router: Router) -> Router {
+        return mem::replace(&mut self.router, router);
+    }
+
+    /// Return a handle to the metrics registry for this stream
+    pub fn metrics(&self) -> MetricsRegistry {
+        return self.metrics.clone();
+    }
+
+    /// Return the id of every active source, in poll order
+    pub fn source_ids(&self) -> Vec<String> {
+        return self.sources.iter().map(|s| s.id.clone()).collect();
+    }
+
+    /// Return the id of every target, in dispatch order
+    pub fn target_ids(&self) -> Vec<String> {
+        return self.targets.iter().map(|t| t.id.clone()).collect();
+    }
+
+    /// Remove every source with the given id.
+    /// Any data still pending in a removed source is drained to the targets
+    /// before it is dropped, so nothing already written to it is lost.
+    /// Returns the number of sources removed.
+    pub fn remove_source(&mut self, id: &str) -> Result<usize, LoghaulErrorAggregate> {
+        let mut errors = LoghaulErrorAggregate::new();
+        let mut source_list = Vec::new();
+        mem::swap(&mut self.sources, &mut source_list);
+        let (removed, mut active): (Vec<_>, Vec<_>) = source_list.into_iter().partition(|ref e| e.id == id);
+        mem::swap(&mut self.sources, &mut active);
+        let count = removed.len();
+        for source in removed.into_iter() {
+            self.drain_source(source.to_source(), &mut errors);
+        }
+        self.metrics.remove_source(id);
+        return errors.to_result().map(|_| count);
+    }
+
+    /// Drain any pending data from a source that is no longer part of the stream, then drop it
+    fn drain_source(&mut self, mut source: Box<Source + Send + 'static>, errors: &mut LoghaulErrorAggregate) {
+        let id = source.id().unwrap_or(String::new());
+        let mut buffer = Vec::new();
+        for _ in 0..MAX_DRAIN_POLLS {
+            buffer.clear();
+            match source.poll(&mut buffer) {
+                Ok(StreamEntry::Data) => {
+                    self.metrics.source_polled(&id, StreamEntry::Data, buffer.len());
+                    let record = Record::new(&id, buffer.clone());
+                    Stream::dispatch_record(&mut self.processors, &self.router, &mut self.targets, &self.metrics, record, errors);
+                }
+                Ok(_) => {
+                    break;
+                }
+                Err(e) => {
+                    self.metrics.source_error(&id);
+                    errors.push(e);
+                    break;
+                }
+            }
+        }
+        Stream::end_source(&mut self.processors, &self.router, &mut self.targets, &self.metrics, &id, errors);
+    }
+
+    /// Remove every target with the given id, returning the number removed
+    pub fn remove_target(&mut self, id: &str) -> usize {
+        let before = self.targets.len();
+        self.targets.retain(|t| t.id != id);
+        self.metrics.remove_target(id);
+        return before - self.targets.len();
     }
 
     /// Process every input once and pass every received value to every output
@@ -82,28 +273,35 @@ impl Stream {
             source.buffer.clear();
             match source.source.poll(&mut source.buffer) {
                 Ok(entry) => {
-                    for target in self.targets.iter_mut() {
-                        match target.consume(entry, &source.buffer) {
-                            Ok(_) => {}
-                            Err(e) => {
-                                errors.push(e);
-                            }
+                    self.metrics.source_polled(&source.id, entry, source.buffer.len());
+                    match entry {
+                        StreamEntry::Data => {
+                            let record = Record::new(&source.id, source.buffer.clone());
+                            Stream::dispatch_record(&mut self.processors, &self.router, &mut self.targets, &self.metrics, record, &mut errors);
                         }
+                        _ => Stream::dispatch(&mut self.targets, &self.metrics, entry, &source.buffer, &mut errors)
                     }
                     match entry {
                         StreamEntry::EOF => {
                             source.eof = true;
                             eof_count += 1;
+                            Stream::end_source(&mut self.processors, &self.router, &mut self.targets, &self.metrics, &source.id, &mut errors);
                         }
                         _ => {}
                     }
                 }
                 Err(e) => {
+                    self.metrics.source_error(&source.id);
                     errors.push(e);
                 }
             }
         }
 
+        // Release whatever processors were holding back that is now due
+        let mut released = Vec::new();
+        self.processors.flush(Instant::now(), &self.metrics, &mut released, &mut errors);
+        Stream::deliver(&self.router, &mut self.targets, &self.metrics, released, &mut errors);
+
         // Remove eof sources, they can never generate again
         if eof_count > 0 {
             let mut source_list = Vec::new();
@@ -117,6 +315,57 @@ impl Stream {
 
         return errors.to_result();
     }
+
+    /// Pass a data record through the processor chain, then every resulting record to the targets it is routed to
+    fn dispatch_record(processors: &mut ProcessorChain, router: &Router, targets: &mut Vec<TargetBucket>, metrics: &MetricsRegistry, record: Record, errors: &mut LoghaulErrorAggregate) {
+        let mut records = Vec::new();
+        processors.run(record, metrics, &mut records, errors);
+        Stream::deliver(router, targets, metrics, records, errors);
+    }
+
+    /// Let processors release what they hold for a source that ended, and deliver it
+    fn end_source(processors: &mut ProcessorChain, router: &Router, targets: &mut Vec<TargetBucket>, metrics: &MetricsRegistry, source: &str, errors: &mut LoghaulErrorAggregate) {
+        let mut records = Vec::new();
+        processors.end_source(source, metrics, &mut records, errors);
+        Stream::deliver(router, targets, metrics, records, errors);
+    }
+
+    /// Hand processed records to the targets they are routed to
+    fn deliver(router: &Router, targets: &mut Vec<TargetBucket>, metrics: &MetricsRegistry, records: Vec<Record>, errors: &mut LoghaulErrorAggregate) {
+        for record in records.iter() {
+            let selected = router.select(record);
+            match selected {
+                Some(ref ids) if ids.is_empty() => metrics.record_unrouted(),
+                _ => {}
+            }
+            for target in targets.iter_mut() {
+                match selected {
+                    Some(ref ids) if !ids.contains(&target.id.as_str()) => continue,
+                    _ => {}
+                }
+                match target.target.consume_record(record) {
+                    Ok(_) => metrics.target_consumed(&target.id, record.payload.len()),
+                    Err(e) => {
+                        metrics.target_error(&target.id);
+                        errors.push(e);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Pass a NoData or EOF entry to every target
+    fn dispatch(targets: &mut Vec<TargetBucket>, metrics: &MetricsRegistry, entry: StreamEntry, buffer: &Vec<u8>, errors: &mut LoghaulErrorAggregate) {
+        for target in targets.iter_mut() {
+            match target.target.consume(entry, buffer) {
+                Ok(_) => {}
+                Err(e) => {
+                    metrics.target_error(&target.id);
+                    errors.push(e);
+                }
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -172,4 +421,279 @@ mod tests {
         assert!(s.step(&mut dropped).is_ok());
         assert_eq!(dropped.len(), 0);
     }
+
+    #[test]
+    fn test_stream_step_updates_metrics() {
+        let mut s = Stream::new()
+            .with_named_source("numbers", MockSource::closed(vec!("1", "22")))
+            .with_source(MockSource::empty())
+            .with_named_target("sink", MockTarget::new(|_, _| -> Result<(), LoghaulError> {
+                Ok(())
+            }))
+            .with_target(MockTarget::new(|_, _| -> Result<(), LoghaulError> {
+                Err(LoghaulError::from(LoghaulErrorCode::NotImplemented))
+            }));
+
+        let mut dropped = Vec::new();
+        for _ in 0..3 {
+            let _ = s.step(&mut dropped);
+        }
+
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(dropped[0].id(), Some("numbers".to_string()));
+
+        let snapshot = s.metrics().snapshot();
+        let numbers = snapshot.source("numbers").unwrap();
+        assert_eq!(numbers.records_in, 2);
+        assert_eq!(numbers.bytes_in, 3);
+        assert_eq!(numbers.eofs, 1);
+        assert_eq!(snapshot.source("source-0").unwrap().records_in, 0);
+        assert_eq!(snapshot.target("sink").unwrap().records_out, 2);
+        assert_eq!(snapshot.target("sink").unwrap().bytes_out, 3);
+        assert_eq!(snapshot.target("target-1").unwrap().errors, 6);
+    }
+
+    #[test]
+    fn test_stream_source_ids_are_unique() {
+        let mut s = Stream::new()
+            .with_named_source("app", MockSource::closed(vec!("1")))
+            .with_source(MockSource::empty())
+            .with_named_source("app", MockSource::closed(vec!("2", "3")))
+            .with_source(MockSource::empty());
+        assert_eq!(s.source_ids(), vec!("app", "source-0", "app#2", "source-1"));
+
+        let mut dropped = Vec::new();
+        assert!(s.step(&mut dropped).is_ok());
+        assert!(s.step(&mut dropped).is_ok());
+        let snapshot = s.metrics().snapshot();
+        assert_eq!(snapshot.source("app").unwrap().records_in, 1);
+        assert_eq!(snapshot.source("app#2").unwrap().records_in, 2);
+
+        // A resumed source keeps its id and doesn't shift the positional ids
+        for source in dropped.into_iter() {
+            s.add_boxed_source(source);
+        }
+        s.add_source(MockSource::empty());
+        assert_eq!(s.source_ids(), vec!("source-0", "app#2", "source-1", "app", "source-2"));
+    }
+
+    #[test]
+    fn test_stream_remove_source_drains_pending_data() {
+        use std::sync::{Arc, Mutex};
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_remote = received.clone();
+
+        let mut s = Stream::new()
+            .with_named_source("keep", MockSource::new(vec!("a")))
+            .with_named_source("drop", MockSource::new(vec!("1", "2", "3")))
+            .with_named_target("sink", MockTarget::new(move |entry, data| -> Result<(), LoghaulError> {
+                match entry {
+                    StreamEntry::Data => received_remote.lock().unwrap().push(String::from_utf8(data.clone()).unwrap()),
+                    _ => {}
+                }
+                Ok(())
+            }));
+
+        let mut dropped = Vec::new();
+        assert!(s.step(&mut dropped).is_ok());
+        assert_eq!(s.remove_source("drop").ok(), Some(1));
+        assert_eq!(s.source_ids(), vec!("keep".to_string()));
+        assert_eq!(*received.lock().unwrap(), vec!("a", "1", "2", "3"));
+        assert!(s.metrics().snapshot().source("drop").is_none());
+
+        assert_eq!(s.remove_target("sink"), 1);
+        assert_eq!(s.target_ids().len(), 0);
+        assert!(s.metrics().snapshot().target("sink").is_none());
+    }
+
+    #[test]
+    fn test_stream_processor_chain() {
+        use std::sync::{Arc, Mutex};
+        use ::{FnProcessor, SplitLinesProcessor, ProcessorErrorPolicy};
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_remote = received.clone();
+
+        let mut s = Stream::new()
+            .with_named_source("lines", MockSource::new(vec!("keep 1\ndrop 2\nfail 3\nkeep 4\n")))
+            .with_processor(SplitLinesProcessor::new())
+            .with_processor(FnProcessor::filter(|record| !record.payload.starts_with(b"drop")))
+            .with_named_target("sink", MockTarget::new(move |entry, data| -> Result<(), LoghaulError> {
+                match entry {
+                    StreamEntry::Data => received_remote.lock().unwrap().push(String::from_utf8(data.clone()).unwrap()),
+                    _ => {}
+                }
+                Ok(())
+            }));
+        s.add_boxed_processor(Some("upper".to_string()), ProcessorErrorPolicy::PassThrough, Box::new(FnProcessor::new(|mut record, output| {
+            if record.payload.starts_with(b"fail") {
+                return Err(LoghaulError::from(LoghaulErrorCode::ProcessorErr("nope".to_string())));
+            }
+            let upper = record.payload_str().unwrap().to_uppercase();
+            record.set_payload_str(&upper);
+            output.push(record);
+            Ok(())
+        })));
+
+        let mut dropped = Vec::new();
+        assert_eq!(s.step(&mut dropped).unwrap_err().len(), 1);
+        assert_eq!(*received.lock().unwrap(), vec!("KEEP 1\n", "fail 3\n", "KEEP 4\n"));
+
+        let snapshot = s.metrics().snapshot();
+        let splitter = snapshot.processor("split_lines").unwrap();
+        assert_eq!((splitter.records_in, splitter.records_out), (1, 4));
+        let filter = snapshot.processor("processor-1").unwrap();
+        assert_eq!((filter.records_in, filter.records_out), (4, 3));
+        let upper = snapshot.processor("upper").unwrap();
+        assert_eq!((upper.records_in, upper.records_out, upper.errors), (3, 2, 1));
+        assert_eq!(snapshot.target("sink").unwrap().records_out, 3);
+    }
+
+    #[test]
+    fn test_stream_routes_records() {
+        use std::sync::{Arc, Mutex};
+        use ::{Route, RouteSelector, Router, SplitLinesProcessor};
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = |name: &'static str, received: Arc<Mutex<Vec<String>>>| MockTarget::new(move |entry, data| -> Result<(), LoghaulError> {
+            match entry {
+                StreamEntry::Data => received.lock().unwrap().push(format!("{} {}", name, String::from_utf8(data.clone()).unwrap().trim())),
+                _ => {}
+            }
+            Ok(())
+        });
+
+        let mut s = Stream::new()
+            .with_named_source("api", MockSource::new(vec!("ERROR down\nINFO up\n")))
+            .with_named_source("web", MockSource::new(vec!("ERROR 404\n", "WARN slow\n")))
+            .with_processor(SplitLinesProcessor::new())
+            .with_named_target("pager", sink("pager", received.clone()))
+            .with_named_target("archive", sink("archive", received.clone()))
+            .with_route(Route::new(&["pager"])
+                .with_selector(RouteSelector::source("api"))
+                .with_selector(RouteSelector::payload("^ERROR").unwrap()))
+            .with_route(Route::new(&["archive"]).with_selector(RouteSelector::source("api")));
+
+        let mut dropped = Vec::new();
+        assert!(s.step(&mut dropped).is_ok());
+        assert_eq!(*received.lock().unwrap(), vec!("pager ERROR down", "archive ERROR down", "archive INFO up"));
+        assert_eq!(s.metrics().snapshot().unrouted, 1);
+
+        received.lock().unwrap().clear();
+        s.set_router(Router::new()
+            .with_route(Route::new(&["pager"]).with_selector(RouteSelector::source("api")))
+            .with_default(&["archive"]));
+        assert!(s.step(&mut dropped).is_ok());
+        assert_eq!(*received.lock().unwrap(), vec!("archive WARN slow"));
+    }
+
+    #[test]
+    fn test_stream_dead_letter() {
+        use std::sync::{Arc, Mutex};
+        use ::{FnProcessor, ProcessorErrorPolicy, Route};
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = |name: &'static str, received: Arc<Mutex<Vec<String>>>| MockTarget::new(move |entry, data| -> Result<(), LoghaulError> {
+            match entry {
+                StreamEntry::Data => received.lock().unwrap().push(format!("{} {}", name, String::from_utf8(data.clone()).unwrap())),
+                _ => {}
+            }
+            Ok(())
+        });
+
+        let mut s = Stream::new()
+            .with_source(MockSource::new(vec!("bad", "good")))
+            .with_named_target("out", sink("out", received.clone()))
+            .with_named_target("rejects", sink("rejects", received.clone()))
+            .with_route(Route::new(&["out"]));
+        s.add_boxed_processor(None, ProcessorErrorPolicy::DeadLetter("rejects".to_string()), Box::new(FnProcessor::new(|mut record, output| {
+            if record.payload == b"bad" {
+                return Err(LoghaulError::from(LoghaulErrorCode::ProcessorErr("bad record".to_string())));
+            }
+            record.payload.extend_from_slice(b"!");
+            output.push(record);
+            Ok(())
+        })));
+        s.add_processor(FnProcessor::map(|mut record| {
+            record.payload.extend_from_slice(b"?");
+            record
+        }));
+
+        let mut dropped = Vec::new();
+        for _ in 0..2 {
+            let _ = s.step(&mut dropped);
+        }
+        let mut received = received.lock().unwrap().clone();
+        received.sort();
+        assert_eq!(received, vec!("out good!?", "rejects bad"));
+    }
+
+    #[test]
+    fn test_stream_releases_held_records_at_eof() {
+        use std::sync::{Arc, Mutex};
+        use ::MultilineProcessor;
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = received.clone();
+        let mut s = Stream::new()
+            .with_source(MockSource::closed(vec!("first\n", "  more\n", "second\n", "  tail\n")))
+            .with_processor(MultilineProcessor::new().with_continuation(r"^\s").unwrap())
+            .with_target(MockTarget::new(move |entry, data| -> Result<(), LoghaulError> {
+                match entry {
+                    StreamEntry::Data => sink.lock().unwrap().push(String::from_utf8(data.clone()).unwrap()),
+                    _ => {}
+                }
+                Ok(())
+            }));
+
+        let mut dropped = Vec::new();
+        for _ in 0..4 {
+            let _ = s.step(&mut dropped);
+        }
+        assert_eq!(*received.lock().unwrap(), vec!("first\n  more\n"));
+        let _ = s.step(&mut dropped);
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(*received.lock().unwrap(), vec!("first\n  more\n", "second\n  tail\n"));
+    }
+
+    #[test]
+    fn test_stream_finish_and_drain_release_held_records() {
+        use std::sync::{Arc, Mutex};
+        use std::time::Duration;
+        use ::{FnProcessor, MultilineProcessor, OrderProcessor, ProcessorChain, ProcessorErrorPolicy};
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = received.clone();
+        let mut s = Stream::new()
+            .with_source(MockSource::new(vec!("first\n", "  more\n")))
+            .with_processor(MultilineProcessor::new().with_continuation(r"^\s").unwrap())
+            .with_target(MockTarget::new(move |entry, data| -> Result<(), LoghaulError> {
+                match entry {
+                    StreamEntry::Data => sink.lock().unwrap().push(String::from_utf8(data.clone()).unwrap()),
+                    _ => {}
+                }
+                Ok(())
+            }));
+
+        let mut dropped = Vec::new();
+        for _ in 0..3 {
+            let _ = s.step(&mut dropped);
+        }
+        assert!(received.lock().unwrap().is_empty());
+
+        // Records re-sourced by one processor are held by the next until their source ends too
+        let mut chain = ProcessorChain::new();
+        chain.push(None, ProcessorErrorPolicy::Drop, Box::new(FnProcessor::map(|mut record| {
+            record.source = "renamed".to_string();
+            record.set_field("timestamp", "2024-05-01T10:00:00Z");
+            record
+        })));
+        chain.push(None, ProcessorErrorPolicy::Drop, Box::new(OrderProcessor::new(Duration::from_secs(60))));
+        let previous = s.set_processors(chain);
+        assert!(s.drain_processors(previous).is_ok());
+        assert_eq!(*received.lock().unwrap(), vec!("first\n  more\n"));
+
+        s.add_source(MockSource::new(vec!("later\n")));
+        for _ in 0..3 {
+            let _ = s.step(&mut dropped);
+        }
+        assert_eq!(received.lock().unwrap().len(), 1);
+        assert!(s.finish().is_ok());
+        assert_eq!(*received.lock().unwrap(), vec!("first\n  more\n", "later\n"));
+    }
 }
@@ -0,0 +1,34 @@
+use Source;
+use StreamEntry;
+use LoghaulError;
+
+/// Attaches an explicit id to a source that doesn't provide its own.
+/// The id travels with the source, so it survives being dropped into the
+/// cooler on EOF and resumed later.
+pub struct NamedSource {
+    id: String,
+    inner: Box<Source + Send + 'static>,
+}
+
+impl NamedSource {
+    pub fn new(id: &str, inner: Box<Source + Send + 'static>) -> NamedSource {
+        return NamedSource {
+            id: id.to_string(),
+            inner,
+        };
+    }
+}
+
+impl Source for NamedSource {
+    fn poll(&mut self, buffer: &mut Vec<u8>) -> Result<StreamEntry, LoghaulError> {
+        self.inner.poll(buffer)
+    }
+
+    fn resume(&mut self) -> Result<(), LoghaulError> {
+        self.inner.resume()
+    }
+
+    fn id(&self) -> Option<String> {
+        Some(self.id.clone())
+    }
+}
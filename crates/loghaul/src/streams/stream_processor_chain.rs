@@ -0,0 +1,148 @@
+use std::collections::BTreeSet;
+use std::time::Instant;
+use Processor;
+use ProcessorErrorPolicy;
+use LoghaulErrorAggregate;
+use records::record::Record;
+use metrics::metrics_registry::MetricsRegistry;
+
+/// Upper bound on the rounds of ending sources in `finish`, beyond the sources already seen,
+/// so a processor that keeps emitting records from new sources can't stall a shutdown
+const MAX_FINISH_ROUNDS: usize = 64;
+
+/// An ordered list of processors; every record passes through each processor in turn.
+/// Records a processor fails on are handled according to that processor's error policy.
+pub struct ProcessorChain {
+    stages: Vec<ProcessorStage>,
+    sources: BTreeSet<String>,
+}
+
+struct ProcessorStage {
+    id: String,
+    policy: ProcessorErrorPolicy,
+    processor: Box<Processor + Send + 'static>,
+}
+
+impl ProcessorChain {
+    pub fn new() -> ProcessorChain {
+        return ProcessorChain {
+            stages: Vec::new(),
+            sources: BTreeSet::new(),
+        };
+    }
+
+    /// Add a processor to the end of the chain, optionally overriding the id it reports
+    pub fn push(&mut self, id: Option<String>, policy: ProcessorErrorPolicy, processor: Box<Processor + Send + 'static>) {
+        let id = id.or(processor.id()).unwrap_or(format!("processor-{}", self.stages.len()));
+        self.stages.push(ProcessorStage {
+            id,
+            policy,
+            processor,
+        });
+    }
+
+    /// Give every processor its id and a handle to the metrics of the stream running it
+    pub fn attach(&mut self, metrics: &MetricsRegistry) {
+        for stage in self.stages.iter_mut() {
+            stage.processor.attach(&stage.id, metrics);
+        }
+    }
+
+    /// Return the id of every processor, in order
+    pub fn ids(&self) -> Vec<String> {
+        return self.stages.iter().map(|s| s.id.clone()).collect();
+    }
+
+    pub fn len(&self) -> usize {
+        return self.stages.len();
+    }
+
+    /// Run a record through every processor, appending whatever comes out of the last one to output
+    pub fn run(&mut self, record: Record, metrics: &MetricsRegistry, output: &mut Vec<Record>, errors: &mut LoghaulErrorAggregate) {
+        self.run_from(0, vec!(record), metrics, output, errors);
+    }
+
+    /// Release records every processor holds that are due, running each through the rest of the chain
+    pub fn flush(&mut self, now: Instant, metrics: &MetricsRegistry, output: &mut Vec<Record>, errors: &mut LoghaulErrorAggregate) {
+        for index in 0..self.stages.len() {
+            let mut released = Vec::new();
+            match self.stages[index].processor.flush(now, &mut released) {
+                Ok(_) => {}
+                Err(e) => {
+                    metrics.processor_error(&self.stages[index].id);
+                    errors.push(e);
+                }
+            }
+            self.run_from(index + 1, released, metrics, output, errors);
+        }
+    }
+
+    /// Release everything every processor holds for a source that has ended
+    pub fn end_source(&mut self, source: &str, metrics: &MetricsRegistry, output: &mut Vec<Record>, errors: &mut LoghaulErrorAggregate) {
+        self.sources.remove(source);
+        for index in 0..self.stages.len() {
+            let mut released = Vec::new();
+            match self.stages[index].processor.end_source(source, &mut released) {
+                Ok(_) => {}
+                Err(e) => {
+                    metrics.processor_error(&self.stages[index].id);
+                    errors.push(e);
+                }
+            }
+            self.run_from(index + 1, released, metrics, output, errors);
+        }
+    }
+
+    /// Release everything every processor holds, by ending every source whose records reached
+    /// the chain; records released this way may come from sources of their own, which are ended in turn
+    pub fn finish(&mut self, metrics: &MetricsRegistry, output: &mut Vec<Record>, errors: &mut LoghaulErrorAggregate) {
+        for _ in 0..self.sources.len() + MAX_FINISH_ROUNDS {
+            let source = match self.sources.iter().next() {
+                Some(source) => source.clone(),
+                None => return,
+            };
+            self.end_source(&source, metrics, output, errors);
+        }
+    }
+
+    /// Run records through the processors from the given position onwards
+    fn run_from(&mut self, start: usize, records: Vec<Record>, metrics: &MetricsRegistry, output: &mut Vec<Record>, errors: &mut LoghaulErrorAggregate) {
+        let mut current = records;
+        for stage in self.stages.iter_mut().skip(start) {
+            if current.is_empty() {
+                break;
+            }
+            let mut next = Vec::with_capacity(current.len());
+            for record in current.into_iter() {
+                if !self.sources.contains(&record.source) {
+                    self.sources.insert(record.source.clone());
+                }
+                let backup = match stage.policy {
+                    ProcessorErrorPolicy::Drop => None,
+                    _ => Some(record.clone())
+                };
+                let before = next.len();
+                match stage.processor.process(record, &mut next) {
+                    Ok(_) => {
+                        metrics.processor_processed(&stage.id, next.len() - before);
+                    }
+                    Err(e) => {
+                        next.truncate(before);
+                        metrics.processor_error(&stage.id);
+                        errors.push(e);
+                        match (&stage.policy, backup) {
+                            (ProcessorErrorPolicy::PassThrough, Some(original)) => next.push(original),
+                            (ProcessorErrorPolicy::DeadLetter(ref target), Some(mut original)) => {
+                                original.route_to(target);
+                                output.push(original);
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            current = next;
+        }
+        output.extend(current.into_iter());
+    }
+}
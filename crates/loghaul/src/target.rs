@@ -1,6 +1,19 @@
 use streams::stream_entry::StreamEntry;
 use LoghaulError;
+use records::record::Record;
 
 pub trait Target {
     fn consume(&mut self, entry: StreamEntry, data: &Vec<u8>) -> Result<(), LoghaulError>;
+
+    /// Consume a single data record.
+    /// Targets that only care about the raw payload don't need to implement this.
+    fn consume_record(&mut self, record: &Record) -> Result<(), LoghaulError> {
+        self.consume(StreamEntry::Data, &record.payload)
+    }
+
+    /// A stable name for this target, used to label metrics.
+    /// Targets that return None are given a positional id by the stream.
+    fn id(&self) -> Option<String> {
+        None
+    }
 }
\ No newline at end of file
@@ -0,0 +1,122 @@
+use std::path::PathBuf;
+
+pub const USAGE: &str = "usage: loghaul <command> [options]
+
+commands:
+    run --config <pipeline.toml>       run a pipeline in the foreground until SIGINT or SIGTERM;
+        [--watch]                      SIGHUP reloads the config, --watch also reloads when it changes
+    validate --config <pipeline.toml>  check a pipeline config and report every problem
+    tail <paths...>                    follow files and copy new data to stdout
+    cat <paths...>                     copy files to stdout once and exit
+    help                               show this message
+    version                            show the loghaul version
+";
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum CliCommand {
+    Run { config: PathBuf, watch: bool },
+    Validate { config: PathBuf },
+    Tail { paths: Vec<PathBuf> },
+    Cat { paths: Vec<PathBuf> },
+    Help,
+    Version,
+}
+
+/// Parse the command line arguments, not including the program name
+pub fn parse_args(args: &[String]) -> Result<CliCommand, String> {
+    let (command, rest) = match args.split_first() {
+        Some((command, rest)) => (command.as_str(), rest),
+        None => {
+            return Err("missing command".to_string());
+        }
+    };
+    return match command {
+        "run" => {
+            let watch = rest.iter().any(|a| a == "--watch");
+            let rest: Vec<String> = rest.iter().filter(|a| *a != "--watch").cloned().collect();
+            Ok(CliCommand::Run { config: parse_config_arg(command, &rest)?, watch })
+        }
+        "validate" => Ok(CliCommand::Validate { config: parse_config_arg(command, rest)? }),
+        "tail" => Ok(CliCommand::Tail { paths: parse_paths_arg(command, rest)? }),
+        "cat" => Ok(CliCommand::Cat { paths: parse_paths_arg(command, rest)? }),
+        "help" | "-h" | "--help" => Ok(CliCommand::Help),
+        "version" | "-V" | "--version" => Ok(CliCommand::Version),
+        other => Err(format!("unknown command '{}'", other))
+    };
+}
+
+/// Accepts `--config <path>`, `-c <path>`, `--config=<path>` or a single bare path
+fn parse_config_arg(command: &str, args: &[String]) -> Result<PathBuf, String> {
+    let mut config = None;
+    let mut offset = 0;
+    while offset < args.len() {
+        let arg = &args[offset];
+        let value = if arg == "--config" || arg == "-c" {
+            offset += 1;
+            match args.get(offset) {
+                Some(v) => v.clone(),
+                None => {
+                    return Err(format!("{}: {} requires a path", command, arg));
+                }
+            }
+        } else if arg.starts_with("--config=") {
+            arg["--config=".len()..].to_string()
+        } else if arg.starts_with("-") {
+            return Err(format!("{}: unknown option '{}'", command, arg));
+        } else {
+            arg.clone()
+        };
+        if config.is_some() {
+            return Err(format!("{}: only one config file may be given", command));
+        }
+        config = Some(PathBuf::from(value));
+        offset += 1;
+    }
+    return config.ok_or(format!("{}: missing --config <path>", command));
+}
+
+fn parse_paths_arg(command: &str, args: &[String]) -> Result<Vec<PathBuf>, String> {
+    match args.iter().find(|a| a.starts_with("-")) {
+        Some(option) => {
+            return Err(format!("{}: unknown option '{}'", command, option));
+        }
+        None => {}
+    }
+    if args.is_empty() {
+        return Err(format!("{}: at least one path is required", command));
+    }
+    return Ok(args.iter().map(PathBuf::from).collect());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_args;
+    use super::CliCommand;
+    use std::path::PathBuf;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(parse_args(&args(&["run", "--config", "p.toml"])), Ok(CliCommand::Run { config: PathBuf::from("p.toml"), watch: false }));
+        assert_eq!(parse_args(&args(&["run", "--config=p.toml", "--watch"])), Ok(CliCommand::Run { config: PathBuf::from("p.toml"), watch: true }));
+        assert_eq!(parse_args(&args(&["validate", "p.toml"])), Ok(CliCommand::Validate { config: PathBuf::from("p.toml") }));
+        assert_eq!(parse_args(&args(&["tail", "a.log", "b.log"])), Ok(CliCommand::Tail { paths: vec!(PathBuf::from("a.log"), PathBuf::from("b.log")) }));
+        assert_eq!(parse_args(&args(&["cat", "a.log"])), Ok(CliCommand::Cat { paths: vec!(PathBuf::from("a.log")) }));
+        assert_eq!(parse_args(&args(&["--help"])), Ok(CliCommand::Help));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse_args(&args(&[])), Err("missing command".to_string()));
+        assert_eq!(parse_args(&args(&["launch"])), Err("unknown command 'launch'".to_string()));
+        assert_eq!(parse_args(&args(&["run"])), Err("run: missing --config <path>".to_string()));
+        assert_eq!(parse_args(&args(&["run", "-c"])), Err("run: -c requires a path".to_string()));
+        assert_eq!(parse_args(&args(&["run", "a.toml", "b.toml"])), Err("run: only one config file may be given".to_string()));
+        assert_eq!(parse_args(&args(&["cat"])), Err("cat: at least one path is required".to_string()));
+        assert_eq!(parse_args(&args(&["tail", "-f", "a.log"])), Err("tail: unknown option '-f'".to_string()));
+        assert_eq!(parse_args(&args(&["validate", "--watch", "a.toml"])), Err("validate: unknown option '--watch'".to_string()));
+    }
+}
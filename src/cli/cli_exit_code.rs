@@ -0,0 +1,11 @@
+/// The command completed successfully, or was halted by a signal
+pub const EXIT_OK: i32 = 0;
+
+/// The command failed while running
+pub const EXIT_FAILURE: i32 = 1;
+
+/// The command line could not be understood
+pub const EXIT_USAGE: i32 = 2;
+
+/// The pipeline config could not be read or is invalid
+pub const EXIT_INVALID_CONFIG: i32 = 3;
@@ -0,0 +1,50 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::time::SystemTime;
+use signal_hook::consts::SIGHUP;
+use signal_hook::flag;
+
+/// Tracks whether the pipeline config should be reloaded, either because SIGHUP
+/// was received or, when watching, because the config file was modified.
+pub struct CliReload {
+    requested: Arc<AtomicBool>,
+    watch: Option<PathBuf>,
+    modified: Option<SystemTime>,
+}
+
+impl CliReload {
+    pub fn register(config: &Path, watch: bool) -> io::Result<CliReload> {
+        let requested = Arc::new(AtomicBool::new(false));
+        flag::register(SIGHUP, requested.clone())?;
+        return Ok(CliReload {
+            requested,
+            watch: if watch { Some(config.to_path_buf()) } else { None },
+            modified: modified_time(config),
+        });
+    }
+
+    /// Returns true once for each reload request
+    pub fn take_requested(&mut self) -> bool {
+        let mut requested = self.requested.swap(false, Ordering::SeqCst);
+        match self.watch {
+            Some(ref path) => {
+                let modified = modified_time(path);
+                if modified.is_some() && modified != self.modified {
+                    self.modified = modified;
+                    requested = true;
+                }
+            }
+            None => {}
+        }
+        return requested;
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    return fs::metadata(path).and_then(|m| m.modified()).ok();
+}
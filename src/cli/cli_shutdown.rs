@@ -0,0 +1,31 @@
+use std::io;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use signal_hook::consts::SIGINT;
+use signal_hook::consts::SIGTERM;
+use signal_hook::flag;
+use cli::cli_exit_code::EXIT_FAILURE;
+
+/// Tracks whether SIGINT or SIGTERM has been received.
+/// A second signal while shutting down exits immediately.
+pub struct CliShutdown {
+    requested: Arc<AtomicBool>,
+}
+
+impl CliShutdown {
+    pub fn register() -> io::Result<CliShutdown> {
+        let requested = Arc::new(AtomicBool::new(false));
+        for signal in [SIGINT, SIGTERM].iter() {
+            flag::register_conditional_shutdown(*signal, EXIT_FAILURE, requested.clone())?;
+            flag::register(*signal, requested.clone())?;
+        }
+        return Ok(CliShutdown {
+            requested
+        });
+    }
+
+    pub fn is_requested(&self) -> bool {
+        return self.requested.load(Ordering::SeqCst);
+    }
+}
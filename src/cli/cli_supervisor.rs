@@ -0,0 +1,30 @@
+use std::thread;
+use std::time::Duration;
+use loghaul::Keeper;
+use cli::cli_shutdown::CliShutdown;
+
+/// How often the supervisor checks for signals and flushes keeper logs
+const SUPERVISOR_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Upper bound on log entries flushed per tick, so a noisy keeper can't starve signal checks
+const MAX_LOGS_PER_TICK: usize = 1000;
+
+/// Block until a shutdown signal arrives, `on_tick` returns true or the keeper stops on
+/// its own, then halt the keeper. `on_tick` runs on every tick, and may also change the
+/// running keeper. Returns true if a shutdown signal stopped it.
+pub fn supervise(keeper: &mut Keeper, shutdown: &CliShutdown, mut on_tick: impl FnMut(&mut Keeper) -> bool) -> bool {
+    let signalled = loop {
+        thread::sleep(SUPERVISOR_INTERVAL);
+        for _ in 0..MAX_LOGS_PER_TICK {
+            let _ = keeper.step();
+        }
+        if shutdown.is_requested() {
+            break true;
+        }
+        if on_tick(keeper) || !keeper.is_running() {
+            break false;
+        }
+    };
+    keeper.halt();
+    return signalled;
+}
@@ -0,0 +1,6 @@
+pub mod cli_args;
+pub mod cli_exit_code;
+pub mod cli_reload;
+pub mod cli_shutdown;
+pub mod cli_supervisor;
+pub mod stderr_keeper_log;
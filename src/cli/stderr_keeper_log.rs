@@ -0,0 +1,39 @@
+use loghaul::KeeperLog;
+use loghaul::KeeperLogEntry;
+
+/// Reports keeper errors on stderr; lifecycle events are only shown when verbose.
+pub struct StderrKeeperLog {
+    verbose: bool,
+}
+
+impl StderrKeeperLog {
+    pub fn new(verbose: bool) -> StderrKeeperLog {
+        return StderrKeeperLog {
+            verbose
+        };
+    }
+}
+
+impl KeeperLog for StderrKeeperLog {
+    fn log(&mut self, record: KeeperLogEntry) {
+        match record {
+            KeeperLogEntry::KeeperError(err) => {
+                eprintln!("loghaul: error: {}", err);
+            }
+            KeeperLogEntry::KeeperReloadRejected(message) => {
+                eprintln!("loghaul: error: config reload rejected, the previous pipeline is still running");
+                for line in message.lines() {
+                    eprintln!("    {}", line);
+                }
+            }
+            KeeperLogEntry::KeeperWaitWorkerError | KeeperLogEntry::KeeperSendWorkerHaltError => {
+                eprintln!("loghaul: error: {:?}", record);
+            }
+            other => {
+                if self.verbose {
+                    eprintln!("loghaul: {:?}", other);
+                }
+            }
+        }
+    }
+}
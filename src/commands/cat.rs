@@ -0,0 +1,56 @@
+use std::path::PathBuf;
+use std::time::Duration;
+use loghaul::Stream;
+use loghaul::Keeper;
+use loghaul::KeeperConfig;
+use loghaul::KeeperEofStrategy;
+use loghaul_file::FileSource;
+use loghaul_stdio::StdoutTarget;
+use cli::cli_exit_code::EXIT_OK;
+use cli::cli_exit_code::EXIT_FAILURE;
+use cli::cli_shutdown::CliShutdown;
+use cli::cli_supervisor::supervise;
+use cli::stderr_keeper_log::StderrKeeperLog;
+
+/// Copy each file to stdout once, exiting when every file has been read
+pub fn cat(paths: &[PathBuf]) -> i32 {
+    let missing: Vec<&PathBuf> = paths.iter().filter(|p| !p.is_file()).collect();
+    if missing.len() > 0 {
+        for path in missing {
+            eprintln!("loghaul: cat: {}: no such file", path.display());
+        }
+        return EXIT_FAILURE;
+    }
+
+    let shutdown = match CliShutdown::register() {
+        Ok(s) => s,
+        Err(err) => {
+            eprintln!("loghaul: unable to install signal handlers: {}", err);
+            return EXIT_FAILURE;
+        }
+    };
+
+    let mut stream = Stream::new().with_target(StdoutTarget::new());
+    for path in paths {
+        stream.add_source(FileSource::closed(path));
+    }
+
+    let mut keeper = Keeper::new(stream, Some(KeeperConfig {
+        interval: Duration::from_millis(1),
+        eof_strategy: KeeperEofStrategy::DropSource,
+        logger: Some(Box::new(StderrKeeperLog::new(false))),
+    }));
+
+    // Every source has its own metrics, even when a path is given twice; one that can't be
+    // read reports an error and then EOFs
+    let expected = paths.len();
+    supervise(&mut keeper, &shutdown, |keeper| {
+        keeper.metrics().sources.values().filter(|s| s.eofs > 0).count() >= expected
+    });
+
+    let metrics = keeper.metrics();
+    if metrics.sources.values().any(|s| s.errors > 0) {
+        return EXIT_FAILURE;
+    }
+    return EXIT_OK;
+}
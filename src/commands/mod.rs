@@ -0,0 +1,4 @@
+pub mod run;
+pub mod validate;
+pub mod tail;
+pub mod cat;
@@ -0,0 +1,58 @@
+use std::path::Path;
+use loghaul_config::PipelineConfig;
+use loghaul_config::PipelineReloader;
+use cli::cli_exit_code::EXIT_OK;
+use cli::cli_exit_code::EXIT_FAILURE;
+use cli::cli_exit_code::EXIT_INVALID_CONFIG;
+use cli::cli_reload::CliReload;
+use cli::cli_shutdown::CliShutdown;
+use cli::cli_supervisor::supervise;
+use cli::stderr_keeper_log::StderrKeeperLog;
+
+/// Run the pipeline described by a config file until SIGINT or SIGTERM.
+/// SIGHUP, or a change to the file when watching, applies the new config in place.
+pub fn run(config: &Path, watch: bool) -> i32 {
+    let pipeline = match PipelineConfig::from_file(config) {
+        Ok(p) => p,
+        Err(err) => {
+            eprintln!("loghaul: {}: invalid config", config.display());
+            for line in err.message().lines() {
+                eprintln!("    {}", line);
+            }
+            return EXIT_INVALID_CONFIG;
+        }
+    };
+
+    let (shutdown, mut reload) = match CliShutdown::register().and_then(|s| CliReload::register(config, watch).map(|r| (s, r))) {
+        Ok(s) => s,
+        Err(err) => {
+            eprintln!("loghaul: unable to install signal handlers: {}", err);
+            return EXIT_FAILURE;
+        }
+    };
+
+    let (mut keeper, mut reloader) = match PipelineReloader::start(pipeline, Some(Box::new(StderrKeeperLog::new(true)))) {
+        Ok(k) => k,
+        Err(err) => {
+            eprintln!("loghaul: {}", err);
+            return EXIT_FAILURE;
+        }
+    };
+
+    let signalled = supervise(&mut keeper, &shutdown, |keeper| {
+        if reload.take_requested() {
+            match reloader.reload(keeper, PipelineConfig::from_file(config)) {
+                Ok(_) => {}
+                // Already logged as a rejected reload, and the running pipeline is kept;
+                // if the keeper has stopped, supervising ends on this tick
+                Err(_) => {}
+            }
+        }
+        false
+    });
+    if !signalled {
+        eprintln!("loghaul: the pipeline stopped unexpectedly");
+        return EXIT_FAILURE;
+    }
+    return EXIT_OK;
+}
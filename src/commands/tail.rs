@@ -0,0 +1,42 @@
+use std::path::PathBuf;
+use std::time::Duration;
+use loghaul::Stream;
+use loghaul::Keeper;
+use loghaul::KeeperConfig;
+use loghaul::KeeperEofStrategy;
+use loghaul_file::FileSource;
+use loghaul_stdio::StdoutTarget;
+use cli::cli_exit_code::EXIT_OK;
+use cli::cli_exit_code::EXIT_FAILURE;
+use cli::cli_shutdown::CliShutdown;
+use cli::cli_supervisor::supervise;
+use cli::stderr_keeper_log::StderrKeeperLog;
+
+/// Follow files, copying anything written to them to stdout until SIGINT or SIGTERM.
+/// Files that don't exist yet, or disappear, are retried every second.
+pub fn tail(paths: &[PathBuf]) -> i32 {
+    let shutdown = match CliShutdown::register() {
+        Ok(s) => s,
+        Err(err) => {
+            eprintln!("loghaul: unable to install signal handlers: {}", err);
+            return EXIT_FAILURE;
+        }
+    };
+
+    let mut stream = Stream::new().with_target(StdoutTarget::new());
+    for path in paths {
+        stream.add_source(FileSource::new(path));
+    }
+
+    let mut keeper = Keeper::new(stream, Some(KeeperConfig {
+        interval: Duration::from_millis(100),
+        eof_strategy: KeeperEofStrategy::ResumeSourceAfterCooldown(Duration::from_secs(1)),
+        logger: Some(Box::new(StderrKeeperLog::new(false))),
+    }));
+
+    if !supervise(&mut keeper, &shutdown, |_| false) {
+        eprintln!("loghaul: tail stopped unexpectedly");
+        return EXIT_FAILURE;
+    }
+    return EXIT_OK;
+}
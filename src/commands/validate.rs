@@ -0,0 +1,21 @@
+use std::path::Path;
+use loghaul_config::PipelineConfig;
+use cli::cli_exit_code::EXIT_OK;
+use cli::cli_exit_code::EXIT_INVALID_CONFIG;
+
+/// Check a config file, printing every problem found
+pub fn validate(config: &Path) -> i32 {
+    match PipelineConfig::from_file(config) {
+        Ok(pipeline) => {
+            println!("{}: ok ({} sources, {} targets)", config.display(), pipeline.sources.len(), pipeline.targets.len());
+            EXIT_OK
+        }
+        Err(err) => {
+            eprintln!("{}: invalid config", config.display());
+            for line in err.message().lines() {
+                eprintln!("    {}", line);
+            }
+            EXIT_INVALID_CONFIG
+        }
+    }
+}
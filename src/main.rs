@@ -1,6 +1,39 @@
-extern crate loghaul_core;
+extern crate loghaul;
 extern crate loghaul_file;
+extern crate loghaul_stdio;
+extern crate loghaul_config;
+extern crate signal_hook;
+
+mod cli;
+mod commands;
+
+use std::env;
+use std::process;
+use cli::cli_args::parse_args;
+use cli::cli_args::CliCommand;
+use cli::cli_args::USAGE;
+use cli::cli_exit_code::EXIT_OK;
+use cli::cli_exit_code::EXIT_USAGE;
 
 fn main() {
-    println!("Hello, world!");
+    let args: Vec<String> = env::args().skip(1).collect();
+    let code = match parse_args(&args) {
+        Ok(CliCommand::Run { config, watch }) => commands::run::run(&config, watch),
+        Ok(CliCommand::Validate { config }) => commands::validate::validate(&config),
+        Ok(CliCommand::Tail { paths }) => commands::tail::tail(&paths),
+        Ok(CliCommand::Cat { paths }) => commands::cat::cat(&paths),
+        Ok(CliCommand::Help) => {
+            print!("{}", USAGE);
+            EXIT_OK
+        }
+        Ok(CliCommand::Version) => {
+            println!("loghaul {}", env!("CARGO_PKG_VERSION"));
+            EXIT_OK
+        }
+        Err(err) => {
+            eprintln!("loghaul: {}\n\n{}", err, USAGE);
+            EXIT_USAGE
+        }
+    };
+    process::exit(code);
 }